@@ -0,0 +1,84 @@
+//! Allow-lists of takers, built off-chain into the Merkle tree whose root
+//! the maker stores in the escrow with `SetAllowList`. Takers need the whole
+//! list to work out their proof, so the maker hands it out along with the
+//! escrow's address.
+
+use std::collections::HashMap;
+
+use solana_escrow::merkle;
+use solana_sdk::pubkey::Pubkey;
+
+/// A Merkle tree over a list of takers
+#[derive(Debug, Clone)]
+pub struct AllowList {
+    /// Every level of the tree, from the sorted leaves up to the root
+    levels: Vec<Vec<[u8; 32]>>,
+    /// Where each taker's leaf is in the bottom level
+    positions: HashMap<Pubkey, usize>,
+}
+
+impl AllowList {
+    /// Builds the tree. The order of `takers` doesn't matter and duplicates
+    /// are dropped, so the same set of takers always gives the same root.
+    pub fn new(takers: &[Pubkey]) -> Self {
+        let mut leaves: Vec<([u8; 32], Pubkey)> = takers
+            .iter()
+            .map(|taker| (merkle::leaf(taker), *taker))
+            .collect();
+        leaves.sort_unstable();
+        leaves.dedup();
+        let positions = leaves
+            .iter()
+            .enumerate()
+            .map(|(i, (_, taker))| (*taker, i))
+            .collect();
+
+        let mut levels = vec![leaves.into_iter().map(|(leaf, _)| leaf).collect::<Vec<_>>()];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            // an odd node out moves up a level as it is
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => merkle::parent(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        AllowList { levels, positions }
+    }
+
+    /// How many takers are on the list
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The root to store in the escrow. All zeroes for an empty list, which
+    /// is the same as having no allow list at all.
+    pub fn root(&self) -> [u8; 32] {
+        match self.levels.last().map(Vec::as_slice) {
+            Some([root]) => *root,
+            _ => [0; 32],
+        }
+    }
+
+    /// The taker's proof of being on the list, or `None` if they aren't
+    pub fn proof(&self, taker: &Pubkey) -> Option<Vec<[u8; 32]>> {
+        let mut index = *self.positions.get(taker)?;
+        let mut proof = vec![];
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some(proof)
+    }
+}
@@ -0,0 +1,50 @@
+//! Decodes the [`EscrowEvent`]s the program emits with `sol_log_data`.
+//!
+//! Each event shows up in the transaction logs as a `Program data: <base64>`
+//! line. Only lines logged while the escrow program itself is executing are
+//! decoded; a program it invokes, or one invoking it, may log data too.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_escrow::event::EscrowEvent;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{ClientError, Result};
+
+const DATA_PREFIX: &str = "Program data: ";
+
+/// Decodes one `sol_log_data` payload, as printed after `Program data: `
+pub fn decode_event(encoded: &str) -> Result<EscrowEvent> {
+    let bytes = STANDARD
+        .decode(encoded)
+        .map_err(|e| ClientError::Encoding(e.to_string()))?;
+    Ok(EscrowEvent::unpack(&bytes)?)
+}
+
+/// Returns, in order, every event `program_id` emitted in a transaction's
+/// log messages
+pub fn decode_logs(program_id: &Pubkey, logs: &[String]) -> Result<Vec<EscrowEvent>> {
+    let program_id = program_id.to_string();
+    let mut invocations: Vec<&str> = vec![];
+    let mut events = vec![];
+
+    for line in logs {
+        if let Some(data) = line.strip_prefix(DATA_PREFIX) {
+            if invocations.last() == Some(&program_id.as_str()) {
+                // sol_log_data logs every slice it was given; events are a single one
+                if let Some(encoded) = data.split(' ').next() {
+                    events.push(decode_event(encoded)?);
+                }
+            }
+        } else if let Some(rest) = line.strip_prefix("Program ") {
+            let mut words = rest.split(' ');
+            match (words.next(), words.next()) {
+                (Some(program), Some("invoke")) => invocations.push(program),
+                (Some(_), Some("success")) | (Some(_), Some("failed:")) => {
+                    invocations.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(events)
+}
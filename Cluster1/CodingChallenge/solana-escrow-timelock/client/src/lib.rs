@@ -0,0 +1,1751 @@
+//! Client-side helpers for the timelocked escrow program.
+//!
+//! Everything here builds on the instruction builders in
+//! [`solana_escrow::instruction`]; the `escrow` binary is a thin command-line
+//! wrapper around [`EscrowClient`].
+
+pub mod allow_list;
+pub mod events;
+pub mod offline;
+pub mod orders;
+
+use solana_escrow::{
+    error::EscrowError,
+    instruction::{self, EscrowToTake, MilestoneTerms},
+    oracle::{OracleKind, PriceCondition},
+    order::Order,
+    state::{
+        AccountType, DefaultOutcome, Escrow, EscrowMode, HashKind, OrderNonce, Pool,
+        ESCROW_VERSION, LEGACY_ESCROW_LEN, MAX_MILESTONES,
+    },
+};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    client_error::Error as RpcError,
+    config::RpcProgramAccountsConfig,
+    filter::{Memcmp, RpcFilterType},
+    response::RpcSimulateTransactionResult,
+};
+use solana_rpc_client_nonce_utils as nonce_utils;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Signature,
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use spl_token::state::Account as TokenAccount;
+use thiserror::Error;
+
+use crate::{allow_list::AllowList, offline::Lifetime, orders::SignedOrder};
+
+/// Byte offset of `initializer_pubkey` inside the packed [`Escrow`]
+pub const ESCROW_INITIALIZER_OFFSET: usize = 2;
+
+/// Byte offset of `initializer_pubkey` in escrows from before the versioned
+/// layout, which are [`LEGACY_ESCROW_LEN`] bytes long
+pub const LEGACY_ESCROW_INITIALIZER_OFFSET: usize = 1;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error(transparent)]
+    Rpc(Box<RpcError>),
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+    #[error("account {0} does not exist")]
+    AccountNotFound(Pubkey),
+    #[error("account {0} is not owned by the escrow program")]
+    NotAnEscrow(Pubkey),
+    #[error("invalid nonce account: {0}")]
+    Nonce(String),
+    #[error("malformed transaction: {0}")]
+    Encoding(String),
+    #[error("signing failed: {0}")]
+    Signing(String),
+    #[error("{0} is not a required signer of this transaction")]
+    NotASigner(Pubkey),
+    #[error("the transactions wrap different messages")]
+    MessageMismatch,
+    #[error("missing signatures from {0:?}")]
+    MissingSignatures(Vec<Pubkey>),
+    #[error("{0} is not on the escrow's allow list")]
+    NotAllowed(Pubkey),
+}
+
+impl From<RpcError> for ClientError {
+    fn from(e: RpcError) -> Self {
+        ClientError::Rpc(Box::new(e))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+/// Terms of a new escrow: the maker locks `send_amount` of `send_mint` and
+/// asks for `receive_amount` of whatever mint `receive_account` holds.
+pub struct CreateTerms {
+    pub send_mint: Pubkey,
+    pub send_account: Pubkey,
+    pub send_amount: u64,
+    pub receive_account: Pubkey,
+    pub receive_amount: u64,
+    pub unlock_slots: u64,
+    pub time_out_slots: u64,
+    pub bond: Option<BondTerms>,
+}
+
+/// A bond the maker puts up with a swap: `amount` lamports, or tokens when
+/// `token` is set, forfeited to `recipient`, or the program's fee vault
+/// without one, if the maker cancels while the escrow can be taken.
+pub struct BondTerms {
+    pub recipient: Option<Pubkey>,
+    pub amount: u64,
+    pub token: Option<TokenBond>,
+}
+
+/// Where a token bond comes from. `vault` is a new account that becomes the
+/// bond vault, and must sign alongside the maker.
+pub struct TokenBond {
+    pub mint: Pubkey,
+    pub send_account: Pubkey,
+    pub vault: Pubkey,
+}
+
+/// Terms of a new hash lock: the maker locks `send_amount` of `send_mint`
+/// for `counterparty`, who can claim it with the preimage of `hash`.
+pub struct HashLockTerms {
+    pub send_mint: Pubkey,
+    pub send_account: Pubkey,
+    pub send_amount: u64,
+    pub hash_kind: HashKind,
+    pub hash: [u8; 32],
+    pub counterparty: Pubkey,
+    pub unlock_slots: u64,
+    pub time_out_slots: u64,
+}
+
+/// Terms of a new dead man's switch: the maker locks `send_amount` of
+/// `send_mint`, and `beneficiary` can claim it unless the maker resets the
+/// time out within `time_out_slots`.
+pub struct SwitchTerms {
+    pub send_mint: Pubkey,
+    pub send_account: Pubkey,
+    pub send_amount: u64,
+    pub beneficiary: Pubkey,
+    pub time_out_slots: u64,
+}
+
+/// Terms of a new savings lock: the maker locks `send_amount` of `send_mint`
+/// for `unlock_slots`, to withdraw it themselves afterwards.
+pub struct LockTerms {
+    pub send_mint: Pubkey,
+    pub send_account: Pubkey,
+    pub send_amount: u64,
+    pub unlock_slots: u64,
+}
+
+/// Terms of a new milestone escrow: the maker locks what `milestones` add up
+/// to of `send_mint`, paid to `payee` one tranche at a time.
+pub struct MilestonesTerms {
+    pub send_mint: Pubkey,
+    pub send_account: Pubkey,
+    pub payee: Pubkey,
+    pub milestones: Vec<MilestoneTerms>,
+}
+
+/// Terms of a new arbitrated deal: the maker locks `send_amount` of
+/// `send_mint` for `taker`, and `arbiter` settles it if either side disputes.
+pub struct ArbitratedTerms {
+    pub send_mint: Pubkey,
+    pub send_account: Pubkey,
+    pub send_amount: u64,
+    pub taker: Pubkey,
+    pub arbiter: Pubkey,
+    pub default_outcome: DefaultOutcome,
+    pub time_out_slots: u64,
+}
+
+/// Terms of a new pool: makers deposit `send_mint` for `unlock_slots`, then
+/// takers buy lots of `offered_per_lot` for `requested_per_lot` of
+/// `receive_mint` for `time_out_slots`.
+pub struct PoolTerms {
+    pub send_mint: Pubkey,
+    pub receive_mint: Pubkey,
+    pub offered_per_lot: u64,
+    pub requested_per_lot: u64,
+    pub unlock_slots: u64,
+    pub time_out_slots: u64,
+}
+
+/// A price condition on a swap: `oracle`'s price, at most `max_staleness`
+/// slots old, has to meet `condition` against `trigger_price *
+/// 10^trigger_expo` for the swap to be taken.
+pub struct PriceTerms {
+    pub oracle: Pubkey,
+    pub oracle_kind: OracleKind,
+    pub condition: PriceCondition,
+    pub trigger_price: i64,
+    pub trigger_expo: i32,
+    pub max_staleness: u64,
+}
+
+/// Where an escrow stands relative to the current slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockStatus {
+    Locked,
+    Open,
+    TimedOut,
+}
+
+impl LockStatus {
+    pub fn at(escrow: &Escrow, slot: u64) -> Self {
+        if slot < escrow.unlock_time {
+            Self::Locked
+        } else if slot > escrow.time_out {
+            Self::TimedOut
+        } else {
+            Self::Open
+        }
+    }
+}
+
+/// Either the signature of a sent transaction or the result of simulating it
+pub enum Outcome {
+    Sent(Signature),
+    Simulated(RpcSimulateTransactionResult),
+}
+
+pub struct EscrowClient {
+    pub rpc: RpcClient,
+    pub program_id: Pubkey,
+}
+
+impl EscrowClient {
+    pub fn new(url: String, program_id: Pubkey) -> Self {
+        Self {
+            rpc: RpcClient::new_with_commitment(url, CommitmentConfig::confirmed()),
+            program_id,
+        }
+    }
+
+    pub fn get_escrow(&self, escrow: &Pubkey) -> Result<Escrow> {
+        let account = self
+            .rpc
+            .get_account_with_commitment(escrow, self.rpc.commitment())?
+            .value
+            .ok_or(ClientError::AccountNotFound(*escrow))?;
+        if account.owner != self.program_id {
+            return Err(ClientError::NotAnEscrow(*escrow));
+        }
+        Ok(Escrow::unpack(&account.data)?)
+    }
+
+    pub fn get_pool(&self, pool: &Pubkey) -> Result<Pool> {
+        let account = self
+            .rpc
+            .get_account_with_commitment(pool, self.rpc.commitment())?
+            .value
+            .ok_or(ClientError::AccountNotFound(*pool))?;
+        if account.owner != self.program_id {
+            return Err(ClientError::NotAnEscrow(*pool));
+        }
+        Ok(Pool::unpack(&account.data)?)
+    }
+
+    pub fn get_token_account(&self, token_account: &Pubkey) -> Result<TokenAccount> {
+        let account = self
+            .rpc
+            .get_account_with_commitment(token_account, self.rpc.commitment())?
+            .value
+            .ok_or(ClientError::AccountNotFound(*token_account))?;
+        Ok(TokenAccount::unpack(&account.data)?)
+    }
+
+    /// Lists every initialized escrow opened by `maker`, legacy ones included
+    pub fn escrows_by_maker(&self, maker: &Pubkey) -> Result<Vec<(Pubkey, Escrow)>> {
+        let maker = maker.to_bytes().to_vec();
+        let mut escrows = vec![];
+        for filters in [
+            // current escrows are as long as the extensions they use
+            vec![
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    0,
+                    vec![AccountType::Escrow as u8, ESCROW_VERSION],
+                )),
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    ESCROW_INITIALIZER_OFFSET,
+                    maker.clone(),
+                )),
+            ],
+            vec![
+                RpcFilterType::DataSize(LEGACY_ESCROW_LEN as u64),
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    LEGACY_ESCROW_INITIALIZER_OFFSET,
+                    maker.clone(),
+                )),
+            ],
+        ] {
+            let config = RpcProgramAccountsConfig {
+                filters: Some(filters),
+                ..RpcProgramAccountsConfig::default()
+            };
+            let accounts = self
+                .rpc
+                .get_program_accounts_with_config(&self.program_id, config)?;
+            escrows.extend(
+                accounts.into_iter().filter_map(|(key, account)| {
+                    Escrow::unpack(&account.data).ok().map(|e| (key, e))
+                }),
+            );
+        }
+        Ok(escrows)
+    }
+
+    /// Layout version of the escrow as stored on chain
+    pub fn escrow_version(&self, escrow: &Pubkey) -> Result<u8> {
+        let account = self
+            .rpc
+            .get_account_with_commitment(escrow, self.rpc.commitment())?
+            .value
+            .ok_or(ClientError::AccountNotFound(*escrow))?;
+        Ok(Escrow::version(&account.data)?)
+    }
+
+    /// Creates the temp token account, funds it and opens the escrow, the same
+    /// way `scripts/src/alice.ts` does, taking `terms.bond` with it if any.
+    /// `temp_token_account` and `escrow_account` must sign alongside the maker.
+    pub fn create(
+        &self,
+        maker: &Pubkey,
+        terms: &CreateTerms,
+        temp_token_account: &Pubkey,
+        escrow_account: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let escrow_len = Escrow {
+            bond: terms.bond.as_ref().map_or(0, |bond| bond.amount),
+            ..Escrow::default()
+        }
+        .packed_len();
+        let mut instructions = self.fund_escrow(
+            maker,
+            &terms.send_mint,
+            &terms.send_account,
+            terms.send_amount,
+            temp_token_account,
+            escrow_account,
+            escrow_len,
+        )?;
+        let (fund_bond, bond) = self.fund_bond(maker, escrow_account, terms.bond.as_ref())?;
+        instructions.extend(fund_bond);
+        instructions.push(instruction::init_escrow(
+            &self.program_id,
+            maker,
+            temp_token_account,
+            &terms.receive_account,
+            escrow_account,
+            &spl_token::id(),
+            terms.receive_amount,
+            terms.unlock_slots,
+            terms.time_out_slots,
+            bond,
+            Self::bond_vault(terms.bond.as_ref()),
+        )?);
+        Ok(instructions)
+    }
+
+    /// Like [`create`](Self::create), but leaves the offered tokens in
+    /// `terms.send_account` and only approves the PDA to take them on
+    /// exchange. The approval is added to whatever the PDA may already move
+    /// out of that account for other escrows or signed orders.
+    /// `escrow_account` must sign alongside the maker.
+    pub fn create_delegated(
+        &self,
+        maker: &Pubkey,
+        terms: &CreateTerms,
+        escrow_account: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let pda = instruction::escrow_authority(&self.program_id);
+        let send_account = self.get_token_account(&terms.send_account)?;
+        let approved = if send_account.delegate == Some(pda).into() {
+            send_account.delegated_amount
+        } else {
+            0
+        };
+        let escrow_len = Escrow {
+            delegated_amount: terms.send_amount,
+            bond: terms.bond.as_ref().map_or(0, |bond| bond.amount),
+            ..Escrow::default()
+        }
+        .packed_len();
+        let escrow_rent = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(escrow_len)?;
+        let (fund_bond, bond) = self.fund_bond(maker, escrow_account, terms.bond.as_ref())?;
+        let mut instructions = vec![
+            spl_token::instruction::approve(
+                &spl_token::id(),
+                &terms.send_account,
+                &pda,
+                maker,
+                &[maker],
+                approved.saturating_add(terms.send_amount),
+            )?,
+            system_instruction::create_account(
+                maker,
+                escrow_account,
+                escrow_rent,
+                escrow_len as u64,
+                &self.program_id,
+            ),
+        ];
+        instructions.extend(fund_bond);
+        instructions.push(instruction::init_delegated_escrow(
+            &self.program_id,
+            maker,
+            &terms.send_account,
+            &terms.receive_account,
+            escrow_account,
+            terms.send_amount,
+            terms.receive_amount,
+            terms.unlock_slots,
+            terms.time_out_slots,
+            bond,
+            Self::bond_vault(terms.bond.as_ref()),
+        )?);
+        Ok(instructions)
+    }
+
+    /// The instructions putting `bond` where `InitEscrow` takes it from, and
+    /// the terms to open the swap with. A lamport bond is paid into the new
+    /// escrow account on top of its rent; a token bond moves `bond.amount`
+    /// into a new bond vault at `token.vault`, which must sign alongside the
+    /// maker.
+    fn fund_bond(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        bond: Option<&BondTerms>,
+    ) -> Result<(Vec<Instruction>, Option<instruction::BondTerms>)> {
+        let Some(bond) = bond else {
+            return Ok((vec![], None));
+        };
+        let recipient = bond.recipient.unwrap_or_default();
+        let Some(token) = &bond.token else {
+            let terms = instruction::BondTerms {
+                lamports: bond.amount,
+                recipient,
+            };
+            return Ok((
+                vec![system_instruction::transfer(
+                    maker,
+                    escrow_account,
+                    bond.amount,
+                )],
+                Some(terms),
+            ));
+        };
+        let token_rent = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(TokenAccount::LEN)?;
+        let terms = instruction::BondTerms {
+            lamports: 0,
+            recipient,
+        };
+        let instructions = vec![
+            system_instruction::create_account(
+                maker,
+                &token.vault,
+                token_rent,
+                TokenAccount::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &token.vault,
+                &token.mint,
+                maker,
+            )?,
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &token.send_account,
+                &token.vault,
+                maker,
+                &[],
+                bond.amount,
+            )?,
+        ];
+        Ok((instructions, Some(terms)))
+    }
+
+    /// The bond vault `InitEscrow` takes a token bond from, if `bond` is one
+    fn bond_vault(bond: Option<&BondTerms>) -> Option<&Pubkey> {
+        bond?.token.as_ref().map(|token| &token.vault)
+    }
+
+    /// Like [`create`](Self::create), but opens a hash lock for
+    /// `terms.counterparty` instead of a swap
+    pub fn create_hash_lock(
+        &self,
+        maker: &Pubkey,
+        terms: &HashLockTerms,
+        temp_token_account: &Pubkey,
+        escrow_account: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let mut instructions = self.fund_escrow(
+            maker,
+            &terms.send_mint,
+            &terms.send_account,
+            terms.send_amount,
+            temp_token_account,
+            escrow_account,
+            Escrow {
+                mode: EscrowMode::HashLock,
+                hash_kind: terms.hash_kind,
+                hash: terms.hash,
+                ..Escrow::default()
+            }
+            .packed_len(),
+        )?;
+        instructions.push(instruction::init_hash_lock(
+            &self.program_id,
+            maker,
+            temp_token_account,
+            escrow_account,
+            &spl_token::id(),
+            terms.hash_kind,
+            terms.hash,
+            &terms.counterparty,
+            terms.unlock_slots,
+            terms.time_out_slots,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Like [`create`](Self::create), but arms a dead man's switch for
+    /// `terms.beneficiary` instead of opening a swap
+    pub fn create_switch(
+        &self,
+        maker: &Pubkey,
+        terms: &SwitchTerms,
+        temp_token_account: &Pubkey,
+        escrow_account: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let mut instructions = self.fund_escrow(
+            maker,
+            &terms.send_mint,
+            &terms.send_account,
+            terms.send_amount,
+            temp_token_account,
+            escrow_account,
+            Escrow::LEN,
+        )?;
+        instructions.push(instruction::init_dead_mans_switch(
+            &self.program_id,
+            maker,
+            temp_token_account,
+            escrow_account,
+            &spl_token::id(),
+            &terms.beneficiary,
+            terms.time_out_slots,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Like [`create`](Self::create), but locks the maker's tokens for
+    /// themselves instead of opening a swap
+    pub fn lock(
+        &self,
+        maker: &Pubkey,
+        terms: &LockTerms,
+        temp_token_account: &Pubkey,
+        escrow_account: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let mut instructions = self.fund_escrow(
+            maker,
+            &terms.send_mint,
+            &terms.send_account,
+            terms.send_amount,
+            temp_token_account,
+            escrow_account,
+            Escrow::LEN,
+        )?;
+        instructions.push(instruction::lock(
+            &self.program_id,
+            maker,
+            temp_token_account,
+            escrow_account,
+            &spl_token::id(),
+            terms.unlock_slots,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Like [`create`](Self::create), but locks payment for `terms.payee` in
+    /// tranches instead of opening a swap
+    pub fn create_milestones(
+        &self,
+        maker: &Pubkey,
+        terms: &MilestonesTerms,
+        temp_token_account: &Pubkey,
+        escrow_account: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let total = terms
+            .milestones
+            .iter()
+            .try_fold(0u64, |total, milestone| total.checked_add(milestone.amount))
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        let mut instructions = self.fund_escrow(
+            maker,
+            &terms.send_mint,
+            &terms.send_account,
+            total,
+            temp_token_account,
+            escrow_account,
+            Escrow {
+                milestone_count: terms.milestones.len().min(MAX_MILESTONES) as u8,
+                ..Escrow::default()
+            }
+            .packed_len(),
+        )?;
+        instructions.push(instruction::init_milestones(
+            &self.program_id,
+            maker,
+            temp_token_account,
+            escrow_account,
+            &spl_token::id(),
+            &terms.payee,
+            &terms.milestones,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Like [`create`](Self::create), but opens an arbitrated deal instead
+    /// of a swap
+    pub fn create_arbitrated(
+        &self,
+        maker: &Pubkey,
+        terms: &ArbitratedTerms,
+        temp_token_account: &Pubkey,
+        escrow_account: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let mut instructions = self.fund_escrow(
+            maker,
+            &terms.send_mint,
+            &terms.send_account,
+            terms.send_amount,
+            temp_token_account,
+            escrow_account,
+            Escrow {
+                arbiter: terms.arbiter,
+                default_outcome: terms.default_outcome,
+                ..Escrow::default()
+            }
+            .packed_len(),
+        )?;
+        instructions.push(instruction::init_arbitrated(
+            &self.program_id,
+            maker,
+            temp_token_account,
+            escrow_account,
+            &spl_token::id(),
+            &terms.taker,
+            &terms.arbiter,
+            terms.default_outcome,
+            terms.time_out_slots,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Creates and funds the temp token account and allocates `escrow_len`
+    /// bytes for the escrow account, everything opening an escrow needs
+    /// before the program runs
+    #[allow(clippy::too_many_arguments)]
+    fn fund_escrow(
+        &self,
+        maker: &Pubkey,
+        send_mint: &Pubkey,
+        send_account: &Pubkey,
+        send_amount: u64,
+        temp_token_account: &Pubkey,
+        escrow_account: &Pubkey,
+        escrow_len: usize,
+    ) -> Result<Vec<Instruction>> {
+        let token_rent = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(TokenAccount::LEN)?;
+        let escrow_rent = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(escrow_len)?;
+        Ok(vec![
+            system_instruction::create_account(
+                maker,
+                temp_token_account,
+                token_rent,
+                TokenAccount::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                temp_token_account,
+                send_mint,
+                maker,
+            )?,
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                send_account,
+                temp_token_account,
+                maker,
+                &[],
+                send_amount,
+            )?,
+            system_instruction::create_account(
+                maker,
+                escrow_account,
+                escrow_rent,
+                escrow_len as u64,
+                &self.program_id,
+            ),
+        ])
+    }
+
+    /// Takes the escrow. When no accounts are given the taker's associated
+    /// token accounts are used, and the receiving one is created if missing,
+    /// as is the maker's for a token bond to go back to. An escrow with an
+    /// allow list needs the list to prove the taker is on it, and one with a
+    /// callback program passes it `callback_accounts`.
+    pub fn take(
+        &self,
+        taker: &Pubkey,
+        escrow_account: &Pubkey,
+        send_account: Option<Pubkey>,
+        receive_account: Option<Pubkey>,
+        allow_list: Option<&AllowList>,
+        callback_accounts: &[AccountMeta],
+    ) -> Result<Vec<Instruction>> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let proof = allow_list_proof(&escrow, taker, allow_list)?;
+        let vault = self.get_token_account(&escrow.temp_token_account_pubkey)?;
+        let maker_receive =
+            self.get_token_account(&escrow.initializer_token_to_receive_account_pubkey)?;
+
+        let mut instructions = vec![];
+        let send_account = send_account
+            .unwrap_or_else(|| get_associated_token_address(taker, &maker_receive.mint));
+        let receive_account = match receive_account {
+            Some(account) => account,
+            None => {
+                instructions.push(create_associated_token_account_idempotent(
+                    taker,
+                    taker,
+                    &vault.mint,
+                    &spl_token::id(),
+                ));
+                get_associated_token_address(taker, &vault.mint)
+            }
+        };
+        let mut exchange = instruction::exchange(
+            &self.program_id,
+            taker,
+            &send_account,
+            &receive_account,
+            &escrow.temp_token_account_pubkey,
+            &escrow.initializer_pubkey,
+            &escrow.initializer_token_to_receive_account_pubkey,
+            escrow_account,
+            &spl_token::id(),
+            if escrow.is_delegated() {
+                escrow.delegated_amount
+            } else {
+                vault.amount
+            },
+            &proof,
+        )?;
+        let (create_bond_account, bond_accounts) = self.bond_accounts(taker, &escrow, false)?;
+        exchange.accounts.extend(bond_accounts);
+        if escrow.has_price_condition() {
+            exchange
+                .accounts
+                .push(AccountMeta::new_readonly(escrow.oracle, false));
+        }
+        exchange.accounts.extend(self.callback_accounts(
+            escrow_account,
+            &escrow,
+            callback_accounts,
+        ));
+        instructions.extend(create_bond_account);
+        instructions.push(exchange);
+        Ok(instructions)
+    }
+
+    /// Takes every escrow in `escrow_accounts` in one instruction, cheapest
+    /// first, paying at most `max_total`. They all have to swap the same two
+    /// mints, which the first one decides; the accounts default as in
+    /// [`take`](Self::take).
+    pub fn take_many(
+        &self,
+        taker: &Pubkey,
+        escrow_accounts: &[Pubkey],
+        max_total: u64,
+        skip_unfillable: bool,
+        send_account: Option<Pubkey>,
+        receive_account: Option<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        let mut escrows = Vec::with_capacity(escrow_accounts.len());
+        for escrow_account in escrow_accounts {
+            let escrow = self.get_escrow(escrow_account)?;
+            escrows.push(EscrowToTake {
+                escrow: *escrow_account,
+                vault: escrow.temp_token_account_pubkey,
+                maker: escrow.initializer_pubkey,
+                maker_receive_account: escrow.initializer_token_to_receive_account_pubkey,
+            });
+        }
+        let first = escrows
+            .first()
+            .ok_or(ClientError::Program(EscrowError::NothingFilled.into()))?;
+        let offered_mint = self.get_token_account(&first.vault)?.mint;
+        let requested_mint = self.get_token_account(&first.maker_receive_account)?.mint;
+
+        let mut instructions = vec![];
+        let send_account =
+            send_account.unwrap_or_else(|| get_associated_token_address(taker, &requested_mint));
+        let receive_account = match receive_account {
+            Some(account) => account,
+            None => {
+                instructions.push(create_associated_token_account_idempotent(
+                    taker,
+                    taker,
+                    &offered_mint,
+                    &spl_token::id(),
+                ));
+                get_associated_token_address(taker, &offered_mint)
+            }
+        };
+        instructions.push(instruction::exchange_many(
+            &self.program_id,
+            taker,
+            &send_account,
+            &receive_account,
+            &spl_token::id(),
+            &escrows,
+            max_total,
+            skip_unfillable,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Settles the ring of `escrow_accounts`, in order: each maker is paid by
+    /// the next one's vault and the last by the first's
+    pub fn settle_ring(
+        &self,
+        settler: &Pubkey,
+        escrow_accounts: &[Pubkey],
+    ) -> Result<Vec<Instruction>> {
+        let mut escrows = Vec::with_capacity(escrow_accounts.len());
+        for escrow_account in escrow_accounts {
+            let escrow = self.get_escrow(escrow_account)?;
+            escrows.push(EscrowToTake {
+                escrow: *escrow_account,
+                vault: escrow.temp_token_account_pubkey,
+                maker: escrow.initializer_pubkey,
+                maker_receive_account: escrow.initializer_token_to_receive_account_pubkey,
+            });
+        }
+        Ok(vec![instruction::settle_ring(
+            &self.program_id,
+            settler,
+            &spl_token::id(),
+            &escrows,
+        )?])
+    }
+
+    /// Opens a pool. `pool`, `vault` and `proceeds_vault` are fresh accounts
+    /// that must sign alongside the creator.
+    pub fn open_pool(
+        &self,
+        creator: &Pubkey,
+        terms: &PoolTerms,
+        pool: &Pubkey,
+        vault: &Pubkey,
+        proceeds_vault: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        let token_rent = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(TokenAccount::LEN)?;
+        let pool_rent = self.rpc.get_minimum_balance_for_rent_exemption(Pool::LEN)?;
+        let mut instructions = vec![];
+        for (account, mint) in [
+            (vault, &terms.send_mint),
+            (proceeds_vault, &terms.receive_mint),
+        ] {
+            instructions.extend([
+                system_instruction::create_account(
+                    creator,
+                    account,
+                    token_rent,
+                    TokenAccount::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_account(
+                    &spl_token::id(),
+                    account,
+                    mint,
+                    creator,
+                )?,
+            ]);
+        }
+        instructions.push(system_instruction::create_account(
+            creator,
+            pool,
+            pool_rent,
+            Pool::LEN as u64,
+            &self.program_id,
+        ));
+        instructions.push(instruction::init_pool(
+            &self.program_id,
+            creator,
+            pool,
+            vault,
+            proceeds_vault,
+            &spl_token::id(),
+            terms.offered_per_lot,
+            terms.requested_per_lot,
+            terms.unlock_slots,
+            terms.time_out_slots,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Deposits `amount` into the pool from `send_account`, or the maker's
+    /// associated token account
+    pub fn deposit_pool(
+        &self,
+        maker: &Pubkey,
+        pool: &Pubkey,
+        amount: u64,
+        send_account: Option<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        let pool_info = self.get_pool(pool)?;
+        let send_account = match send_account {
+            Some(account) => account,
+            None => {
+                let mint = self.get_token_account(&pool_info.vault)?.mint;
+                get_associated_token_address(maker, &mint)
+            }
+        };
+        Ok(vec![instruction::deposit_pool(
+            &self.program_id,
+            maker,
+            &send_account,
+            pool,
+            &pool_info.vault,
+            &spl_token::id(),
+            amount,
+        )?])
+    }
+
+    /// Buys `lots` lots out of the pool. The accounts default as in
+    /// [`take`](Self::take).
+    pub fn fill_pool(
+        &self,
+        taker: &Pubkey,
+        pool: &Pubkey,
+        lots: u64,
+        send_account: Option<Pubkey>,
+        receive_account: Option<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        let pool_info = self.get_pool(pool)?;
+        let offered_mint = self.get_token_account(&pool_info.vault)?.mint;
+        let requested_mint = self.get_token_account(&pool_info.proceeds_vault)?.mint;
+
+        let mut instructions = vec![];
+        let send_account =
+            send_account.unwrap_or_else(|| get_associated_token_address(taker, &requested_mint));
+        let receive_account = match receive_account {
+            Some(account) => account,
+            None => {
+                instructions.push(create_associated_token_account_idempotent(
+                    taker,
+                    taker,
+                    &offered_mint,
+                    &spl_token::id(),
+                ));
+                get_associated_token_address(taker, &offered_mint)
+            }
+        };
+        instructions.push(instruction::fill_pool(
+            &self.program_id,
+            taker,
+            &send_account,
+            &receive_account,
+            pool,
+            &pool_info.vault,
+            &pool_info.proceeds_vault,
+            &spl_token::id(),
+            lots,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Withdraws the maker's share of the pool into their associated token
+    /// accounts, creating them if missing
+    pub fn withdraw_pool(&self, maker: &Pubkey, pool: &Pubkey) -> Result<Vec<Instruction>> {
+        let pool_info = self.get_pool(pool)?;
+        let mut instructions = vec![];
+        let mut payout_accounts = vec![];
+        for vault in [&pool_info.vault, &pool_info.proceeds_vault] {
+            let mint = self.get_token_account(vault)?.mint;
+            instructions.push(create_associated_token_account_idempotent(
+                maker,
+                maker,
+                &mint,
+                &spl_token::id(),
+            ));
+            payout_accounts.push(get_associated_token_address(maker, &mint));
+        }
+        instructions.push(instruction::withdraw_pool(
+            &self.program_id,
+            maker,
+            pool,
+            &pool_info.vault,
+            &pool_info.proceeds_vault,
+            &payout_accounts[0],
+            &payout_accounts[1],
+            &pool_info.creator,
+            &spl_token::id(),
+        )?);
+        Ok(instructions)
+    }
+
+    /// Sets the premium for reserving a swap; 0 stops offering reservations
+    pub fn set_premium(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        premium: u64,
+    ) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::set_premium(
+            &self.program_id,
+            maker,
+            escrow_account,
+            premium,
+        )?])
+    }
+
+    /// Makes `taker` the only one who can take a swap for the first
+    /// `exclusive_slots` slots after it unlocks. `Pubkey::default()` makes it
+    /// public again.
+    pub fn set_exclusive_taker(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        taker: &Pubkey,
+        exclusive_slots: u64,
+    ) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::set_exclusive_taker(
+            &self.program_id,
+            maker,
+            escrow_account,
+            taker,
+            exclusive_slots,
+        )?])
+    }
+
+    /// Lets a swap be taken only while an oracle's price meets `terms`.
+    /// `None` drops the condition.
+    pub fn set_price_condition(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        terms: Option<&PriceTerms>,
+    ) -> Result<Vec<Instruction>> {
+        let instruction = match terms {
+            Some(terms) => instruction::set_price_condition(
+                &self.program_id,
+                maker,
+                escrow_account,
+                &terms.oracle,
+                terms.oracle_kind,
+                terms.condition,
+                terms.trigger_price,
+                terms.trigger_expo,
+                terms.max_staleness,
+            )?,
+            None => instruction::set_price_condition(
+                &self.program_id,
+                maker,
+                escrow_account,
+                &Pubkey::default(),
+                OracleKind::default(),
+                PriceCondition::None,
+                0,
+                0,
+                0,
+            )?,
+        };
+        Ok(vec![instruction])
+    }
+
+    /// Has `Exchange` and `Cancel` call `callback_program` once the swap
+    /// settles. `None` stops calling one.
+    pub fn set_callback(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        callback_program: Option<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::set_callback(
+            &self.program_id,
+            maker,
+            escrow_account,
+            &callback_program.unwrap_or_default(),
+        )?])
+    }
+
+    /// Lets only the takers on `allow_list` take a swap. `None` lets anyone
+    /// take it again.
+    pub fn set_allow_list(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        allow_list: Option<&AllowList>,
+    ) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::set_allow_list(
+            &self.program_id,
+            maker,
+            escrow_account,
+            &allow_list.map_or([0; 32], AllowList::root),
+        )?])
+    }
+
+    /// Reserves a swap for the taker at its current premium, paid from
+    /// `send_account` or the taker's associated token account for the
+    /// requested mint
+    pub fn reserve(
+        &self,
+        taker: &Pubkey,
+        escrow_account: &Pubkey,
+        send_account: Option<Pubkey>,
+        allow_list: Option<&AllowList>,
+    ) -> Result<Vec<Instruction>> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let proof = allow_list_proof(&escrow, taker, allow_list)?;
+        let send_account = match send_account {
+            Some(account) => account,
+            None => {
+                let maker_receive =
+                    self.get_token_account(&escrow.initializer_token_to_receive_account_pubkey)?;
+                get_associated_token_address(taker, &maker_receive.mint)
+            }
+        };
+        Ok(vec![instruction::reserve(
+            &self.program_id,
+            taker,
+            &send_account,
+            &escrow.initializer_token_to_receive_account_pubkey,
+            escrow_account,
+            &spl_token::id(),
+            escrow.premium,
+            &proof,
+        )?])
+    }
+
+    /// Lets the program's PDA move up to `amount` out of `send_account`, which
+    /// is what every order the maker signs sells from
+    pub fn approve_orders(
+        &self,
+        maker: &Pubkey,
+        send_account: &Pubkey,
+        amount: u64,
+    ) -> Result<Vec<Instruction>> {
+        Ok(vec![spl_token::instruction::approve(
+            &spl_token::id(),
+            send_account,
+            &instruction::escrow_authority(&self.program_id),
+            maker,
+            &[maker],
+            amount,
+        )?])
+    }
+
+    /// The nonce the maker's next orders have to be signed with
+    pub fn order_nonce(&self, maker: &Pubkey) -> Result<u64> {
+        let address = instruction::order_nonce_address(&self.program_id, maker);
+        let account = self
+            .rpc
+            .get_account_with_commitment(&address, self.rpc.commitment())?
+            .value;
+        match account {
+            Some(account) if account.owner == self.program_id => {
+                Ok(OrderNonce::unpack(&account.data)?.nonce)
+            }
+            _ => Ok(0),
+        }
+    }
+
+    /// An order at the maker's current nonce, selling `offered_amount` out of
+    /// `send_account` for `requested_amount` paid into `receive_account`, that
+    /// can be filled for `expires_in` slots, by `taker` only if set
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_order(
+        &self,
+        maker: &Pubkey,
+        send_account: &Pubkey,
+        receive_account: &Pubkey,
+        offered_amount: u64,
+        requested_amount: u64,
+        expires_in: u64,
+        taker: Option<Pubkey>,
+    ) -> Result<Order> {
+        Ok(Order {
+            maker: *maker,
+            maker_send_account: *send_account,
+            maker_receive_account: *receive_account,
+            offered_amount,
+            requested_amount,
+            nonce: self.order_nonce(maker)?,
+            expires_at: self.rpc.get_slot()?.saturating_add(expires_in),
+            taker: taker.unwrap_or_default(),
+        })
+    }
+
+    /// Fills a signed order, paying from `send_account` or the taker's
+    /// associated token account for the requested mint, into `receive_account`
+    /// or the taker's associated token account for the offered mint, created
+    /// if missing
+    pub fn fill_order(
+        &self,
+        taker: &Pubkey,
+        signed: &SignedOrder,
+        send_account: Option<Pubkey>,
+        receive_account: Option<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        let order = &signed.order;
+        let maker_send = self.get_token_account(&order.maker_send_account)?;
+        let maker_receive = self.get_token_account(&order.maker_receive_account)?;
+
+        let mut instructions = vec![];
+        let send_account = send_account
+            .unwrap_or_else(|| get_associated_token_address(taker, &maker_receive.mint));
+        let receive_account = match receive_account {
+            Some(account) => account,
+            None => {
+                instructions.push(create_associated_token_account_idempotent(
+                    taker,
+                    taker,
+                    &maker_send.mint,
+                    &spl_token::id(),
+                ));
+                get_associated_token_address(taker, &maker_send.mint)
+            }
+        };
+        // the program looks for the signature check right before the fill
+        instructions.push(signed.ed25519_instruction(&self.program_id));
+        instructions.push(instruction::fill_signed_order(
+            &self.program_id,
+            taker,
+            &send_account,
+            &receive_account,
+            order,
+            &spl_token::id(),
+        )?);
+        Ok(instructions)
+    }
+
+    /// Cancels every order the maker has signed so far
+    pub fn cancel_all_orders(&self, maker: &Pubkey) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::cancel_all_orders(
+            &self.program_id,
+            maker,
+        )?])
+    }
+
+    /// Redeems a hash lock with its preimage, paying into `receive_account`
+    /// or the counterparty's associated token account, created if missing
+    pub fn redeem(
+        &self,
+        counterparty: &Pubkey,
+        escrow_account: &Pubkey,
+        receive_account: Option<Pubkey>,
+        preimage: &[u8],
+    ) -> Result<Vec<Instruction>> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let vault = self.get_token_account(&escrow.temp_token_account_pubkey)?;
+
+        let mut instructions = vec![];
+        let receive_account = match receive_account {
+            Some(account) => account,
+            None => {
+                instructions.push(create_associated_token_account_idempotent(
+                    counterparty,
+                    counterparty,
+                    &vault.mint,
+                    &spl_token::id(),
+                ));
+                get_associated_token_address(counterparty, &vault.mint)
+            }
+        };
+        instructions.push(instruction::redeem(
+            &self.program_id,
+            counterparty,
+            &receive_account,
+            &escrow.temp_token_account_pubkey,
+            &escrow.initializer_pubkey,
+            escrow_account,
+            &spl_token::id(),
+            preimage,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Claims a timed-out dead man's switch, paying into `receive_account` or
+    /// the beneficiary's associated token account, created if missing
+    pub fn claim(
+        &self,
+        beneficiary: &Pubkey,
+        escrow_account: &Pubkey,
+        receive_account: Option<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let vault = self.get_token_account(&escrow.temp_token_account_pubkey)?;
+
+        let mut instructions = vec![];
+        let receive_account = match receive_account {
+            Some(account) => account,
+            None => {
+                instructions.push(create_associated_token_account_idempotent(
+                    beneficiary,
+                    beneficiary,
+                    &vault.mint,
+                    &spl_token::id(),
+                ));
+                get_associated_token_address(beneficiary, &vault.mint)
+            }
+        };
+        instructions.push(instruction::claim(
+            &self.program_id,
+            beneficiary,
+            &receive_account,
+            &escrow.temp_token_account_pubkey,
+            &escrow.initializer_pubkey,
+            escrow_account,
+            &spl_token::id(),
+        )?);
+        Ok(instructions)
+    }
+
+    /// Cancels the escrow, refunding into `refund_account` or the maker's
+    /// associated token account for the vault's mint. A bond is forfeited if
+    /// the escrow can be taken at the current slot, and refunded otherwise.
+    /// An escrow with a callback program passes it `callback_accounts`.
+    pub fn cancel(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        refund_account: Option<Pubkey>,
+        callback_accounts: &[AccountMeta],
+    ) -> Result<Vec<Instruction>> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let refund_account = match refund_account {
+            Some(account) => account,
+            None => {
+                let vault = self.get_token_account(&escrow.temp_token_account_pubkey)?;
+                get_associated_token_address(maker, &vault.mint)
+            }
+        };
+        let slot = self.rpc.get_slot()?;
+        let forfeit = LockStatus::at(&escrow, slot) == LockStatus::Open;
+        let (mut instructions, bond_accounts) = self.bond_accounts(maker, &escrow, forfeit)?;
+        let mut cancel = instruction::cancel(
+            &self.program_id,
+            maker,
+            &escrow.temp_token_account_pubkey,
+            &refund_account,
+            escrow_account,
+            &spl_token::id(),
+        )?;
+        cancel.accounts.extend(bond_accounts);
+        cancel
+            .accounts
+            .extend(self.callback_accounts(escrow_account, &escrow, callback_accounts));
+        instructions.push(cancel);
+        Ok(instructions)
+    }
+
+    /// The accounts `Exchange` and `Cancel` take last to call the escrow's
+    /// callback program, `passed` being the ones it gets; nothing if the
+    /// escrow has no callback
+    fn callback_accounts(
+        &self,
+        escrow_account: &Pubkey,
+        escrow: &Escrow,
+        passed: &[AccountMeta],
+    ) -> Vec<AccountMeta> {
+        if !escrow.has_callback() {
+            return vec![];
+        }
+        let mut accounts = vec![
+            AccountMeta::new_readonly(escrow.callback_program, false),
+            AccountMeta::new_readonly(
+                instruction::callback_authority(&self.program_id, escrow_account),
+                false,
+            ),
+        ];
+        accounts.extend_from_slice(passed);
+        accounts
+    }
+
+    /// The accounts `Exchange` and `Cancel` take after their own to settle
+    /// the escrow's bond, and the instructions creating the associated token
+    /// account a token bond goes to, paid by `payer`
+    fn bond_accounts(
+        &self,
+        payer: &Pubkey,
+        escrow: &Escrow,
+        forfeit: bool,
+    ) -> Result<(Vec<Instruction>, Vec<AccountMeta>)> {
+        if escrow.bond == 0 {
+            return Ok((vec![], vec![]));
+        }
+        // without a recipient, a forfeited bond goes to the fee vault
+        let recipient = match escrow.bond_recipient == Pubkey::default() {
+            true => instruction::fee_vault(&self.program_id),
+            false => escrow.bond_recipient,
+        };
+        if escrow.bond_vault == Pubkey::default() {
+            let accounts = match forfeit {
+                true => vec![AccountMeta::new(recipient, false)],
+                false => vec![],
+            };
+            return Ok((vec![], accounts));
+        }
+        let owner = match forfeit {
+            true => recipient,
+            false => escrow.initializer_pubkey,
+        };
+        let mint = self.get_token_account(&escrow.bond_vault)?.mint;
+        Ok((
+            vec![create_associated_token_account_idempotent(
+                payer,
+                &owner,
+                &mint,
+                &spl_token::id(),
+            )],
+            vec![
+                AccountMeta::new(escrow.bond_vault, false),
+                AccountMeta::new(get_associated_token_address(&owner, &mint), false),
+            ],
+        ))
+    }
+
+    /// Withdraws an unlocked savings lock into `refund_account` or the maker's
+    /// associated token account for the vault's mint
+    pub fn unlock(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        refund_account: Option<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let refund_account = match refund_account {
+            Some(account) => account,
+            None => {
+                let vault = self.get_token_account(&escrow.temp_token_account_pubkey)?;
+                get_associated_token_address(maker, &vault.mint)
+            }
+        };
+        Ok(vec![instruction::unlock(
+            &self.program_id,
+            maker,
+            &escrow.temp_token_account_pubkey,
+            &refund_account,
+            escrow_account,
+            &spl_token::id(),
+        )?])
+    }
+
+    pub fn extend_lock(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        extra_slots: u64,
+    ) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::extend_lock(
+            &self.program_id,
+            maker,
+            escrow_account,
+            extra_slots,
+        )?])
+    }
+
+    /// Pays one milestone to the payee's associated token account, created
+    /// if missing
+    pub fn approve_milestone(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        index: u8,
+    ) -> Result<Vec<Instruction>> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let vault = self.get_token_account(&escrow.temp_token_account_pubkey)?;
+        Ok(vec![
+            create_associated_token_account_idempotent(
+                maker,
+                &escrow.counterparty,
+                &vault.mint,
+                &spl_token::id(),
+            ),
+            instruction::approve_milestone(
+                &self.program_id,
+                maker,
+                &get_associated_token_address(&escrow.counterparty, &vault.mint),
+                &escrow.temp_token_account_pubkey,
+                escrow_account,
+                &spl_token::id(),
+                index,
+            )?,
+        ])
+    }
+
+    /// Takes back the milestones past their deadline, into `refund_account`
+    /// or the maker's associated token account
+    pub fn refund_unapproved(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        refund_account: Option<Pubkey>,
+    ) -> Result<Vec<Instruction>> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let refund_account = match refund_account {
+            Some(account) => account,
+            None => {
+                let vault = self.get_token_account(&escrow.temp_token_account_pubkey)?;
+                get_associated_token_address(maker, &vault.mint)
+            }
+        };
+        Ok(vec![instruction::refund_unapproved(
+            &self.program_id,
+            maker,
+            &refund_account,
+            &escrow.temp_token_account_pubkey,
+            escrow_account,
+            &spl_token::id(),
+        )?])
+    }
+
+    pub fn mark_delivered(
+        &self,
+        taker: &Pubkey,
+        escrow_account: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::mark_delivered(
+            &self.program_id,
+            taker,
+            escrow_account,
+        )?])
+    }
+
+    pub fn open_dispute(
+        &self,
+        party: &Pubkey,
+        escrow_account: &Pubkey,
+    ) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::open_dispute(
+            &self.program_id,
+            party,
+            escrow_account,
+        )?])
+    }
+
+    /// Pays an arbitrated deal's vault to the taker's associated token
+    /// account, created if missing
+    pub fn release(&self, maker: &Pubkey, escrow_account: &Pubkey) -> Result<Vec<Instruction>> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let vault = self.get_token_account(&escrow.temp_token_account_pubkey)?;
+        Ok(vec![
+            create_associated_token_account_idempotent(
+                maker,
+                &escrow.counterparty,
+                &vault.mint,
+                &spl_token::id(),
+            ),
+            instruction::release(
+                &self.program_id,
+                maker,
+                &get_associated_token_address(&escrow.counterparty, &vault.mint),
+                &escrow.temp_token_account_pubkey,
+                escrow_account,
+                &spl_token::id(),
+            )?,
+        ])
+    }
+
+    /// Rules on a disputed deal, splitting the vault between the taker's and
+    /// the maker's associated token accounts
+    pub fn resolve(
+        &self,
+        arbiter: &Pubkey,
+        escrow_account: &Pubkey,
+        taker_bps: u16,
+    ) -> Result<Vec<Instruction>> {
+        let (mut instructions, taker_account, maker_account) =
+            self.payout_accounts(arbiter, escrow_account)?;
+        let escrow = self.get_escrow(escrow_account)?;
+        instructions.push(instruction::resolve(
+            &self.program_id,
+            arbiter,
+            &taker_account,
+            &maker_account,
+            &escrow.initializer_pubkey,
+            &escrow.temp_token_account_pubkey,
+            escrow_account,
+            &spl_token::id(),
+            taker_bps,
+        )?);
+        Ok(instructions)
+    }
+
+    /// Settles a timed-out, undisputed deal; `payer` only pays the fees and
+    /// for any associated token account that has to be created
+    pub fn settle(&self, payer: &Pubkey, escrow_account: &Pubkey) -> Result<Vec<Instruction>> {
+        let (mut instructions, taker_account, maker_account) =
+            self.payout_accounts(payer, escrow_account)?;
+        let escrow = self.get_escrow(escrow_account)?;
+        instructions.push(instruction::settle(
+            &self.program_id,
+            &taker_account,
+            &maker_account,
+            &escrow.initializer_pubkey,
+            &escrow.temp_token_account_pubkey,
+            escrow_account,
+            &spl_token::id(),
+        )?);
+        Ok(instructions)
+    }
+
+    /// The taker's and the maker's associated token accounts for a deal's
+    /// vault, and the instructions creating them if they are missing
+    fn payout_accounts(
+        &self,
+        payer: &Pubkey,
+        escrow_account: &Pubkey,
+    ) -> Result<(Vec<Instruction>, Pubkey, Pubkey)> {
+        let escrow = self.get_escrow(escrow_account)?;
+        let vault = self.get_token_account(&escrow.temp_token_account_pubkey)?;
+        let owners = [escrow.counterparty, escrow.initializer_pubkey];
+        let instructions = owners
+            .iter()
+            .map(|owner| {
+                create_associated_token_account_idempotent(
+                    payer,
+                    owner,
+                    &vault.mint,
+                    &spl_token::id(),
+                )
+            })
+            .collect();
+        Ok((
+            instructions,
+            get_associated_token_address(&owners[0], &vault.mint),
+            get_associated_token_address(&owners[1], &vault.mint),
+        ))
+    }
+
+    pub fn reset_lock(
+        &self,
+        maker: &Pubkey,
+        escrow_account: &Pubkey,
+        unlock_slots: u64,
+        time_out_slots: u64,
+    ) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::reset_time_lock(
+            &self.program_id,
+            maker,
+            escrow_account,
+            unlock_slots,
+            time_out_slots,
+        )?])
+    }
+
+    /// Rewrites the escrow in the current layout; the maker pays any extra rent
+    pub fn migrate(&self, maker: &Pubkey, escrow_account: &Pubkey) -> Result<Vec<Instruction>> {
+        Ok(vec![instruction::migrate(
+            &self.program_id,
+            maker,
+            escrow_account,
+        )?])
+    }
+
+    /// Picks the lifetime for a new transaction: the given blockhash, the
+    /// value stored in `nonce`'s account, or the cluster's latest blockhash.
+    pub fn lifetime(
+        &self,
+        blockhash: Option<Hash>,
+        nonce: Option<(Pubkey, Pubkey)>,
+    ) -> Result<Lifetime> {
+        Ok(match nonce {
+            Some((account, authority)) => {
+                let blockhash = match blockhash {
+                    Some(blockhash) => blockhash,
+                    None => self.nonce_blockhash(&account)?,
+                };
+                Lifetime::Nonce {
+                    account,
+                    authority,
+                    blockhash,
+                }
+            }
+            None => Lifetime::Blockhash(match blockhash {
+                Some(blockhash) => blockhash,
+                None => self.rpc.get_latest_blockhash()?,
+            }),
+        })
+    }
+
+    /// The durable blockhash currently stored in a nonce account
+    pub fn nonce_blockhash(&self, nonce_account: &Pubkey) -> Result<Hash> {
+        let account = nonce_utils::get_account_with_commitment(
+            &self.rpc,
+            nonce_account,
+            self.rpc.commitment(),
+        )
+        .map_err(|e| ClientError::Nonce(e.to_string()))?;
+        let data = nonce_utils::data_from_account(&account)
+            .map_err(|e| ClientError::Nonce(e.to_string()))?;
+        Ok(data.blockhash())
+    }
+
+    /// Sends a fully signed transaction or, with `dry_run`, only simulates it
+    pub fn submit(&self, transaction: &Transaction, dry_run: bool) -> Result<Outcome> {
+        if dry_run {
+            Ok(Outcome::Simulated(
+                self.rpc.simulate_transaction(transaction)?.value,
+            ))
+        } else {
+            Ok(Outcome::Sent(
+                self.rpc.send_and_confirm_transaction(transaction)?,
+            ))
+        }
+    }
+}
+
+/// The taker's proof for an escrow's allow list, empty if it has none
+fn allow_list_proof(
+    escrow: &Escrow,
+    taker: &Pubkey,
+    allow_list: Option<&AllowList>,
+) -> Result<Vec<[u8; 32]>> {
+    if !escrow.has_allow_list() {
+        return Ok(vec![]);
+    }
+    allow_list
+        .filter(|allow_list| allow_list.root() == escrow.allow_list_root)
+        .and_then(|allow_list| allow_list.proof(taker))
+        .ok_or(ClientError::NotAllowed(*taker))
+}
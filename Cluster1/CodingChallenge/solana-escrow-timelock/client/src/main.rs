@@ -0,0 +1,1664 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use escrow_client::{
+    allow_list::AllowList, events, offline, orders, ArbitratedTerms, BondTerms, CreateTerms,
+    EscrowClient, HashLockTerms, LockStatus, LockTerms, MilestonesTerms, Outcome, PoolTerms,
+    PriceTerms, SwitchTerms, TokenBond,
+};
+use solana_escrow::{
+    instruction::MilestoneTerms,
+    oracle::{OracleKind, PriceCondition},
+    state::{DefaultOutcome, EscrowMode, HashKind, MAX_PREIMAGE_LEN},
+};
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+#[derive(Parser)]
+#[command(name = "escrow", about = "Open, inspect and settle timelocked escrows")]
+struct Cli {
+    /// RPC endpoint of the cluster
+    #[arg(long, short, global = true, default_value = "http://localhost:8899")]
+    url: String,
+    /// Keypair file of the maker or taker [default: ~/.config/solana/id.json]
+    #[arg(long, short, global = true)]
+    keypair: Option<PathBuf>,
+    /// Address of the escrow program
+    #[arg(long, global = true, default_value_t = solana_escrow::id())]
+    program_id: Pubkey,
+    /// Simulate the transaction and print its logs instead of sending it
+    #[arg(long, global = true)]
+    dry_run: bool,
+    #[command(flatten)]
+    offline: OfflineArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Options for building transactions that are signed somewhere else
+#[derive(Args)]
+struct OfflineArgs {
+    /// Write the unsigned transaction to this file instead of sending it
+    #[arg(long, global = true)]
+    export: Option<PathBuf>,
+    /// Act for this public key instead of loading its keypair (with --export)
+    #[arg(long, global = true)]
+    signer: Option<Pubkey>,
+    /// Recent blockhash, or the durable nonce value with --nonce [default: fetched from the cluster]
+    #[arg(long, global = true)]
+    blockhash: Option<Hash>,
+    /// Durable nonce account that keeps the transaction valid until it is submitted
+    #[arg(long, global = true)]
+    nonce: Option<Pubkey>,
+    /// Authority of the nonce account [default: the signer]
+    #[arg(long, global = true, requires = "nonce")]
+    nonce_authority: Option<Pubkey>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Lock tokens in a new escrow
+    Create {
+        /// Mint of the tokens the maker locks up
+        #[arg(long)]
+        send_mint: Pubkey,
+        /// How many of those tokens to lock
+        #[arg(long)]
+        send_amount: u64,
+        /// Token account to take them from [default: the maker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// Mint of the tokens the maker wants in return
+        #[arg(long)]
+        receive_mint: Pubkey,
+        /// How many of those tokens the maker wants
+        #[arg(long)]
+        receive_amount: u64,
+        /// Token account to pay them into [default: the maker's associated token account]
+        #[arg(long)]
+        receive_account: Option<Pubkey>,
+        /// Slots from now until the escrow can be taken
+        #[arg(long, default_value_t = 0)]
+        unlock_slots: u64,
+        /// Slots after unlocking during which the escrow can be taken
+        #[arg(long)]
+        time_out_slots: u64,
+        /// Put up a bond of this many lamports, or tokens with --bond-mint, lost on cancelling
+        /// while the escrow can be taken
+        #[arg(long)]
+        bond: Option<u64>,
+        /// Who gets the bond if it is forfeited [default: the program's fee vault]
+        #[arg(long, requires = "bond")]
+        bond_recipient: Option<Pubkey>,
+        /// Mint of a token bond [default: the bond is in lamports]
+        #[arg(long, requires = "bond")]
+        bond_mint: Option<Pubkey>,
+        /// Token account to take a token bond from [default: the maker's associated token account]
+        #[arg(long, requires = "bond_mint")]
+        bond_account: Option<Pubkey>,
+        /// Leave the tokens in the send account and only approve the program to take them on exchange
+        #[arg(long)]
+        delegate: bool,
+    },
+    /// Lock tokens for a counterparty who claims them with the preimage of a hash
+    HashLock {
+        /// Mint of the tokens the maker locks up
+        #[arg(long)]
+        send_mint: Pubkey,
+        /// How many of those tokens to lock
+        #[arg(long)]
+        send_amount: u64,
+        /// Token account to take them from [default: the maker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// The only account that can redeem the escrow
+        #[arg(long)]
+        counterparty: Pubkey,
+        /// Hash of the preimage, as 64 hex digits
+        #[arg(long, value_parser = parse_hex32)]
+        hash: [u8; 32],
+        /// Function `hash` was computed with
+        #[arg(long, value_enum, default_value_t = HashArg::Sha256)]
+        hash_kind: HashArg,
+        /// Slots from now until the escrow can be redeemed
+        #[arg(long, default_value_t = 0)]
+        unlock_slots: u64,
+        /// Slots after unlocking during which the escrow can be redeemed
+        #[arg(long)]
+        time_out_slots: u64,
+    },
+    /// Lock tokens for a beneficiary who can claim them unless the lock is reset in time
+    Switch {
+        /// Mint of the tokens the maker locks up
+        #[arg(long)]
+        send_mint: Pubkey,
+        /// How many of those tokens to lock
+        #[arg(long)]
+        send_amount: u64,
+        /// Token account to take them from [default: the maker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// The account that can claim the tokens once the switch times out
+        #[arg(long)]
+        beneficiary: Pubkey,
+        /// Slots from now until the beneficiary can claim; extend with reset-lock
+        #[arg(long)]
+        time_out_slots: u64,
+    },
+    /// Lock your own tokens until a slot, with no trade
+    Lock {
+        /// Mint of the tokens to lock up
+        #[arg(long)]
+        send_mint: Pubkey,
+        /// How many of those tokens to lock
+        #[arg(long)]
+        send_amount: u64,
+        /// Token account to take them from [default: the maker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// Slots from now until the tokens can be withdrawn
+        #[arg(long)]
+        unlock_slots: u64,
+    },
+    /// Lock payment for a service deal that an arbiter settles if disputed
+    Deal {
+        /// Mint of the tokens the maker locks up
+        #[arg(long)]
+        send_mint: Pubkey,
+        /// How many of those tokens to lock
+        #[arg(long)]
+        send_amount: u64,
+        /// Token account to take them from [default: the maker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// The account providing the service
+        #[arg(long)]
+        taker: Pubkey,
+        /// The account that settles disputes
+        #[arg(long)]
+        arbiter: Pubkey,
+        /// Pay the taker, instead of refunding the maker, if the deal times out undelivered
+        #[arg(long)]
+        default_to_taker: bool,
+        /// Slots from now until the deal can be settled
+        #[arg(long)]
+        time_out_slots: u64,
+    },
+    /// Lock payment for a contract that pays out in tranches
+    Milestones {
+        /// Mint of the tokens the maker locks up
+        #[arg(long)]
+        send_mint: Pubkey,
+        /// Token account to take them from [default: the maker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// The account each approved tranche is paid to
+        #[arg(long)]
+        payee: Pubkey,
+        /// A tranche as AMOUNT, or AMOUNT@SLOTS when the maker can take it back SLOTS from now;
+        /// repeat for each one, up to 8
+        #[arg(long = "milestone", required = true, value_parser = parse_milestone)]
+        milestones: Vec<MilestoneTerms>,
+    },
+    /// Print the state of an escrow
+    Show { escrow: Pubkey },
+    /// List the escrows opened by a maker
+    List {
+        /// Maker to list escrows for [default: the keypair's public key]
+        #[arg(long)]
+        maker: Option<Pubkey>,
+    },
+    /// Take an escrow, paying the maker and receiving the locked tokens
+    Take {
+        escrow: Pubkey,
+        /// Token account the payment comes from [default: the taker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// Token account receiving the locked tokens [default: the taker's associated token account]
+        #[arg(long)]
+        receive_account: Option<Pubkey>,
+        /// File of the pubkeys the escrow is limited to, one per line, if it has an allow list
+        #[arg(long)]
+        allow_list: Option<PathBuf>,
+        /// An account for the escrow's callback program, as PUBKEY, or PUBKEY:w when writable;
+        /// repeat for each one, in order
+        #[arg(long = "callback-account", value_parser = parse_callback_account)]
+        callback_accounts: Vec<AccountMeta>,
+    },
+    /// Take several escrows of the same two mints in one transaction, cheapest first
+    TakeMany {
+        #[arg(required = true)]
+        escrows: Vec<Pubkey>,
+        /// Most to pay for all of them together
+        #[arg(long)]
+        max_total: u64,
+        /// Skip escrows that can't be taken, or would go over --max-total, instead of failing
+        #[arg(long)]
+        skip_unfillable: bool,
+        /// Token account the payment comes from [default: the taker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// Token account receiving the locked tokens [default: the taker's associated token account]
+        #[arg(long)]
+        receive_account: Option<Pubkey>,
+    },
+    /// Settle a ring of swaps in which each maker wants what the next one offers
+    SettleRing {
+        /// The escrows in ring order; the last maker is paid by the first
+        #[arg(required = true, num_args = 2..)]
+        escrows: Vec<Pubkey>,
+    },
+    /// Open a pool that makers deposit the same token into, sold at one price in lots
+    OpenPool {
+        /// Mint of the tokens the makers sell
+        #[arg(long)]
+        send_mint: Pubkey,
+        /// Mint of the tokens they want in return
+        #[arg(long)]
+        receive_mint: Pubkey,
+        /// How many tokens a lot holds
+        #[arg(long)]
+        lot_size: u64,
+        /// What a lot costs, in the tokens the makers want
+        #[arg(long)]
+        lot_price: u64,
+        /// Slots from now during which makers can deposit, before takers can fill
+        #[arg(long)]
+        deposit_slots: u64,
+        /// Slots after that during which takers can fill
+        #[arg(long)]
+        time_out_slots: u64,
+    },
+    /// Deposit into a pool before it opens for fills
+    DepositPool {
+        pool: Pubkey,
+        #[arg(long)]
+        amount: u64,
+        /// Token account to take them from [default: the maker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+    },
+    /// Buy lots out of an open pool
+    FillPool {
+        pool: Pubkey,
+        #[arg(long)]
+        lots: u64,
+        /// Token account the payment comes from [default: the taker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// Token account receiving the lots [default: the taker's associated token account]
+        #[arg(long)]
+        receive_account: Option<Pubkey>,
+    },
+    /// Withdraw your share of a pool's unfilled tokens and proceeds
+    WithdrawPool { pool: Pubkey },
+    /// Set what a taker pays to reserve a swap, as its maker; 0 stops offering reservations
+    SetPremium {
+        escrow: Pubkey,
+        /// Premium in the mint the maker asks for
+        #[arg(long)]
+        premium: u64,
+    },
+    /// Give one taker the first slots after a swap unlocks, as its maker; without --taker the swap is public again
+    SetExclusiveTaker {
+        escrow: Pubkey,
+        /// The only one who can take the swap at first
+        #[arg(long, requires = "exclusive_slots")]
+        taker: Option<Pubkey>,
+        /// How many slots after the unlock only the taker can take it
+        #[arg(long, requires = "taker")]
+        exclusive_slots: Option<u64>,
+    },
+    /// Limit who can take a swap to a list of pubkeys, as its maker; without --allow-list anyone can take it again
+    SetAllowList {
+        escrow: Pubkey,
+        /// File of the pubkeys allowed to take the swap, one per line
+        #[arg(long)]
+        allow_list: Option<PathBuf>,
+    },
+    /// Let a swap be taken only while an oracle's price meets a condition, as its maker; without --oracle the condition is dropped
+    SetPriceCondition {
+        escrow: Pubkey,
+        /// Oracle account to read the price from
+        #[arg(long, requires_all = ["condition", "price", "max_staleness"])]
+        oracle: Option<Pubkey>,
+        /// Format of the oracle account
+        #[arg(long, value_enum, default_value_t = OracleArg::Mock)]
+        oracle_kind: OracleArg,
+        /// Whether the price has to be at or above --price, or at or below it
+        #[arg(long, value_enum, requires = "oracle")]
+        condition: Option<ConditionArg>,
+        /// Trigger price, worth price * 10^expo
+        #[arg(long, requires = "oracle", allow_hyphen_values = true)]
+        price: Option<i64>,
+        /// Exponent of --price
+        #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+        expo: i32,
+        /// How many slots old the oracle's price may be
+        #[arg(long, requires = "oracle")]
+        max_staleness: Option<u64>,
+    },
+    /// Have a program called once a swap settles, as its maker; without --program none is called
+    SetCallback {
+        escrow: Pubkey,
+        /// The program to call
+        #[arg(long)]
+        program: Option<Pubkey>,
+    },
+    /// Pay a swap's premium to be the only one who can take it until it times out
+    Reserve {
+        escrow: Pubkey,
+        /// Token account the premium comes from [default: the taker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// File of the pubkeys the escrow is limited to, one per line, if it has an allow list
+        #[arg(long)]
+        allow_list: Option<PathBuf>,
+    },
+    /// Let the program sell up to an amount of a token for the orders you sign
+    ApproveOrders {
+        /// Mint of the tokens the orders sell
+        #[arg(long)]
+        send_mint: Pubkey,
+        /// Token account the orders sell from [default: the maker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// Most the program may move out of that account
+        #[arg(long)]
+        amount: u64,
+    },
+    /// Sign an order off-chain and print it for a taker to fill
+    SignOrder {
+        /// Mint of the tokens the maker sells
+        #[arg(long)]
+        send_mint: Pubkey,
+        /// How many of those tokens to sell
+        #[arg(long)]
+        send_amount: u64,
+        /// Token account to sell them from, approved with approve-orders [default: the maker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// Mint of the tokens the maker wants in return
+        #[arg(long)]
+        receive_mint: Pubkey,
+        /// How many of those tokens the maker wants
+        #[arg(long)]
+        receive_amount: u64,
+        /// Token account to pay them into [default: the maker's associated token account]
+        #[arg(long)]
+        receive_account: Option<Pubkey>,
+        /// Slots from now during which the order can be filled
+        #[arg(long)]
+        expires_in_slots: u64,
+        /// The only taker who can fill the order [default: anyone]
+        #[arg(long)]
+        taker: Option<Pubkey>,
+    },
+    /// Fill an order printed by sign-order
+    FillOrder {
+        /// The signed order
+        order: String,
+        /// Token account paying the maker [default: the taker's associated token account]
+        #[arg(long)]
+        send_account: Option<Pubkey>,
+        /// Token account receiving what the order sells [default: the taker's associated token account]
+        #[arg(long)]
+        receive_account: Option<Pubkey>,
+    },
+    /// Make every order signed so far unfillable
+    CancelAllOrders,
+    /// Redeem a hash lock with the preimage, receiving the locked tokens
+    Redeem {
+        escrow: Pubkey,
+        /// The preimage of the escrow's hash, in hex, up to 64 bytes
+        #[arg(long, value_parser = parse_preimage)]
+        preimage: Vec<u8>,
+        /// Token account receiving the locked tokens [default: the counterparty's associated token account]
+        #[arg(long)]
+        receive_account: Option<Pubkey>,
+    },
+    /// Claim a dead man's switch that timed out, receiving the locked tokens
+    Claim {
+        escrow: Pubkey,
+        /// Token account receiving the locked tokens [default: the beneficiary's associated token account]
+        #[arg(long)]
+        receive_account: Option<Pubkey>,
+    },
+    /// Cancel an escrow and get the locked tokens back
+    Cancel {
+        escrow: Pubkey,
+        /// Token account receiving the refund [default: the maker's associated token account]
+        #[arg(long)]
+        refund_account: Option<Pubkey>,
+        /// An account for the escrow's callback program, as PUBKEY, or PUBKEY:w when writable;
+        /// repeat for each one, in order
+        #[arg(long = "callback-account", value_parser = parse_callback_account)]
+        callback_accounts: Vec<AccountMeta>,
+    },
+    /// Withdraw the tokens of a savings lock that has unlocked
+    Unlock {
+        escrow: Pubkey,
+        /// Token account receiving the tokens [default: the maker's associated token account]
+        #[arg(long)]
+        refund_account: Option<Pubkey>,
+    },
+    /// Keep a savings lock locked for longer
+    ExtendLock {
+        escrow: Pubkey,
+        /// Slots to add to the unlock slot
+        #[arg(long)]
+        slots: u64,
+    },
+    /// Pay one milestone to the payee, as the maker
+    ApproveMilestone {
+        escrow: Pubkey,
+        /// Position of the milestone, from 0
+        #[arg(long)]
+        index: u8,
+    },
+    /// Take back the milestones past their deadline, as the maker
+    RefundUnapproved {
+        escrow: Pubkey,
+        /// Token account receiving the refund [default: the maker's associated token account]
+        #[arg(long)]
+        refund_account: Option<Pubkey>,
+    },
+    /// Mark an arbitrated deal delivered, as its taker
+    Deliver { escrow: Pubkey },
+    /// Hand an arbitrated deal to its arbiter, as its maker or taker
+    Dispute { escrow: Pubkey },
+    /// Pay an arbitrated deal's vault to the taker, as its maker
+    Release { escrow: Pubkey },
+    /// Rule on a disputed deal, as its arbiter
+    Resolve {
+        escrow: Pubkey,
+        /// The taker's share in basis points; the maker gets the rest
+        #[arg(long)]
+        taker_bps: u16,
+    },
+    /// Settle an undisputed deal that timed out
+    Settle { escrow: Pubkey },
+    /// Move the escrow's unlock and time out slots forward
+    ResetLock {
+        escrow: Pubkey,
+        /// Slots from now until the escrow can be taken
+        #[arg(long, default_value_t = 0)]
+        unlock_slots: u64,
+        /// Slots after unlocking during which the escrow can be taken
+        #[arg(long)]
+        time_out_slots: u64,
+    },
+    /// Rewrite an escrow created by an older program version in the current layout
+    Migrate { escrow: Pubkey },
+    /// Add the keypair's signature to an exported transaction, without touching the network
+    Sign {
+        /// File written by --export; the signature is added in place
+        file: PathBuf,
+    },
+    /// Combine the signatures of exported transactions and send the result
+    Submit {
+        /// Copies of the same exported transaction, each carrying some signatures
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HashArg {
+    Sha256,
+    Keccak256,
+}
+
+impl From<HashArg> for HashKind {
+    fn from(arg: HashArg) -> Self {
+        match arg {
+            HashArg::Sha256 => HashKind::Sha256,
+            HashArg::Keccak256 => HashKind::Keccak256,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OracleArg {
+    Mock,
+}
+
+impl From<OracleArg> for OracleKind {
+    fn from(arg: OracleArg) -> Self {
+        match arg {
+            OracleArg::Mock => OracleKind::Mock,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ConditionArg {
+    AtOrAbove,
+    AtOrBelow,
+}
+
+impl From<ConditionArg> for PriceCondition {
+    fn from(arg: ConditionArg) -> Self {
+        match arg {
+            ConditionArg::AtOrAbove => PriceCondition::AtOrAbove,
+            ConditionArg::AtOrBelow => PriceCondition::AtOrBelow,
+        }
+    }
+}
+
+fn parse_hex32(s: &str) -> Result<[u8; 32], String> {
+    parse_hex(s)?
+        .try_into()
+        .map_err(|_| "expected 64 hex digits".to_string())
+}
+
+fn parse_preimage(s: &str) -> Result<Vec<u8>, String> {
+    let bytes = parse_hex(s)?;
+    if bytes.is_empty() || bytes.len() > MAX_PREIMAGE_LEN {
+        return Err(format!("expected 1 to {} bytes", MAX_PREIMAGE_LEN));
+    }
+    Ok(bytes)
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err("expected pairs of hex digits".to_string());
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).unwrap();
+            u8::from_str_radix(pair, 16).map_err(|err| err.to_string())
+        })
+        .collect()
+}
+
+fn parse_milestone(s: &str) -> Result<MilestoneTerms, String> {
+    let (amount, deadline_slots) = match s.split_once('@') {
+        Some((amount, slots)) => (amount, Some(slots)),
+        None => (s, None),
+    };
+    Ok(MilestoneTerms {
+        amount: amount.parse().map_err(|err| format!("amount: {}", err))?,
+        deadline_slots: deadline_slots
+            .map(|slots| slots.parse().map_err(|err| format!("slots: {}", err)))
+            .transpose()?,
+    })
+}
+
+fn parse_callback_account(s: &str) -> Result<AccountMeta, String> {
+    let (pubkey, writable) = match s.strip_suffix(":w") {
+        Some(pubkey) => (pubkey, true),
+        None => (s, false),
+    };
+    let pubkey = pubkey.parse().map_err(|err| format!("{}", err))?;
+    Ok(match writable {
+        true => AccountMeta::new(pubkey, false),
+        false => AccountMeta::new_readonly(pubkey, false),
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The maker or taker: a local keypair, or just a public key when the
+/// transaction is exported for offline signing
+enum Party {
+    Keypair(Keypair),
+    Pubkey(Pubkey),
+}
+
+impl Party {
+    fn load(cli: &Cli) -> Self {
+        match cli.offline.signer {
+            Some(pubkey) if cli.offline.export.is_some() => Party::Pubkey(pubkey),
+            Some(_) => {
+                eprintln!("error: --signer only works together with --export");
+                exit(1);
+            }
+            None => Party::Keypair(load_keypair(cli.keypair.clone())),
+        }
+    }
+
+    fn pubkey(&self) -> Pubkey {
+        match self {
+            Party::Keypair(keypair) => keypair.pubkey(),
+            Party::Pubkey(pubkey) => *pubkey,
+        }
+    }
+}
+
+fn default_keypair_path() -> PathBuf {
+    let home = std::env::var_os("HOME").unwrap_or_default();
+    PathBuf::from(home).join(".config/solana/id.json")
+}
+
+fn load_keypair(path: Option<PathBuf>) -> Keypair {
+    let path = path.unwrap_or_else(default_keypair_path);
+    read_keypair_file(&path).unwrap_or_else(|err| {
+        eprintln!("error: could not read keypair {}: {}", path.display(), err);
+        exit(1);
+    })
+}
+
+fn report(client: &EscrowClient, outcome: Outcome) {
+    match outcome {
+        Outcome::Sent(signature) => println!("Signature: {}", signature),
+        Outcome::Simulated(result) => {
+            let logs = result.logs.unwrap_or_default();
+            for line in &logs {
+                println!("  {}", line);
+            }
+            match events::decode_logs(&client.program_id, &logs) {
+                Ok(decoded) => {
+                    for event in decoded {
+                        println!("Event: {:?}", event);
+                    }
+                }
+                Err(err) => eprintln!("warning: could not decode events: {}", err),
+            }
+            if let Some(units) = result.units_consumed {
+                println!("Compute units: {}", units);
+            }
+            match result.err {
+                Some(err) => {
+                    eprintln!("Simulation failed: {}", err);
+                    exit(1);
+                }
+                None => println!("Simulation succeeded"),
+            }
+        }
+    }
+}
+
+/// Signs what can be signed here, then either sends the transaction or
+/// exports it for the remaining signers
+fn send(
+    client: &EscrowClient,
+    cli: &Cli,
+    party: &Party,
+    instructions: &[Instruction],
+    ephemeral: &[&Keypair],
+) -> escrow_client::Result<()> {
+    let nonce = cli.offline.nonce.map(|account| {
+        (
+            account,
+            cli.offline
+                .nonce_authority
+                .unwrap_or_else(|| party.pubkey()),
+        )
+    });
+    let lifetime = client.lifetime(cli.offline.blockhash, nonce)?;
+    let mut transaction = offline::unsigned_transaction(instructions, &party.pubkey(), &lifetime);
+    for keypair in ephemeral {
+        offline::sign(&mut transaction, *keypair)?;
+    }
+    if let Party::Keypair(keypair) = party {
+        offline::sign(&mut transaction, keypair)?;
+    }
+
+    match &cli.offline.export {
+        Some(path) => {
+            write_transaction(path, &transaction);
+            println!("Transaction written to {}", path.display());
+            for signer in offline::missing_signers(&transaction) {
+                println!("  needs a signature from {}", signer);
+            }
+        }
+        None => report(
+            client,
+            client.submit(&offline::assemble(transaction)?, cli.dry_run)?,
+        ),
+    }
+    Ok(())
+}
+
+fn read_transaction(path: &Path) -> escrow_client::Result<Transaction> {
+    let encoded = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", path.display(), err);
+        exit(1);
+    });
+    offline::decode(&encoded)
+}
+
+/// An allow list from a file of pubkeys, one per line. Blank lines and
+/// lines starting with `#` are skipped.
+fn read_allow_list(path: &Path) -> AllowList {
+    let contents = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("error: could not read {}: {}", path.display(), err);
+        exit(1);
+    });
+    let takers: Vec<Pubkey> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.parse().unwrap_or_else(|err| {
+                eprintln!("error: {} in {}: {}", line, path.display(), err);
+                exit(1);
+            })
+        })
+        .collect();
+    AllowList::new(&takers)
+}
+
+fn write_transaction(path: &Path, transaction: &Transaction) {
+    if let Err(err) = fs::write(path, offline::encode(transaction) + "\n") {
+        eprintln!("error: could not write {}: {}", path.display(), err);
+        exit(1);
+    }
+}
+
+fn run(client: &EscrowClient, cli: Cli) -> escrow_client::Result<()> {
+    match &cli.command {
+        Command::Create {
+            send_mint,
+            send_amount,
+            send_account,
+            receive_mint,
+            receive_amount,
+            receive_account,
+            unlock_slots,
+            time_out_slots,
+            bond,
+            bond_recipient,
+            bond_mint,
+            bond_account,
+            delegate,
+        } => {
+            let maker = Party::load(&cli);
+            let temp_token_account = Keypair::new();
+            let escrow_account = Keypair::new();
+            let bond_vault = Keypair::new();
+            let bond = bond.map(|amount| BondTerms {
+                recipient: *bond_recipient,
+                amount,
+                token: bond_mint.map(|mint| TokenBond {
+                    mint,
+                    send_account: bond_account
+                        .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), &mint)),
+                    vault: bond_vault.pubkey(),
+                }),
+            });
+            let mut ephemeral = vec![&escrow_account];
+            if !delegate {
+                ephemeral.push(&temp_token_account);
+            }
+            if bond_mint.is_some() {
+                ephemeral.push(&bond_vault);
+            }
+            let terms = CreateTerms {
+                send_mint: *send_mint,
+                send_account: send_account
+                    .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), send_mint)),
+                send_amount: *send_amount,
+                receive_account: receive_account
+                    .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), receive_mint)),
+                receive_amount: *receive_amount,
+                unlock_slots: *unlock_slots,
+                time_out_slots: *time_out_slots,
+                bond,
+            };
+            let instructions = if *delegate {
+                client.create_delegated(&maker.pubkey(), &terms, &escrow_account.pubkey())?
+            } else {
+                client.create(
+                    &maker.pubkey(),
+                    &terms,
+                    &temp_token_account.pubkey(),
+                    &escrow_account.pubkey(),
+                )?
+            };
+            println!("Escrow: {}", escrow_account.pubkey());
+            send(client, &cli, &maker, &instructions, &ephemeral)?;
+        }
+        Command::HashLock {
+            send_mint,
+            send_amount,
+            send_account,
+            counterparty,
+            hash,
+            hash_kind,
+            unlock_slots,
+            time_out_slots,
+        } => {
+            let maker = Party::load(&cli);
+            let temp_token_account = Keypair::new();
+            let escrow_account = Keypair::new();
+            let terms = HashLockTerms {
+                send_mint: *send_mint,
+                send_account: send_account
+                    .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), send_mint)),
+                send_amount: *send_amount,
+                hash_kind: (*hash_kind).into(),
+                hash: *hash,
+                counterparty: *counterparty,
+                unlock_slots: *unlock_slots,
+                time_out_slots: *time_out_slots,
+            };
+            let instructions = client.create_hash_lock(
+                &maker.pubkey(),
+                &terms,
+                &temp_token_account.pubkey(),
+                &escrow_account.pubkey(),
+            )?;
+            println!("Escrow: {}", escrow_account.pubkey());
+            send(
+                client,
+                &cli,
+                &maker,
+                &instructions,
+                &[&temp_token_account, &escrow_account],
+            )?;
+        }
+        Command::Switch {
+            send_mint,
+            send_amount,
+            send_account,
+            beneficiary,
+            time_out_slots,
+        } => {
+            let maker = Party::load(&cli);
+            let temp_token_account = Keypair::new();
+            let escrow_account = Keypair::new();
+            let terms = SwitchTerms {
+                send_mint: *send_mint,
+                send_account: send_account
+                    .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), send_mint)),
+                send_amount: *send_amount,
+                beneficiary: *beneficiary,
+                time_out_slots: *time_out_slots,
+            };
+            let instructions = client.create_switch(
+                &maker.pubkey(),
+                &terms,
+                &temp_token_account.pubkey(),
+                &escrow_account.pubkey(),
+            )?;
+            println!("Escrow: {}", escrow_account.pubkey());
+            send(
+                client,
+                &cli,
+                &maker,
+                &instructions,
+                &[&temp_token_account, &escrow_account],
+            )?;
+        }
+        Command::Lock {
+            send_mint,
+            send_amount,
+            send_account,
+            unlock_slots,
+        } => {
+            let maker = Party::load(&cli);
+            let temp_token_account = Keypair::new();
+            let escrow_account = Keypair::new();
+            let terms = LockTerms {
+                send_mint: *send_mint,
+                send_account: send_account
+                    .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), send_mint)),
+                send_amount: *send_amount,
+                unlock_slots: *unlock_slots,
+            };
+            let instructions = client.lock(
+                &maker.pubkey(),
+                &terms,
+                &temp_token_account.pubkey(),
+                &escrow_account.pubkey(),
+            )?;
+            println!("Escrow: {}", escrow_account.pubkey());
+            send(
+                client,
+                &cli,
+                &maker,
+                &instructions,
+                &[&temp_token_account, &escrow_account],
+            )?;
+        }
+        Command::Deal {
+            send_mint,
+            send_amount,
+            send_account,
+            taker,
+            arbiter,
+            default_to_taker,
+            time_out_slots,
+        } => {
+            let maker = Party::load(&cli);
+            let temp_token_account = Keypair::new();
+            let escrow_account = Keypair::new();
+            let terms = ArbitratedTerms {
+                send_mint: *send_mint,
+                send_account: send_account
+                    .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), send_mint)),
+                send_amount: *send_amount,
+                taker: *taker,
+                arbiter: *arbiter,
+                default_outcome: if *default_to_taker {
+                    DefaultOutcome::PayTaker
+                } else {
+                    DefaultOutcome::RefundMaker
+                },
+                time_out_slots: *time_out_slots,
+            };
+            let instructions = client.create_arbitrated(
+                &maker.pubkey(),
+                &terms,
+                &temp_token_account.pubkey(),
+                &escrow_account.pubkey(),
+            )?;
+            println!("Escrow: {}", escrow_account.pubkey());
+            send(
+                client,
+                &cli,
+                &maker,
+                &instructions,
+                &[&temp_token_account, &escrow_account],
+            )?;
+        }
+        Command::Milestones {
+            send_mint,
+            send_account,
+            payee,
+            milestones,
+        } => {
+            let maker = Party::load(&cli);
+            let temp_token_account = Keypair::new();
+            let escrow_account = Keypair::new();
+            let terms = MilestonesTerms {
+                send_mint: *send_mint,
+                send_account: send_account
+                    .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), send_mint)),
+                payee: *payee,
+                milestones: milestones.clone(),
+            };
+            let instructions = client.create_milestones(
+                &maker.pubkey(),
+                &terms,
+                &temp_token_account.pubkey(),
+                &escrow_account.pubkey(),
+            )?;
+            println!("Escrow: {}", escrow_account.pubkey());
+            send(
+                client,
+                &cli,
+                &maker,
+                &instructions,
+                &[&temp_token_account, &escrow_account],
+            )?;
+        }
+        Command::Show { escrow } => {
+            let escrow_info = client.get_escrow(escrow)?;
+            let vault = client.get_token_account(&escrow_info.temp_token_account_pubkey)?;
+            let slot = client.rpc.get_slot()?;
+            println!("Escrow:          {}", escrow);
+            println!("Layout version:  {}", client.escrow_version(escrow)?);
+            println!("Maker:           {}", escrow_info.initializer_pubkey);
+            if escrow_info.is_delegated() {
+                println!(
+                    "Maker account:   {} ({} of mint {} on exchange, {} approved)",
+                    escrow_info.temp_token_account_pubkey,
+                    escrow_info.delegated_amount,
+                    vault.mint,
+                    vault.delegated_amount
+                );
+            } else {
+                println!(
+                    "Vault:           {} ({} of mint {})",
+                    escrow_info.temp_token_account_pubkey, vault.amount, vault.mint
+                );
+            }
+            println!("Mode:            {:?}", escrow_info.mode);
+            match escrow_info.mode {
+                EscrowMode::Swap => {
+                    println!(
+                        "Receive account: {}",
+                        escrow_info.initializer_token_to_receive_account_pubkey
+                    );
+                    println!("Expected amount: {}", escrow_info.expected_amount);
+                    if escrow_info.bond > 0 {
+                        let bond = if escrow_info.bond_vault == Pubkey::default() {
+                            format!("{} lamports", escrow_info.bond)
+                        } else {
+                            let bond_vault = client.get_token_account(&escrow_info.bond_vault)?;
+                            format!(
+                                "{} of mint {} in {}",
+                                escrow_info.bond, bond_vault.mint, escrow_info.bond_vault
+                            )
+                        };
+                        println!("Bond:            {}", bond);
+                        if escrow_info.bond_recipient == Pubkey::default() {
+                            println!("Bond recipient:  the fee vault");
+                        } else {
+                            println!("Bond recipient:  {}", escrow_info.bond_recipient);
+                        }
+                    }
+                    if escrow_info.premium > 0 {
+                        println!("Premium:         {}", escrow_info.premium);
+                    }
+                    if escrow_info.counterparty != Pubkey::default() {
+                        println!("Reserved by:     {}", escrow_info.counterparty);
+                    }
+                    if escrow_info.has_price_condition() {
+                        println!(
+                            "Price:           {:?} {}e{} on {} ({:?}, at most {} slots old)",
+                            escrow_info.price_condition,
+                            escrow_info.trigger_price,
+                            escrow_info.trigger_expo,
+                            escrow_info.oracle,
+                            escrow_info.oracle_kind,
+                            escrow_info.max_staleness
+                        );
+                    }
+                    if escrow_info.has_callback() {
+                        println!("Callback:        {}", escrow_info.callback_program);
+                    }
+                    if escrow_info.has_allow_list() {
+                        println!("Allow list:      {}", hex(&escrow_info.allow_list_root));
+                    }
+                    if escrow_info.designated_taker != Pubkey::default() {
+                        println!(
+                            "Exclusive taker: {} (until slot {})",
+                            escrow_info.designated_taker, escrow_info.exclusive_until
+                        );
+                    }
+                }
+                EscrowMode::HashLock => {
+                    println!("Counterparty:    {}", escrow_info.counterparty);
+                    println!(
+                        "Hash:            {} ({:?})",
+                        hex(&escrow_info.hash),
+                        escrow_info.hash_kind
+                    );
+                }
+                EscrowMode::DeadMansSwitch => {
+                    println!("Beneficiary:     {}", escrow_info.counterparty);
+                }
+                EscrowMode::SavingsLock => {}
+                EscrowMode::Arbitrated => {
+                    println!("Taker:           {}", escrow_info.counterparty);
+                    println!("Arbiter:         {}", escrow_info.arbiter);
+                    println!("Deal state:      {:?}", escrow_info.deal_state);
+                    println!("Default outcome: {:?}", escrow_info.default_outcome);
+                }
+                EscrowMode::Milestones => {
+                    println!("Payee:           {}", escrow_info.counterparty);
+                    println!("Remaining:       {}", escrow_info.remaining);
+                    for (i, milestone) in escrow_info.milestones().iter().enumerate() {
+                        let deadline = milestone
+                            .deadline
+                            .map_or("no deadline".to_string(), |slot| {
+                                format!("deadline {}", slot)
+                            });
+                        println!(
+                            "Milestone {}:     {} ({}, {:?})",
+                            i, milestone.amount, deadline, milestone.state
+                        );
+                    }
+                }
+            }
+            println!("Unlock slot:     {}", escrow_info.unlock_time);
+            println!("Time out slot:   {}", escrow_info.time_out);
+            println!(
+                "Status:          {:?} (current slot {})",
+                LockStatus::at(&escrow_info, slot),
+                slot
+            );
+        }
+        Command::List { maker } => {
+            let maker = maker.unwrap_or_else(|| Party::load(&cli).pubkey());
+            let slot = client.rpc.get_slot()?;
+            for (key, escrow_info) in client.escrows_by_maker(&maker)? {
+                println!(
+                    "{}  expects {:>20}  unlock {:>12}  time out {:>12}  {:?}",
+                    key,
+                    escrow_info.expected_amount,
+                    escrow_info.unlock_time,
+                    escrow_info.time_out,
+                    LockStatus::at(&escrow_info, slot)
+                );
+            }
+        }
+        Command::Take {
+            escrow,
+            send_account,
+            receive_account,
+            allow_list,
+            callback_accounts,
+        } => {
+            let taker = Party::load(&cli);
+            let allow_list = allow_list.as_deref().map(read_allow_list);
+            let instructions = client.take(
+                &taker.pubkey(),
+                escrow,
+                *send_account,
+                *receive_account,
+                allow_list.as_ref(),
+                callback_accounts,
+            )?;
+            send(client, &cli, &taker, &instructions, &[])?;
+        }
+        Command::TakeMany {
+            escrows,
+            max_total,
+            skip_unfillable,
+            send_account,
+            receive_account,
+        } => {
+            let taker = Party::load(&cli);
+            let instructions = client.take_many(
+                &taker.pubkey(),
+                escrows,
+                *max_total,
+                *skip_unfillable,
+                *send_account,
+                *receive_account,
+            )?;
+            send(client, &cli, &taker, &instructions, &[])?;
+        }
+        Command::SettleRing { escrows } => {
+            let settler = Party::load(&cli);
+            let instructions = client.settle_ring(&settler.pubkey(), escrows)?;
+            send(client, &cli, &settler, &instructions, &[])?;
+        }
+        Command::OpenPool {
+            send_mint,
+            receive_mint,
+            lot_size,
+            lot_price,
+            deposit_slots,
+            time_out_slots,
+        } => {
+            let creator = Party::load(&cli);
+            let pool = Keypair::new();
+            let vault = Keypair::new();
+            let proceeds_vault = Keypair::new();
+            let terms = PoolTerms {
+                send_mint: *send_mint,
+                receive_mint: *receive_mint,
+                offered_per_lot: *lot_size,
+                requested_per_lot: *lot_price,
+                unlock_slots: *deposit_slots,
+                time_out_slots: *time_out_slots,
+            };
+            let instructions = client.open_pool(
+                &creator.pubkey(),
+                &terms,
+                &pool.pubkey(),
+                &vault.pubkey(),
+                &proceeds_vault.pubkey(),
+            )?;
+            println!("Pool: {}", pool.pubkey());
+            send(
+                client,
+                &cli,
+                &creator,
+                &instructions,
+                &[&pool, &vault, &proceeds_vault],
+            )?;
+        }
+        Command::DepositPool {
+            pool,
+            amount,
+            send_account,
+        } => {
+            let maker = Party::load(&cli);
+            let instructions =
+                client.deposit_pool(&maker.pubkey(), pool, *amount, *send_account)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::FillPool {
+            pool,
+            lots,
+            send_account,
+            receive_account,
+        } => {
+            let taker = Party::load(&cli);
+            let instructions = client.fill_pool(
+                &taker.pubkey(),
+                pool,
+                *lots,
+                *send_account,
+                *receive_account,
+            )?;
+            send(client, &cli, &taker, &instructions, &[])?;
+        }
+        Command::WithdrawPool { pool } => {
+            let maker = Party::load(&cli);
+            let instructions = client.withdraw_pool(&maker.pubkey(), pool)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::Redeem {
+            escrow,
+            preimage,
+            receive_account,
+        } => {
+            let counterparty = Party::load(&cli);
+            let instructions =
+                client.redeem(&counterparty.pubkey(), escrow, *receive_account, preimage)?;
+            send(client, &cli, &counterparty, &instructions, &[])?;
+        }
+        Command::Claim {
+            escrow,
+            receive_account,
+        } => {
+            let beneficiary = Party::load(&cli);
+            let instructions = client.claim(&beneficiary.pubkey(), escrow, *receive_account)?;
+            send(client, &cli, &beneficiary, &instructions, &[])?;
+        }
+        Command::SetPremium { escrow, premium } => {
+            let maker = Party::load(&cli);
+            let instructions = client.set_premium(&maker.pubkey(), escrow, *premium)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::SetExclusiveTaker {
+            escrow,
+            taker,
+            exclusive_slots,
+        } => {
+            let maker = Party::load(&cli);
+            let instructions = client.set_exclusive_taker(
+                &maker.pubkey(),
+                escrow,
+                &taker.unwrap_or_default(),
+                exclusive_slots.unwrap_or_default(),
+            )?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::SetPriceCondition {
+            escrow,
+            oracle,
+            oracle_kind,
+            condition,
+            price,
+            expo,
+            max_staleness,
+        } => {
+            let maker = Party::load(&cli);
+            // clap requires the rest of the terms along with --oracle
+            let terms = oracle.map(|oracle| PriceTerms {
+                oracle,
+                oracle_kind: (*oracle_kind).into(),
+                condition: condition.unwrap().into(),
+                trigger_price: price.unwrap(),
+                trigger_expo: *expo,
+                max_staleness: max_staleness.unwrap(),
+            });
+            let instructions =
+                client.set_price_condition(&maker.pubkey(), escrow, terms.as_ref())?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::SetCallback { escrow, program } => {
+            let maker = Party::load(&cli);
+            let instructions = client.set_callback(&maker.pubkey(), escrow, *program)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::SetAllowList { escrow, allow_list } => {
+            let maker = Party::load(&cli);
+            let allow_list = allow_list.as_deref().map(read_allow_list);
+            let instructions =
+                client.set_allow_list(&maker.pubkey(), escrow, allow_list.as_ref())?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::Reserve {
+            escrow,
+            send_account,
+            allow_list,
+        } => {
+            let taker = Party::load(&cli);
+            let allow_list = allow_list.as_deref().map(read_allow_list);
+            let instructions =
+                client.reserve(&taker.pubkey(), escrow, *send_account, allow_list.as_ref())?;
+            send(client, &cli, &taker, &instructions, &[])?;
+        }
+        Command::ApproveOrders {
+            send_mint,
+            send_account,
+            amount,
+        } => {
+            let maker = Party::load(&cli);
+            let send_account = send_account
+                .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), send_mint));
+            let instructions = client.approve_orders(&maker.pubkey(), &send_account, *amount)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::SignOrder {
+            send_mint,
+            send_amount,
+            send_account,
+            receive_mint,
+            receive_amount,
+            receive_account,
+            expires_in_slots,
+            taker,
+        } => {
+            let maker = load_keypair(cli.keypair.clone());
+            let order = client.new_order(
+                &maker.pubkey(),
+                &send_account
+                    .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), send_mint)),
+                &receive_account
+                    .unwrap_or_else(|| get_associated_token_address(&maker.pubkey(), receive_mint)),
+                *send_amount,
+                *receive_amount,
+                *expires_in_slots,
+                *taker,
+            )?;
+            let signed = orders::sign(order, &cli.program_id, &maker)?;
+            println!("Expires at slot: {}", order.expires_at);
+            println!("{}", orders::encode(&signed));
+        }
+        Command::FillOrder {
+            order,
+            send_account,
+            receive_account,
+        } => {
+            let signed = orders::decode(order)?;
+            if !signed.verify(&cli.program_id) {
+                eprintln!("error: the order is not signed by its maker");
+                exit(1);
+            }
+            let taker = Party::load(&cli);
+            let instructions =
+                client.fill_order(&taker.pubkey(), &signed, *send_account, *receive_account)?;
+            send(client, &cli, &taker, &instructions, &[])?;
+        }
+        Command::CancelAllOrders => {
+            let maker = Party::load(&cli);
+            let instructions = client.cancel_all_orders(&maker.pubkey())?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::Cancel {
+            escrow,
+            refund_account,
+            callback_accounts,
+        } => {
+            let maker = Party::load(&cli);
+            let instructions =
+                client.cancel(&maker.pubkey(), escrow, *refund_account, callback_accounts)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::Unlock {
+            escrow,
+            refund_account,
+        } => {
+            let maker = Party::load(&cli);
+            let instructions = client.unlock(&maker.pubkey(), escrow, *refund_account)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::ExtendLock { escrow, slots } => {
+            let maker = Party::load(&cli);
+            let instructions = client.extend_lock(&maker.pubkey(), escrow, *slots)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::ApproveMilestone { escrow, index } => {
+            let maker = Party::load(&cli);
+            let instructions = client.approve_milestone(&maker.pubkey(), escrow, *index)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::RefundUnapproved {
+            escrow,
+            refund_account,
+        } => {
+            let maker = Party::load(&cli);
+            let instructions =
+                client.refund_unapproved(&maker.pubkey(), escrow, *refund_account)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::Deliver { escrow } => {
+            let taker = Party::load(&cli);
+            let instructions = client.mark_delivered(&taker.pubkey(), escrow)?;
+            send(client, &cli, &taker, &instructions, &[])?;
+        }
+        Command::Dispute { escrow } => {
+            let party = Party::load(&cli);
+            let instructions = client.open_dispute(&party.pubkey(), escrow)?;
+            send(client, &cli, &party, &instructions, &[])?;
+        }
+        Command::Release { escrow } => {
+            let maker = Party::load(&cli);
+            let instructions = client.release(&maker.pubkey(), escrow)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::Resolve { escrow, taker_bps } => {
+            let arbiter = Party::load(&cli);
+            let instructions = client.resolve(&arbiter.pubkey(), escrow, *taker_bps)?;
+            send(client, &cli, &arbiter, &instructions, &[])?;
+        }
+        Command::Settle { escrow } => {
+            let payer = Party::load(&cli);
+            let instructions = client.settle(&payer.pubkey(), escrow)?;
+            send(client, &cli, &payer, &instructions, &[])?;
+        }
+        Command::ResetLock {
+            escrow,
+            unlock_slots,
+            time_out_slots,
+        } => {
+            let maker = Party::load(&cli);
+            let instructions =
+                client.reset_lock(&maker.pubkey(), escrow, *unlock_slots, *time_out_slots)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::Migrate { escrow } => {
+            let maker = Party::load(&cli);
+            let instructions = client.migrate(&maker.pubkey(), escrow)?;
+            send(client, &cli, &maker, &instructions, &[])?;
+        }
+        Command::Sign { file } => {
+            let keypair = load_keypair(cli.keypair.clone());
+            let mut transaction = read_transaction(file)?;
+            offline::sign(&mut transaction, &keypair)?;
+            write_transaction(file, &transaction);
+            let missing = offline::missing_signers(&transaction);
+            if missing.is_empty() {
+                println!("Fully signed, ready to submit");
+            }
+            for signer in missing {
+                println!("  still needs a signature from {}", signer);
+            }
+        }
+        Command::Submit { files } => {
+            let mut transaction = read_transaction(&files[0])?;
+            for file in &files[1..] {
+                offline::merge(&mut transaction, &read_transaction(file)?)?;
+            }
+            let transaction = offline::assemble(transaction)?;
+            report(client, client.submit(&transaction, cli.dry_run)?);
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let client = EscrowClient::new(cli.url.clone(), cli.program_id);
+    if let Err(err) = run(&client, cli) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::Value;
+    use solana_rpc_client::rpc_client::RpcClient;
+    use solana_rpc_client_api::request::RpcRequest;
+    use solana_sdk::signature::write_keypair_file;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from([&["escrow"], args].concat()).unwrap()
+    }
+
+    /// A client for a cluster that fails anything sent to it but simulates
+    /// every transaction successfully
+    fn simulating_client() -> EscrowClient {
+        let mocks = HashMap::from([(RpcRequest::SendTransaction, Value::Null)]);
+        EscrowClient {
+            rpc: RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks),
+            program_id: solana_escrow::id(),
+        }
+    }
+
+    /// A new keypair, written to a file the way `solana-keygen` does
+    fn keypair_file() -> PathBuf {
+        let keypair = Keypair::new();
+        let path = std::env::temp_dir().join(format!("escrow-{}.json", keypair.pubkey()));
+        write_keypair_file(&keypair, &path).unwrap();
+        path
+    }
+
+    #[test]
+    fn create_reads_amounts_mints_and_lock_durations() {
+        let (send_mint, receive_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let cli = parse(&[
+            "create",
+            "--send-mint",
+            &send_mint.to_string(),
+            "--send-amount",
+            "1000",
+            "--receive-mint",
+            &receive_mint.to_string(),
+            "--receive-amount",
+            "2000",
+            "--unlock-slots",
+            "10",
+            "--time-out-slots",
+            "500",
+        ]);
+        assert!(!cli.dry_run);
+        match cli.command {
+            Command::Create {
+                send_mint: parsed_send_mint,
+                send_amount: 1_000,
+                send_account: None,
+                receive_mint: parsed_receive_mint,
+                receive_amount: 2_000,
+                receive_account: None,
+                unlock_slots: 10,
+                time_out_slots: 500,
+                bond: None,
+                delegate: false,
+                ..
+            } => {
+                assert_eq!(parsed_send_mint, send_mint);
+                assert_eq!(parsed_receive_mint, receive_mint);
+            }
+            _ => panic!("not the create terms"),
+        }
+    }
+
+    #[test]
+    fn global_options_can_follow_the_subcommand() {
+        let escrow = Pubkey::new_unique();
+        let cli = parse(&[
+            "take",
+            &escrow.to_string(),
+            "--dry-run",
+            "--keypair",
+            "taker.json",
+            "--url",
+            "http://cluster:8899",
+        ]);
+        assert!(cli.dry_run);
+        assert_eq!(cli.keypair, Some(PathBuf::from("taker.json")));
+        assert_eq!(cli.url, "http://cluster:8899");
+        assert!(matches!(cli.command, Command::Take { escrow: taken, .. } if taken == escrow));
+    }
+
+    #[test]
+    fn reset_lock_unlocks_right_away_by_default() {
+        let escrow = Pubkey::new_unique().to_string();
+        let cli = parse(&["reset-lock", &escrow, "--time-out-slots", "300"]);
+        assert!(matches!(
+            cli.command,
+            Command::ResetLock {
+                unlock_slots: 0,
+                time_out_slots: 300,
+                ..
+            }
+        ));
+        // without a time out the window would close right away
+        assert!(Cli::try_parse_from(["escrow", "reset-lock", &escrow]).is_err());
+    }
+
+    #[test]
+    fn a_bond_recipient_needs_a_bond() {
+        let mint = Pubkey::new_unique().to_string();
+        let create = [
+            "escrow",
+            "create",
+            "--send-mint",
+            &mint,
+            "--send-amount",
+            "1",
+            "--receive-mint",
+            &mint,
+            "--receive-amount",
+            "1",
+            "--time-out-slots",
+            "1",
+        ];
+        let recipient = Pubkey::new_unique().to_string();
+        let without_bond = [&create[..], &["--bond-recipient", &recipient]].concat();
+        assert!(Cli::try_parse_from(without_bond).is_err());
+        // without a recipient, the bond is forfeited to the fee vault
+        let with_bond = [&create[..], &["--bond", "5000"]].concat();
+        assert!(Cli::try_parse_from(&with_bond).is_ok());
+        let with_recipient = [&with_bond[..], &["--bond-recipient", &recipient]].concat();
+        assert!(Cli::try_parse_from(with_recipient).is_ok());
+    }
+
+    #[test]
+    fn dry_runs_simulate_instead_of_sending() {
+        let path = keypair_file();
+        let keypair = path.to_str().unwrap();
+        let (escrow, mint) = (
+            Pubkey::new_unique().to_string(),
+            Pubkey::new_unique().to_string(),
+        );
+        let commands: [&[&str]; 2] = [
+            &["reset-lock", &escrow, "--time-out-slots", "300"],
+            &[
+                "create",
+                "--send-mint",
+                &mint,
+                "--send-amount",
+                "1",
+                "--receive-mint",
+                &mint,
+                "--receive-amount",
+                "1",
+                "--time-out-slots",
+                "1",
+            ],
+        ];
+        for command in commands {
+            let args = [&["--keypair", keypair], command].concat();
+            assert!(
+                run(&simulating_client(), parse(&args)).is_err(),
+                "{:?} was not sent",
+                command
+            );
+            let args = [&["--keypair", keypair, "--dry-run"], command].concat();
+            run(&simulating_client(), parse(&args)).unwrap();
+        }
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,259 @@
+//! Unsigned escrow transactions that can be carried to an air-gapped signer
+//! and back.
+//!
+//! Transactions travel as base64 of the standard wire format, with zeroed
+//! signatures for the signers that haven't signed yet, so any tool that reads
+//! wire transactions (web3.js `Transaction.from`, hardware wallet tooling, ...)
+//! can inspect them too.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_sdk::{
+    hash::Hash, instruction::Instruction, message::Message, pubkey::Pubkey, sanitize::Sanitize,
+    signature::Signature, signer::Signer, transaction::Transaction,
+};
+
+use crate::{ClientError, Result};
+
+/// What keeps an exported transaction valid until it is submitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifetime {
+    /// A recent blockhash, only valid for about 150 slots
+    Blockhash(Hash),
+    /// A durable nonce, valid until the nonce account is advanced. `blockhash`
+    /// is the value currently stored in the nonce account.
+    Nonce {
+        account: Pubkey,
+        authority: Pubkey,
+        blockhash: Hash,
+    },
+}
+
+/// Builds a transaction nobody has signed yet. With a durable nonce the
+/// `AdvanceNonceAccount` instruction is prepended, as the runtime requires.
+pub fn unsigned_transaction(
+    instructions: &[Instruction],
+    fee_payer: &Pubkey,
+    lifetime: &Lifetime,
+) -> Transaction {
+    let message = match lifetime {
+        Lifetime::Blockhash(blockhash) => {
+            Message::new_with_blockhash(instructions, Some(fee_payer), blockhash)
+        }
+        Lifetime::Nonce {
+            account,
+            authority,
+            blockhash,
+        } => {
+            let mut message =
+                Message::new_with_nonce(instructions.to_vec(), Some(fee_payer), account, authority);
+            message.recent_blockhash = *blockhash;
+            message
+        }
+    };
+    Transaction::new_unsigned(message)
+}
+
+pub fn encode(transaction: &Transaction) -> String {
+    STANDARD.encode(bincode::serialize(transaction).expect("transactions always serialize"))
+}
+
+pub fn decode(encoded: &str) -> Result<Transaction> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| ClientError::Encoding(e.to_string()))?;
+    let transaction: Transaction =
+        bincode::deserialize(&bytes).map_err(|e| ClientError::Encoding(e.to_string()))?;
+    transaction
+        .sanitize()
+        .map_err(|e| ClientError::Encoding(e.to_string()))?;
+    Ok(transaction)
+}
+
+/// Adds `signer`'s signature, leaving every other slot untouched
+pub fn sign(transaction: &mut Transaction, signer: &dyn Signer) -> Result<()> {
+    let pubkey = signer.pubkey();
+    let position = signer_position(transaction, &pubkey)?;
+    let signature = signer
+        .try_sign_message(&transaction.message_data())
+        .map_err(|e| ClientError::Signing(e.to_string()))?;
+    transaction.signatures[position] = signature;
+    Ok(())
+}
+
+/// Copies the signatures `other` carries into `transaction`. Both must wrap
+/// the very same message.
+pub fn merge(transaction: &mut Transaction, other: &Transaction) -> Result<()> {
+    if transaction.message != other.message {
+        return Err(ClientError::MessageMismatch);
+    }
+    for (mine, theirs) in transaction.signatures.iter_mut().zip(&other.signatures) {
+        if *theirs != Signature::default() {
+            *mine = *theirs;
+        }
+    }
+    Ok(())
+}
+
+/// Signers whose signature slot is still empty
+pub fn missing_signers(transaction: &Transaction) -> Vec<Pubkey> {
+    transaction
+        .signatures
+        .iter()
+        .zip(&transaction.message.account_keys)
+        .filter(|(signature, _)| **signature == Signature::default())
+        .map(|(_, key)| *key)
+        .collect()
+}
+
+/// Checks every signature is present and valid before the transaction goes
+/// out
+pub fn assemble(transaction: Transaction) -> Result<Transaction> {
+    let missing = missing_signers(&transaction);
+    if !missing.is_empty() {
+        return Err(ClientError::MissingSignatures(missing));
+    }
+    transaction
+        .verify()
+        .map_err(|e| ClientError::Signing(e.to_string()))?;
+    Ok(transaction)
+}
+
+fn signer_position(transaction: &Transaction, pubkey: &Pubkey) -> Result<usize> {
+    let required = transaction.message.header.num_required_signatures as usize;
+    transaction.message.account_keys[..required]
+        .iter()
+        .position(|key| key == pubkey)
+        .ok_or(ClientError::NotASigner(*pubkey))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        instruction::AccountMeta,
+        signature::Keypair,
+        system_instruction::{self, SystemInstruction},
+        system_program,
+    };
+
+    use super::*;
+
+    /// A transfer that needs both the fee payer and a co-signer
+    fn two_signer_transaction(
+        fee_payer: &Keypair,
+        cosigner: &Keypair,
+        lifetime: &Lifetime,
+    ) -> Transaction {
+        let mut transfer =
+            system_instruction::transfer(&fee_payer.pubkey(), &Pubkey::new_unique(), 1);
+        transfer
+            .accounts
+            .push(AccountMeta::new_readonly(cosigner.pubkey(), true));
+        unsigned_transaction(&[transfer], &fee_payer.pubkey(), lifetime)
+    }
+
+    fn blockhash() -> Lifetime {
+        Lifetime::Blockhash(Hash::new_unique())
+    }
+
+    #[test]
+    fn encoded_transactions_decode_unchanged() {
+        let (fee_payer, cosigner) = (Keypair::new(), Keypair::new());
+        let mut transaction = two_signer_transaction(&fee_payer, &cosigner, &blockhash());
+        sign(&mut transaction, &fee_payer).unwrap();
+
+        assert_eq!(decode(&encode(&transaction)).unwrap(), transaction);
+        assert_eq!(
+            decode(&format!("{}\n", encode(&transaction))).unwrap(),
+            transaction
+        );
+        assert!(matches!(
+            decode("not base64!"),
+            Err(ClientError::Encoding(_))
+        ));
+        assert!(matches!(
+            decode(&STANDARD.encode([1, 2, 3])),
+            Err(ClientError::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn partial_signatures_merge() {
+        let (fee_payer, cosigner) = (Keypair::new(), Keypair::new());
+        let unsigned = two_signer_transaction(&fee_payer, &cosigner, &blockhash());
+        let (mut first, mut second) = (unsigned.clone(), unsigned);
+        sign(&mut first, &fee_payer).unwrap();
+        sign(&mut second, &cosigner).unwrap();
+
+        merge(&mut first, &second).unwrap();
+        assert!(missing_signers(&first).is_empty());
+        assert_eq!(assemble(first.clone()).unwrap(), first);
+
+        let other = two_signer_transaction(&fee_payer, &cosigner, &blockhash());
+        assert!(matches!(
+            merge(&mut first, &other),
+            Err(ClientError::MessageMismatch)
+        ));
+    }
+
+    #[test]
+    fn missing_signers_shrink_as_signatures_come_in() {
+        let (fee_payer, cosigner) = (Keypair::new(), Keypair::new());
+        let mut transaction = two_signer_transaction(&fee_payer, &cosigner, &blockhash());
+        assert_eq!(
+            missing_signers(&transaction),
+            vec![fee_payer.pubkey(), cosigner.pubkey()]
+        );
+
+        sign(&mut transaction, &cosigner).unwrap();
+        assert_eq!(missing_signers(&transaction), vec![fee_payer.pubkey()]);
+        sign(&mut transaction, &fee_payer).unwrap();
+        assert!(missing_signers(&transaction).is_empty());
+
+        let stranger = Keypair::new();
+        assert!(matches!(
+            sign(&mut transaction, &stranger),
+            Err(ClientError::NotASigner(key)) if key == stranger.pubkey()
+        ));
+    }
+
+    #[test]
+    fn partly_signed_transactions_do_not_assemble() {
+        let (fee_payer, cosigner) = (Keypair::new(), Keypair::new());
+        let mut transaction = two_signer_transaction(&fee_payer, &cosigner, &blockhash());
+        sign(&mut transaction, &fee_payer).unwrap();
+
+        assert!(matches!(
+            assemble(transaction),
+            Err(ClientError::MissingSignatures(missing)) if missing == vec![cosigner.pubkey()]
+        ));
+    }
+
+    #[test]
+    fn nonces_are_advanced_first() {
+        let (fee_payer, cosigner) = (Keypair::new(), Keypair::new());
+        let (account, blockhash) = (Pubkey::new_unique(), Hash::new_unique());
+        let transaction = two_signer_transaction(
+            &fee_payer,
+            &cosigner,
+            &Lifetime::Nonce {
+                account,
+                authority: fee_payer.pubkey(),
+                blockhash,
+            },
+        );
+        let message = &transaction.message;
+        assert_eq!(message.recent_blockhash, blockhash);
+        assert_eq!(message.instructions.len(), 2);
+
+        let advance = &message.instructions[0];
+        assert_eq!(
+            message.account_keys[advance.program_id_index as usize],
+            system_program::id()
+        );
+        assert_eq!(message.account_keys[advance.accounts[0] as usize], account);
+        assert_eq!(
+            advance.data,
+            bincode::serialize(&SystemInstruction::AdvanceNonceAccount).unwrap()
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Orders signed off-chain, passed from maker to taker as text.
+//!
+//! A signed order travels as base64 of the packed [`Order`] followed by the
+//! maker's 64-byte signature of [`Order::message`].
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_escrow::order::{self, Order};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Signature, signer::Signer};
+
+use crate::{ClientError, Result};
+
+/// An order together with its maker's signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedOrder {
+    pub order: Order,
+    pub signature: Signature,
+}
+
+impl SignedOrder {
+    /// The ed25519 program instruction that has to go right before the fill
+    pub fn ed25519_instruction(&self, program_id: &Pubkey) -> Instruction {
+        order::ed25519_instruction(
+            &self.order.maker,
+            self.signature
+                .as_ref()
+                .try_into()
+                .expect("signatures are 64 bytes"),
+            &self.order.message(program_id),
+        )
+    }
+
+    /// Whether the signature really is the maker's, checked before spending
+    /// a transaction on it
+    pub fn verify(&self, program_id: &Pubkey) -> bool {
+        self.signature
+            .verify(self.order.maker.as_ref(), &self.order.message(program_id))
+    }
+}
+
+/// Signs `order` as its maker
+pub fn sign(order: Order, program_id: &Pubkey, maker: &dyn Signer) -> Result<SignedOrder> {
+    if maker.pubkey() != order.maker {
+        return Err(ClientError::NotASigner(maker.pubkey()));
+    }
+    let signature = maker
+        .try_sign_message(&order.message(program_id))
+        .map_err(|e| ClientError::Signing(e.to_string()))?;
+    Ok(SignedOrder { order, signature })
+}
+
+pub fn encode(signed: &SignedOrder) -> String {
+    STANDARD.encode([&signed.order.pack()[..], signed.signature.as_ref()].concat())
+}
+
+pub fn decode(encoded: &str) -> Result<SignedOrder> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| ClientError::Encoding(e.to_string()))?;
+    if bytes.len() != Order::LEN + 64 {
+        return Err(ClientError::Encoding(format!(
+            "a signed order is {} bytes, not {}",
+            Order::LEN + 64,
+            bytes.len()
+        )));
+    }
+    let (order, signature) = bytes.split_at(Order::LEN);
+    Ok(SignedOrder {
+        order: Order::unpack(order)?,
+        signature: Signature::try_from(signature)
+            .map_err(|e| ClientError::Encoding(e.to_string()))?,
+    })
+}
@@ -0,0 +1,75 @@
+use escrow_client::allow_list::AllowList;
+use solana_escrow::merkle::{self, MAX_PROOF_LEN};
+use solana_sdk::pubkey::Pubkey;
+
+fn takers(count: usize) -> Vec<Pubkey> {
+    (0..count).map(|_| Pubkey::new_unique()).collect()
+}
+
+#[test]
+fn every_taker_on_a_large_list_proves_membership() {
+    for count in [1, 2, 3, 1_000, 4_097] {
+        let takers = takers(count);
+        let allow_list = AllowList::new(&takers);
+        let root = allow_list.root();
+        assert_eq!(allow_list.len(), count);
+        for taker in &takers {
+            let proof = allow_list.proof(taker).unwrap();
+            assert!(proof.len() <= MAX_PROOF_LEN);
+            assert!(
+                merkle::verify(&root, taker, &proof),
+                "{} of {}",
+                taker,
+                count
+            );
+        }
+    }
+}
+
+#[test]
+fn proofs_stay_logarithmic() {
+    let takers = takers(100_000);
+    let allow_list = AllowList::new(&takers);
+    let root = allow_list.root();
+    // 2^17 is the first power of two over 100,000
+    for taker in takers.iter().step_by(997) {
+        let proof = allow_list.proof(taker).unwrap();
+        assert!(proof.len() <= 17);
+        assert!(merkle::verify(&root, taker, &proof));
+    }
+}
+
+#[test]
+fn outsiders_and_tampered_proofs_fail() {
+    let takers = takers(10_000);
+    let allow_list = AllowList::new(&takers);
+    let root = allow_list.root();
+    let outsider = Pubkey::new_unique();
+    assert!(allow_list.proof(&outsider).is_none());
+
+    // someone else's proof doesn't work for an outsider or another taker
+    let proof = allow_list.proof(&takers[0]).unwrap();
+    assert!(!merkle::verify(&root, &outsider, &proof));
+    assert!(!merkle::verify(&root, &takers[1], &proof));
+
+    let mut tampered = proof.clone();
+    tampered[0][0] ^= 1;
+    assert!(!merkle::verify(&root, &takers[0], &tampered));
+    assert!(!merkle::verify(&root, &takers[0], &proof[1..]));
+    // an inner node can't pass itself off as a leaf
+    assert!(!merkle::verify(&root, &takers[0], &[]));
+}
+
+#[test]
+fn the_root_depends_only_on_who_is_listed() {
+    let mut takers = takers(500);
+    let root = AllowList::new(&takers).root();
+    takers.reverse();
+    takers.push(takers[0]);
+    assert_eq!(AllowList::new(&takers).root(), root);
+    assert_eq!(AllowList::new(&takers).len(), 500);
+
+    takers.push(Pubkey::new_unique());
+    assert_ne!(AllowList::new(&takers).root(), root);
+    assert_eq!(AllowList::new(&[]).root(), [0; 32]);
+}
@@ -0,0 +1,478 @@
+//! A local order book of escrows, kept in SQLite.
+//!
+//! The book is fed from two kinds of input: the [`EscrowEvent`]s found in
+//! transaction logs, and the escrow accounts themselves. [`replay`] pulls both
+//! from an RPC node; anything else that sees transactions or account updates
+//! (a Geyser plugin, a block replayer, ...) can call the `apply_*` methods of
+//! [`OrderBook`] directly.
+
+pub mod replay;
+pub mod store;
+
+use escrow_client::ClientError;
+use solana_escrow::{
+    event::EscrowEvent,
+    state::{Escrow, EscrowMode},
+};
+use solana_rpc_client_api::client_error::Error as RpcError;
+use solana_sdk::{pubkey::Pubkey, signature::ParseSignatureError};
+use spl_token::state::Account as TokenAccount;
+use thiserror::Error;
+
+pub use store::OrderBook;
+
+#[derive(Error, Debug)]
+pub enum IndexerError {
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error(transparent)]
+    Signature(#[from] ParseSignatureError),
+}
+
+impl From<RpcError> for IndexerError {
+    fn from(e: RpcError) -> Self {
+        IndexerError::Client(e.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, IndexerError>;
+
+/// Where an escrow is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Open,
+    Exchanged,
+    Cancelled,
+    Expired,
+    /// The account is gone but no event said why yet
+    Closed,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Open => "open",
+            Status::Exchanged => "exchanged",
+            Status::Cancelled => "cancelled",
+            Status::Expired => "expired",
+            Status::Closed => "closed",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        Some(match status {
+            "open" => Status::Open,
+            "exchanged" => Status::Exchanged,
+            "cancelled" => Status::Cancelled,
+            "expired" => Status::Expired,
+            "closed" => Status::Closed,
+            _ => return None,
+        })
+    }
+
+    /// Settled one way or another, and known how
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            Status::Exchanged | Status::Cancelled | Status::Expired
+        )
+    }
+}
+
+/// One row of the order book. Fields are `None` until some event or account
+/// update has told the indexer about them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    pub address: Pubkey,
+    pub maker: Pubkey,
+    pub vault: Option<Pubkey>,
+    pub receive_account: Option<Pubkey>,
+    pub offered_mint: Option<Pubkey>,
+    pub offered_amount: Option<u64>,
+    pub requested_mint: Option<Pubkey>,
+    pub requested_amount: Option<u64>,
+    pub unlock_time: Option<u64>,
+    pub time_out: Option<u64>,
+    pub status: Status,
+    pub taker: Option<Pubkey>,
+    /// Slot of the newest event or account update applied to this row
+    pub updated_slot: u64,
+}
+
+impl Order {
+    fn new(address: Pubkey, maker: Pubkey, status: Status, updated_slot: u64) -> Self {
+        Order {
+            address,
+            maker,
+            vault: None,
+            receive_account: None,
+            offered_mint: None,
+            offered_amount: None,
+            requested_mint: None,
+            requested_amount: None,
+            unlock_time: None,
+            time_out: None,
+            status,
+            taker: None,
+            updated_slot,
+        }
+    }
+
+    /// What an escrow account says about the order, plus the mints of its
+    /// token accounts when those were fetched too
+    pub fn from_escrow(
+        address: Pubkey,
+        escrow: &Escrow,
+        vault: Option<&TokenAccount>,
+        receive_account: Option<&TokenAccount>,
+        slot: u64,
+    ) -> Self {
+        // only swaps ask for a payment on this chain. Savings locks and
+        // milestone escrows never time out and nobody can take them, so they
+        // stay out of the open queries.
+        let swap = escrow.mode == EscrowMode::Swap;
+        let takeable = !matches!(
+            escrow.mode,
+            EscrowMode::SavingsLock | EscrowMode::Milestones
+        );
+        Order {
+            vault: Some(escrow.temp_token_account_pubkey),
+            receive_account: swap.then_some(escrow.initializer_token_to_receive_account_pubkey),
+            offered_mint: vault.map(|account| account.mint),
+            offered_amount: if escrow.is_delegated() {
+                Some(escrow.delegated_amount)
+            } else {
+                vault.map(|account| account.amount)
+            },
+            requested_mint: receive_account.map(|account| account.mint),
+            requested_amount: swap.then_some(escrow.expected_amount),
+            unlock_time: Some(escrow.unlock_time),
+            time_out: takeable.then_some(escrow.time_out),
+            ..Order::new(address, escrow.initializer_pubkey, Status::Open, slot)
+        }
+    }
+
+    /// What an event says about the order, or `None` for events that
+    /// aren't about a single order
+    pub fn from_event(event: &EscrowEvent) -> Option<Self> {
+        let slot = event.clock().slot;
+        let order = match event {
+            EscrowEvent::Created {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                requested_mint,
+                requested_amount,
+                unlock_time,
+                time_out,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                requested_mint: Some(*requested_mint),
+                requested_amount: Some(*requested_amount),
+                unlock_time: Some(*unlock_time),
+                time_out: Some(*time_out),
+                ..Order::new(*escrow, *maker, Status::Open, slot)
+            },
+            EscrowEvent::Exchanged {
+                escrow,
+                maker,
+                taker,
+                offered_mint,
+                offered_amount,
+                requested_mint,
+                requested_amount,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                requested_mint: Some(*requested_mint),
+                requested_amount: Some(*requested_amount),
+                taker: Some(*taker),
+                ..Order::new(*escrow, *maker, Status::Exchanged, slot)
+            },
+            EscrowEvent::Cancelled {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                ..Order::new(*escrow, *maker, Status::Cancelled, slot)
+            },
+            EscrowEvent::LockReset {
+                escrow,
+                maker,
+                unlock_time,
+                time_out,
+                ..
+            } => Order {
+                unlock_time: Some(*unlock_time),
+                time_out: Some(*time_out),
+                ..Order::new(*escrow, *maker, Status::Open, slot)
+            },
+            EscrowEvent::Expired {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                time_out,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                time_out: Some(*time_out),
+                ..Order::new(*escrow, *maker, Status::Expired, slot)
+            },
+            EscrowEvent::HashLocked {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                unlock_time,
+                time_out,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                unlock_time: Some(*unlock_time),
+                time_out: Some(*time_out),
+                ..Order::new(*escrow, *maker, Status::Open, slot)
+            },
+            EscrowEvent::Redeemed {
+                escrow,
+                maker,
+                counterparty,
+                offered_mint,
+                offered_amount,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                taker: Some(*counterparty),
+                ..Order::new(*escrow, *maker, Status::Exchanged, slot)
+            },
+            EscrowEvent::SwitchArmed {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                time_out,
+                clock,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                unlock_time: Some(clock.slot),
+                time_out: Some(*time_out),
+                ..Order::new(*escrow, *maker, Status::Open, slot)
+            },
+            EscrowEvent::Claimed {
+                escrow,
+                maker,
+                beneficiary,
+                offered_mint,
+                offered_amount,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                taker: Some(*beneficiary),
+                ..Order::new(*escrow, *maker, Status::Exchanged, slot)
+            },
+            EscrowEvent::Locked {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                unlock_time,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                unlock_time: Some(*unlock_time),
+                ..Order::new(*escrow, *maker, Status::Open, slot)
+            },
+            EscrowEvent::Unlocked {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                ..Order::new(*escrow, *maker, Status::Cancelled, slot)
+            },
+            EscrowEvent::Arbitrated {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                time_out,
+                clock,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                unlock_time: Some(clock.slot),
+                time_out: Some(*time_out),
+                ..Order::new(*escrow, *maker, Status::Open, slot)
+            },
+            // a bond settles alongside the Exchanged or Cancelled that
+            // follows it, which decides the status
+            EscrowEvent::Delivered { escrow, maker, .. }
+            | EscrowEvent::Disputed { escrow, maker, .. }
+            | EscrowEvent::BondPosted { escrow, maker, .. }
+            | EscrowEvent::BondRefunded { escrow, maker, .. }
+            | EscrowEvent::BondForfeited { escrow, maker, .. }
+            | EscrowEvent::PremiumSet { escrow, maker, .. }
+            | EscrowEvent::Reserved { escrow, maker, .. }
+            | EscrowEvent::ExclusiveTakerSet { escrow, maker, .. }
+            | EscrowEvent::AllowListSet { escrow, maker, .. }
+            | EscrowEvent::PriceConditionSet { escrow, maker, .. }
+            | EscrowEvent::CallbackSet { escrow, maker, .. } => {
+                Order::new(*escrow, *maker, Status::Open, slot)
+            }
+            EscrowEvent::Settled {
+                escrow,
+                maker,
+                taker,
+                offered_mint,
+                to_taker,
+                to_maker,
+                ..
+            } => {
+                // a deal that paid the taker nothing was effectively cancelled
+                let (status, taker) = if *to_taker > 0 {
+                    (Status::Exchanged, Some(*taker))
+                } else {
+                    (Status::Cancelled, None)
+                };
+                Order {
+                    offered_mint: Some(*offered_mint),
+                    offered_amount: Some(to_taker + to_maker),
+                    taker,
+                    ..Order::new(*escrow, *maker, status, slot)
+                }
+            }
+            EscrowEvent::MilestonesOpened {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                clock,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                unlock_time: Some(clock.slot),
+                ..Order::new(*escrow, *maker, Status::Open, slot)
+            },
+            // the vault holds what is still pending, until the last tranche
+            // settles and closes it
+            EscrowEvent::MilestoneApproved {
+                escrow,
+                maker,
+                payee,
+                offered_mint,
+                remaining,
+                ..
+            } => {
+                let status = if *remaining == 0 {
+                    Status::Exchanged
+                } else {
+                    Status::Open
+                };
+                Order {
+                    offered_mint: Some(*offered_mint),
+                    offered_amount: Some(*remaining),
+                    taker: Some(*payee),
+                    ..Order::new(*escrow, *maker, status, slot)
+                }
+            }
+            EscrowEvent::MilestonesRefunded {
+                escrow,
+                maker,
+                offered_mint,
+                remaining,
+                ..
+            } => {
+                let status = if *remaining == 0 {
+                    Status::Cancelled
+                } else {
+                    Status::Open
+                };
+                Order {
+                    offered_mint: Some(*offered_mint),
+                    offered_amount: Some(*remaining),
+                    ..Order::new(*escrow, *maker, status, slot)
+                }
+            }
+            // a signed order has no escrow account; its receipt stands in
+            EscrowEvent::OrderFilled {
+                escrow,
+                maker,
+                taker,
+                offered_mint,
+                offered_amount,
+                requested_mint,
+                requested_amount,
+                ..
+            } => Order {
+                offered_mint: Some(*offered_mint),
+                offered_amount: Some(*offered_amount),
+                requested_mint: Some(*requested_mint),
+                requested_amount: Some(*requested_amount),
+                taker: Some(*taker),
+                ..Order::new(*escrow, *maker, Status::Exchanged, slot)
+            },
+            EscrowEvent::OrdersCancelled { .. } => return None,
+            // a pool isn't any one maker's order
+            EscrowEvent::PoolOpened { .. }
+            | EscrowEvent::PoolDeposited { .. }
+            | EscrowEvent::PoolFilled { .. }
+            | EscrowEvent::PoolWithdrawn { .. } => return None,
+        };
+        Some(order)
+    }
+
+    /// Folds a newer or older observation of the same escrow into this one.
+    ///
+    /// The newer of the two decides the status and lock window, except that
+    /// an event saying how an escrow ended always beats a bare `Closed`, and
+    /// nothing reopens a settled escrow. Fields only one side knows are kept.
+    pub fn merge(self, other: Order) -> Order {
+        let (older, newer) = if other.updated_slot >= self.updated_slot {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let status = if older.status.is_final() && !newer.status.is_final() {
+            older.status
+        } else {
+            newer.status
+        };
+        Order {
+            address: newer.address,
+            maker: newer.maker,
+            vault: newer.vault.or(older.vault),
+            receive_account: newer.receive_account.or(older.receive_account),
+            offered_mint: newer.offered_mint.or(older.offered_mint),
+            offered_amount: newer.offered_amount.or(older.offered_amount),
+            requested_mint: newer.requested_mint.or(older.requested_mint),
+            requested_amount: newer.requested_amount.or(older.requested_amount),
+            unlock_time: newer.unlock_time.or(older.unlock_time),
+            time_out: newer.time_out.or(older.time_out),
+            status,
+            taker: newer.taker.or(older.taker),
+            updated_slot: newer.updated_slot,
+        }
+    }
+}
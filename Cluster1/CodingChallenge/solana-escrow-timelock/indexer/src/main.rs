@@ -0,0 +1,131 @@
+use std::{path::PathBuf, process::exit, thread::sleep, time::Duration};
+
+use clap::{Parser, Subcommand};
+use escrow_client::EscrowClient;
+use escrow_indexer::{replay, Order, OrderBook};
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Parser)]
+#[command(
+    name = "escrow-indexer",
+    about = "Keep a local order book of escrows and query it"
+)]
+struct Cli {
+    /// RPC endpoint of the cluster
+    #[arg(long, short, global = true, default_value = "http://localhost:8899")]
+    url: String,
+    /// Address of the escrow program
+    #[arg(long, global = true, default_value_t = solana_escrow::id())]
+    program_id: Pubkey,
+    /// SQLite file holding the order book
+    #[arg(long, global = true, default_value = "escrow-index.sqlite")]
+    db: PathBuf,
+    /// Slot to evaluate lock windows at [default: the cluster's current slot]
+    #[arg(long, global = true)]
+    slot: Option<u64>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Catch up with the cluster once
+    Sync,
+    /// Keep catching up with the cluster
+    Watch {
+        /// Seconds between syncs
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+    /// List the orders that have not timed out
+    Open,
+    /// List the open orders that time out soon
+    Expiring {
+        /// How many slots ahead to look
+        #[arg(long)]
+        within: u64,
+    },
+    /// List the orders of a maker
+    Maker {
+        maker: Pubkey,
+        /// Include settled and timed out orders
+        #[arg(long)]
+        all: bool,
+    },
+    /// List the orders offering or asking for a mint
+    Mint {
+        mint: Pubkey,
+        /// Include settled and timed out orders
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+fn print_orders(orders: &[Order]) {
+    fn show<T: ToString>(value: &Option<T>) -> String {
+        value.as_ref().map_or("?".to_string(), T::to_string)
+    }
+    for order in orders {
+        println!(
+            "{}  {:<9}  offers {:>20} {:<44}  for {:>20} {:<44}  unlock {:>12}  time out {:>12}",
+            order.address,
+            order.status.as_str(),
+            show(&order.offered_amount),
+            show(&order.offered_mint),
+            show(&order.requested_amount),
+            show(&order.requested_mint),
+            show(&order.unlock_time),
+            show(&order.time_out),
+        );
+    }
+}
+
+fn run(cli: Cli) -> escrow_indexer::Result<()> {
+    let client = EscrowClient::new(cli.url.clone(), cli.program_id);
+    let mut book = OrderBook::open(&cli.db)?;
+    let slot = || -> escrow_indexer::Result<u64> {
+        Ok(match cli.slot {
+            Some(slot) => slot,
+            None => client.rpc.get_slot()?,
+        })
+    };
+
+    match &cli.command {
+        Command::Sync => {
+            let stats = replay::sync(&client, &mut book)?;
+            println!(
+                "{} transactions, {} new events, {} live escrows",
+                stats.transactions, stats.events, stats.escrows
+            );
+        }
+        Command::Watch { interval } => loop {
+            match replay::sync(&client, &mut book) {
+                Ok(stats) if stats.transactions > 0 => println!(
+                    "{} transactions, {} new events, {} live escrows",
+                    stats.transactions, stats.events, stats.escrows
+                ),
+                Ok(_) => {}
+                Err(err) => eprintln!("warning: sync failed: {}", err),
+            }
+            sleep(Duration::from_secs(*interval));
+        },
+        Command::Open => print_orders(&book.open_orders(slot()?)?),
+        Command::Expiring { within } => print_orders(&book.expiring(slot()?, *within)?),
+        Command::Maker { maker, all } => {
+            let open_at = if *all { None } else { Some(slot()?) };
+            print_orders(&book.by_maker(maker, open_at)?);
+        }
+        Command::Mint { mint, all } => {
+            let open_at = if *all { None } else { Some(slot()?) };
+            print_orders(&book.by_mint(mint, open_at)?);
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,153 @@
+//! Catches the order book up with a cluster over RPC.
+
+use std::collections::HashMap;
+
+use escrow_client::EscrowClient;
+use solana_escrow::state::{AccountType, Escrow, ESCROW_VERSION, LEGACY_ESCROW_LEN};
+use solana_rpc_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_rpc_client_api::{
+    config::{RpcProgramAccountsConfig, RpcTransactionConfig},
+    filter::{Memcmp, RpcFilterType},
+    request::MAX_MULTIPLE_ACCOUNTS,
+};
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::UiTransactionEncoding;
+use spl_token::state::Account as TokenAccount;
+
+use crate::{Order, OrderBook, Result};
+
+/// What one [`sync`] did
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyncStats {
+    pub transactions: usize,
+    pub events: usize,
+    pub escrows: usize,
+}
+
+/// Replays the transactions since the last sync, then refreshes the book
+/// from the escrow accounts that exist now
+pub fn sync(client: &EscrowClient, book: &mut OrderBook) -> Result<SyncStats> {
+    let (transactions, events) = replay(client, book)?;
+    let escrows = snapshot(client, book)?;
+    Ok(SyncStats {
+        transactions,
+        events,
+        escrows,
+    })
+}
+
+/// Applies, oldest first, every transaction that touched the program since
+/// the book's cursor. Returns how many transactions and new events there were.
+///
+/// On the first run this goes back as far as the node keeps history.
+pub fn replay(client: &EscrowClient, book: &mut OrderBook) -> Result<(usize, usize)> {
+    let until = book.cursor(&client.program_id)?;
+    let mut pending = vec![];
+    let mut before = None;
+    loop {
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before,
+            until,
+            limit: None,
+            commitment: Some(client.rpc.commitment()),
+        };
+        // newest first, a page at a time
+        let page = client
+            .rpc
+            .get_signatures_for_address_with_config(&client.program_id, config)?;
+        match page.last() {
+            Some(oldest) => before = Some(oldest.signature.parse()?),
+            None => break,
+        }
+        pending.extend(page);
+    }
+
+    let mut events = 0;
+    for status in pending.iter().rev() {
+        let signature: Signature = status.signature.parse()?;
+        let logs = match status.err {
+            Some(_) => vec![],
+            None => transaction_logs(client, &signature)?,
+        };
+        events += book.apply_transaction(&client.program_id, &signature, &logs)?;
+    }
+    Ok((pending.len(), events))
+}
+
+/// Applies every escrow account the program owns right now, with the mints
+/// of their token accounts. Returns how many there were.
+pub fn snapshot(client: &EscrowClient, book: &mut OrderBook) -> Result<usize> {
+    let slot = client.rpc.get_slot()?;
+    let mut escrows: Vec<(Pubkey, Escrow)> = vec![];
+    for filter in [
+        // current escrows are as long as the extensions they use
+        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            vec![AccountType::Escrow as u8, ESCROW_VERSION],
+        )),
+        RpcFilterType::DataSize(LEGACY_ESCROW_LEN as u64),
+    ] {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![filter]),
+            ..RpcProgramAccountsConfig::default()
+        };
+        escrows.extend(
+            client
+                .rpc
+                .get_program_accounts_with_config(&client.program_id, config)?
+                .into_iter()
+                .filter_map(|(key, account)| Escrow::unpack(&account.data).ok().map(|e| (key, e))),
+        );
+    }
+
+    let token_keys: Vec<Pubkey> = escrows
+        .iter()
+        .flat_map(|(_, escrow)| {
+            [
+                escrow.temp_token_account_pubkey,
+                escrow.initializer_token_to_receive_account_pubkey,
+            ]
+        })
+        .collect();
+    let mut token_accounts = HashMap::new();
+    for keys in token_keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let accounts = client.rpc.get_multiple_accounts(keys)?;
+        for (key, account) in keys.iter().zip(accounts) {
+            if let Some(token_account) =
+                account.and_then(|account| TokenAccount::unpack(&account.data).ok())
+            {
+                token_accounts.insert(*key, token_account);
+            }
+        }
+    }
+
+    let orders: Vec<Order> = escrows
+        .iter()
+        .map(|(key, escrow)| {
+            Order::from_escrow(
+                *key,
+                escrow,
+                token_accounts.get(&escrow.temp_token_account_pubkey),
+                token_accounts.get(&escrow.initializer_token_to_receive_account_pubkey),
+                slot,
+            )
+        })
+        .collect();
+    let count = orders.len();
+    book.apply_snapshot(orders, slot)?;
+    Ok(count)
+}
+
+fn transaction_logs(client: &EscrowClient, signature: &Signature) -> Result<Vec<String>> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(client.rpc.commitment()),
+        max_supported_transaction_version: Some(0),
+    };
+    let transaction = client.rpc.get_transaction_with_config(signature, config)?;
+    Ok(transaction
+        .transaction
+        .meta
+        .and_then(|meta| meta.log_messages.into())
+        .unwrap_or_default())
+}
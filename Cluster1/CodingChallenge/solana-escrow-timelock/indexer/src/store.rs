@@ -0,0 +1,371 @@
+//! The SQLite side of the order book.
+
+use std::{collections::HashSet, path::Path};
+
+use escrow_client::events;
+use rusqlite::{
+    params, types::Type, Connection, OptionalExtension, Row, Transaction as DbTransaction,
+};
+use solana_escrow::{event::EscrowEvent, state::Escrow};
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey, signature::Signature};
+
+use crate::{Order, Result, Status};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS escrows (
+    address          TEXT PRIMARY KEY,
+    maker            TEXT NOT NULL,
+    vault            TEXT,
+    receive_account  TEXT,
+    offered_mint     TEXT,
+    offered_amount   INTEGER,
+    requested_mint   TEXT,
+    requested_amount INTEGER,
+    unlock_time      INTEGER,
+    time_out         INTEGER,
+    status           TEXT NOT NULL,
+    taker            TEXT,
+    updated_slot     INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS escrows_maker ON escrows (maker);
+CREATE INDEX IF NOT EXISTS escrows_offered_mint ON escrows (offered_mint);
+CREATE INDEX IF NOT EXISTS escrows_requested_mint ON escrows (requested_mint);
+CREATE INDEX IF NOT EXISTS escrows_time_out ON escrows (status, time_out);
+
+CREATE TABLE IF NOT EXISTS events (
+    signature      TEXT NOT NULL,
+    idx            INTEGER NOT NULL,
+    escrow         TEXT NOT NULL,
+    kind           TEXT NOT NULL,
+    slot           INTEGER NOT NULL,
+    unix_timestamp INTEGER NOT NULL,
+    PRIMARY KEY (signature, idx)
+);
+
+CREATE TABLE IF NOT EXISTS cursors (
+    program_id TEXT PRIMARY KEY,
+    signature  TEXT NOT NULL
+);
+";
+
+const COLUMNS: &str = "address, maker, vault, receive_account, offered_mint, offered_amount, \
+    requested_mint, requested_amount, unlock_time, time_out, status, taker, updated_slot";
+
+pub struct OrderBook {
+    conn: Connection,
+}
+
+impl OrderBook {
+    /// Opens the book at `path`, creating it if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(OrderBook { conn })
+    }
+
+    /// Applies every event `program_id` emitted in a successful transaction
+    /// and remembers it as the newest one seen. Pass no logs for a failed
+    /// transaction. Returns how many events were new.
+    pub fn apply_transaction(
+        &mut self,
+        program_id: &Pubkey,
+        signature: &Signature,
+        logs: &[String],
+    ) -> Result<usize> {
+        let decoded = events::decode_logs(program_id, logs)?;
+        let tx = self.conn.transaction()?;
+        let mut applied = 0;
+        for (index, event) in decoded.iter().enumerate() {
+            if apply_event(&tx, signature, index, event)? {
+                applied += 1;
+            }
+        }
+        tx.execute(
+            "INSERT INTO cursors (program_id, signature) VALUES (?1, ?2)
+             ON CONFLICT (program_id) DO UPDATE SET signature = excluded.signature",
+            params![program_id.to_string(), signature.to_string()],
+        )?;
+        tx.commit()?;
+        Ok(applied)
+    }
+
+    /// Applies the `index`th event of a transaction. Returns false if it was
+    /// applied before.
+    pub fn apply_event(
+        &mut self,
+        signature: &Signature,
+        index: usize,
+        event: &EscrowEvent,
+    ) -> Result<bool> {
+        let tx = self.conn.transaction()?;
+        let applied = apply_event(&tx, signature, index, event)?;
+        tx.commit()?;
+        Ok(applied)
+    }
+
+    /// Applies an update of an account owned by the escrow program. Data that
+    /// no longer holds an initialized escrow means the account was closed.
+    pub fn apply_account(&mut self, address: &Pubkey, data: &[u8], slot: u64) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        match Escrow::unpack(data) {
+            Ok(escrow) => upsert(&tx, Order::from_escrow(*address, &escrow, None, None, slot))?,
+            Err(_) => close(&tx, address, slot)?,
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Applies every escrow that exists at `slot`. Open orders missing from
+    /// `orders` were closed since they were last seen.
+    pub fn apply_snapshot(&mut self, orders: Vec<Order>, slot: u64) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let present: HashSet<Pubkey> = orders.iter().map(|order| order.address).collect();
+        for order in orders {
+            upsert(&tx, order)?;
+        }
+        let open = query(
+            &tx,
+            "WHERE status = 'open' AND updated_slot <= ?1",
+            params![slot as i64],
+        )?;
+        for order in open {
+            if !present.contains(&order.address) {
+                close(&tx, &order.address, slot)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The newest transaction [`apply_transaction`](Self::apply_transaction)
+    /// saw for `program_id`
+    pub fn cursor(&self, program_id: &Pubkey) -> Result<Option<Signature>> {
+        let signature: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT signature FROM cursors WHERE program_id = ?1",
+                params![program_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(match signature {
+            Some(signature) => Some(signature.parse()?),
+            None => None,
+        })
+    }
+
+    pub fn get(&self, address: &Pubkey) -> Result<Option<Order>> {
+        Ok(query(
+            &self.conn,
+            "WHERE address = ?1",
+            params![address.to_string()],
+        )?
+        .pop())
+    }
+
+    /// Orders that can still be taken at `slot`, or will be once unlocked,
+    /// soonest to time out first
+    pub fn open_orders(&self, slot: u64) -> Result<Vec<Order>> {
+        query(
+            &self.conn,
+            "WHERE status = 'open' AND time_out >= ?1 ORDER BY time_out",
+            params![slot as i64],
+        )
+    }
+
+    /// Open orders that time out within `slots` slots of `slot`
+    pub fn expiring(&self, slot: u64, slots: u64) -> Result<Vec<Order>> {
+        query(
+            &self.conn,
+            "WHERE status = 'open' AND time_out BETWEEN ?1 AND ?2 ORDER BY time_out",
+            params![slot as i64, slot.saturating_add(slots) as i64],
+        )
+    }
+
+    /// Every order `maker` opened, newest first, or only those still open at
+    /// `open_at`
+    pub fn by_maker(&self, maker: &Pubkey, open_at: Option<u64>) -> Result<Vec<Order>> {
+        query(
+            &self.conn,
+            "WHERE maker = ?1
+             AND (?2 IS NULL OR (status = 'open' AND time_out >= ?2))
+             ORDER BY updated_slot DESC",
+            params![maker.to_string(), open_at.map(|slot| slot as i64)],
+        )
+    }
+
+    /// Every order offering or asking for `mint`, newest first, or only those
+    /// still open at `open_at`
+    pub fn by_mint(&self, mint: &Pubkey, open_at: Option<u64>) -> Result<Vec<Order>> {
+        query(
+            &self.conn,
+            "WHERE (offered_mint = ?1 OR requested_mint = ?1)
+             AND (?2 IS NULL OR (status = 'open' AND time_out >= ?2))
+             ORDER BY updated_slot DESC",
+            params![mint.to_string(), open_at.map(|slot| slot as i64)],
+        )
+    }
+}
+
+fn apply_event(
+    tx: &DbTransaction,
+    signature: &Signature,
+    index: usize,
+    event: &EscrowEvent,
+) -> Result<bool> {
+    let clock = event.clock();
+    let inserted = tx.execute(
+        "INSERT OR IGNORE INTO events (signature, idx, escrow, kind, slot, unix_timestamp)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            signature.to_string(),
+            index as i64,
+            event.escrow().to_string(),
+            event_kind(event),
+            clock.slot as i64,
+            clock.unix_timestamp,
+        ],
+    )?;
+    if inserted == 0 {
+        return Ok(false);
+    }
+    if let Some(order) = Order::from_event(event) {
+        upsert(tx, order)?;
+    }
+    Ok(true)
+}
+
+fn event_kind(event: &EscrowEvent) -> &'static str {
+    match event {
+        EscrowEvent::Created { .. } => "created",
+        EscrowEvent::Exchanged { .. } => "exchanged",
+        EscrowEvent::Cancelled { .. } => "cancelled",
+        EscrowEvent::LockReset { .. } => "lock_reset",
+        EscrowEvent::Expired { .. } => "expired",
+        EscrowEvent::HashLocked { .. } => "hash_locked",
+        EscrowEvent::Redeemed { .. } => "redeemed",
+        EscrowEvent::SwitchArmed { .. } => "switch_armed",
+        EscrowEvent::Claimed { .. } => "claimed",
+        EscrowEvent::Locked { .. } => "locked",
+        EscrowEvent::Unlocked { .. } => "unlocked",
+        EscrowEvent::Arbitrated { .. } => "arbitrated",
+        EscrowEvent::Delivered { .. } => "delivered",
+        EscrowEvent::Disputed { .. } => "disputed",
+        EscrowEvent::Settled { .. } => "settled",
+        EscrowEvent::MilestonesOpened { .. } => "milestones_opened",
+        EscrowEvent::MilestoneApproved { .. } => "milestone_approved",
+        EscrowEvent::MilestonesRefunded { .. } => "milestones_refunded",
+        EscrowEvent::BondPosted { .. } => "bond_posted",
+        EscrowEvent::BondRefunded { .. } => "bond_refunded",
+        EscrowEvent::BondForfeited { .. } => "bond_forfeited",
+        EscrowEvent::PremiumSet { .. } => "premium_set",
+        EscrowEvent::Reserved { .. } => "reserved",
+        EscrowEvent::OrderFilled { .. } => "order_filled",
+        EscrowEvent::OrdersCancelled { .. } => "orders_cancelled",
+        EscrowEvent::PoolOpened { .. } => "pool_opened",
+        EscrowEvent::PoolDeposited { .. } => "pool_deposited",
+        EscrowEvent::PoolFilled { .. } => "pool_filled",
+        EscrowEvent::PoolWithdrawn { .. } => "pool_withdrawn",
+        EscrowEvent::ExclusiveTakerSet { .. } => "exclusive_taker_set",
+        EscrowEvent::AllowListSet { .. } => "allow_list_set",
+        EscrowEvent::PriceConditionSet { .. } => "price_condition_set",
+        EscrowEvent::CallbackSet { .. } => "callback_set",
+    }
+}
+
+fn upsert(tx: &DbTransaction, order: Order) -> Result<()> {
+    let order = match query(tx, "WHERE address = ?1", params![order.address.to_string()])?.pop() {
+        Some(existing) => existing.merge(order),
+        None => order,
+    };
+    // SQLite integers are signed; amounts keep their bits and are cast back on read
+    tx.execute(
+        &format!(
+            "INSERT OR REPLACE INTO escrows ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            COLUMNS
+        ),
+        params![
+            order.address.to_string(),
+            order.maker.to_string(),
+            order.vault.map(|key| key.to_string()),
+            order.receive_account.map(|key| key.to_string()),
+            order.offered_mint.map(|key| key.to_string()),
+            order.offered_amount.map(|n| n as i64),
+            order.requested_mint.map(|key| key.to_string()),
+            order.requested_amount.map(|n| n as i64),
+            order.unlock_time.map(|n| n as i64),
+            order.time_out.map(|n| n as i64),
+            order.status.as_str(),
+            order.taker.map(|key| key.to_string()),
+            order.updated_slot as i64,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Marks an open order closed, unless something newer than `slot` was seen
+fn close(tx: &DbTransaction, address: &Pubkey, slot: u64) -> Result<()> {
+    tx.execute(
+        "UPDATE escrows SET status = 'closed', updated_slot = ?2
+         WHERE address = ?1 AND status = 'open' AND updated_slot <= ?2",
+        params![address.to_string(), slot as i64],
+    )?;
+    Ok(())
+}
+
+fn query(conn: &Connection, clause: &str, params: impl rusqlite::Params) -> Result<Vec<Order>> {
+    let mut statement = conn.prepare(&format!("SELECT {} FROM escrows {}", COLUMNS, clause))?;
+    let orders = statement
+        .query_map(params, read_order)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(orders)
+}
+
+fn read_order(row: &Row) -> rusqlite::Result<Order> {
+    let status: String = row.get(10)?;
+    Ok(Order {
+        address: pubkey(row, 0)?,
+        maker: pubkey(row, 1)?,
+        vault: optional_pubkey(row, 2)?,
+        receive_account: optional_pubkey(row, 3)?,
+        offered_mint: optional_pubkey(row, 4)?,
+        offered_amount: optional_u64(row, 5)?,
+        requested_mint: optional_pubkey(row, 6)?,
+        requested_amount: optional_u64(row, 7)?,
+        unlock_time: optional_u64(row, 8)?,
+        time_out: optional_u64(row, 9)?,
+        status: Status::parse(&status).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                10,
+                Type::Text,
+                format!("unknown status {}", status).into(),
+            )
+        })?,
+        taker: optional_pubkey(row, 11)?,
+        updated_slot: row.get::<_, i64>(12)? as u64,
+    })
+}
+
+fn pubkey(row: &Row, index: usize) -> rusqlite::Result<Pubkey> {
+    let text: String = row.get(index)?;
+    text.parse()
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+}
+
+fn optional_pubkey(row: &Row, index: usize) -> rusqlite::Result<Option<Pubkey>> {
+    match row.get::<_, Option<String>>(index)? {
+        Some(_) => pubkey(row, index).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn optional_u64(row: &Row, index: usize) -> rusqlite::Result<Option<u64>> {
+    Ok(row.get::<_, Option<i64>>(index)?.map(|n| n as u64))
+}
@@ -0,0 +1,242 @@
+use escrow_indexer::{Order, OrderBook, Status};
+use solana_escrow::{
+    event::{EscrowEvent, EventClock},
+    state::{Escrow, EscrowMode},
+};
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey, signature::Signature};
+
+fn key(n: u8) -> Pubkey {
+    Pubkey::new_from_array([n; 32])
+}
+
+fn clock(slot: u64) -> EventClock {
+    EventClock {
+        slot,
+        unix_timestamp: slot as i64,
+    }
+}
+
+fn created(escrow: u8, maker: u8, time_out: u64, slot: u64) -> EscrowEvent {
+    EscrowEvent::Created {
+        escrow: key(escrow),
+        maker: key(maker),
+        offered_mint: key(100),
+        offered_amount: 10,
+        requested_mint: key(101),
+        requested_amount: u64::MAX,
+        unlock_time: slot + 5,
+        time_out,
+        clock: clock(slot),
+    }
+}
+
+fn escrow_account(maker: u8, time_out: u64) -> Vec<u8> {
+    let mut data = vec![0; Escrow::LEN];
+    Escrow::pack(
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey: key(maker),
+            temp_token_account_pubkey: key(50),
+            initializer_token_to_receive_account_pubkey: key(51),
+            expected_amount: 7,
+            unlock_time: 0,
+            time_out,
+            ..Escrow::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    data
+}
+
+#[test]
+fn queries_follow_the_lifecycle() {
+    let mut book = OrderBook::in_memory().unwrap();
+    let signature = Signature::new_unique();
+    book.apply_event(&signature, 0, &created(1, 10, 100, 1))
+        .unwrap();
+    book.apply_event(&signature, 1, &created(2, 11, 300, 1))
+        .unwrap();
+
+    let order = book.get(&key(1)).unwrap().unwrap();
+    assert_eq!(order.status, Status::Open);
+    assert_eq!(order.requested_amount, Some(u64::MAX));
+    assert_eq!(book.open_orders(50).unwrap().len(), 2);
+    assert_eq!(book.open_orders(200).unwrap().len(), 1);
+    assert_eq!(book.expiring(50, 100).unwrap()[0].address, key(1));
+    assert_eq!(
+        book.by_maker(&key(11), Some(50)).unwrap()[0].address,
+        key(2)
+    );
+    assert_eq!(book.by_mint(&key(101), Some(50)).unwrap().len(), 2);
+
+    let taken = EscrowEvent::Exchanged {
+        escrow: key(1),
+        maker: key(10),
+        taker: key(20),
+        offered_mint: key(100),
+        offered_amount: 10,
+        requested_mint: key(101),
+        requested_amount: u64::MAX,
+        clock: clock(60),
+    };
+    book.apply_event(&Signature::new_unique(), 0, &taken)
+        .unwrap();
+    assert_eq!(book.open_orders(50).unwrap().len(), 1);
+    assert!(book.by_maker(&key(10), Some(50)).unwrap().is_empty());
+    let order = &book.by_maker(&key(10), None).unwrap()[0];
+    assert_eq!(order.status, Status::Exchanged);
+    assert_eq!(order.taker, Some(key(20)));
+}
+
+#[test]
+fn events_apply_once() {
+    let mut book = OrderBook::in_memory().unwrap();
+    let signature = Signature::new_unique();
+    assert!(book
+        .apply_event(&signature, 0, &created(1, 10, 100, 1))
+        .unwrap());
+    assert!(!book
+        .apply_event(&signature, 0, &created(1, 10, 100, 1))
+        .unwrap());
+}
+
+#[test]
+fn transactions_advance_the_cursor() {
+    let program_id = solana_escrow::id();
+    let mut book = OrderBook::in_memory().unwrap();
+    assert_eq!(book.cursor(&program_id).unwrap(), None);
+
+    let signature = Signature::new_unique();
+    let logs = vec![
+        format!("Program {} invoke [1]", program_id),
+        format!(
+            "Program data: {}",
+            base64_event(&created(1, 10, 100, 1).pack())
+        ),
+        format!("Program {} success", program_id),
+    ];
+    assert_eq!(
+        book.apply_transaction(&program_id, &signature, &logs)
+            .unwrap(),
+        1
+    );
+    assert_eq!(book.cursor(&program_id).unwrap(), Some(signature));
+
+    let failed = Signature::new_unique();
+    assert_eq!(
+        book.apply_transaction(&program_id, &failed, &[]).unwrap(),
+        0
+    );
+    assert_eq!(book.cursor(&program_id).unwrap(), Some(failed));
+}
+
+#[test]
+fn accounts_fill_in_and_close_orders() {
+    let mut book = OrderBook::in_memory().unwrap();
+    book.apply_account(&key(1), &escrow_account(10, 100), 5)
+        .unwrap();
+    let order = book.get(&key(1)).unwrap().unwrap();
+    assert_eq!(order.vault, Some(key(50)));
+    assert_eq!(order.offered_mint, None);
+
+    // the event is older but still knows the mints
+    book.apply_event(&Signature::new_unique(), 0, &created(1, 10, 100, 1))
+        .unwrap();
+    let order = book.get(&key(1)).unwrap().unwrap();
+    assert_eq!(order.vault, Some(key(50)));
+    assert_eq!(order.offered_mint, Some(key(100)));
+    assert_eq!(order.requested_amount, Some(7));
+
+    book.apply_account(&key(1), &[], 8).unwrap();
+    assert_eq!(book.get(&key(1)).unwrap().unwrap().status, Status::Closed);
+
+    // how it closed shows up later
+    let cancelled = EscrowEvent::Cancelled {
+        escrow: key(1),
+        maker: key(10),
+        offered_mint: key(100),
+        offered_amount: 10,
+        clock: clock(8),
+    };
+    book.apply_event(&Signature::new_unique(), 0, &cancelled)
+        .unwrap();
+    assert_eq!(
+        book.get(&key(1)).unwrap().unwrap().status,
+        Status::Cancelled
+    );
+}
+
+#[test]
+fn snapshots_close_missing_orders() {
+    let mut book = OrderBook::in_memory().unwrap();
+    book.apply_event(&Signature::new_unique(), 0, &created(1, 10, 100, 1))
+        .unwrap();
+    book.apply_event(&Signature::new_unique(), 0, &created(2, 10, 100, 1))
+        .unwrap();
+
+    let escrow = Escrow::unpack(&escrow_account(10, 100)).unwrap();
+    book.apply_snapshot(vec![Order::from_escrow(key(2), &escrow, None, None, 9)], 9)
+        .unwrap();
+    assert_eq!(book.get(&key(1)).unwrap().unwrap().status, Status::Closed);
+    assert_eq!(book.get(&key(2)).unwrap().unwrap().status, Status::Open);
+}
+
+#[test]
+fn savings_locks_are_not_open_orders() {
+    let mut book = OrderBook::in_memory().unwrap();
+    let mut escrow = Escrow::unpack(&escrow_account(10, u64::MAX)).unwrap();
+    escrow.mode = EscrowMode::SavingsLock;
+    let mut data = vec![0; Escrow::LEN];
+    Escrow::pack(escrow, &mut data).unwrap();
+    book.apply_account(&key(1), &data, 5).unwrap();
+
+    let order = book.get(&key(1)).unwrap().unwrap();
+    assert_eq!(order.status, Status::Open);
+    assert_eq!(order.time_out, None);
+    assert!(book.open_orders(5).unwrap().is_empty());
+}
+
+#[test]
+fn milestone_escrows_settle_with_their_last_tranche() {
+    let mut book = OrderBook::in_memory().unwrap();
+    let opened = EscrowEvent::MilestonesOpened {
+        escrow: key(1),
+        maker: key(10),
+        payee: key(20),
+        offered_mint: key(100),
+        offered_amount: 30,
+        milestone_count: 2,
+        clock: clock(1),
+    };
+    book.apply_event(&Signature::new_unique(), 0, &opened)
+        .unwrap();
+    assert!(book.open_orders(5).unwrap().is_empty());
+
+    let approved = |remaining, slot| EscrowEvent::MilestoneApproved {
+        escrow: key(1),
+        maker: key(10),
+        payee: key(20),
+        offered_mint: key(100),
+        index: 0,
+        amount: 10,
+        remaining,
+        clock: clock(slot),
+    };
+    book.apply_event(&Signature::new_unique(), 0, &approved(20, 2))
+        .unwrap();
+    let order = book.get(&key(1)).unwrap().unwrap();
+    assert_eq!(order.status, Status::Open);
+    assert_eq!(order.offered_amount, Some(20));
+
+    book.apply_event(&Signature::new_unique(), 0, &approved(0, 3))
+        .unwrap();
+    let order = book.get(&key(1)).unwrap().unwrap();
+    assert_eq!(order.status, Status::Exchanged);
+    assert_eq!(order.taker, Some(key(20)));
+}
+
+fn base64_event(bytes: &[u8]) -> String {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    STANDARD.encode(bytes)
+}
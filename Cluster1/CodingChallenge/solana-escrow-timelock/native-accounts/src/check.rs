@@ -0,0 +1,99 @@
+//! The constraints [`accounts!`](crate::accounts) understands, also usable on
+//! their own for checks that need state loaded after parsing.
+//!
+//! `name` is what a failure logs the account as.
+
+use solana_program::{account_info::AccountInfo, msg, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{token_account, AccountError};
+
+fn fail(name: &str, error: AccountError) -> ProgramError {
+    msg!("{}: {}", name, error);
+    error.into()
+}
+
+pub fn signer(name: &str, account: &AccountInfo) -> Result<(), ProgramError> {
+    if !account.is_signer {
+        return Err(fail(name, AccountError::ConstraintSigner));
+    }
+    Ok(())
+}
+
+pub fn writable(name: &str, account: &AccountInfo) -> Result<(), ProgramError> {
+    if !account.is_writable {
+        return Err(fail(name, AccountError::ConstraintWritable));
+    }
+    Ok(())
+}
+
+pub fn owner(name: &str, account: &AccountInfo, owner: &Pubkey) -> Result<(), ProgramError> {
+    if account.owner != owner {
+        return Err(fail(name, AccountError::ConstraintOwner));
+    }
+    Ok(())
+}
+
+pub fn address(name: &str, account: &AccountInfo, address: &Pubkey) -> Result<(), ProgramError> {
+    if account.key != address {
+        return Err(fail(name, AccountError::ConstraintAddress));
+    }
+    Ok(())
+}
+
+/// Returns the bump of the PDA
+pub fn seeds(
+    name: &str,
+    account: &AccountInfo,
+    seeds: &[&[u8]],
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (pda, bump) = Pubkey::find_program_address(seeds, program_id);
+    if *account.key != pda {
+        return Err(fail(name, AccountError::ConstraintSeeds));
+    }
+    Ok(bump)
+}
+
+/// Like [`seeds`], but with a bump stored earlier it only has to derive one
+/// address instead of searching for it. Falls back to [`seeds`] without one.
+pub fn seeds_with_bump(
+    name: &str,
+    account: &AccountInfo,
+    seeds_: &[&[u8]],
+    bump: Option<u8>,
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let Some(bump) = bump else {
+        return seeds(name, account, seeds_, program_id);
+    };
+    let bump_seed = [bump];
+    let mut with_bump = seeds_.to_vec();
+    with_bump.push(&bump_seed);
+    match Pubkey::create_program_address(&with_bump, program_id) {
+        Ok(pda) if *account.key == pda => Ok(bump),
+        _ => Err(fail(name, AccountError::ConstraintSeeds)),
+    }
+}
+
+pub fn mint(name: &str, account: &AccountInfo, mint: &Pubkey) -> Result<(), ProgramError> {
+    if token(name, account)?.mint != *mint {
+        return Err(fail(name, AccountError::ConstraintTokenMint));
+    }
+    Ok(())
+}
+
+pub fn authority(
+    name: &str,
+    account: &AccountInfo,
+    authority: &Pubkey,
+) -> Result<(), ProgramError> {
+    if token(name, account)?.owner != *authority {
+        return Err(fail(name, AccountError::ConstraintTokenOwner));
+    }
+    Ok(())
+}
+
+fn token(name: &str, account: &AccountInfo) -> Result<spl_token::state::Account, ProgramError> {
+    owner(name, account, &spl_token::id())?;
+    token_account(account)
+}
@@ -0,0 +1,214 @@
+//! Declarative account checks for native programs, in the spirit of Anchor's
+//! `#[derive(Accounts)]`.
+//!
+//! [`accounts!`] declares one struct per instruction, listing every account
+//! with the constraints it must meet:
+//!
+//! ```
+//! use native_accounts::accounts;
+//!
+//! accounts! {
+//!     /// Accounts of a transfer out of a PDA-owned vault
+//!     pub struct Withdraw(program_id) {
+//!         owner: [signer],
+//!         vault: [writable, authority(authority.key)],
+//!         destination: [writable, mint(&native_accounts::token_account(vault)?.mint)],
+//!         token_program: [address(&spl_token::id())],
+//!         authority: [seeds(&[b"vault".as_ref(), owner.key.as_ref()])],
+//!     }
+//! }
+//! ```
+//!
+//! `Withdraw::parse(program_id, accounts)` takes the accounts in declaration
+//! order and checks every constraint, in order too. Accounts past the declared
+//! ones are left unchecked in `remaining`, for instructions that take a
+//! variable number of them. Constraint arguments are
+//! plain expressions and can use `program_id` (named in the declaration) and
+//! any of the accounts. A failed check logs which account broke which
+//! constraint and returns the matching [`AccountError`].
+//!
+//! | constraint           | passes when the account                                    |
+//! |----------------------|------------------------------------------------------------|
+//! | `signer`             | signed the transaction                                     |
+//! | `writable`           | is writable                                                |
+//! | `owner(key)`         | is owned by the program `key`                              |
+//! | `address(key)`       | is `key`                                                   |
+//! | `seeds(seeds)`       | is the PDA of `seeds` under `program_id`; the bump is kept |
+//! | `seeds(seeds, bump)` | the same, checked with `bump: Option<u8>` when it is known |
+//! | `mint(key)`          | is a token account of mint `key`                           |
+//! | `authority(key)`     | is a token account owned by `key`                          |
+
+pub mod check;
+
+use solana_program::{
+    account_info::AccountInfo, decode_error::DecodeError, program_error::ProgramError,
+    program_pack::Pack,
+};
+use spl_token::state::Account as TokenAccount;
+use thiserror::Error;
+
+#[doc(hidden)]
+pub use solana_program as __solana_program;
+
+/// Which constraint an account broke. The codes follow Anchor's constraint
+/// errors so they don't collide with a program's own error enum.
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AccountError {
+    /// Constraint Writable
+    #[error("Constraint Writable")]
+    ConstraintWritable = 2000,
+    /// Constraint Signer
+    #[error("Constraint Signer")]
+    ConstraintSigner = 2002,
+    /// Constraint Owner
+    #[error("Constraint Owner")]
+    ConstraintOwner = 2004,
+    /// Constraint Seeds
+    #[error("Constraint Seeds")]
+    ConstraintSeeds = 2006,
+    /// Constraint Address
+    #[error("Constraint Address")]
+    ConstraintAddress = 2012,
+    /// Constraint Token Mint
+    #[error("Constraint Token Mint")]
+    ConstraintTokenMint = 2014,
+    /// Constraint Token Owner, i.e. the `authority` constraint
+    #[error("Constraint Token Owner")]
+    ConstraintTokenOwner = 2015,
+    /// Fewer accounts than the instruction declares
+    #[error("Account Not Enough Keys")]
+    AccountNotEnoughKeys = 3005,
+}
+
+impl From<AccountError> for ProgramError {
+    fn from(e: AccountError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for AccountError {
+    fn type_of() -> &'static str {
+        "AccountError"
+    }
+}
+
+/// Bumps of the accounts checked with `seeds`, by account name
+#[derive(Debug, Default)]
+pub struct Bumps(Vec<(&'static str, u8)>);
+
+impl Bumps {
+    #[doc(hidden)]
+    pub fn insert(&mut self, name: &'static str, bump: u8) {
+        self.0.push((name, bump));
+    }
+
+    /// Bump of the account `name`, or `ConstraintSeeds` if it has no `seeds`
+    /// constraint
+    pub fn get(&self, name: &str) -> Result<u8, ProgramError> {
+        self.0
+            .iter()
+            .find(|(account, _)| *account == name)
+            .map(|(_, bump)| *bump)
+            .ok_or_else(|| {
+                solana_program::msg!("{}: no seeds constraint", name);
+                AccountError::ConstraintSeeds.into()
+            })
+    }
+}
+
+/// The next account of the instruction, which the declaration calls `name`
+#[doc(hidden)]
+pub fn next<'a, 'info>(
+    accounts: &mut std::slice::Iter<'a, AccountInfo<'info>>,
+    name: &str,
+) -> Result<&'a AccountInfo<'info>, ProgramError> {
+    accounts.next().ok_or_else(|| {
+        solana_program::msg!("{}: missing", name);
+        AccountError::AccountNotEnoughKeys.into()
+    })
+}
+
+/// Reads an SPL token account
+pub fn token_account(account: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+    TokenAccount::unpack(&account.try_borrow_data()?)
+}
+
+/// Declares the accounts of an instruction and their constraints; see the
+/// crate docs
+#[macro_export]
+macro_rules! accounts {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident ($program_id:ident) {
+            $(
+                $(#[$field_meta:meta])*
+                $field:ident : [ $( $constraint:ident $( ( $($arg:expr),* ) )? ),* $(,)? ]
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<'a, 'info> {
+            $(
+                $(#[$field_meta])*
+                pub $field: &'a $crate::__solana_program::account_info::AccountInfo<'info>,
+            )*
+            pub bumps: $crate::Bumps,
+            /// The accounts after the declared ones, unchecked
+            pub remaining: &'a [$crate::__solana_program::account_info::AccountInfo<'info>],
+        }
+
+        impl<'a, 'info> $name<'a, 'info> {
+            /// Takes the accounts in declaration order and checks their
+            /// constraints
+            pub fn parse(
+                $program_id: &$crate::__solana_program::pubkey::Pubkey,
+                accounts: &'a [$crate::__solana_program::account_info::AccountInfo<'info>],
+            ) -> ::std::result::Result<Self, $crate::__solana_program::program_error::ProgramError> {
+                let _ = $program_id;
+                let accounts = &mut accounts.iter();
+                $( let $field = $crate::next(accounts, stringify!($field))?; )*
+                #[allow(unused_mut)]
+                let mut bumps = $crate::Bumps::default();
+                $(
+                    $(
+                        $crate::accounts!(
+                            @check $constraint, $field, $program_id, bumps $(, $($arg),*)?
+                        );
+                    )*
+                )*
+                Ok(Self { $($field,)* bumps, remaining: accounts.as_slice() })
+            }
+        }
+    };
+
+    (@check signer, $field:ident, $program_id:ident, $bumps:ident) => {
+        $crate::check::signer(stringify!($field), $field)?
+    };
+    (@check writable, $field:ident, $program_id:ident, $bumps:ident) => {
+        $crate::check::writable(stringify!($field), $field)?
+    };
+    (@check owner, $field:ident, $program_id:ident, $bumps:ident, $owner:expr) => {
+        $crate::check::owner(stringify!($field), $field, $owner)?
+    };
+    (@check address, $field:ident, $program_id:ident, $bumps:ident, $address:expr) => {
+        $crate::check::address(stringify!($field), $field, $address)?
+    };
+    (@check seeds, $field:ident, $program_id:ident, $bumps:ident, $seeds:expr) => {
+        $bumps.insert(
+            stringify!($field),
+            $crate::check::seeds(stringify!($field), $field, $seeds, $program_id)?,
+        )
+    };
+    (@check seeds, $field:ident, $program_id:ident, $bumps:ident, $seeds:expr, $bump:expr) => {
+        $bumps.insert(
+            stringify!($field),
+            $crate::check::seeds_with_bump(stringify!($field), $field, $seeds, $bump, $program_id)?,
+        )
+    };
+    (@check mint, $field:ident, $program_id:ident, $bumps:ident, $mint:expr) => {
+        $crate::check::mint(stringify!($field), $field, $mint)?
+    };
+    (@check authority, $field:ident, $program_id:ident, $bumps:ident, $authority:expr) => {
+        $crate::check::authority(stringify!($field), $field, $authority)?
+    };
+}
@@ -0,0 +1,180 @@
+use native_accounts::{accounts, AccountError};
+use solana_program::{
+    account_info::AccountInfo, program_error::ProgramError, program_option::COption,
+    program_pack::Pack, pubkey::Pubkey,
+};
+use spl_token::state::{Account as TokenAccount, AccountState};
+
+accounts! {
+    struct Withdraw(program_id) {
+        owner: [signer],
+        vault: [writable, authority(authority.key), mint(&MINT)],
+        token_program: [address(&spl_token::id())],
+        authority: [seeds(&[b"vault".as_ref(), owner.key.as_ref()])],
+    }
+}
+
+const MINT: Pubkey = Pubkey::new_from_array([9; 32]);
+
+/// Backing storage for one `AccountInfo`
+struct Account {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+impl Account {
+    fn new(key: Pubkey) -> Self {
+        Account {
+            key,
+            owner: Pubkey::default(),
+            lamports: 0,
+            data: vec![],
+            is_signer: false,
+            is_writable: false,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            self.is_writable,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+/// Accounts that meet every constraint of `Withdraw`
+fn valid(program_id: &Pubkey) -> Vec<Account> {
+    let mut owner = Account::new(Pubkey::new_unique());
+    owner.is_signer = true;
+    let (authority, _) = Pubkey::find_program_address(&[b"vault", owner.key.as_ref()], program_id);
+
+    let mut vault = Account::new(Pubkey::new_unique());
+    vault.is_writable = true;
+    vault.owner = spl_token::id();
+    vault.data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: MINT,
+            owner: authority,
+            amount: 1,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        },
+        &mut vault.data,
+    )
+    .unwrap();
+
+    vec![
+        owner,
+        vault,
+        Account::new(spl_token::id()),
+        Account::new(authority),
+    ]
+}
+
+/// Breaks one constraint of otherwise valid accounts
+type Breaks = fn(&mut Vec<Account>);
+
+fn parse(program_id: &Pubkey, accounts: &mut [Account]) -> Result<u8, ProgramError> {
+    let infos: Vec<AccountInfo> = accounts.iter_mut().map(Account::info).collect();
+    Withdraw::parse(program_id, &infos)?.bumps.get("authority")
+}
+
+#[test]
+fn accepts_valid_accounts_and_keeps_bumps() {
+    let program_id = Pubkey::new_unique();
+    let mut accounts = valid(&program_id);
+    let (_, bump) =
+        Pubkey::find_program_address(&[b"vault", accounts[0].key.as_ref()], &program_id);
+    assert_eq!(parse(&program_id, &mut accounts), Ok(bump));
+}
+
+#[test]
+fn leaves_extra_accounts_in_remaining() {
+    let program_id = Pubkey::new_unique();
+    let mut accounts = valid(&program_id);
+    let extra = Pubkey::new_unique();
+    accounts.push(Account::new(extra));
+    let infos: Vec<AccountInfo> = accounts.iter_mut().map(Account::info).collect();
+    let parsed = Withdraw::parse(&program_id, &infos).unwrap();
+    assert_eq!(parsed.remaining.len(), 1);
+    assert_eq!(*parsed.remaining[0].key, extra);
+}
+
+#[test]
+fn accounts_without_seeds_have_no_bump() {
+    let program_id = Pubkey::new_unique();
+    let mut accounts = valid(&program_id);
+    let infos: Vec<AccountInfo> = accounts.iter_mut().map(Account::info).collect();
+    let parsed = Withdraw::parse(&program_id, &infos).unwrap();
+    assert_eq!(
+        parsed.bumps.get("vault"),
+        Err(AccountError::ConstraintSeeds.into())
+    );
+}
+
+#[test]
+fn names_the_broken_constraint() {
+    let program_id = Pubkey::new_unique();
+    let cases: Vec<(Breaks, AccountError)> = vec![
+        (|a| a[0].is_signer = false, AccountError::ConstraintSigner),
+        (
+            |a| a[1].is_writable = false,
+            AccountError::ConstraintWritable,
+        ),
+        (
+            |a| a[1].owner = Pubkey::new_unique(),
+            AccountError::ConstraintOwner,
+        ),
+        (
+            |a| a[1].data[..32].fill(1),
+            AccountError::ConstraintTokenMint,
+        ),
+        (
+            |a| a[1].data[32..64].fill(1),
+            AccountError::ConstraintTokenOwner,
+        ),
+        (
+            |a| a[2].key = Pubkey::new_unique(),
+            AccountError::ConstraintAddress,
+        ),
+        (
+            // keep the vault pointing at it so only the seeds check fails
+            |a| {
+                let key = Pubkey::new_unique();
+                a[1].data[32..64].copy_from_slice(key.as_ref());
+                a[3].key = key;
+            },
+            AccountError::ConstraintSeeds,
+        ),
+        (
+            |a| {
+                a.pop();
+            },
+            AccountError::AccountNotEnoughKeys,
+        ),
+    ];
+    for (breaks, error) in cases {
+        let mut accounts = valid(&program_id);
+        breaks(&mut accounts);
+        assert_eq!(
+            parse(&program_id, &mut accounts),
+            Err(error.into()),
+            "{}",
+            error
+        );
+    }
+}
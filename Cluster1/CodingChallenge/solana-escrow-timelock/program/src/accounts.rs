@@ -0,0 +1,508 @@
+//! The accounts each instruction expects, in order, with their constraints.
+//! See [`EscrowInstruction`](crate::instruction::EscrowInstruction) for what
+//! each of them is.
+
+use native_accounts::{accounts, token_account};
+use solana_program::{system_program, sysvar};
+
+use crate::state::Escrow;
+
+accounts! {
+    pub struct InitEscrowAccounts(program_id) {
+        initializer: [signer],
+        // set_authority needs the initializer to still own it
+        temp_token_account: [writable, authority(initializer.key)],
+        token_to_receive_account: [owner(&spl_token::id())],
+        escrow_account: [writable],
+        rent: [address(&sysvar::rent::id())],
+        token_program: [address(&spl_token::id())],
+    }
+}
+
+accounts! {
+    pub struct ExchangeAccounts(program_id) {
+        taker: [signer],
+        takers_sending_token_account: [writable],
+        takers_token_to_receive_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        // owned by the PDA, or by the maker in a delegate-approval escrow,
+        // which the processor tells apart
+        pdas_temp_token_account: [writable, owner(&spl_token::id())],
+        initializers_main_account: [writable],
+        initializers_token_to_receive_account: [writable],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    pub struct ResetTimeLockAccounts(program_id) {
+        initializer: [signer],
+        escrow_account: [writable],
+    }
+}
+
+accounts! {
+    pub struct CancelAccounts(program_id) {
+        initializer: [signer, writable],
+        // owned by the PDA, or by the maker in a delegate-approval escrow
+        pda_temp_token_account: [writable, owner(&spl_token::id())],
+        initializer_sent_token_account: [
+            writable,
+            mint(&token_account(pda_temp_token_account)?.mint)
+        ],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    pub struct MigrateAccounts(program_id) {
+        initializer: [signer, writable],
+        escrow_account: [writable],
+        system_program: [address(&system_program::id())],
+    }
+}
+
+accounts! {
+    pub struct InitHashLockAccounts(program_id) {
+        initializer: [signer],
+        temp_token_account: [writable, authority(initializer.key)],
+        escrow_account: [writable],
+        rent: [address(&sysvar::rent::id())],
+        token_program: [address(&spl_token::id())],
+    }
+}
+
+accounts! {
+    pub struct RedeemAccounts(program_id) {
+        counterparty: [signer],
+        counterparty_token_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        pdas_temp_token_account: [writable, authority(pda.key)],
+        initializers_main_account: [writable],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    pub struct InitDeadMansSwitchAccounts(program_id) {
+        initializer: [signer],
+        temp_token_account: [writable, authority(initializer.key)],
+        escrow_account: [writable],
+        rent: [address(&sysvar::rent::id())],
+        token_program: [address(&spl_token::id())],
+    }
+}
+
+accounts! {
+    pub struct ClaimAccounts(program_id) {
+        beneficiary: [signer],
+        beneficiary_token_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        pdas_temp_token_account: [writable, authority(pda.key)],
+        initializers_main_account: [writable],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    pub struct LockAccounts(program_id) {
+        initializer: [signer],
+        temp_token_account: [writable, authority(initializer.key)],
+        escrow_account: [writable],
+        rent: [address(&sysvar::rent::id())],
+        token_program: [address(&spl_token::id())],
+    }
+}
+
+accounts! {
+    pub struct UnlockAccounts(program_id) {
+        initializer: [signer, writable],
+        pda_temp_token_account: [writable, authority(pda.key)],
+        initializer_token_account: [
+            writable,
+            mint(&token_account(pda_temp_token_account)?.mint)
+        ],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    pub struct ExtendLockAccounts(program_id) {
+        initializer: [signer],
+        escrow_account: [writable],
+    }
+}
+
+accounts! {
+    pub struct InitArbitratedAccounts(program_id) {
+        initializer: [signer],
+        temp_token_account: [writable, authority(initializer.key)],
+        escrow_account: [writable],
+        rent: [address(&sysvar::rent::id())],
+        token_program: [address(&spl_token::id())],
+    }
+}
+
+accounts! {
+    pub struct MarkDeliveredAccounts(program_id) {
+        taker: [signer],
+        escrow_account: [writable],
+    }
+}
+
+accounts! {
+    pub struct OpenDisputeAccounts(program_id) {
+        party: [signer],
+        escrow_account: [writable],
+    }
+}
+
+accounts! {
+    pub struct ReleaseAccounts(program_id) {
+        initializer: [signer, writable],
+        taker_token_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        pdas_temp_token_account: [writable, authority(pda.key)],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    pub struct ResolveAccounts(program_id) {
+        arbiter: [signer],
+        taker_token_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        maker_token_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        initializers_main_account: [writable],
+        pdas_temp_token_account: [writable, authority(pda.key)],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    pub struct SettleAccounts(program_id) {
+        taker_token_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        maker_token_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        initializers_main_account: [writable],
+        pdas_temp_token_account: [writable, authority(pda.key)],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    pub struct InitMilestonesAccounts(program_id) {
+        initializer: [signer],
+        temp_token_account: [writable, authority(initializer.key)],
+        escrow_account: [writable],
+        rent: [address(&sysvar::rent::id())],
+        token_program: [address(&spl_token::id())],
+    }
+}
+
+accounts! {
+    pub struct ApproveMilestoneAccounts(program_id) {
+        initializer: [signer, writable],
+        payee_token_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        pdas_temp_token_account: [writable, authority(pda.key)],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    pub struct RefundUnapprovedAccounts(program_id) {
+        initializer: [signer, writable],
+        initializer_token_account: [
+            writable,
+            mint(&token_account(pdas_temp_token_account)?.mint)
+        ],
+        pdas_temp_token_account: [writable, authority(pda.key)],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(
+            &[b"escrow".as_ref()],
+            Escrow::pda_bump(&escrow_account.try_borrow_data()?)?
+        )],
+    }
+}
+
+accounts! {
+    /// Where a token bond comes from, after `InitEscrow`'s or
+    /// `InitDelegatedEscrow`'s own accounts
+    pub struct InitTokenBondAccounts(program_id) {
+        bond_token_account: [writable],
+        token_program: [address(&spl_token::id())],
+    }
+}
+
+accounts! {
+    /// Where a forfeited bond in lamports goes, after `Cancel`'s own accounts
+    pub struct LamportBondAccounts(program_id) {
+        bond_recipient: [writable],
+    }
+}
+
+accounts! {
+    /// Where a token bond goes, after `Exchange`'s or `Cancel`'s own accounts
+    pub struct TokenBondAccounts(program_id) {
+        bond_vault: [writable],
+        bond_destination: [writable, mint(&token_account(bond_vault)?.mint)],
+    }
+}
+
+accounts! {
+    pub struct SetPremiumAccounts(program_id) {
+        initializer: [signer, writable],
+        escrow_account: [writable],
+        system_program: [address(&system_program::id())],
+    }
+}
+
+accounts! {
+    pub struct SetExclusiveTakerAccounts(program_id) {
+        initializer: [signer, writable],
+        escrow_account: [writable],
+        system_program: [address(&system_program::id())],
+    }
+}
+
+accounts! {
+    pub struct SetAllowListAccounts(program_id) {
+        initializer: [signer, writable],
+        escrow_account: [writable],
+        system_program: [address(&system_program::id())],
+    }
+}
+
+accounts! {
+    pub struct SetPriceConditionAccounts(program_id) {
+        initializer: [signer, writable],
+        escrow_account: [writable],
+        oracle: [],
+        system_program: [address(&system_program::id())],
+    }
+}
+
+accounts! {
+    /// The oracle of a swap with a price condition, after `Exchange`'s own
+    /// accounts and any token bond's
+    pub struct OracleAccounts(program_id) {
+        oracle: [],
+    }
+}
+
+accounts! {
+    pub struct SetCallbackAccounts(program_id) {
+        initializer: [signer, writable],
+        escrow_account: [writable],
+        callback_program: [],
+        system_program: [address(&system_program::id())],
+    }
+}
+
+accounts! {
+    /// The callback program of a swap, after `Exchange`'s or `Cancel`'s own
+    /// accounts, any bond's and any oracle. The accounts after these go to
+    /// the callback program.
+    pub struct CallbackAccounts(program_id) {
+        callback_program: [],
+        // checked against the escrow it signs for by the processor
+        callback_authority: [],
+    }
+}
+
+accounts! {
+    pub struct ReserveAccounts(program_id) {
+        taker: [signer],
+        takers_sending_token_account: [writable],
+        initializers_token_to_receive_account: [writable],
+        escrow_account: [writable],
+        token_program: [address(&spl_token::id())],
+    }
+}
+
+accounts! {
+    pub struct FillSignedOrderAccounts(program_id) {
+        taker: [signer, writable],
+        takers_sending_token_account: [writable],
+        takers_token_to_receive_account: [
+            writable,
+            mint(&token_account(makers_sending_token_account)?.mint)
+        ],
+        makers_sending_token_account: [writable],
+        makers_token_to_receive_account: [writable],
+        // its address depends on the order's maker, checked in the processor
+        order_nonce: [],
+        order_receipt: [writable],
+        instructions: [address(&sysvar::instructions::id())],
+        system_program: [address(&system_program::id())],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(&[b"escrow".as_ref()])],
+    }
+}
+
+accounts! {
+    pub struct CancelAllOrdersAccounts(program_id) {
+        maker: [signer, writable],
+        order_nonce: [writable, seeds(&[b"nonce".as_ref(), maker.key.as_ref()])],
+        system_program: [address(&system_program::id())],
+    }
+}
+
+accounts! {
+    pub struct InitDelegatedEscrowAccounts(program_id) {
+        initializer: [signer],
+        initializers_sending_token_account: [authority(initializer.key)],
+        token_to_receive_account: [owner(&spl_token::id())],
+        escrow_account: [writable],
+        rent: [address(&sysvar::rent::id())],
+    }
+}
+
+accounts! {
+    pub struct ExchangeManyAccounts(program_id) {
+        taker: [signer],
+        takers_sending_token_account: [writable],
+        takers_token_to_receive_account: [writable],
+        token_program: [address(&spl_token::id())],
+        // one search for every escrow, rather than one each
+        pda: [seeds(&[b"escrow".as_ref()])],
+    }
+}
+
+accounts! {
+    pub struct SettleRingAccounts(program_id) {
+        settler: [signer],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(&[b"escrow".as_ref()])],
+    }
+}
+
+accounts! {
+    /// One escrow of an `ExchangeMany` or a `SettleRing`. Nothing is checked here, so that an
+    /// escrow that can't be taken can be skipped.
+    pub struct EscrowToTakeAccounts(program_id) {
+        vault: [],
+        initializers_main_account: [],
+        initializers_token_to_receive_account: [],
+        escrow_account: [],
+    }
+}
+
+accounts! {
+    pub struct InitPoolAccounts(program_id) {
+        creator: [signer],
+        pool: [writable],
+        // set_authority needs the creator to still own them
+        vault: [writable, authority(creator.key)],
+        proceeds_vault: [writable, authority(creator.key)],
+        rent: [address(&sysvar::rent::id())],
+        token_program: [address(&spl_token::id())],
+    }
+}
+
+accounts! {
+    pub struct DepositPoolAccounts(program_id) {
+        maker: [signer, writable],
+        makers_token_account: [writable],
+        pool: [writable],
+        vault: [writable],
+        share: [writable, seeds(&[b"share".as_ref(), pool.key.as_ref(), maker.key.as_ref()])],
+        token_program: [address(&spl_token::id())],
+        system_program: [address(&system_program::id())],
+    }
+}
+
+accounts! {
+    pub struct FillPoolAccounts(program_id) {
+        taker: [signer],
+        takers_sending_token_account: [writable],
+        takers_token_to_receive_account: [writable],
+        pool: [writable],
+        vault: [writable],
+        proceeds_vault: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(&[b"escrow".as_ref()])],
+    }
+}
+
+accounts! {
+    pub struct WithdrawPoolAccounts(program_id) {
+        maker: [signer, writable],
+        share: [writable],
+        pool: [writable],
+        vault: [writable],
+        proceeds_vault: [writable],
+        makers_offered_account: [writable],
+        makers_requested_account: [writable],
+        creator: [writable],
+        token_program: [address(&spl_token::id())],
+        pda: [seeds(&[b"escrow".as_ref()])],
+    }
+}
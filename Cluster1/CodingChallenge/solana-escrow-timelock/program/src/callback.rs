@@ -0,0 +1,131 @@
+//! The instruction a swap with a `callback_program` sends it once
+//! `Exchange` or `Cancel` has moved the tokens, before it closes the escrow.
+//!
+//! The data is a [`Settlement`]: [`CALLBACK_DISCRIMINATOR`], a version byte,
+//! then the fields in the order below. Later versions only append fields, so
+//! a program reading version 1 reads every version. The accounts are the
+//! escrow's [callback authority](crate::instruction::callback_authority) as a
+//! signer, which only this program can sign for, then the escrow account,
+//! read-only and with its vault already emptied, then whatever accounts the
+//! caller of `Exchange` or `Cancel` passed along for the callback program.
+//! Passed accounts keep whether they are writable, but never sign: the
+//! authority is the callback's only signer.
+//!
+//! The callback runs in the same transaction, so a callback that fails fails
+//! the settlement with it.
+
+use arrayref::{array_ref, array_refs};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+/// First bytes of every callback's data, telling it apart from the callback
+/// program's own instructions
+pub const CALLBACK_DISCRIMINATOR: [u8; 8] = *b"escrowcb";
+
+/// Version of the [`Settlement`] layout this program sends
+pub const CALLBACK_VERSION: u8 = 1;
+
+/// How a swap settled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum SettlementKind {
+    /// A taker took the swap
+    Exchanged = 0,
+    /// The maker cancelled it before it timed out
+    Cancelled = 1,
+    /// The maker cancelled it after it timed out
+    Expired = 2,
+}
+
+/// What a callback program learns about a settled swap
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Settlement {
+    pub kind: SettlementKind,
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    /// `Pubkey::default()` unless the swap was exchanged
+    pub taker: Pubkey,
+    pub offered_mint: Pubkey,
+    /// What left the vault, for the taker or back to the maker
+    pub offered_amount: u64,
+    /// `Pubkey::default()` unless the swap was exchanged, since `Cancel`
+    /// doesn't see the maker's receiving account
+    pub requested_mint: Pubkey,
+    /// What the maker asked for, whether or not they got it
+    pub requested_amount: u64,
+    pub slot: u64,
+    pub unix_timestamp: i64,
+}
+
+impl Settlement {
+    /// Length of version 1 data, discriminator included
+    pub const LEN: usize = 8 + 1 + 1 + 32 * 5 + 8 * 4;
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::LEN);
+        buf.extend_from_slice(&CALLBACK_DISCRIMINATOR);
+        buf.push(CALLBACK_VERSION);
+        buf.push(self.kind as u8);
+        buf.extend_from_slice(self.escrow.as_ref());
+        buf.extend_from_slice(self.maker.as_ref());
+        buf.extend_from_slice(self.taker.as_ref());
+        buf.extend_from_slice(self.offered_mint.as_ref());
+        buf.extend_from_slice(&self.offered_amount.to_le_bytes());
+        buf.extend_from_slice(self.requested_mint.as_ref());
+        buf.extend_from_slice(&self.requested_amount.to_le_bytes());
+        buf.extend_from_slice(&self.slot.to_le_bytes());
+        buf.extend_from_slice(&self.unix_timestamp.to_le_bytes());
+        buf
+    }
+
+    /// Reads a callback's data, of version 1 or later. For callback programs.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < Self::LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (
+            discriminator,
+            version,
+            kind,
+            escrow,
+            maker,
+            taker,
+            offered_mint,
+            offered_amount,
+            requested_mint,
+            requested_amount,
+            slot,
+            unix_timestamp,
+        ) = array_refs![
+            array_ref![data, 0, Settlement::LEN],
+            8,
+            1,
+            1,
+            32,
+            32,
+            32,
+            32,
+            8,
+            32,
+            8,
+            8,
+            8
+        ];
+        if *discriminator != CALLBACK_DISCRIMINATOR || version[0] == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(Settlement {
+            kind: SettlementKind::from_u8(kind[0]).ok_or(ProgramError::InvalidInstructionData)?,
+            escrow: Pubkey::new_from_array(*escrow),
+            maker: Pubkey::new_from_array(*maker),
+            taker: Pubkey::new_from_array(*taker),
+            offered_mint: Pubkey::new_from_array(*offered_mint),
+            offered_amount: u64::from_le_bytes(*offered_amount),
+            requested_mint: Pubkey::new_from_array(*requested_mint),
+            requested_amount: u64::from_le_bytes(*requested_amount),
+            slot: u64::from_le_bytes(*slot),
+            unix_timestamp: i64::from_le_bytes(*unix_timestamp),
+        })
+    }
+}
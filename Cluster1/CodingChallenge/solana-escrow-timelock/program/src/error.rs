@@ -1,8 +1,9 @@
+use num_derive::FromPrimitive;
 use thiserror::Error;
 
-use solana_program::program_error::ProgramError;
+use solana_program::{decode_error::DecodeError, program_error::ProgramError};
 
-#[derive(Error, Debug, Copy, Clone)]
+#[derive(Error, Debug, Copy, Clone, FromPrimitive, PartialEq, Eq)]
 pub enum EscrowError {
     /// Invalid instruction
     #[error("Invalid Instruction")]
@@ -22,6 +23,131 @@ pub enum EscrowError {
     /// Escrow Time Out
     #[error("Escrow Time Out")]
     EscrowTimeOut,
+    /// Invalid Event
+    #[error("Invalid Event")]
+    InvalidEvent,
+    /// Outdated Escrow, run Migrate first
+    #[error("Outdated Escrow")]
+    OutdatedEscrow,
+    /// Account Not Writable
+    #[error("Account Not Writable")]
+    AccountNotWritable,
+    /// Wrong Account Type, the discriminator names another account type
+    #[error("Wrong Account Type")]
+    WrongAccountType,
+    /// Wrong Escrow Mode, the instruction doesn't apply to this kind of escrow
+    #[error("Wrong Escrow Mode")]
+    WrongEscrowMode,
+    /// Escrow Not Expired, only allowed once the escrow timed out
+    #[error("Escrow Not Expired")]
+    EscrowNotExpired,
+    /// Preimage Mismatch, the preimage doesn't hash to the escrow's hash
+    #[error("Preimage Mismatch")]
+    PreimageMismatch,
+    /// Deal Disputed, only the arbiter can settle it
+    #[error("Deal Disputed")]
+    DealDisputed,
+    /// Deal Not Disputed, the arbiter only rules on disputes
+    #[error("Deal Not Disputed")]
+    DealNotDisputed,
+    /// Invalid Basis Points, a share can't exceed 10000
+    #[error("Invalid Basis Points")]
+    InvalidBasisPoints,
+    /// Invalid Milestones, there must be one to eight, none of them empty,
+    /// adding up to what the vault holds
+    #[error("Invalid Milestones")]
+    InvalidMilestones,
+    /// Milestone Settled, it was already approved or refunded
+    #[error("Milestone Settled")]
+    MilestoneSettled,
+    /// No Expired Milestones, no pending milestone is past its deadline
+    #[error("No Expired Milestones")]
+    NoExpiredMilestones,
+    /// Invalid Bond, a bond can't be empty, and one in lamports has to be in
+    /// the escrow account on top of its rent
+    #[error("Invalid Bond")]
+    InvalidBond,
+    /// Bond At Stake, a bonded escrow's window can't move while it is open
+    #[error("Bond At Stake")]
+    BondAtStake,
+    /// Escrow Reserved, another taker holds a reservation on the swap
+    #[error("Escrow Reserved")]
+    EscrowReserved,
+    /// Not Reservable, the maker doesn't offer reservations on this escrow
+    #[error("Not Reservable")]
+    NotReservable,
+    /// Invalid Order Signature, the instruction before the fill must verify the maker's signature of the order
+    #[error("Invalid Order Signature")]
+    InvalidOrderSignature,
+    /// Order Expired
+    #[error("Order Expired")]
+    OrderExpired,
+    /// Stale Order Nonce, the maker cancelled every order signed with it
+    #[error("Stale Order Nonce")]
+    StaleOrderNonce,
+    /// Order Already Filled
+    #[error("Order Already Filled")]
+    OrderAlreadyFilled,
+    /// Order Not For Taker, the maker signed it for another taker
+    #[error("Order Not For Taker")]
+    OrderNotForTaker,
+    /// Delegation Revoked, the token account doesn't have the PDA as its delegate (any more)
+    #[error("Delegation Revoked")]
+    DelegationRevoked,
+    /// Insufficient Delegation, the PDA may not move that much out of the token account
+    #[error("Insufficient Delegation")]
+    InsufficientDelegation,
+    /// Price Limit Exceeded, taking every escrow would cost the taker more than they allowed
+    #[error("Price Limit Exceeded")]
+    PriceLimitExceeded,
+    /// Nothing Filled, none of the escrows could be taken
+    #[error("Nothing Filled")]
+    NothingFilled,
+    /// Ring Mismatch, a maker in the ring isn't paid what they asked for by the next one
+    #[error("Ring Mismatch")]
+    RingMismatch,
+    /// Pool Window Open, makers can't deposit or withdraw while takers can fill
+    #[error("Pool Window Open")]
+    PoolWindowOpen,
+    /// Pool Exhausted, the pool has fewer lots left than asked for
+    #[error("Pool Exhausted")]
+    PoolExhausted,
+    /// Unsuitable Pool Vault, a new pool's vaults must be two empty accounts
+    /// without a close authority
+    #[error("Unsuitable Pool Vault")]
+    UnsuitablePoolVault,
+    /// Not Yet Public, only the designated taker can exchange until the
+    /// exclusive period ends
+    #[error("Not Yet Public")]
+    NotYetPublic,
+    /// Exclusivity Started, the designated taker and the allow list can only
+    /// change before the swap unlocks
+    #[error("Exclusivity Started")]
+    ExclusivityStarted,
+    /// Taker Not Allowed, the proof doesn't put the taker on the escrow's
+    /// allow list
+    #[error("Taker Not Allowed")]
+    TakerNotAllowed,
+    /// Price Condition Not Met, the oracle's price doesn't meet the swap's
+    /// condition
+    #[error("Price Condition Not Met")]
+    PriceConditionNotMet,
+    /// Stale Oracle Price, the oracle's price is older than the swap allows
+    #[error("Stale Oracle Price")]
+    StaleOraclePrice,
+    /// Callback Reentrancy, a callback could reach back into the escrow
+    /// settling it: the callback program is this program, or the escrow is
+    /// among the accounts passed to it
+    #[error("Callback Reentrancy")]
+    CallbackReentrancy,
+    /// Exclusive Period Too Long, the designated taker's period has to end
+    /// before the swap's time out so it turns public
+    #[error("Exclusive Period Too Long")]
+    ExclusivePeriodTooLong,
+    /// Insufficient Balance, the maker's token account holds less than the
+    /// PDA is to move out of it as its delegate
+    #[error("Insufficient Balance")]
+    InsufficientBalance,
 }
 
 impl From<EscrowError> for ProgramError {
@@ -29,3 +155,9 @@ impl From<EscrowError> for ProgramError {
         ProgramError::Custom(e as u32)
     }
 }
+
+impl<T> DecodeError<T> for EscrowError {
+    fn type_of() -> &'static str {
+        "EscrowError"
+    }
+}
@@ -0,0 +1,1323 @@
+use num_traits::FromPrimitive;
+use solana_program::{
+    clock::Clock, log::sol_log_data, program_error::ProgramError, pubkey::Pubkey,
+};
+use std::convert::TryInto;
+
+use crate::{
+    error::EscrowError::InvalidEvent,
+    oracle::{OracleKind, PriceCondition},
+    state::{DefaultOutcome, HashKind},
+};
+
+/// Bumped whenever the binary layout of an event changes
+pub const EVENT_VERSION: u8 = 1;
+
+/// When an event happened
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventClock {
+    pub slot: u64,
+    pub unix_timestamp: i64,
+}
+
+impl From<&Clock> for EventClock {
+    fn from(clock: &Clock) -> Self {
+        EventClock {
+            slot: clock.slot,
+            unix_timestamp: clock.unix_timestamp,
+        }
+    }
+}
+
+/// Emitted with `sol_log_data` on every escrow state transition, so indexers
+/// don't have to parse the free-text `msg!` logs.
+///
+/// Layout: `[EVENT_VERSION, tag, fields...]`, pubkeys as 32 raw bytes and
+/// integers little endian, in the order the fields are declared.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EscrowEvent {
+    /// 0. A maker opened an escrow
+    Created {
+        escrow: Pubkey,
+        maker: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        requested_mint: Pubkey,
+        requested_amount: u64,
+        unlock_time: u64,
+        time_out: u64,
+        clock: EventClock,
+    },
+    /// 1. A taker settled the escrow
+    Exchanged {
+        escrow: Pubkey,
+        maker: Pubkey,
+        taker: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        requested_mint: Pubkey,
+        requested_amount: u64,
+        clock: EventClock,
+    },
+    /// 2. The maker cancelled before the escrow timed out
+    Cancelled {
+        escrow: Pubkey,
+        maker: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        clock: EventClock,
+    },
+    /// 3. The maker moved the lock window
+    LockReset {
+        escrow: Pubkey,
+        maker: Pubkey,
+        unlock_time: u64,
+        time_out: u64,
+        clock: EventClock,
+    },
+    /// 4. The maker reclaimed an escrow nobody took before it timed out
+    Expired {
+        escrow: Pubkey,
+        maker: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        time_out: u64,
+        clock: EventClock,
+    },
+    /// 5. A maker opened a hash-lock escrow
+    HashLocked {
+        escrow: Pubkey,
+        maker: Pubkey,
+        counterparty: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        hash_kind: HashKind,
+        hash: [u8; 32],
+        unlock_time: u64,
+        time_out: u64,
+        clock: EventClock,
+    },
+    /// 6. The counterparty revealed the preimage and got the vault
+    Redeemed {
+        escrow: Pubkey,
+        maker: Pubkey,
+        counterparty: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        preimage: Vec<u8>,
+        clock: EventClock,
+    },
+    /// 7. A maker armed a dead man's switch
+    SwitchArmed {
+        escrow: Pubkey,
+        maker: Pubkey,
+        beneficiary: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        time_out: u64,
+        clock: EventClock,
+    },
+    /// 8. The beneficiary claimed a dead man's switch the maker let time out
+    Claimed {
+        escrow: Pubkey,
+        maker: Pubkey,
+        beneficiary: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        time_out: u64,
+        clock: EventClock,
+    },
+    /// 9. A maker locked their own tokens; extending the lock emits `LockReset`
+    Locked {
+        escrow: Pubkey,
+        maker: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        unlock_time: u64,
+        clock: EventClock,
+    },
+    /// 10. The maker withdrew an unlocked savings lock
+    Unlocked {
+        escrow: Pubkey,
+        maker: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        clock: EventClock,
+    },
+    /// 11. A maker opened an arbitrated deal
+    Arbitrated {
+        escrow: Pubkey,
+        maker: Pubkey,
+        taker: Pubkey,
+        arbiter: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        default_outcome: DefaultOutcome,
+        time_out: u64,
+        clock: EventClock,
+    },
+    /// 12. The taker marked the deal delivered
+    Delivered {
+        escrow: Pubkey,
+        maker: Pubkey,
+        taker: Pubkey,
+        clock: EventClock,
+    },
+    /// 13. The maker or the taker handed the deal to the arbiter
+    Disputed {
+        escrow: Pubkey,
+        maker: Pubkey,
+        party: Pubkey,
+        clock: EventClock,
+    },
+    /// 14. An arbitrated deal paid out, by release, ruling or time out
+    Settled {
+        escrow: Pubkey,
+        maker: Pubkey,
+        taker: Pubkey,
+        offered_mint: Pubkey,
+        to_taker: u64,
+        to_maker: u64,
+        clock: EventClock,
+    },
+    /// 15. A maker opened a milestone escrow
+    MilestonesOpened {
+        escrow: Pubkey,
+        maker: Pubkey,
+        payee: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        milestone_count: u8,
+        clock: EventClock,
+    },
+    /// 16. The maker paid one milestone to the payee
+    MilestoneApproved {
+        escrow: Pubkey,
+        maker: Pubkey,
+        payee: Pubkey,
+        offered_mint: Pubkey,
+        index: u8,
+        amount: u64,
+        remaining: u64,
+        clock: EventClock,
+    },
+    /// 17. The maker took back the milestones that passed their deadline
+    MilestonesRefunded {
+        escrow: Pubkey,
+        maker: Pubkey,
+        offered_mint: Pubkey,
+        amount: u64,
+        remaining: u64,
+        clock: EventClock,
+    },
+    /// 18. The maker of a swap put up a bond, in lamports when `bond_mint`
+    ///     is `Pubkey::default()`
+    BondPosted {
+        escrow: Pubkey,
+        maker: Pubkey,
+        recipient: Pubkey,
+        bond_mint: Pubkey,
+        amount: u64,
+        clock: EventClock,
+    },
+    /// 19. The maker got their bond back with the escrow's exchange or cancel
+    BondRefunded {
+        escrow: Pubkey,
+        maker: Pubkey,
+        bond_mint: Pubkey,
+        amount: u64,
+        clock: EventClock,
+    },
+    /// 20. The maker cancelled while the escrow was open and lost the bond
+    BondForfeited {
+        escrow: Pubkey,
+        maker: Pubkey,
+        recipient: Pubkey,
+        bond_mint: Pubkey,
+        amount: u64,
+        clock: EventClock,
+    },
+    /// 21. The maker changed the premium for reserving a swap, 0 to stop
+    ///     offering reservations
+    PremiumSet {
+        escrow: Pubkey,
+        maker: Pubkey,
+        premium: u64,
+        clock: EventClock,
+    },
+    /// 22. A taker paid the premium and reserved the swap until `time_out`
+    Reserved {
+        escrow: Pubkey,
+        maker: Pubkey,
+        taker: Pubkey,
+        premium_mint: Pubkey,
+        premium: u64,
+        time_out: u64,
+        clock: EventClock,
+    },
+    /// 23. A taker filled a signed order. `escrow` is the order's receipt.
+    OrderFilled {
+        escrow: Pubkey,
+        maker: Pubkey,
+        taker: Pubkey,
+        offered_mint: Pubkey,
+        offered_amount: u64,
+        requested_mint: Pubkey,
+        requested_amount: u64,
+        nonce: u64,
+        clock: EventClock,
+    },
+    /// 24. The maker cancelled every order signed before `nonce`. `escrow` is
+    ///     the maker's order nonce account.
+    OrdersCancelled {
+        escrow: Pubkey,
+        maker: Pubkey,
+        nonce: u64,
+        clock: EventClock,
+    },
+    /// 25. A pool opened for deposits. `escrow` is the pool, here and in the
+    ///     other pool events.
+    PoolOpened {
+        escrow: Pubkey,
+        creator: Pubkey,
+        offered_mint: Pubkey,
+        offered_per_lot: u64,
+        requested_mint: Pubkey,
+        requested_per_lot: u64,
+        unlock_time: u64,
+        time_out: u64,
+        clock: EventClock,
+    },
+    /// 26. A maker deposited into a pool, for as many shares
+    PoolDeposited {
+        escrow: Pubkey,
+        maker: Pubkey,
+        amount: u64,
+        clock: EventClock,
+    },
+    /// 27. A taker filled lots out of a pool
+    PoolFilled {
+        escrow: Pubkey,
+        taker: Pubkey,
+        offered_amount: u64,
+        requested_amount: u64,
+        clock: EventClock,
+    },
+    /// 28. A maker withdrew their share of what was unfilled and of the
+    ///     proceeds
+    PoolWithdrawn {
+        escrow: Pubkey,
+        maker: Pubkey,
+        unfilled: u64,
+        proceeds: u64,
+        clock: EventClock,
+    },
+    /// 29. The maker named the only taker who may exchange from `unlock_time`
+    ///     through `exclusive_until`, `Pubkey::default()` if anyone may
+    ExclusiveTakerSet {
+        escrow: Pubkey,
+        maker: Pubkey,
+        taker: Pubkey,
+        exclusive_until: u64,
+        clock: EventClock,
+    },
+    /// 30. The maker limited who may take a swap to the allow list with
+    ///     Merkle root `root`, all zeroes to let anyone take it again
+    AllowListSet {
+        escrow: Pubkey,
+        maker: Pubkey,
+        root: [u8; 32],
+        clock: EventClock,
+    },
+    /// 31. The maker made a swap takeable only while `oracle`'s price meets
+    ///     `condition` against `trigger_price * 10^trigger_expo`, or dropped
+    ///     the condition with `PriceCondition::None`
+    PriceConditionSet {
+        escrow: Pubkey,
+        maker: Pubkey,
+        oracle: Pubkey,
+        oracle_kind: OracleKind,
+        condition: PriceCondition,
+        trigger_price: i64,
+        trigger_expo: i32,
+        max_staleness: u64,
+        clock: EventClock,
+    },
+    /// 32. The maker named the program a swap calls once it settles, or
+    ///     stopped calling one with the default pubkey
+    CallbackSet {
+        escrow: Pubkey,
+        maker: Pubkey,
+        callback_program: Pubkey,
+        clock: EventClock,
+    },
+}
+
+impl EscrowEvent {
+    pub fn emit(&self) {
+        sol_log_data(&[&self.pack()]);
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = vec![EVENT_VERSION];
+        match self {
+            Self::Created {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                requested_mint,
+                requested_amount,
+                unlock_time,
+                time_out,
+                clock,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(requested_mint.as_ref());
+                buf.extend_from_slice(&requested_amount.to_le_bytes());
+                buf.extend_from_slice(&unlock_time.to_le_bytes());
+                buf.extend_from_slice(&time_out.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Exchanged {
+                escrow,
+                maker,
+                taker,
+                offered_mint,
+                offered_amount,
+                requested_mint,
+                requested_amount,
+                clock,
+            } => {
+                buf.push(1);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(requested_mint.as_ref());
+                buf.extend_from_slice(&requested_amount.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Cancelled {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                clock,
+            } => {
+                buf.push(2);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::LockReset {
+                escrow,
+                maker,
+                unlock_time,
+                time_out,
+                clock,
+            } => {
+                buf.push(3);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(&unlock_time.to_le_bytes());
+                buf.extend_from_slice(&time_out.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Expired {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                time_out,
+                clock,
+            } => {
+                buf.push(4);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&time_out.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::HashLocked {
+                escrow,
+                maker,
+                counterparty,
+                offered_mint,
+                offered_amount,
+                hash_kind,
+                hash,
+                unlock_time,
+                time_out,
+                clock,
+            } => {
+                buf.push(5);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(counterparty.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.push(*hash_kind as u8);
+                buf.extend_from_slice(hash);
+                buf.extend_from_slice(&unlock_time.to_le_bytes());
+                buf.extend_from_slice(&time_out.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Redeemed {
+                escrow,
+                maker,
+                counterparty,
+                offered_mint,
+                offered_amount,
+                preimage,
+                clock,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(counterparty.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&(preimage.len() as u32).to_le_bytes());
+                buf.extend_from_slice(preimage);
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::SwitchArmed {
+                escrow,
+                maker,
+                beneficiary,
+                offered_mint,
+                offered_amount,
+                time_out,
+                clock,
+            } => {
+                buf.push(7);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(beneficiary.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&time_out.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Claimed {
+                escrow,
+                maker,
+                beneficiary,
+                offered_mint,
+                offered_amount,
+                time_out,
+                clock,
+            } => {
+                buf.push(8);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(beneficiary.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&time_out.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Locked {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                unlock_time,
+                clock,
+            } => {
+                buf.push(9);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&unlock_time.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Unlocked {
+                escrow,
+                maker,
+                offered_mint,
+                offered_amount,
+                clock,
+            } => {
+                buf.push(10);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Arbitrated {
+                escrow,
+                maker,
+                taker,
+                arbiter,
+                offered_mint,
+                offered_amount,
+                default_outcome,
+                time_out,
+                clock,
+            } => {
+                buf.push(11);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(arbiter.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.push(*default_outcome as u8);
+                buf.extend_from_slice(&time_out.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Delivered {
+                escrow,
+                maker,
+                taker,
+                clock,
+            } => {
+                buf.push(12);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(taker.as_ref());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Disputed {
+                escrow,
+                maker,
+                party,
+                clock,
+            } => {
+                buf.push(13);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(party.as_ref());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Settled {
+                escrow,
+                maker,
+                taker,
+                offered_mint,
+                to_taker,
+                to_maker,
+                clock,
+            } => {
+                buf.push(14);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&to_taker.to_le_bytes());
+                buf.extend_from_slice(&to_maker.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::MilestonesOpened {
+                escrow,
+                maker,
+                payee,
+                offered_mint,
+                offered_amount,
+                milestone_count,
+                clock,
+            } => {
+                buf.push(15);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(payee.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.push(*milestone_count);
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::MilestoneApproved {
+                escrow,
+                maker,
+                payee,
+                offered_mint,
+                index,
+                amount,
+                remaining,
+                clock,
+            } => {
+                buf.push(16);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(payee.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.push(*index);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&remaining.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::MilestonesRefunded {
+                escrow,
+                maker,
+                offered_mint,
+                amount,
+                remaining,
+                clock,
+            } => {
+                buf.push(17);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&remaining.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::BondPosted {
+                escrow,
+                maker,
+                recipient,
+                bond_mint,
+                amount,
+                clock,
+            } => {
+                buf.push(18);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(recipient.as_ref());
+                buf.extend_from_slice(bond_mint.as_ref());
+                buf.extend_from_slice(&amount.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::BondRefunded {
+                escrow,
+                maker,
+                bond_mint,
+                amount,
+                clock,
+            } => {
+                buf.push(19);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(bond_mint.as_ref());
+                buf.extend_from_slice(&amount.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::BondForfeited {
+                escrow,
+                maker,
+                recipient,
+                bond_mint,
+                amount,
+                clock,
+            } => {
+                buf.push(20);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(recipient.as_ref());
+                buf.extend_from_slice(bond_mint.as_ref());
+                buf.extend_from_slice(&amount.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::PremiumSet {
+                escrow,
+                maker,
+                premium,
+                clock,
+            } => {
+                buf.push(21);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(&premium.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::Reserved {
+                escrow,
+                maker,
+                taker,
+                premium_mint,
+                premium,
+                time_out,
+                clock,
+            } => {
+                buf.push(22);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(premium_mint.as_ref());
+                buf.extend_from_slice(&premium.to_le_bytes());
+                buf.extend_from_slice(&time_out.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::OrderFilled {
+                escrow,
+                maker,
+                taker,
+                offered_mint,
+                offered_amount,
+                requested_mint,
+                requested_amount,
+                nonce,
+                clock,
+            } => {
+                buf.push(23);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(requested_mint.as_ref());
+                buf.extend_from_slice(&requested_amount.to_le_bytes());
+                buf.extend_from_slice(&nonce.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::OrdersCancelled {
+                escrow,
+                maker,
+                nonce,
+                clock,
+            } => {
+                buf.push(24);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(&nonce.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::PoolOpened {
+                escrow,
+                creator,
+                offered_mint,
+                offered_per_lot,
+                requested_mint,
+                requested_per_lot,
+                unlock_time,
+                time_out,
+                clock,
+            } => {
+                buf.push(25);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(creator.as_ref());
+                buf.extend_from_slice(offered_mint.as_ref());
+                buf.extend_from_slice(&offered_per_lot.to_le_bytes());
+                buf.extend_from_slice(requested_mint.as_ref());
+                buf.extend_from_slice(&requested_per_lot.to_le_bytes());
+                buf.extend_from_slice(&unlock_time.to_le_bytes());
+                buf.extend_from_slice(&time_out.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::PoolDeposited {
+                escrow,
+                maker,
+                amount,
+                clock,
+            } => {
+                buf.push(26);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(&amount.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::PoolFilled {
+                escrow,
+                taker,
+                offered_amount,
+                requested_amount,
+                clock,
+            } => {
+                buf.push(27);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&requested_amount.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::PoolWithdrawn {
+                escrow,
+                maker,
+                unfilled,
+                proceeds,
+                clock,
+            } => {
+                buf.push(28);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(&unfilled.to_le_bytes());
+                buf.extend_from_slice(&proceeds.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::ExclusiveTakerSet {
+                escrow,
+                maker,
+                taker,
+                exclusive_until,
+                clock,
+            } => {
+                buf.push(29);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(&exclusive_until.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::AllowListSet {
+                escrow,
+                maker,
+                root,
+                clock,
+            } => {
+                buf.push(30);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(root);
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::PriceConditionSet {
+                escrow,
+                maker,
+                oracle,
+                oracle_kind,
+                condition,
+                trigger_price,
+                trigger_expo,
+                max_staleness,
+                clock,
+            } => {
+                buf.push(31);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(oracle.as_ref());
+                buf.push(*oracle_kind as u8);
+                buf.push(*condition as u8);
+                buf.extend_from_slice(&trigger_price.to_le_bytes());
+                buf.extend_from_slice(&trigger_expo.to_le_bytes());
+                buf.extend_from_slice(&max_staleness.to_le_bytes());
+                Self::pack_clock(clock, &mut buf);
+            }
+            Self::CallbackSet {
+                escrow,
+                maker,
+                callback_program,
+                clock,
+            } => {
+                buf.push(32);
+                buf.extend_from_slice(escrow.as_ref());
+                buf.extend_from_slice(maker.as_ref());
+                buf.extend_from_slice(callback_program.as_ref());
+                Self::pack_clock(clock, &mut buf);
+            }
+        }
+        buf
+    }
+
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (version, rest) = input.split_first().ok_or(InvalidEvent)?;
+        if *version != EVENT_VERSION {
+            return Err(InvalidEvent.into());
+        }
+        let (tag, rest) = rest.split_first().ok_or(InvalidEvent)?;
+        let src = &mut Reader(rest);
+
+        let event = match tag {
+            0 => Self::Created {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                requested_mint: src.pubkey()?,
+                requested_amount: src.u64()?,
+                unlock_time: src.u64()?,
+                time_out: src.u64()?,
+                clock: src.clock()?,
+            },
+            1 => Self::Exchanged {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                taker: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                requested_mint: src.pubkey()?,
+                requested_amount: src.u64()?,
+                clock: src.clock()?,
+            },
+            2 => Self::Cancelled {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                clock: src.clock()?,
+            },
+            3 => Self::LockReset {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                unlock_time: src.u64()?,
+                time_out: src.u64()?,
+                clock: src.clock()?,
+            },
+            4 => Self::Expired {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                time_out: src.u64()?,
+                clock: src.clock()?,
+            },
+            5 => Self::HashLocked {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                counterparty: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                hash_kind: HashKind::from_u8(src.take::<1>()?[0]).ok_or(InvalidEvent)?,
+                hash: src.take()?,
+                unlock_time: src.u64()?,
+                time_out: src.u64()?,
+                clock: src.clock()?,
+            },
+            6 => Self::Redeemed {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                counterparty: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                preimage: src.bytes()?,
+                clock: src.clock()?,
+            },
+            7 => Self::SwitchArmed {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                beneficiary: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                time_out: src.u64()?,
+                clock: src.clock()?,
+            },
+            8 => Self::Claimed {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                beneficiary: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                time_out: src.u64()?,
+                clock: src.clock()?,
+            },
+            9 => Self::Locked {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                unlock_time: src.u64()?,
+                clock: src.clock()?,
+            },
+            10 => Self::Unlocked {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                clock: src.clock()?,
+            },
+            11 => Self::Arbitrated {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                taker: src.pubkey()?,
+                arbiter: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                default_outcome: DefaultOutcome::from_u8(src.take::<1>()?[0])
+                    .ok_or(InvalidEvent)?,
+                time_out: src.u64()?,
+                clock: src.clock()?,
+            },
+            12 => Self::Delivered {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                taker: src.pubkey()?,
+                clock: src.clock()?,
+            },
+            13 => Self::Disputed {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                party: src.pubkey()?,
+                clock: src.clock()?,
+            },
+            14 => Self::Settled {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                taker: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                to_taker: src.u64()?,
+                to_maker: src.u64()?,
+                clock: src.clock()?,
+            },
+            15 => Self::MilestonesOpened {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                payee: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                milestone_count: src.take::<1>()?[0],
+                clock: src.clock()?,
+            },
+            16 => Self::MilestoneApproved {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                payee: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                index: src.take::<1>()?[0],
+                amount: src.u64()?,
+                remaining: src.u64()?,
+                clock: src.clock()?,
+            },
+            17 => Self::MilestonesRefunded {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                amount: src.u64()?,
+                remaining: src.u64()?,
+                clock: src.clock()?,
+            },
+            18 => Self::BondPosted {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                recipient: src.pubkey()?,
+                bond_mint: src.pubkey()?,
+                amount: src.u64()?,
+                clock: src.clock()?,
+            },
+            19 => Self::BondRefunded {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                bond_mint: src.pubkey()?,
+                amount: src.u64()?,
+                clock: src.clock()?,
+            },
+            20 => Self::BondForfeited {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                recipient: src.pubkey()?,
+                bond_mint: src.pubkey()?,
+                amount: src.u64()?,
+                clock: src.clock()?,
+            },
+            21 => Self::PremiumSet {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                premium: src.u64()?,
+                clock: src.clock()?,
+            },
+            22 => Self::Reserved {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                taker: src.pubkey()?,
+                premium_mint: src.pubkey()?,
+                premium: src.u64()?,
+                time_out: src.u64()?,
+                clock: src.clock()?,
+            },
+            23 => Self::OrderFilled {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                taker: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_amount: src.u64()?,
+                requested_mint: src.pubkey()?,
+                requested_amount: src.u64()?,
+                nonce: src.u64()?,
+                clock: src.clock()?,
+            },
+            24 => Self::OrdersCancelled {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                nonce: src.u64()?,
+                clock: src.clock()?,
+            },
+            25 => Self::PoolOpened {
+                escrow: src.pubkey()?,
+                creator: src.pubkey()?,
+                offered_mint: src.pubkey()?,
+                offered_per_lot: src.u64()?,
+                requested_mint: src.pubkey()?,
+                requested_per_lot: src.u64()?,
+                unlock_time: src.u64()?,
+                time_out: src.u64()?,
+                clock: src.clock()?,
+            },
+            26 => Self::PoolDeposited {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                amount: src.u64()?,
+                clock: src.clock()?,
+            },
+            27 => Self::PoolFilled {
+                escrow: src.pubkey()?,
+                taker: src.pubkey()?,
+                offered_amount: src.u64()?,
+                requested_amount: src.u64()?,
+                clock: src.clock()?,
+            },
+            28 => Self::PoolWithdrawn {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                unfilled: src.u64()?,
+                proceeds: src.u64()?,
+                clock: src.clock()?,
+            },
+            29 => Self::ExclusiveTakerSet {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                taker: src.pubkey()?,
+                exclusive_until: src.u64()?,
+                clock: src.clock()?,
+            },
+            30 => Self::AllowListSet {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                root: src.take()?,
+                clock: src.clock()?,
+            },
+            31 => Self::PriceConditionSet {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                oracle: src.pubkey()?,
+                oracle_kind: OracleKind::from_u8(src.take::<1>()?[0]).ok_or(InvalidEvent)?,
+                condition: PriceCondition::from_u8(src.take::<1>()?[0]).ok_or(InvalidEvent)?,
+                trigger_price: i64::from_le_bytes(src.take()?),
+                trigger_expo: i32::from_le_bytes(src.take()?),
+                max_staleness: src.u64()?,
+                clock: src.clock()?,
+            },
+            32 => Self::CallbackSet {
+                escrow: src.pubkey()?,
+                maker: src.pubkey()?,
+                callback_program: src.pubkey()?,
+                clock: src.clock()?,
+            },
+            _ => return Err(InvalidEvent.into()),
+        };
+        if !src.0.is_empty() {
+            return Err(InvalidEvent.into());
+        }
+        Ok(event)
+    }
+
+    /// The escrow account every event is about
+    pub fn escrow(&self) -> &Pubkey {
+        match self {
+            Self::Created { escrow, .. }
+            | Self::Exchanged { escrow, .. }
+            | Self::Cancelled { escrow, .. }
+            | Self::LockReset { escrow, .. }
+            | Self::Expired { escrow, .. }
+            | Self::HashLocked { escrow, .. }
+            | Self::Redeemed { escrow, .. }
+            | Self::SwitchArmed { escrow, .. }
+            | Self::Claimed { escrow, .. }
+            | Self::Locked { escrow, .. }
+            | Self::Unlocked { escrow, .. }
+            | Self::Arbitrated { escrow, .. }
+            | Self::Delivered { escrow, .. }
+            | Self::Disputed { escrow, .. }
+            | Self::Settled { escrow, .. }
+            | Self::MilestonesOpened { escrow, .. }
+            | Self::MilestoneApproved { escrow, .. }
+            | Self::MilestonesRefunded { escrow, .. }
+            | Self::BondPosted { escrow, .. }
+            | Self::BondRefunded { escrow, .. }
+            | Self::BondForfeited { escrow, .. }
+            | Self::PremiumSet { escrow, .. }
+            | Self::Reserved { escrow, .. }
+            | Self::OrderFilled { escrow, .. }
+            | Self::OrdersCancelled { escrow, .. }
+            | Self::PoolOpened { escrow, .. }
+            | Self::PoolDeposited { escrow, .. }
+            | Self::PoolFilled { escrow, .. }
+            | Self::PoolWithdrawn { escrow, .. }
+            | Self::ExclusiveTakerSet { escrow, .. }
+            | Self::AllowListSet { escrow, .. }
+            | Self::PriceConditionSet { escrow, .. }
+            | Self::CallbackSet { escrow, .. } => escrow,
+        }
+    }
+
+    /// When the transition happened
+    pub fn clock(&self) -> &EventClock {
+        match self {
+            Self::Created { clock, .. }
+            | Self::Exchanged { clock, .. }
+            | Self::Cancelled { clock, .. }
+            | Self::LockReset { clock, .. }
+            | Self::Expired { clock, .. }
+            | Self::HashLocked { clock, .. }
+            | Self::Redeemed { clock, .. }
+            | Self::SwitchArmed { clock, .. }
+            | Self::Claimed { clock, .. }
+            | Self::Locked { clock, .. }
+            | Self::Unlocked { clock, .. }
+            | Self::Arbitrated { clock, .. }
+            | Self::Delivered { clock, .. }
+            | Self::Disputed { clock, .. }
+            | Self::Settled { clock, .. }
+            | Self::MilestonesOpened { clock, .. }
+            | Self::MilestoneApproved { clock, .. }
+            | Self::MilestonesRefunded { clock, .. }
+            | Self::BondPosted { clock, .. }
+            | Self::BondRefunded { clock, .. }
+            | Self::BondForfeited { clock, .. }
+            | Self::PremiumSet { clock, .. }
+            | Self::Reserved { clock, .. }
+            | Self::OrderFilled { clock, .. }
+            | Self::OrdersCancelled { clock, .. }
+            | Self::PoolOpened { clock, .. }
+            | Self::PoolDeposited { clock, .. }
+            | Self::PoolFilled { clock, .. }
+            | Self::PoolWithdrawn { clock, .. }
+            | Self::ExclusiveTakerSet { clock, .. }
+            | Self::AllowListSet { clock, .. }
+            | Self::PriceConditionSet { clock, .. }
+            | Self::CallbackSet { clock, .. } => clock,
+        }
+    }
+
+    fn pack_clock(clock: &EventClock, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&clock.slot.to_le_bytes());
+        buf.extend_from_slice(&clock.unix_timestamp.to_le_bytes());
+    }
+}
+
+/// Reads fields front to back out of a packed event
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], ProgramError> {
+        let bytes = self
+            .0
+            .get(..N)
+            .and_then(|slice| slice.try_into().ok())
+            .ok_or(InvalidEvent)?;
+        self.0 = &self.0[N..];
+        Ok(bytes)
+    }
+
+    /// A u32 length and that many bytes
+    fn bytes(&mut self) -> Result<Vec<u8>, ProgramError> {
+        let len = u32::from_le_bytes(self.take()?) as usize;
+        let bytes = self.0.get(..len).ok_or(InvalidEvent)?.to_vec();
+        self.0 = &self.0[len..];
+        Ok(bytes)
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey, ProgramError> {
+        Ok(Pubkey::new_from_array(self.take()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, ProgramError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn clock(&mut self) -> Result<EventClock, ProgramError> {
+        Ok(EventClock {
+            slot: self.u64()?,
+            unix_timestamp: i64::from_le_bytes(self.take()?),
+        })
+    }
+}
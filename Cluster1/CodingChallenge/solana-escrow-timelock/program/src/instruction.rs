@@ -2,11 +2,55 @@ use solana_program::{
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
+    system_program, sysvar,
 };
 use std::{convert::TryInto, mem::size_of};
 
-use crate::error::EscrowError::InvalidInstruction;
+use num_traits::FromPrimitive;
 
+use crate::{
+    error::EscrowError::InvalidInstruction,
+    merkle::MAX_PROOF_LEN,
+    oracle::{OracleKind, PriceCondition},
+    order::Order,
+    state::{DefaultOutcome, HashKind, MAX_MILESTONES, MAX_PREIMAGE_LEN},
+};
+
+/// One tranche of a new milestone escrow
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MilestoneTerms {
+    pub amount: u64,
+    /// Slots from now after which the maker can take the tranche back, or
+    /// `None` if only approving it settles it
+    pub deadline_slots: Option<u64>,
+}
+
+/// A bond the maker of a swap puts up as they open it. It comes back on
+/// exchange, or on a cancel before `unlock_time` or after `time_out`;
+/// cancelling in between forfeits it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BondTerms {
+    /// Lamports the escrow account holds on top of its rent, or 0 for a
+    /// token bond, which is the whole balance of the bond token account
+    pub lamports: u64,
+    /// Who gets a forfeited bond, or for a token bond the owner of the token
+    /// account it goes to. `Pubkey::default()` forfeits it to the
+    /// [`fee_vault`].
+    pub recipient: Pubkey,
+}
+
+/// The accounts of one of the escrows an `ExchangeMany` takes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EscrowToTake {
+    pub escrow: Pubkey,
+    /// The escrow's vault, or the maker's own token account for a
+    /// delegate-approval escrow
+    pub vault: Pubkey,
+    pub maker: Pubkey,
+    pub maker_receive_account: Pubkey,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum EscrowInstruction {
     /// Starts the trade by creating and populating an escrow account and transferring ownership of the given temp token account to the PDA
     ///
@@ -19,9 +63,24 @@ pub enum EscrowInstruction {
     /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
     /// 4. `[]` The rent sysvar
     /// 5. `[]` The token program
+    ///
+    /// A bond in lamports has to be in the escrow account already, on top of
+    /// its rent, and the account needs room for it, see
+    /// [`Escrow::packed_len`](crate::state::Escrow::packed_len). A token bond
+    /// also takes:
+    ///
+    /// 6. `[writable]` Token account holding the bond, owned by the initializer, which the PDA takes over as the bond vault
+    /// 7. `[]` The token program
     InitEscrow {
         /// The amount party A expects to receive of token Y
         amount: u64,
+        /// How many slots from now until the escrow can be taken
+        unlock_slots: u64,
+        /// How many slots after unlocking the escrow stays takeable
+        time_out_slots: u64,
+        /// The maker's bond, if any. Left out of the data entirely when
+        /// there is none.
+        bond: Option<BondTerms>,
     },
     /// Accepts a trade
     ///
@@ -37,23 +96,602 @@ pub enum EscrowInstruction {
     /// 6. `[writable]` The escrow account holding the escrow info
     /// 7. `[]` The token program
     /// 8. `[]` The PDA account
+    ///
+    /// In a delegate-approval escrow, account 3 is the initializer's own token
+    /// account. The PDA pulls the escrowed amount out of it as delegate and
+    /// leaves it open.
+    ///
+    /// An escrow with a token bond also takes, to refund it:
+    ///
+    /// 9. `[writable]` The bond vault, to empty and close
+    /// 10. `[writable]` The initializer's token account to get the bond back in
+    ///
+    /// A swap with a callback program takes it and the escrow's
+    /// [`callback_authority`] after those and any oracle, followed by the
+    /// accounts to pass it; see [`callback`](crate::callback).
     Exchange {
         /// the amount the taker expects to be paid in the other token, as a u64 because that's the max possible supply of a token
         amount: u64,
+        /// Where the escrow has an allow list, the taker's Merkle proof of
+        /// being on it. Left out of the data entirely when empty.
+        proof: Vec<[u8; 32]>,
     },
     //Reset time_lock and time_out
     /// 0. `[signer]` The initializer that is reseting the timelock
     /// 1. `[writeable]` The escrow account holding the escrow info
-    ResetTimeLock {},
+    ResetTimeLock {
+        /// How many slots from now until the escrow can be taken
+        unlock_slots: u64,
+        /// How many slots after unlocking the escrow stays takeable
+        time_out_slots: u64,
+    },
     //Cancel Escrow
     /// 0. `[signer]` The initializer canceling their escrow
     /// 1. `[writable]` PDA temp token account
-    /// REMOVED. `[writable]` Initializer main account to send rent fees (same as signer, this can be deleted...)
     /// 2. `[writable]` Initializer's token account to receive tokens (token account for the tokens they sent to escrow!)
     /// 3. `[writable]` Escrow account holding the escrow info
     /// 4. `[]` The token program
     /// 5. `[]` The PDA account
+    ///
+    /// A delegate-approval escrow has no vault: account 1 is the initializer's
+    /// own token account, which is left alone, and only the escrow closes.
+    ///
+    /// A bonded escrow also takes the accounts its bond goes to. The bond is
+    /// forfeited when cancelling between `unlock_time` and `time_out`, and
+    /// refunded otherwise. For a bond in lamports, only a forfeited one needs:
+    ///
+    /// 6. `[writable]` The bond recipient
+    ///
+    /// and for a token bond:
+    ///
+    /// 6. `[writable]` The bond vault, to empty and close
+    /// 7. `[writable]` The bond recipient's token account if forfeited, the initializer's otherwise
+    ///
+    /// A swap with a callback program takes it and the escrow's
+    /// [`callback_authority`] after those, followed by the accounts to pass
+    /// it; see [`callback`](crate::callback).
     Cancel {},
+    /// Rewrites an escrow created with an older state layout in the current
+    /// one, growing the account if needed
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, who pays for any extra rent
+    /// 1. `[writable]` The escrow account holding the escrow info
+    /// 2. `[]` The system program
+    Migrate {},
+    /// Opens a hash-lock escrow for a cross-chain swap: the counterparty gets
+    /// the vault by revealing a preimage of `hash` with `Redeem` inside the
+    /// lock window, after which the maker can only reclaim it with `Cancel`.
+    /// The escrow account needs room for the hash, see
+    /// [`Escrow::packed_len`](crate::state::Escrow::packed_len).
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
+    /// 2. `[writable]` The escrow account, it will hold all necessary info about the trade.
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The token program
+    InitHashLock {
+        /// How `hash` is computed from the preimage
+        hash_kind: HashKind,
+        hash: [u8; 32],
+        /// The only account that can redeem the escrow
+        counterparty: Pubkey,
+        /// How many slots from now until the escrow can be redeemed
+        unlock_slots: u64,
+        /// How many slots after unlocking the escrow stays redeemable
+        time_out_slots: u64,
+    },
+    /// Releases a hash-lock escrow's vault to its counterparty. The preimage
+    /// ends up in the transaction and in the `Redeemed` event, where the
+    /// maker picks it up to claim the other chain's side of the swap.
+    ///
+    /// This is not `Exchange`, since nothing is paid on this chain: the
+    /// counterparty pays with their lock on the other one, so there is no
+    /// taker's token account to send from nor a maker's to receive into.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The escrow's counterparty
+    /// 1. `[writable]` The counterparty's token account to receive the vault's tokens
+    /// 2. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 3. `[writable]` The initializer's main account to send their rent fees to
+    /// 4. `[writable]` The escrow account holding the escrow info
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    Redeem {
+        /// Hashes to the escrow's `hash`, as a u32 length and up to
+        /// `MAX_PREIMAGE_LEN` bytes, so it can match a lock on a chain
+        /// that doesn't use 32-byte secrets
+        preimage: Vec<u8>,
+    },
+    /// Opens a dead man's switch: the beneficiary can `Claim` the vault once
+    /// it times out. The maker keeps it armed with `ResetTimeLock` and can
+    /// withdraw with `Cancel` at any time.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
+    /// 2. `[writable]` The escrow account, it will hold all necessary info about the switch.
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The token program
+    InitDeadMansSwitch {
+        /// The account that inherits the vault
+        beneficiary: Pubkey,
+        /// How many slots from now until the beneficiary can claim
+        time_out_slots: u64,
+    },
+    /// Releases a timed-out dead man's switch to its beneficiary
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The escrow's beneficiary
+    /// 1. `[writable]` The beneficiary's token account to receive the vault's tokens
+    /// 2. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 3. `[writable]` The initializer's main account to send their rent fees to
+    /// 4. `[writable]` The escrow account holding the escrow info
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    Claim {},
+    /// Locks the maker's own tokens until `unlock_time`, with no trade. Only
+    /// `Unlock` releases them, and the lock can be extended but never shortened.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person locking their tokens
+    /// 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
+    /// 2. `[writable]` The escrow account, it will hold all necessary info about the lock.
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The token program
+    Lock {
+        /// How many slots from now until the tokens can be withdrawn
+        unlock_slots: u64,
+    },
+    /// Returns an unlocked savings lock's tokens to its owner
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer withdrawing their tokens
+    /// 1. `[writable]` PDA temp token account
+    /// 2. `[writable]` Initializer's token account to receive tokens
+    /// 3. `[writable]` Escrow account holding the escrow info
+    /// 4. `[]` The token program
+    /// 5. `[]` The PDA account
+    Unlock {},
+    /// Pushes a savings lock's `unlock_time` further out
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer extending their lock
+    /// 1. `[writable]` The escrow account holding the escrow info
+    ExtendLock {
+        /// How many slots to add to the current `unlock_time`
+        extra_slots: u64,
+    },
+    /// Opens a deal between the maker and a taker that an arbiter settles if
+    /// either side disputes it. Undisputed, the maker can `Release` the vault
+    /// to the taker, and after `time_out` anyone can `Settle` it: to the
+    /// taker if they marked it delivered, otherwise by `default_outcome`.
+    /// The escrow account needs room for the arbiter, see
+    /// [`Escrow::packed_len`](crate::state::Escrow::packed_len).
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
+    /// 2. `[writable]` The escrow account, it will hold all necessary info about the deal.
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The token program
+    InitArbitrated {
+        /// Where the vault goes if the deal times out undelivered and undisputed
+        default_outcome: DefaultOutcome,
+        /// The account the maker pays for the service
+        taker: Pubkey,
+        /// The account that settles disputes
+        arbiter: Pubkey,
+        /// How many slots from now until the deal can be settled
+        time_out_slots: u64,
+    },
+    /// The taker says they delivered, so the deal settles in their favour if
+    /// the maker neither releases nor disputes it before `time_out`
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The deal's taker
+    /// 1. `[writable]` The escrow account holding the escrow info
+    MarkDelivered {},
+    /// Hands the deal to the arbiter
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The deal's maker or taker
+    /// 1. `[writable]` The escrow account holding the escrow info
+    OpenDispute {},
+    /// The maker pays the whole vault to the taker
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer releasing the vault
+    /// 1. `[writable]` The taker's token account to receive the vault's tokens
+    /// 2. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 3. `[writable]` The escrow account holding the escrow info
+    /// 4. `[]` The token program
+    /// 5. `[]` The PDA account
+    Release {},
+    /// The arbiter settles a disputed deal, splitting the vault between the
+    /// taker and the maker
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The deal's arbiter
+    /// 1. `[writable]` The taker's token account for their share
+    /// 2. `[writable]` The maker's token account for their share
+    /// 3. `[writable]` The initializer's main account to send their rent fees to
+    /// 4. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 5. `[writable]` The escrow account holding the escrow info
+    /// 6. `[]` The token program
+    /// 7. `[]` The PDA account
+    Resolve {
+        /// The taker's share in basis points; the maker gets the rest
+        taker_bps: u16,
+    },
+    /// Settles an undisputed deal that timed out, or a disputed one the
+    /// arbiter left alone for [`DISPUTE_GRACE_SLOTS`](crate::state::DISPUTE_GRACE_SLOTS)
+    /// after that. Anyone can send it.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The taker's token account, paid if the deal goes their way
+    /// 1. `[writable]` The maker's token account, refunded otherwise
+    /// 2. `[writable]` The initializer's main account to send their rent fees to
+    /// 3. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 4. `[writable]` The escrow account holding the escrow info
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    Settle {},
+    /// Locks payment for a contract that pays out in tranches. The vault has
+    /// to hold exactly what the milestones add up to. The maker pays each
+    /// tranche to the payee with `ApproveMilestone`, and takes back the ones
+    /// still pending after their deadline with `RefundUnapproved`. The
+    /// escrow account needs room for the milestones, see
+    /// [`Escrow::packed_len`](crate::state::Escrow::packed_len).
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
+    /// 2. `[writable]` The escrow account, it will hold all necessary info about the deal.
+    /// 3. `[]` The rent sysvar
+    /// 4. `[]` The token program
+    InitMilestones {
+        /// The account each approved tranche is paid to
+        payee: Pubkey,
+        /// Up to `MAX_MILESTONES` tranches, in the order they are expected
+        milestones: Vec<MilestoneTerms>,
+    },
+    /// The maker pays one pending tranche to the payee. Paying the last one
+    /// closes the vault and the escrow.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer approving the milestone
+    /// 1. `[writable]` The payee's token account to receive the tranche
+    /// 2. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 3. `[writable]` The escrow account holding the escrow info
+    /// 4. `[]` The token program
+    /// 5. `[]` The PDA account
+    ApproveMilestone {
+        /// Position of the milestone in the escrow
+        index: u8,
+    },
+    /// The maker takes back every pending tranche past its deadline. Once
+    /// nothing is pending any more, it closes the vault and the escrow.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer reclaiming the tranches
+    /// 1. `[writable]` The initializer's token account to get the tranches back in
+    /// 2. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 3. `[writable]` The escrow account holding the escrow info
+    /// 4. `[]` The token program
+    /// 5. `[]` The PDA account
+    RefundUnapproved {},
+    /// The maker of a swap sets the premium a taker pays to reserve it, or
+    /// stops offering reservations with a premium of 0
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, paying the rent for any room the escrow needs
+    /// 1. `[writable]` The escrow account holding the escrow info
+    /// 2. `[]` The system program
+    SetPremium {
+        /// Premium in the requested mint
+        premium: u64,
+    },
+    /// A taker pays the maker the premium and becomes the only one who can
+    /// `Exchange` until `time_out`. The maker can't cancel or reset the lock
+    /// while the reservation holds, and keeps the premium whatever happens.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The taker reserving the swap
+    /// 1. `[writable]` The taker's token account for the requested mint, to pay the premium from
+    /// 2. `[writable]` The initializer's token account that receives the premium
+    /// 3. `[writable]` The escrow account holding the escrow info
+    /// 4. `[]` The token program
+    Reserve {
+        /// The premium the taker expects to pay, to guard against the maker raising it
+        premium: u64,
+        /// Where the escrow has an allow list, the taker's Merkle proof of
+        /// being on it. Left out of the data entirely when empty.
+        proof: Vec<[u8; 32]>,
+    },
+    /// Settles an order the maker signed off-chain, without an escrow. The
+    /// instruction right before it must be an ed25519 program instruction
+    /// verifying the maker's signature of `order.message(program_id)`. The
+    /// offered tokens move out of the maker's account with the PDA as its
+    /// delegate, and an order receipt is created so the order fills only once.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The taker, who pays for the order receipt
+    /// 1. `[writable]` The taker's token account for the token they send
+    /// 2. `[writable]` The taker's token account for the token they receive
+    /// 3. `[writable]` The maker's token account the order sells from, delegated to the PDA
+    /// 4. `[writable]` The maker's token account the order pays into
+    /// 5. `[]` The maker's order nonce account, at `["nonce", maker]`, which may not exist yet
+    /// 6. `[writable]` The order receipt, at `["order", order.hash(program_id)]`
+    /// 7. `[]` The instructions sysvar
+    /// 8. `[]` The system program
+    /// 9. `[]` The token program
+    /// 10. `[]` The PDA account
+    FillSignedOrder { order: Order },
+    /// Moves the maker's order nonce forward, so no order signed so far can
+    /// be filled any more. Creates the nonce account on first use.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The maker
+    /// 1. `[writable]` The maker's order nonce account, at `["nonce", maker]`
+    /// 2. `[]` The system program
+    CancelAllOrders {},
+    /// Opens a swap like `InitEscrow`, except that the maker keeps the
+    /// offered tokens in their own token account and only approves the PDA as
+    /// its delegate for `offered_amount`. `Exchange` pulls them from there,
+    /// and fails if the maker revoked or lowered the approval in the meantime.
+    /// The escrow account needs room for the delegated amount, see
+    /// [`Escrow::packed_len`](crate::state::Escrow::packed_len).
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[]` The initializer's token account for the token they send, delegated to the PDA
+    /// 2. `[]` The initializer's token account for the token they will receive should the trade go through
+    /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
+    /// 4. `[]` The rent sysvar
+    ///
+    /// A bond is taken the same way as `InitEscrow`'s, a token bond from:
+    ///
+    /// 5. `[writable]` Token account holding the bond, owned by the initializer, which the PDA takes over as the bond vault
+    /// 6. `[]` The token program
+    InitDelegatedEscrow {
+        /// How much the maker offers, at most what the PDA is approved for
+        offered_amount: u64,
+        /// The amount party A expects to receive of token Y
+        amount: u64,
+        /// How many slots from now until the escrow can be taken
+        unlock_slots: u64,
+        /// How many slots after unlocking the escrow stays takeable
+        time_out_slots: u64,
+        /// The maker's bond, if any. Left out of the data entirely when
+        /// there is none.
+        bond: Option<BondTerms>,
+    },
+    /// Takes several swaps offering the same mint for the same requested mint
+    /// at once, cheapest first, for at most `max_total` of the requested mint
+    /// altogether. Escrows with a token bond need their own `Exchange`.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person taking the trades
+    /// 1. `[writable]` The taker's token account for the token they send
+    /// 2. `[writable]` The taker's token account for the token they will receive
+    /// 3. `[]` The token program
+    /// 4. `[]` The PDA account
+    ///
+    /// followed by, for each escrow:
+    ///
+    /// 0. `[writable]` The PDA's temp token account, or the initializer's own one in a delegate-approval escrow
+    /// 1. `[writable]` The initializer's main account to send their rent fees to
+    /// 2. `[writable]` The initializer's token account that will receive tokens
+    /// 3. `[writable]` The escrow account holding the escrow info
+    ExchangeMany {
+        /// The most the taker pays for all the escrows together
+        max_total: u64,
+        /// Skip escrows that can't be taken, or would go over `max_total`,
+        /// instead of failing the whole instruction
+        skip_unfillable: bool,
+    },
+    /// Settles a cycle of swaps that take each other's tokens: every maker
+    /// is paid by the next maker's vault and the last one by the first's.
+    /// Anyone can settle a ring.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account settling the ring
+    /// 1. `[]` The token program
+    /// 2. `[]` The PDA account
+    ///
+    /// followed by, for each escrow of the ring in order, at least two:
+    ///
+    /// 0. `[writable]` The PDA's temp token account, or the initializer's own one in a delegate-approval escrow
+    /// 1. `[writable]` The initializer's main account to send their rent fees to
+    /// 2. `[writable]` The initializer's token account that will receive tokens
+    /// 3. `[writable]` The escrow account holding the escrow info
+    SettleRing {},
+    /// Opens a pool that makers deposit the same mint into, sold at one price
+    /// in whole lots. Makers deposit for `unlock_slots`, then takers can fill
+    /// for `time_out_slots`.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person opening the pool
+    /// 1. `[writable]` The pool account, created and rent exempt
+    /// 2. `[writable]` An empty token account of the offered mint, owned by the creator, that becomes the pool's vault
+    /// 3. `[writable]` An empty token account of the requested mint, owned by the creator, that collects the proceeds
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program
+    InitPool {
+        offered_per_lot: u64,
+        requested_per_lot: u64,
+        unlock_slots: u64,
+        time_out_slots: u64,
+    },
+    /// Deposits into a pool before it opens for fills, for as many shares
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The maker, paying for their share account
+    /// 1. `[writable]` The maker's token account the deposit comes from
+    /// 2. `[writable]` The pool account
+    /// 3. `[writable]` The pool's vault
+    /// 4. `[writable]` The maker's share account, at `["share", pool, maker]`
+    /// 5. `[]` The token program
+    /// 6. `[]` The system program
+    DepositPool { amount: u64 },
+    /// Buys `lots` lots out of a pool while it is open
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person taking the lots
+    /// 1. `[writable]` The taker's token account for the token they send
+    /// 2. `[writable]` The taker's token account for the token they will receive
+    /// 3. `[writable]` The pool account
+    /// 4. `[writable]` The pool's vault
+    /// 5. `[writable]` The pool's proceeds vault
+    /// 6. `[]` The token program
+    /// 7. `[]` The PDA account
+    FillPool { lots: u64 },
+    /// Pays a maker their share of what is unfilled and of the proceeds,
+    /// before the pool opens or once it has timed out. The last withdrawal
+    /// closes the pool, and the creator closes a pool nobody deposited into
+    /// by withdrawing from it, without a share account.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The maker, getting their share account's rent back
+    /// 1. `[writable]` The maker's share account, unused when the pool is empty
+    /// 2. `[writable]` The pool account
+    /// 3. `[writable]` The pool's vault
+    /// 4. `[writable]` The pool's proceeds vault
+    /// 5. `[writable]` The maker's token account for the offered mint
+    /// 6. `[writable]` The maker's token account for the requested mint
+    /// 7. `[writable]` The pool's creator, getting the pool's rent back
+    /// 8. `[]` The token program
+    /// 9. `[]` The PDA account
+    WithdrawPool {},
+    /// The maker of a swap names the only taker who may exchange for the
+    /// first `exclusive_slots` slots after it unlocks. Anyone can take it
+    /// after that, until `time_out`. Only before the swap unlocks; the
+    /// default pubkey makes the swap public again.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, paying the rent for any room the escrow needs
+    /// 1. `[writable]` The escrow account holding the escrow info
+    /// 2. `[]` The system program
+    SetExclusiveTaker {
+        /// The designated taker
+        taker: Pubkey,
+        /// How many slots from `unlock_time` on only the designated taker
+        /// may exchange
+        exclusive_slots: u64,
+    },
+    /// The maker of a swap limits who may take it to an allow list, given
+    /// as the root of its Merkle tree, or lets anyone take it again with an
+    /// all-zero root. Only before the swap unlocks.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, paying the rent for any room the escrow needs
+    /// 1. `[writable]` The escrow account holding the escrow info
+    /// 2. `[]` The system program
+    SetAllowList {
+        /// Root of the allow list's Merkle tree
+        root: [u8; 32],
+    },
+    /// The maker of a swap makes it takeable only while an oracle's price
+    /// meets a condition, or drops the condition with `PriceCondition::None`.
+    /// The oracle is read once here, to make sure it is of `oracle_kind`.
+    /// Not while the swap is reserved.
+    ///
+    /// `Exchange` then takes the oracle account, read-only, after its other
+    /// accounts and any token bond's.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, paying the rent for any room the escrow needs
+    /// 1. `[writable]` The escrow account holding the escrow info
+    /// 2. `[]` The oracle account, ignored when dropping the condition
+    /// 3. `[]` The system program
+    SetPriceCondition {
+        oracle_kind: OracleKind,
+        condition: PriceCondition,
+        /// The trigger, worth `trigger_price * 10^trigger_expo`
+        trigger_price: i64,
+        trigger_expo: i32,
+        /// How many slots old the oracle's price may be
+        max_staleness: u64,
+    },
+    /// The maker of a swap names a program for `Exchange` and `Cancel` to
+    /// call once the swap settles, or stops calling one with the default
+    /// pubkey. Not while the swap is reserved, and never this program itself.
+    ///
+    /// `Exchange` and `Cancel` then take the callback program and the
+    /// escrow's [`callback_authority`] after their other accounts, followed
+    /// by whatever accounts the callback program needs.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, paying the rent for any room the escrow needs
+    /// 1. `[writable]` The escrow account holding the escrow info
+    /// 2. `[]` The callback program, ignored when no longer calling one
+    /// 3. `[]` The system program
+    SetCallback { callback_program: Pubkey },
 }
 
 impl EscrowInstruction {
@@ -62,49 +700,579 @@ impl EscrowInstruction {
         let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
 
         Ok(match tag {
-            0 => Self::InitEscrow {
-                amount: Self::unpack_amount(rest)?,
+            0 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (unlock_slots, rest) = Self::unpack_u64(rest)?;
+                let (time_out_slots, rest) = Self::unpack_u64(rest)?;
+                Self::InitEscrow {
+                    amount,
+                    unlock_slots,
+                    time_out_slots,
+                    bond: Self::unpack_bond(rest)?,
+                }
+            }
+            1 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                Self::Exchange {
+                    amount,
+                    proof: Self::unpack_proof(rest)?,
+                }
+            }
+            2 => {
+                let (unlock_slots, rest) = Self::unpack_u64(rest)?;
+                let (time_out_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::ResetTimeLock {
+                    unlock_slots,
+                    time_out_slots,
+                }
+            }
+            3 => Self::Cancel {},
+            4 => Self::Migrate {},
+            5 => {
+                let (hash_kind, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let (hash, rest) = Self::unpack_bytes(rest)?;
+                let (counterparty, rest) = Self::unpack_bytes(rest)?;
+                let (unlock_slots, rest) = Self::unpack_u64(rest)?;
+                let (time_out_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::InitHashLock {
+                    hash_kind: HashKind::from_u8(*hash_kind).ok_or(InvalidInstruction)?,
+                    hash,
+                    counterparty: Pubkey::new_from_array(counterparty),
+                    unlock_slots,
+                    time_out_slots,
+                }
+            }
+            6 => Self::Redeem {
+                preimage: Self::unpack_preimage(rest)?,
             },
-            1 => Self::Exchange {
-                amount: Self::unpack_amount(rest)?,
+            7 => {
+                let (beneficiary, rest) = Self::unpack_bytes(rest)?;
+                let (time_out_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::InitDeadMansSwitch {
+                    beneficiary: Pubkey::new_from_array(beneficiary),
+                    time_out_slots,
+                }
+            }
+            8 => Self::Claim {},
+            9 => Self::Lock {
+                unlock_slots: Self::unpack_u64(rest)?.0,
+            },
+            10 => Self::Unlock {},
+            11 => Self::ExtendLock {
+                extra_slots: Self::unpack_u64(rest)?.0,
+            },
+            12 => {
+                let (default_outcome, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let (taker, rest) = Self::unpack_bytes(rest)?;
+                let (arbiter, rest) = Self::unpack_bytes(rest)?;
+                let (time_out_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::InitArbitrated {
+                    default_outcome: DefaultOutcome::from_u8(*default_outcome)
+                        .ok_or(InvalidInstruction)?,
+                    taker: Pubkey::new_from_array(taker),
+                    arbiter: Pubkey::new_from_array(arbiter),
+                    time_out_slots,
+                }
+            }
+            13 => Self::MarkDelivered {},
+            14 => Self::OpenDispute {},
+            15 => Self::Release {},
+            16 => Self::Resolve {
+                taker_bps: rest
+                    .get(..2)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u16::from_le_bytes)
+                    .ok_or(InvalidInstruction)?,
+            },
+            17 => Self::Settle {},
+            18 => {
+                let (payee, rest) = Self::unpack_bytes(rest)?;
+                let (count, mut rest) = rest
+                    .get(..4)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(|count| (u32::from_le_bytes(count), &rest[4..]))
+                    .ok_or(InvalidInstruction)?;
+                // checked here so a huge count can't make us allocate
+                if count as usize > MAX_MILESTONES {
+                    return Err(InvalidInstruction.into());
+                }
+                let mut milestones = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let (amount, tail) = Self::unpack_u64(rest)?;
+                    let (has_deadline, tail) = tail.split_first().ok_or(InvalidInstruction)?;
+                    let (deadline_slots, tail) = match has_deadline {
+                        0 => (None, tail),
+                        1 => {
+                            let (deadline_slots, tail) = Self::unpack_u64(tail)?;
+                            (Some(deadline_slots), tail)
+                        }
+                        _ => return Err(InvalidInstruction.into()),
+                    };
+                    milestones.push(MilestoneTerms {
+                        amount,
+                        deadline_slots,
+                    });
+                    rest = tail;
+                }
+                Self::InitMilestones {
+                    payee: Pubkey::new_from_array(payee),
+                    milestones,
+                }
+            }
+            19 => Self::ApproveMilestone {
+                index: *rest.first().ok_or(InvalidInstruction)?,
+            },
+            20 => Self::RefundUnapproved {},
+            23 => Self::SetPremium {
+                premium: Self::unpack_u64(rest)?.0,
+            },
+            24 => {
+                let (premium, rest) = Self::unpack_u64(rest)?;
+                Self::Reserve {
+                    premium,
+                    proof: Self::unpack_proof(rest)?,
+                }
+            }
+            25 => Self::FillSignedOrder {
+                order: Order::unpack(rest)?,
+            },
+            26 => Self::CancelAllOrders {},
+            27 => {
+                let (offered_amount, rest) = Self::unpack_u64(rest)?;
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (unlock_slots, rest) = Self::unpack_u64(rest)?;
+                let (time_out_slots, rest) = Self::unpack_u64(rest)?;
+                Self::InitDelegatedEscrow {
+                    offered_amount,
+                    amount,
+                    unlock_slots,
+                    time_out_slots,
+                    bond: Self::unpack_bond(rest)?,
+                }
+            }
+            28 => {
+                let (max_total, rest) = Self::unpack_u64(rest)?;
+                let (skip_unfillable, _rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                Self::ExchangeMany {
+                    max_total,
+                    skip_unfillable: match skip_unfillable {
+                        0 => false,
+                        1 => true,
+                        _ => return Err(InvalidInstruction.into()),
+                    },
+                }
+            }
+            29 => Self::SettleRing {},
+            30 => {
+                let (offered_per_lot, rest) = Self::unpack_u64(rest)?;
+                let (requested_per_lot, rest) = Self::unpack_u64(rest)?;
+                let (unlock_slots, rest) = Self::unpack_u64(rest)?;
+                let (time_out_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::InitPool {
+                    offered_per_lot,
+                    requested_per_lot,
+                    unlock_slots,
+                    time_out_slots,
+                }
+            }
+            31 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositPool { amount }
+            }
+            32 => {
+                let (lots, _rest) = Self::unpack_u64(rest)?;
+                Self::FillPool { lots }
+            }
+            33 => Self::WithdrawPool {},
+            34 => {
+                let (taker, rest) = Self::unpack_bytes(rest)?;
+                let (exclusive_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetExclusiveTaker {
+                    taker: Pubkey::new_from_array(taker),
+                    exclusive_slots,
+                }
+            }
+            35 => Self::SetAllowList {
+                root: Self::unpack_bytes(rest)?.0,
+            },
+            36 => {
+                let (oracle_kind, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let (condition, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let (trigger_price, rest) = Self::unpack_u64(rest)?;
+                let trigger_expo = rest
+                    .get(..4)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(i32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                let (max_staleness, _rest) = Self::unpack_u64(&rest[4..])?;
+                Self::SetPriceCondition {
+                    oracle_kind: OracleKind::from_u8(*oracle_kind).ok_or(InvalidInstruction)?,
+                    condition: PriceCondition::from_u8(*condition).ok_or(InvalidInstruction)?,
+                    trigger_price: trigger_price as i64,
+                    trigger_expo,
+                    max_staleness,
+                }
+            }
+            37 => Self::SetCallback {
+                callback_program: Pubkey::new_from_array(Self::unpack_bytes(rest)?.0),
             },
-            2 => Self::ResetTimeLock {},
-            3 => Self::Cancel {},
             _ => return Err(InvalidInstruction.into()),
         })
     }
 
-    fn unpack_amount(input: &[u8]) -> Result<u64, ProgramError> {
-        let amount = input
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        let value = input
             .get(..8)
             .and_then(|slice| slice.try_into().ok())
             .map(u64::from_le_bytes)
             .ok_or(InvalidInstruction)?;
-        Ok(amount)
+        Ok((value, &input[8..]))
     }
 
-    fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match &*self {
-            Self::InitEscrow { amount } => {
-                buf.push(0);
-                buf.extend_from_slice(&amount.to_le_bytes());
-            }
-            Self::Exchange { amount } => {
-                buf.push(1);
-                buf.extend_from_slice(&amount.to_le_bytes());
-            }
-            Self::ResetTimeLock {} => {
-                buf.push(2);
-            }
-            Self::Cancel {} => {
-                buf.push(3);
-            }
+    /// A Merkle proof as a u32 count and the hashes, or none at all if the
+    /// data ends first
+    fn unpack_proof(input: &[u8]) -> Result<Vec<[u8; 32]>, ProgramError> {
+        if input.is_empty() {
+            return Ok(vec![]);
         }
-        buf
+        let (count, mut rest) = input
+            .get(..4)
+            .and_then(|slice| slice.try_into().ok())
+            .map(|count| (u32::from_le_bytes(count), &input[4..]))
+            .ok_or(InvalidInstruction)?;
+        // checked here so a huge count can't make us allocate
+        if count as usize > MAX_PROOF_LEN {
+            return Err(InvalidInstruction.into());
+        }
+        let mut proof = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (node, tail) = Self::unpack_bytes(rest)?;
+            proof.push(node);
+            rest = tail;
+        }
+        Ok(proof)
     }
+
+    fn pack_proof(proof: &[[u8; 32]], buf: &mut Vec<u8>) {
+        if proof.is_empty() {
+            return;
+        }
+        buf.extend_from_slice(&(proof.len() as u32).to_le_bytes());
+        for node in proof {
+            buf.extend_from_slice(node);
+        }
+    }
+
+    /// A bond as a 1 and its terms, or none at all if the data ends first or
+    /// has a 0 instead
+    fn unpack_bond(input: &[u8]) -> Result<Option<BondTerms>, ProgramError> {
+        let Some((has_bond, rest)) = input.split_first() else {
+            return Ok(None);
+        };
+        match has_bond {
+            0 => Ok(None),
+            1 => {
+                let (lamports, rest) = Self::unpack_u64(rest)?;
+                let (recipient, _rest) = Self::unpack_bytes(rest)?;
+                Ok(Some(BondTerms {
+                    lamports,
+                    recipient: Pubkey::new_from_array(recipient),
+                }))
+            }
+            _ => Err(InvalidInstruction.into()),
+        }
+    }
+
+    fn pack_bond(bond: &Option<BondTerms>, buf: &mut Vec<u8>) {
+        if let Some(bond) = bond {
+            buf.push(1);
+            buf.extend_from_slice(&bond.lamports.to_le_bytes());
+            buf.extend_from_slice(bond.recipient.as_ref());
+        }
+    }
+
+    /// A preimage as a u32 length and the bytes
+    fn unpack_preimage(input: &[u8]) -> Result<Vec<u8>, ProgramError> {
+        let (len, rest) = input
+            .get(..4)
+            .and_then(|slice| slice.try_into().ok())
+            .map(|len| (u32::from_le_bytes(len) as usize, &input[4..]))
+            .ok_or(InvalidInstruction)?;
+        // checked here so a huge length can't make us allocate
+        if len > MAX_PREIMAGE_LEN {
+            return Err(InvalidInstruction.into());
+        }
+        let preimage = rest.get(..len).ok_or(InvalidInstruction)?;
+        Ok(preimage.to_vec())
+    }
+
+    fn unpack_bytes(input: &[u8]) -> Result<([u8; 32], &[u8]), ProgramError> {
+        let value = input
+            .get(..32)
+            .and_then(|slice| slice.try_into().ok())
+            .ok_or(InvalidInstruction)?;
+        Ok((value, &input[32..]))
+    }
+
+    /// Packs a [EscrowInstruction](enum.EscrowInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            Self::InitEscrow {
+                amount,
+                unlock_slots,
+                time_out_slots,
+                bond,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&unlock_slots.to_le_bytes());
+                buf.extend_from_slice(&time_out_slots.to_le_bytes());
+                Self::pack_bond(bond, &mut buf);
+            }
+            Self::Exchange { amount, proof } => {
+                buf.push(1);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                Self::pack_proof(proof, &mut buf);
+            }
+            Self::ResetTimeLock {
+                unlock_slots,
+                time_out_slots,
+            } => {
+                buf.push(2);
+                buf.extend_from_slice(&unlock_slots.to_le_bytes());
+                buf.extend_from_slice(&time_out_slots.to_le_bytes());
+            }
+            Self::Cancel {} => {
+                buf.push(3);
+            }
+            Self::Migrate {} => {
+                buf.push(4);
+            }
+            Self::InitHashLock {
+                hash_kind,
+                hash,
+                counterparty,
+                unlock_slots,
+                time_out_slots,
+            } => {
+                buf.push(5);
+                buf.push(*hash_kind as u8);
+                buf.extend_from_slice(hash);
+                buf.extend_from_slice(counterparty.as_ref());
+                buf.extend_from_slice(&unlock_slots.to_le_bytes());
+                buf.extend_from_slice(&time_out_slots.to_le_bytes());
+            }
+            Self::Redeem { preimage } => {
+                buf.push(6);
+                buf.extend_from_slice(&(preimage.len() as u32).to_le_bytes());
+                buf.extend_from_slice(preimage);
+            }
+            Self::InitDeadMansSwitch {
+                beneficiary,
+                time_out_slots,
+            } => {
+                buf.push(7);
+                buf.extend_from_slice(beneficiary.as_ref());
+                buf.extend_from_slice(&time_out_slots.to_le_bytes());
+            }
+            Self::Claim {} => {
+                buf.push(8);
+            }
+            Self::Lock { unlock_slots } => {
+                buf.push(9);
+                buf.extend_from_slice(&unlock_slots.to_le_bytes());
+            }
+            Self::Unlock {} => {
+                buf.push(10);
+            }
+            Self::ExtendLock { extra_slots } => {
+                buf.push(11);
+                buf.extend_from_slice(&extra_slots.to_le_bytes());
+            }
+            Self::InitArbitrated {
+                default_outcome,
+                taker,
+                arbiter,
+                time_out_slots,
+            } => {
+                buf.push(12);
+                buf.push(*default_outcome as u8);
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(arbiter.as_ref());
+                buf.extend_from_slice(&time_out_slots.to_le_bytes());
+            }
+            Self::MarkDelivered {} => {
+                buf.push(13);
+            }
+            Self::OpenDispute {} => {
+                buf.push(14);
+            }
+            Self::Release {} => {
+                buf.push(15);
+            }
+            Self::Resolve { taker_bps } => {
+                buf.push(16);
+                buf.extend_from_slice(&taker_bps.to_le_bytes());
+            }
+            Self::Settle {} => {
+                buf.push(17);
+            }
+            Self::InitMilestones { payee, milestones } => {
+                buf.push(18);
+                buf.extend_from_slice(payee.as_ref());
+                buf.extend_from_slice(&(milestones.len() as u32).to_le_bytes());
+                for milestone in milestones {
+                    buf.extend_from_slice(&milestone.amount.to_le_bytes());
+                    match milestone.deadline_slots {
+                        None => buf.push(0),
+                        Some(deadline_slots) => {
+                            buf.push(1);
+                            buf.extend_from_slice(&deadline_slots.to_le_bytes());
+                        }
+                    }
+                }
+            }
+            Self::ApproveMilestone { index } => {
+                buf.push(19);
+                buf.push(*index);
+            }
+            Self::RefundUnapproved {} => {
+                buf.push(20);
+            }
+            Self::SetPremium { premium } => {
+                buf.push(23);
+                buf.extend_from_slice(&premium.to_le_bytes());
+            }
+            Self::Reserve { premium, proof } => {
+                buf.push(24);
+                buf.extend_from_slice(&premium.to_le_bytes());
+                Self::pack_proof(proof, &mut buf);
+            }
+            Self::FillSignedOrder { order } => {
+                buf.push(25);
+                buf.extend_from_slice(&order.pack());
+            }
+            Self::CancelAllOrders {} => {
+                buf.push(26);
+            }
+            Self::InitDelegatedEscrow {
+                offered_amount,
+                amount,
+                unlock_slots,
+                time_out_slots,
+                bond,
+            } => {
+                buf.push(27);
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&unlock_slots.to_le_bytes());
+                buf.extend_from_slice(&time_out_slots.to_le_bytes());
+                Self::pack_bond(bond, &mut buf);
+            }
+            Self::ExchangeMany {
+                max_total,
+                skip_unfillable,
+            } => {
+                buf.push(28);
+                buf.extend_from_slice(&max_total.to_le_bytes());
+                buf.push(*skip_unfillable as u8);
+            }
+            Self::SettleRing {} => {
+                buf.push(29);
+            }
+            Self::InitPool {
+                offered_per_lot,
+                requested_per_lot,
+                unlock_slots,
+                time_out_slots,
+            } => {
+                buf.push(30);
+                buf.extend_from_slice(&offered_per_lot.to_le_bytes());
+                buf.extend_from_slice(&requested_per_lot.to_le_bytes());
+                buf.extend_from_slice(&unlock_slots.to_le_bytes());
+                buf.extend_from_slice(&time_out_slots.to_le_bytes());
+            }
+            Self::DepositPool { amount } => {
+                buf.push(31);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::FillPool { lots } => {
+                buf.push(32);
+                buf.extend_from_slice(&lots.to_le_bytes());
+            }
+            Self::WithdrawPool {} => {
+                buf.push(33);
+            }
+            Self::SetExclusiveTaker {
+                taker,
+                exclusive_slots,
+            } => {
+                buf.push(34);
+                buf.extend_from_slice(taker.as_ref());
+                buf.extend_from_slice(&exclusive_slots.to_le_bytes());
+            }
+            Self::SetAllowList { root } => {
+                buf.push(35);
+                buf.extend_from_slice(root);
+            }
+            Self::SetPriceCondition {
+                oracle_kind,
+                condition,
+                trigger_price,
+                trigger_expo,
+                max_staleness,
+            } => {
+                buf.push(36);
+                buf.push(*oracle_kind as u8);
+                buf.push(*condition as u8);
+                buf.extend_from_slice(&trigger_price.to_le_bytes());
+                buf.extend_from_slice(&trigger_expo.to_le_bytes());
+                buf.extend_from_slice(&max_staleness.to_le_bytes());
+            }
+            Self::SetCallback { callback_program } => {
+                buf.push(37);
+                buf.extend_from_slice(callback_program.as_ref());
+            }
+        }
+        buf
+    }
+}
+
+/// Returns the PDA that owns every escrow's temp token account
+pub fn escrow_authority(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"escrow"], program_id).0
+}
+
+/// Returns the PDA that collects forfeited bonds without a recipient of
+/// their own. A token bond goes to a token account it owns.
+pub fn fee_vault(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"fees"], program_id).0
+}
+
+/// Returns the PDA that signs the callbacks of `escrow`. It is the escrow's
+/// own, so a callback program can tell which escrow called it, and it owns
+/// nothing, so the callback program can't do anything with its signature.
+pub fn callback_authority(program_id: &Pubkey, escrow: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"callback", escrow.as_ref()], program_id).0
+}
+
+/// Returns the account holding `maker`'s order nonce
+pub fn order_nonce_address(program_id: &Pubkey, maker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"nonce", maker.as_ref()], program_id).0
+}
+
+/// Returns the account holding `maker`'s shares in `pool`
+pub fn pool_share_address(program_id: &Pubkey, pool: &Pubkey, maker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"share", pool.as_ref(), maker.as_ref()], program_id).0
 }
 
+/// Returns the account marking `order` as filled
+pub fn order_receipt_address(program_id: &Pubkey, order: &Order) -> Pubkey {
+    Pubkey::find_program_address(&[b"order", &order.hash(program_id)], program_id).0
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn init_escrow(
     program_id: &Pubkey,
     initiator: &Pubkey,
@@ -113,15 +1281,27 @@ pub fn init_escrow(
     escrow_account: &Pubkey,
     token_program: &Pubkey,
     amount: u64,
+    unlock_slots: u64,
+    time_out_slots: u64,
+    bond: Option<BondTerms>,
+    bond_token_account: Option<&Pubkey>,
 ) -> Result<Instruction, ProgramError> {
-    let data = EscrowInstruction::InitEscrow { amount }.pack();
-    let accounts = vec![
+    let data = EscrowInstruction::InitEscrow {
+        amount,
+        unlock_slots,
+        time_out_slots,
+        bond,
+    }
+    .pack();
+    let mut accounts = vec![
         AccountMeta::new(*initiator, true),
         AccountMeta::new(*pda_temp_token_acct, false),
         AccountMeta::new_readonly(*init_token_acct, false),
         AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
         AccountMeta::new_readonly(*token_program, false),
     ];
+    accounts.extend(token_bond_accounts(bond_token_account));
     Ok(Instruction {
         program_id: *program_id,
         accounts,
@@ -129,28 +1309,46 @@ pub fn init_escrow(
     })
 }
 
+/// The accounts `InitEscrow` and `InitDelegatedEscrow` take a token bond from
+fn token_bond_accounts(bond_token_account: Option<&Pubkey>) -> Vec<AccountMeta> {
+    match bond_token_account {
+        Some(bond_token_account) => vec![
+            AccountMeta::new(*bond_token_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        None => vec![],
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn exchange(
     program_id: &Pubkey,
     taker: &Pubkey,
     taker_token_account: &Pubkey,  //token sending in
     taker_token_account2: &Pubkey, //token they're recieving
     temp_token_account: &Pubkey,   //PDA temp token account
-    initializer_token_account: &Pubkey,
     initializer_main_account: &Pubkey,
+    initializer_token_account: &Pubkey,
     escrow_account: &Pubkey,
     token_program: &Pubkey,
     amount: u64,
+    proof: &[[u8; 32]],
 ) -> Result<Instruction, ProgramError> {
-    let data = EscrowInstruction::Exchange { amount }.pack();
+    let data = EscrowInstruction::Exchange {
+        amount,
+        proof: proof.to_vec(),
+    }
+    .pack();
     let accounts = vec![
         AccountMeta::new(*taker, true),
         AccountMeta::new(*taker_token_account, false),
         AccountMeta::new(*taker_token_account2, false),
         AccountMeta::new(*temp_token_account, false),
-        AccountMeta::new(*initializer_token_account, false),
         AccountMeta::new(*initializer_main_account, false),
+        AccountMeta::new(*initializer_token_account, false),
         AccountMeta::new(*escrow_account, false),
         AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
     ];
     Ok(Instruction {
         program_id: *program_id,
@@ -159,14 +1357,18 @@ pub fn exchange(
     })
 }
 
-//need helper functions for the new instructions
-
 pub fn reset_time_lock(
     program_id: &Pubkey,
     initiator: &Pubkey,
     escrow_account: &Pubkey,
+    unlock_slots: u64,
+    time_out_slots: u64,
 ) -> Result<Instruction, ProgramError> {
-    let data = EscrowInstruction::ResetTimeLock {}.pack();
+    let data = EscrowInstruction::ResetTimeLock {
+        unlock_slots,
+        time_out_slots,
+    }
+    .pack();
     let accounts = vec![
         AccountMeta::new(*initiator, true),
         AccountMeta::new(*escrow_account, false),
@@ -190,9 +1392,865 @@ pub fn cancel(
     let accounts = vec![
         AccountMeta::new(*initiator, true),
         AccountMeta::new(*temp_token_account, false),
-        AccountMeta::new_readonly(*initializer_token_account, false),
+        AccountMeta::new(*initializer_token_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn migrate(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    escrow_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Migrate {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn init_hash_lock(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    pda_temp_token_acct: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+    hash_kind: HashKind,
+    hash: [u8; 32],
+    counterparty: &Pubkey,
+    unlock_slots: u64,
+    time_out_slots: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::InitHashLock {
+        hash_kind,
+        hash,
+        counterparty: *counterparty,
+        unlock_slots,
+        time_out_slots,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*pda_temp_token_acct, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn redeem(
+    program_id: &Pubkey,
+    counterparty: &Pubkey,
+    counterparty_token_account: &Pubkey,
+    temp_token_account: &Pubkey,
+    initializer_main_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+    preimage: &[u8],
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Redeem {
+        preimage: preimage.to_vec(),
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*counterparty, true),
+        AccountMeta::new(*counterparty_token_account, false),
+        AccountMeta::new(*temp_token_account, false),
+        AccountMeta::new(*initializer_main_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn init_dead_mans_switch(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    pda_temp_token_acct: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+    beneficiary: &Pubkey,
+    time_out_slots: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::InitDeadMansSwitch {
+        beneficiary: *beneficiary,
+        time_out_slots,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*pda_temp_token_acct, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn claim(
+    program_id: &Pubkey,
+    beneficiary: &Pubkey,
+    beneficiary_token_account: &Pubkey,
+    temp_token_account: &Pubkey,
+    initializer_main_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Claim {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*beneficiary, true),
+        AccountMeta::new(*beneficiary_token_account, false),
+        AccountMeta::new(*temp_token_account, false),
+        AccountMeta::new(*initializer_main_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn lock(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    pda_temp_token_acct: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+    unlock_slots: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Lock { unlock_slots }.pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*pda_temp_token_acct, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn unlock(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    temp_token_account: &Pubkey,
+    initializer_token_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Unlock {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*temp_token_account, false),
+        AccountMeta::new(*initializer_token_account, false),
         AccountMeta::new(*escrow_account, false),
         AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn extend_lock(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    escrow_account: &Pubkey,
+    extra_slots: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::ExtendLock { extra_slots }.pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*escrow_account, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn init_arbitrated(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    pda_temp_token_acct: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+    taker: &Pubkey,
+    arbiter: &Pubkey,
+    default_outcome: DefaultOutcome,
+    time_out_slots: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::InitArbitrated {
+        default_outcome,
+        taker: *taker,
+        arbiter: *arbiter,
+        time_out_slots,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*pda_temp_token_acct, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn mark_delivered(
+    program_id: &Pubkey,
+    taker: &Pubkey,
+    escrow_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::MarkDelivered {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*taker, true),
+        AccountMeta::new(*escrow_account, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn open_dispute(
+    program_id: &Pubkey,
+    party: &Pubkey,
+    escrow_account: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::OpenDispute {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*party, true),
+        AccountMeta::new(*escrow_account, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn release(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    taker_token_account: &Pubkey,
+    temp_token_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Release {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*taker_token_account, false),
+        AccountMeta::new(*temp_token_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn resolve(
+    program_id: &Pubkey,
+    arbiter: &Pubkey,
+    taker_token_account: &Pubkey,
+    maker_token_account: &Pubkey,
+    initializer_main_account: &Pubkey,
+    temp_token_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+    taker_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Resolve { taker_bps }.pack();
+    let accounts = vec![
+        AccountMeta::new_readonly(*arbiter, true),
+        AccountMeta::new(*taker_token_account, false),
+        AccountMeta::new(*maker_token_account, false),
+        AccountMeta::new(*initializer_main_account, false),
+        AccountMeta::new(*temp_token_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn settle(
+    program_id: &Pubkey,
+    taker_token_account: &Pubkey,
+    maker_token_account: &Pubkey,
+    initializer_main_account: &Pubkey,
+    temp_token_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Settle {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*taker_token_account, false),
+        AccountMeta::new(*maker_token_account, false),
+        AccountMeta::new(*initializer_main_account, false),
+        AccountMeta::new(*temp_token_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn init_milestones(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    pda_temp_token_acct: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+    payee: &Pubkey,
+    milestones: &[MilestoneTerms],
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::InitMilestones {
+        payee: *payee,
+        milestones: milestones.to_vec(),
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*pda_temp_token_acct, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn approve_milestone(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    payee_token_account: &Pubkey,
+    temp_token_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+    index: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::ApproveMilestone { index }.pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*payee_token_account, false),
+        AccountMeta::new(*temp_token_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn refund_unapproved(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    initiator_token_account: &Pubkey,
+    temp_token_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::RefundUnapproved {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*initiator_token_account, false),
+        AccountMeta::new(*temp_token_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_premium(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    escrow_account: &Pubkey,
+    premium: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SetPremium { premium }.pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn reserve(
+    program_id: &Pubkey,
+    taker: &Pubkey,
+    takers_sending_token_account: &Pubkey,
+    initializers_token_to_receive_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program: &Pubkey,
+    premium: u64,
+    proof: &[[u8; 32]],
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Reserve {
+        premium,
+        proof: proof.to_vec(),
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*taker, true),
+        AccountMeta::new(*takers_sending_token_account, false),
+        AccountMeta::new(*initializers_token_to_receive_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Fills a signed order. Goes right after the ed25519 instruction
+/// [`order::ed25519_instruction`](crate::order::ed25519_instruction) builds
+/// from the maker's signature.
+pub fn fill_signed_order(
+    program_id: &Pubkey,
+    taker: &Pubkey,
+    taker_token_account: &Pubkey,
+    taker_token_account2: &Pubkey,
+    order: &Order,
+    token_program: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::FillSignedOrder { order: *order }.pack();
+    let accounts = vec![
+        AccountMeta::new(*taker, true),
+        AccountMeta::new(*taker_token_account, false),
+        AccountMeta::new(*taker_token_account2, false),
+        AccountMeta::new(order.maker_send_account, false),
+        AccountMeta::new(order.maker_receive_account, false),
+        AccountMeta::new_readonly(order_nonce_address(program_id, &order.maker), false),
+        AccountMeta::new(order_receipt_address(program_id, order), false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn cancel_all_orders(program_id: &Pubkey, maker: &Pubkey) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::CancelAllOrders {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*maker, true),
+        AccountMeta::new(order_nonce_address(program_id, maker), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Opens a delegate-approval swap. The maker approves the PDA for
+/// `offered_amount` of `send_account` beforehand, usually in the same
+/// transaction.
+#[allow(clippy::too_many_arguments)]
+pub fn init_delegated_escrow(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    send_account: &Pubkey,
+    init_token_acct: &Pubkey,
+    escrow_account: &Pubkey,
+    offered_amount: u64,
+    amount: u64,
+    unlock_slots: u64,
+    time_out_slots: u64,
+    bond: Option<BondTerms>,
+    bond_token_account: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::InitDelegatedEscrow {
+        offered_amount,
+        amount,
+        unlock_slots,
+        time_out_slots,
+        bond,
+    }
+    .pack();
+    let mut accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new_readonly(*send_account, false),
+        AccountMeta::new_readonly(*init_token_acct, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    accounts.extend(token_bond_accounts(bond_token_account));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Takes every escrow in `escrows`, which all have to offer the mint
+/// `taker_token_account2` holds and ask for the one `taker_token_account` holds
+#[allow(clippy::too_many_arguments)]
+pub fn exchange_many(
+    program_id: &Pubkey,
+    taker: &Pubkey,
+    taker_token_account: &Pubkey,
+    taker_token_account2: &Pubkey,
+    token_program: &Pubkey,
+    escrows: &[EscrowToTake],
+    max_total: u64,
+    skip_unfillable: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::ExchangeMany {
+        max_total,
+        skip_unfillable,
+    }
+    .pack();
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*taker, true),
+        AccountMeta::new(*taker_token_account, false),
+        AccountMeta::new(*taker_token_account2, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    for escrow in escrows {
+        accounts.extend([
+            AccountMeta::new(escrow.vault, false),
+            AccountMeta::new(escrow.maker, false),
+            AccountMeta::new(escrow.maker_receive_account, false),
+            AccountMeta::new(escrow.escrow, false),
+        ]);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Settles the ring of `escrows`, each maker paid by the next one's vault
+pub fn settle_ring(
+    program_id: &Pubkey,
+    settler: &Pubkey,
+    token_program: &Pubkey,
+    escrows: &[EscrowToTake],
+) -> Result<Instruction, ProgramError> {
+    if escrows.len() < 2 {
+        return Err(InvalidInstruction.into());
+    }
+    let data = EscrowInstruction::SettleRing {}.pack();
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*settler, true),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    for escrow in escrows {
+        accounts.extend([
+            AccountMeta::new(escrow.vault, false),
+            AccountMeta::new(escrow.maker, false),
+            AccountMeta::new(escrow.maker_receive_account, false),
+            AccountMeta::new(escrow.escrow, false),
+        ]);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn init_pool(
+    program_id: &Pubkey,
+    creator: &Pubkey,
+    pool: &Pubkey,
+    vault: &Pubkey,
+    proceeds_vault: &Pubkey,
+    token_program: &Pubkey,
+    offered_per_lot: u64,
+    requested_per_lot: u64,
+    unlock_slots: u64,
+    time_out_slots: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::InitPool {
+        offered_per_lot,
+        requested_per_lot,
+        unlock_slots,
+        time_out_slots,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new_readonly(*creator, true),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new(*vault, false),
+        AccountMeta::new(*proceeds_vault, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*token_program, false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn deposit_pool(
+    program_id: &Pubkey,
+    maker: &Pubkey,
+    maker_token_account: &Pubkey,
+    pool: &Pubkey,
+    vault: &Pubkey,
+    token_program: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::DepositPool { amount }.pack();
+    let accounts = vec![
+        AccountMeta::new(*maker, true),
+        AccountMeta::new(*maker_token_account, false),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new(*vault, false),
+        AccountMeta::new(pool_share_address(program_id, pool, maker), false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn fill_pool(
+    program_id: &Pubkey,
+    taker: &Pubkey,
+    taker_token_account: &Pubkey,
+    taker_token_account2: &Pubkey,
+    pool: &Pubkey,
+    vault: &Pubkey,
+    proceeds_vault: &Pubkey,
+    token_program: &Pubkey,
+    lots: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::FillPool { lots }.pack();
+    let accounts = vec![
+        AccountMeta::new_readonly(*taker, true),
+        AccountMeta::new(*taker_token_account, false),
+        AccountMeta::new(*taker_token_account2, false),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new(*vault, false),
+        AccountMeta::new(*proceeds_vault, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_pool(
+    program_id: &Pubkey,
+    maker: &Pubkey,
+    pool: &Pubkey,
+    vault: &Pubkey,
+    proceeds_vault: &Pubkey,
+    maker_offered_account: &Pubkey,
+    maker_requested_account: &Pubkey,
+    creator: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::WithdrawPool {}.pack();
+    let accounts = vec![
+        AccountMeta::new(*maker, true),
+        AccountMeta::new(pool_share_address(program_id, pool, maker), false),
+        AccountMeta::new(*pool, false),
+        AccountMeta::new(*vault, false),
+        AccountMeta::new(*proceeds_vault, false),
+        AccountMeta::new(*maker_offered_account, false),
+        AccountMeta::new(*maker_requested_account, false),
+        AccountMeta::new(*creator, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(escrow_authority(program_id), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_exclusive_taker(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    escrow_account: &Pubkey,
+    taker: &Pubkey,
+    exclusive_slots: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SetExclusiveTaker {
+        taker: *taker,
+        exclusive_slots,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_allow_list(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    escrow_account: &Pubkey,
+    root: &[u8; 32],
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SetAllowList { root: *root }.pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn set_price_condition(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    escrow_account: &Pubkey,
+    oracle: &Pubkey,
+    oracle_kind: OracleKind,
+    condition: PriceCondition,
+    trigger_price: i64,
+    trigger_expo: i32,
+    max_staleness: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SetPriceCondition {
+        oracle_kind,
+        condition,
+        trigger_price,
+        trigger_expo,
+        max_staleness,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+pub fn set_callback(
+    program_id: &Pubkey,
+    initiator: &Pubkey,
+    escrow_account: &Pubkey,
+    callback_program: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SetCallback {
+        callback_program: *callback_program,
+    }
+    .pack();
+    let accounts = vec![
+        AccountMeta::new(*initiator, true),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*callback_program, false),
+        AccountMeta::new_readonly(system_program::id(), false),
     ];
     Ok(Instruction {
         program_id: *program_id,
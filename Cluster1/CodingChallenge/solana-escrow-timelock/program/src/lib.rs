@@ -1,7 +1,16 @@
+pub mod accounts;
+pub mod callback;
 pub mod error;
+pub mod event;
 pub mod instruction;
+pub mod loader;
+pub mod merkle;
+pub mod oracle;
+pub mod order;
 pub mod processor;
 pub mod state;
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
+
+solana_program::declare_id!("4yBTZXsuz7c1X3PJF4PPCJr8G6HnNAgRvzAWVoFZMncH");
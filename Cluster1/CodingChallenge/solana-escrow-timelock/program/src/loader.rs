@@ -0,0 +1,53 @@
+//! The one place program-owned accounts are read from `AccountInfo`s.
+
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    error::EscrowError,
+    state::{AccountType, ProgramAccount},
+};
+
+/// Loads an initialized `T`, after checking the account is owned by
+/// `program_id`, writable if `writable`, and tagged as a `T`
+pub fn load<T: ProgramAccount>(
+    account: &AccountInfo,
+    program_id: &Pubkey,
+    writable: bool,
+) -> Result<T, ProgramError> {
+    check(account, program_id, writable, T::ACCOUNT_TYPE)?;
+    T::unpack(&account.try_borrow_data()?)
+}
+
+/// Loads a freshly allocated account that is about to become a `T`
+pub fn load_uninitialized<T: ProgramAccount>(
+    account: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<T, ProgramError> {
+    check(account, program_id, true, AccountType::Uninitialized)?;
+    let value = T::unpack_unchecked(&account.try_borrow_data()?)?;
+    if value.is_initialized() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    Ok(value)
+}
+
+fn check(
+    account: &AccountInfo,
+    program_id: &Pubkey,
+    writable: bool,
+    account_type: AccountType,
+) -> Result<(), ProgramError> {
+    if account.owner != program_id {
+        return Err(ProgramError::IllegalOwner);
+    }
+    if writable && !account.is_writable {
+        return Err(EscrowError::AccountNotWritable.into());
+    }
+    if account.try_borrow_data()?.first() != Some(&(account_type as u8)) {
+        return Err(match account_type {
+            AccountType::Uninitialized => ProgramError::AccountAlreadyInitialized,
+            _ => EscrowError::WrongAccountType.into(),
+        });
+    }
+    Ok(())
+}
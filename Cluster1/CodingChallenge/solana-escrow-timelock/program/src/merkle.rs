@@ -0,0 +1,36 @@
+//! Allow-lists of takers, committed to as the root of a Merkle tree. The
+//! escrow only stores the root, and a taker proves they are on the list with
+//! the hashes along the path from their leaf up to it.
+//!
+//! Leaves and inner nodes are SHA-256 hashes with different prefixes, so an
+//! inner node can never pass for a leaf. The two children of a node are
+//! hashed in sorted order, which makes a proof just the list of siblings,
+//! without saying which side each one is on.
+
+use solana_program::{hash::hashv, pubkey::Pubkey};
+
+/// Longest proof `Exchange` and `Reserve` accept, enough for any list that
+/// fits in memory
+pub const MAX_PROOF_LEN: usize = 32;
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+/// The leaf for a taker
+pub fn leaf(taker: &Pubkey) -> [u8; 32] {
+    hashv(&[LEAF_PREFIX, taker.as_ref()]).to_bytes()
+}
+
+/// The node above two siblings, whichever order they come in
+pub fn parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[NODE_PREFIX, left, right]).to_bytes()
+}
+
+/// Whether `proof` leads from the taker's leaf up to `root`
+pub fn verify(root: &[u8; 32], taker: &Pubkey, proof: &[[u8; 32]]) -> bool {
+    proof
+        .iter()
+        .fold(leaf(taker), |node, sibling| parent(&node, sibling))
+        == *root
+}
@@ -0,0 +1,142 @@
+//! Prices read from oracle accounts, for swaps that can only be taken while
+//! a price meets the maker's condition, like limit and stop orders.
+//!
+//! Every feed format reads into the same [`OraclePrice`], so the processor
+//! doesn't care where a price comes from. A new format implements
+//! [`PriceFeed`] and gets a variant in [`OracleKind`]; nothing else changes.
+//! The escrow pins the oracle account by address, so a feed only has to
+//! parse the account, not vet it.
+
+use std::convert::TryInto;
+
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use num_derive::FromPrimitive;
+use solana_program::program_error::ProgramError;
+
+use crate::error::EscrowError;
+
+/// A price as a feed reports it, worth `price * 10^expo`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub expo: i32,
+    /// Slot the price was published at
+    pub publish_slot: u64,
+}
+
+impl OraclePrice {
+    /// Whether the price is more than `max_staleness` slots old at `slot`
+    pub fn is_stale(&self, slot: u64, max_staleness: u64) -> bool {
+        slot.saturating_sub(self.publish_slot) > max_staleness
+    }
+}
+
+/// One format of price account
+pub trait PriceFeed {
+    /// The latest price in the account's data
+    fn read_price(data: &[u8]) -> Result<OraclePrice, ProgramError>;
+}
+
+/// The format of the oracle account a swap's condition reads
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum OracleKind {
+    /// [`MockOracle`]
+    #[default]
+    Mock = 0,
+}
+
+impl OracleKind {
+    pub fn read_price(self, data: &[u8]) -> Result<OraclePrice, ProgramError> {
+        match self {
+            Self::Mock => MockOracle::read_price(data),
+        }
+    }
+}
+
+/// What a swap's oracle price has to do before it can be taken
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum PriceCondition {
+    /// No condition, the swap doesn't read an oracle
+    #[default]
+    None = 0,
+    /// The price is at or above the trigger, like a limit sell
+    AtOrAbove = 1,
+    /// The price is at or below the trigger, like a stop loss
+    AtOrBelow = 2,
+}
+
+impl PriceCondition {
+    /// Whether `price` meets the condition against a trigger worth
+    /// `trigger_price * 10^trigger_expo`
+    pub fn is_met(
+        self,
+        price: &OraclePrice,
+        trigger_price: i64,
+        trigger_expo: i32,
+    ) -> Result<bool, ProgramError> {
+        let at_or_above = match self {
+            Self::None => return Ok(true),
+            Self::AtOrAbove => true,
+            Self::AtOrBelow => false,
+        };
+        // bring both to the smaller exponent, where neither loses digits
+        let expo = price.expo.min(trigger_expo);
+        let scale = |value: i64, from: i32| {
+            10i128
+                .checked_pow((from - expo).try_into().ok()?)
+                .and_then(|factor| (value as i128).checked_mul(factor))
+        };
+        let price = scale(price.price, price.expo).ok_or(EscrowError::AmountOverflow)?;
+        let trigger = scale(trigger_price, trigger_expo).ok_or(EscrowError::AmountOverflow)?;
+        Ok(if at_or_above {
+            price >= trigger
+        } else {
+            price <= trigger
+        })
+    }
+}
+
+/// A price account in a layout of our own, for tests and local validators
+/// where no real feed runs: a magic number, then the price, its exponent and
+/// the slot it was published at. Whoever owns the account sets the price.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MockOracle {
+    pub price: i64,
+    pub expo: i32,
+    pub publish_slot: u64,
+}
+
+impl MockOracle {
+    /// First bytes of every mock oracle account
+    pub const MAGIC: [u8; 4] = *b"mock";
+    pub const LEN: usize = 4 + 8 + 4 + 8;
+
+    pub fn pack(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, MockOracle::LEN];
+        let (magic_dst, price_dst, expo_dst, publish_slot_dst) = mut_array_refs![dst, 4, 8, 4, 8];
+        *magic_dst = Self::MAGIC;
+        *price_dst = self.price.to_le_bytes();
+        *expo_dst = self.expo.to_le_bytes();
+        *publish_slot_dst = self.publish_slot.to_le_bytes();
+    }
+}
+
+impl PriceFeed for MockOracle {
+    fn read_price(data: &[u8]) -> Result<OraclePrice, ProgramError> {
+        if data.len() < MockOracle::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (magic, price, expo, publish_slot) =
+            array_refs![array_ref![data, 0, MockOracle::LEN], 4, 8, 4, 8];
+        if *magic != Self::MAGIC {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(OraclePrice {
+            price: i64::from_le_bytes(*price),
+            expo: i32::from_le_bytes(*expo),
+            publish_slot: u64::from_le_bytes(*publish_slot),
+        })
+    }
+}
@@ -0,0 +1,181 @@
+//! Orders a maker signs off-chain instead of opening an escrow. The maker
+//! approves the program's `escrow` PDA as delegate of the token account they
+//! sell from, and a taker settles the order with `FillSignedOrder`. The
+//! maker's signature is checked by the ed25519 program in the instruction
+//! right before the fill, which the processor reads back through the
+//! instructions sysvar.
+
+use std::convert::TryInto;
+
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    ed25519_program, hash, instruction::Instruction, program_error::ProgramError, pubkey::Pubkey,
+};
+
+use crate::error::EscrowError;
+
+/// Prefix of every signed order message, so a maker's signature over an order
+/// can't be mistaken for a signature over anything else
+pub const ORDER_DOMAIN: &[u8] = b"solana-escrow signed order v1";
+
+/// ed25519 program instruction layout: a signature count and a padding byte,
+/// then one offsets record per signature
+const OFFSETS_START: usize = 2;
+const OFFSETS_LEN: usize = 14;
+/// `instruction_index` meaning "this instruction's own data"
+const THIS_INSTRUCTION: u16 = u16::MAX;
+
+/// What a maker signs: `offered_amount` out of `maker_send_account` for
+/// `requested_amount` into `maker_receive_account`, both in the accounts'
+/// mints
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Order {
+    pub maker: Pubkey,
+    /// Owned by the maker, with the PDA as delegate
+    pub maker_send_account: Pubkey,
+    pub maker_receive_account: Pubkey,
+    pub offered_amount: u64,
+    pub requested_amount: u64,
+    /// The maker's order nonce when signing; `CancelAllOrders` moves past it
+    pub nonce: u64,
+    /// Last slot the order can be filled in
+    pub expires_at: u64,
+    /// The only taker who can fill the order, or `Pubkey::default()` for anyone
+    pub taker: Pubkey,
+}
+
+impl Order {
+    pub const LEN: usize = 32 * 3 + 8 * 4 + 32;
+
+    pub fn unpack(src: &[u8]) -> Result<Self, ProgramError> {
+        let src: &[u8; Order::LEN] = src
+            .try_into()
+            .map_err(|_| EscrowError::InvalidInstruction)?;
+        let (
+            maker,
+            maker_send_account,
+            maker_receive_account,
+            offered_amount,
+            requested_amount,
+            nonce,
+            expires_at,
+            taker,
+        ) = array_refs![src, 32, 32, 32, 8, 8, 8, 8, 32];
+        Ok(Order {
+            maker: Pubkey::new_from_array(*maker),
+            maker_send_account: Pubkey::new_from_array(*maker_send_account),
+            maker_receive_account: Pubkey::new_from_array(*maker_receive_account),
+            offered_amount: u64::from_le_bytes(*offered_amount),
+            requested_amount: u64::from_le_bytes(*requested_amount),
+            nonce: u64::from_le_bytes(*nonce),
+            expires_at: u64::from_le_bytes(*expires_at),
+            taker: Pubkey::new_from_array(*taker),
+        })
+    }
+
+    pub fn pack(&self) -> [u8; Order::LEN] {
+        let mut buf = [0; Order::LEN];
+        let dst = array_mut_ref![buf, 0, Order::LEN];
+        let (
+            maker_dst,
+            maker_send_account_dst,
+            maker_receive_account_dst,
+            offered_amount_dst,
+            requested_amount_dst,
+            nonce_dst,
+            expires_at_dst,
+            taker_dst,
+        ) = mut_array_refs![dst, 32, 32, 32, 8, 8, 8, 8, 32];
+        maker_dst.copy_from_slice(self.maker.as_ref());
+        maker_send_account_dst.copy_from_slice(self.maker_send_account.as_ref());
+        maker_receive_account_dst.copy_from_slice(self.maker_receive_account.as_ref());
+        *offered_amount_dst = self.offered_amount.to_le_bytes();
+        *requested_amount_dst = self.requested_amount.to_le_bytes();
+        *nonce_dst = self.nonce.to_le_bytes();
+        *expires_at_dst = self.expires_at.to_le_bytes();
+        taker_dst.copy_from_slice(self.taker.as_ref());
+        buf
+    }
+
+    /// The bytes the maker signs. Including the program id keeps an order
+    /// from being filled by another deployment of the program.
+    pub fn message(&self, program_id: &Pubkey) -> Vec<u8> {
+        [ORDER_DOMAIN, program_id.as_ref(), &self.pack()].concat()
+    }
+
+    /// Identifies the order; its receipt lives at `["order", hash]`
+    pub fn hash(&self, program_id: &Pubkey) -> [u8; 32] {
+        hash::hash(&self.message(program_id)).to_bytes()
+    }
+}
+
+/// An ed25519 program instruction checking `signature` of `message` by
+/// `pubkey`, with all three inline, the way [`verify_ed25519`] expects it
+pub fn ed25519_instruction(pubkey: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    let public_key_offset = OFFSETS_START + OFFSETS_LEN;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+
+    let mut data = Vec::with_capacity(message_offset + message.len());
+    data.extend_from_slice(&[1, 0]);
+    for field in [
+        signature_offset as u16,
+        THIS_INSTRUCTION,
+        public_key_offset as u16,
+        THIS_INSTRUCTION,
+        message_offset as u16,
+        message.len() as u16,
+        THIS_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(pubkey.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Checks that `ix` makes the ed25519 program verify a single signature by
+/// `pubkey` over exactly `message`. The runtime rejects the transaction if
+/// the signature itself is wrong, so only what was verified is left to check.
+pub fn verify_ed25519(
+    ix: &Instruction,
+    pubkey: &Pubkey,
+    message: &[u8],
+) -> Result<(), ProgramError> {
+    let invalid = || ProgramError::from(EscrowError::InvalidOrderSignature);
+    if ix.program_id != ed25519_program::id() || ix.data.first() != Some(&1) {
+        return Err(invalid());
+    }
+    let offsets = ix
+        .data
+        .get(OFFSETS_START..OFFSETS_START + OFFSETS_LEN)
+        .ok_or_else(invalid)?;
+    let offsets = array_ref![offsets, 0, OFFSETS_LEN];
+    let field = |i: usize| u16::from_le_bytes([offsets[2 * i], offsets[2 * i + 1]]);
+    let (public_key_offset, message_offset, message_len) =
+        (field(2) as usize, field(4) as usize, field(5) as usize);
+    // the signature, key and message must all come from the same
+    // instruction, or it could verify a key and message other than these
+    if [field(1), field(3), field(6)] != [THIS_INSTRUCTION; 3] {
+        return Err(invalid());
+    }
+
+    let signed_key = ix
+        .data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or_else(invalid)?;
+    let signed_message = ix
+        .data
+        .get(message_offset..message_offset + message_len)
+        .ok_or_else(invalid)?;
+    if signed_key != pubkey.as_ref() || signed_message != message {
+        return Err(invalid());
+    }
+    Ok(())
+}
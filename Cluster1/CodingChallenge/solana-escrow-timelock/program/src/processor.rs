@@ -1,18 +1,50 @@
+use std::collections::BTreeMap;
+
 use solana_program::{
-    account_info::{next_account_info, AccountInfo},
+    account_info::AccountInfo,
     clock::Clock,
     entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
     msg,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
-    program_pack::{IsInitialized, Pack},
+    program_pack::Pack,
     pubkey::Pubkey,
-    sysvar::{rent::Rent, Sysvar},
+    system_instruction,
+    sysvar::{instructions, rent::Rent, Sysvar},
 };
 
 use spl_token::state::Account as TokenAccount;
 
-use crate::{error::EscrowError, instruction::EscrowInstruction, state::Escrow};
+use native_accounts::check;
+
+use crate::{
+    accounts::{
+        ApproveMilestoneAccounts, CallbackAccounts, CancelAccounts, CancelAllOrdersAccounts,
+        ClaimAccounts, DepositPoolAccounts, EscrowToTakeAccounts, ExchangeAccounts,
+        ExchangeManyAccounts, ExtendLockAccounts, FillPoolAccounts, FillSignedOrderAccounts,
+        InitArbitratedAccounts, InitDeadMansSwitchAccounts, InitDelegatedEscrowAccounts,
+        InitEscrowAccounts, InitHashLockAccounts, InitMilestonesAccounts, InitPoolAccounts,
+        InitTokenBondAccounts, LamportBondAccounts, LockAccounts, MarkDeliveredAccounts,
+        MigrateAccounts, OpenDisputeAccounts, OracleAccounts, RedeemAccounts,
+        RefundUnapprovedAccounts, ReleaseAccounts, ReserveAccounts, ResetTimeLockAccounts,
+        ResolveAccounts, SetAllowListAccounts, SetCallbackAccounts, SetExclusiveTakerAccounts,
+        SetPremiumAccounts, SetPriceConditionAccounts, SettleAccounts, SettleRingAccounts,
+        TokenBondAccounts, UnlockAccounts, WithdrawPoolAccounts,
+    },
+    callback::{Settlement, SettlementKind},
+    error::EscrowError,
+    event::{EscrowEvent, EventClock},
+    instruction::{self, BondTerms, EscrowInstruction, MilestoneTerms},
+    loader, merkle,
+    oracle::{OracleKind, PriceCondition},
+    order::{self, Order},
+    state::{
+        AccountType, DealState, DefaultOutcome, Escrow, EscrowMode, HashKind, Milestone,
+        MilestoneState, OrderNonce, Pool, PoolShare, DISPUTE_GRACE_SLOTS, ESCROW_VERSION,
+        MAX_MILESTONES, ORDER_RECEIPT_LEN,
+    },
+};
 
 pub struct Processor;
 impl Processor {
@@ -24,141 +56,3025 @@ impl Processor {
         let instruction = EscrowInstruction::unpack(instruction_data)?;
 
         match instruction {
-            EscrowInstruction::InitEscrow { amount } => {
+            EscrowInstruction::InitEscrow {
+                amount,
+                unlock_slots,
+                time_out_slots,
+                bond,
+            } => {
                 msg!("Instruction: InitEscrow");
-                Self::process_init_escrow(accounts, amount, program_id)
+                Self::process_init_escrow(
+                    accounts,
+                    amount,
+                    unlock_slots,
+                    time_out_slots,
+                    bond,
+                    program_id,
+                )
             }
-            EscrowInstruction::Exchange { amount } => {
+            EscrowInstruction::Exchange { amount, proof } => {
                 msg!("Instruction: Exchange");
-                Self::process_exchange(accounts, amount, program_id)
+                Self::process_exchange(accounts, amount, &proof, program_id)
             }
-            EscrowInstruction::ResetTimeLock {} => {
+            EscrowInstruction::ResetTimeLock {
+                unlock_slots,
+                time_out_slots,
+            } => {
                 msg!("Instruction: ResetTimeLock");
-                Self::process_reset_time_lock(accounts, program_id)
+                Self::process_reset_time_lock(accounts, unlock_slots, time_out_slots, program_id)
             }
             EscrowInstruction::Cancel {} => {
                 msg!("Instruction: Cancel");
                 Self::process_cancel(accounts, program_id)
             }
+            EscrowInstruction::Migrate {} => {
+                msg!("Instruction: Migrate");
+                Self::process_migrate(accounts, program_id)
+            }
+            EscrowInstruction::InitHashLock {
+                hash_kind,
+                hash,
+                counterparty,
+                unlock_slots,
+                time_out_slots,
+            } => {
+                msg!("Instruction: InitHashLock");
+                Self::process_init_hash_lock(
+                    accounts,
+                    hash_kind,
+                    hash,
+                    counterparty,
+                    unlock_slots,
+                    time_out_slots,
+                    program_id,
+                )
+            }
+            EscrowInstruction::Redeem { preimage } => {
+                msg!("Instruction: Redeem");
+                Self::process_redeem(accounts, preimage, program_id)
+            }
+            EscrowInstruction::InitDeadMansSwitch {
+                beneficiary,
+                time_out_slots,
+            } => {
+                msg!("Instruction: InitDeadMansSwitch");
+                Self::process_init_dead_mans_switch(
+                    accounts,
+                    beneficiary,
+                    time_out_slots,
+                    program_id,
+                )
+            }
+            EscrowInstruction::Claim {} => {
+                msg!("Instruction: Claim");
+                Self::process_claim(accounts, program_id)
+            }
+            EscrowInstruction::Lock { unlock_slots } => {
+                msg!("Instruction: Lock");
+                Self::process_lock(accounts, unlock_slots, program_id)
+            }
+            EscrowInstruction::Unlock {} => {
+                msg!("Instruction: Unlock");
+                Self::process_unlock(accounts, program_id)
+            }
+            EscrowInstruction::ExtendLock { extra_slots } => {
+                msg!("Instruction: ExtendLock");
+                Self::process_extend_lock(accounts, extra_slots, program_id)
+            }
+            EscrowInstruction::InitArbitrated {
+                taker,
+                arbiter,
+                default_outcome,
+                time_out_slots,
+            } => {
+                msg!("Instruction: InitArbitrated");
+                Self::process_init_arbitrated(
+                    accounts,
+                    taker,
+                    arbiter,
+                    default_outcome,
+                    time_out_slots,
+                    program_id,
+                )
+            }
+            EscrowInstruction::MarkDelivered {} => {
+                msg!("Instruction: MarkDelivered");
+                Self::process_mark_delivered(accounts, program_id)
+            }
+            EscrowInstruction::OpenDispute {} => {
+                msg!("Instruction: OpenDispute");
+                Self::process_open_dispute(accounts, program_id)
+            }
+            EscrowInstruction::Release {} => {
+                msg!("Instruction: Release");
+                Self::process_release(accounts, program_id)
+            }
+            EscrowInstruction::Resolve { taker_bps } => {
+                msg!("Instruction: Resolve");
+                Self::process_resolve(accounts, taker_bps, program_id)
+            }
+            EscrowInstruction::Settle {} => {
+                msg!("Instruction: Settle");
+                Self::process_settle(accounts, program_id)
+            }
+            EscrowInstruction::InitMilestones { payee, milestones } => {
+                msg!("Instruction: InitMilestones");
+                Self::process_init_milestones(accounts, payee, &milestones, program_id)
+            }
+            EscrowInstruction::ApproveMilestone { index } => {
+                msg!("Instruction: ApproveMilestone");
+                Self::process_approve_milestone(accounts, index, program_id)
+            }
+            EscrowInstruction::RefundUnapproved {} => {
+                msg!("Instruction: RefundUnapproved");
+                Self::process_refund_unapproved(accounts, program_id)
+            }
+            EscrowInstruction::SetPremium { premium } => {
+                msg!("Instruction: SetPremium");
+                Self::process_set_premium(accounts, premium, program_id)
+            }
+            EscrowInstruction::Reserve { premium, proof } => {
+                msg!("Instruction: Reserve");
+                Self::process_reserve(accounts, premium, &proof, program_id)
+            }
+            EscrowInstruction::FillSignedOrder { order } => {
+                msg!("Instruction: FillSignedOrder");
+                Self::process_fill_signed_order(accounts, &order, program_id)
+            }
+            EscrowInstruction::CancelAllOrders {} => {
+                msg!("Instruction: CancelAllOrders");
+                Self::process_cancel_all_orders(accounts, program_id)
+            }
+            EscrowInstruction::InitDelegatedEscrow {
+                offered_amount,
+                amount,
+                unlock_slots,
+                time_out_slots,
+                bond,
+            } => {
+                msg!("Instruction: InitDelegatedEscrow");
+                Self::process_init_delegated_escrow(
+                    accounts,
+                    offered_amount,
+                    amount,
+                    unlock_slots,
+                    time_out_slots,
+                    bond,
+                    program_id,
+                )
+            }
+            EscrowInstruction::ExchangeMany {
+                max_total,
+                skip_unfillable,
+            } => {
+                msg!("Instruction: ExchangeMany");
+                Self::process_exchange_many(accounts, max_total, skip_unfillable, program_id)
+            }
+            EscrowInstruction::SettleRing {} => {
+                msg!("Instruction: SettleRing");
+                Self::process_settle_ring(accounts, program_id)
+            }
+            EscrowInstruction::InitPool {
+                offered_per_lot,
+                requested_per_lot,
+                unlock_slots,
+                time_out_slots,
+            } => {
+                msg!("Instruction: InitPool");
+                Self::process_init_pool(
+                    accounts,
+                    offered_per_lot,
+                    requested_per_lot,
+                    unlock_slots,
+                    time_out_slots,
+                    program_id,
+                )
+            }
+            EscrowInstruction::DepositPool { amount } => {
+                msg!("Instruction: DepositPool");
+                Self::process_deposit_pool(accounts, amount, program_id)
+            }
+            EscrowInstruction::FillPool { lots } => {
+                msg!("Instruction: FillPool");
+                Self::process_fill_pool(accounts, lots, program_id)
+            }
+            EscrowInstruction::WithdrawPool {} => {
+                msg!("Instruction: WithdrawPool");
+                Self::process_withdraw_pool(accounts, program_id)
+            }
+            EscrowInstruction::SetExclusiveTaker {
+                taker,
+                exclusive_slots,
+            } => {
+                msg!("Instruction: SetExclusiveTaker");
+                Self::process_set_exclusive_taker(accounts, &taker, exclusive_slots, program_id)
+            }
+            EscrowInstruction::SetAllowList { root } => {
+                msg!("Instruction: SetAllowList");
+                Self::process_set_allow_list(accounts, &root, program_id)
+            }
+            EscrowInstruction::SetPriceCondition {
+                oracle_kind,
+                condition,
+                trigger_price,
+                trigger_expo,
+                max_staleness,
+            } => {
+                msg!("Instruction: SetPriceCondition");
+                Self::process_set_price_condition(
+                    accounts,
+                    oracle_kind,
+                    condition,
+                    trigger_price,
+                    trigger_expo,
+                    max_staleness,
+                    program_id,
+                )
+            }
+            EscrowInstruction::SetCallback { callback_program } => {
+                msg!("Instruction: SetCallback");
+                Self::process_set_callback(accounts, &callback_program, program_id)
+            }
+        }
+    }
+
+    fn process_init_escrow(
+        accounts: &[AccountInfo],
+        amount: u64,
+        unlock_slots: u64,
+        time_out_slots: u64,
+        bond: Option<BondTerms>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let InitEscrowAccounts {
+            initializer,
+            temp_token_account,
+            token_to_receive_account,
+            escrow_account,
+            rent,
+            token_program,
+            remaining,
+            ..
+        } = InitEscrowAccounts::parse(program_id, accounts)?;
+        let rent = &Rent::from_account_info(rent)?;
+
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let mut escrow_info: Escrow = loader::load_uninitialized(escrow_account, program_id)?;
+
+        escrow_info.is_initialized = true;
+        escrow_info.initializer_pubkey = *initializer.key;
+        escrow_info.temp_token_account_pubkey = *temp_token_account.key;
+        escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key;
+        escrow_info.expected_amount = amount;
+        let clock = Clock::get()?;
+        (escrow_info.unlock_time, escrow_info.time_out) =
+            Self::lock_window(clock.slot, unlock_slots, time_out_slots)?;
+
+        let offered = TokenAccount::unpack(&temp_token_account.try_borrow_data()?)?;
+        let requested = TokenAccount::unpack(&token_to_receive_account.try_borrow_data()?)?;
+        let event = EscrowEvent::Created {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            offered_mint: offered.mint,
+            offered_amount: offered.amount,
+            requested_mint: requested.mint,
+            requested_amount: amount,
+            unlock_time: escrow_info.unlock_time,
+            time_out: escrow_info.time_out,
+            clock: EventClock::from(&clock),
+        };
+
+        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        escrow_info.pda_bump = nonce;
+        let bond_event = Self::take_bond(
+            &mut escrow_info,
+            bond,
+            initializer,
+            escrow_account,
+            remaining,
+            rent,
+            &pda,
+            &clock,
+        )?;
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        Self::hand_vault_to_pda(initializer, temp_token_account, token_program, &pda)?;
+
+        event.emit();
+        if let Some(bond_event) = bond_event {
+            bond_event.emit();
+        }
+        Ok(())
+    }
+
+    fn process_init_delegated_escrow(
+        accounts: &[AccountInfo],
+        offered_amount: u64,
+        amount: u64,
+        unlock_slots: u64,
+        time_out_slots: u64,
+        bond: Option<BondTerms>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let InitDelegatedEscrowAccounts {
+            initializer,
+            initializers_sending_token_account,
+            token_to_receive_account,
+            escrow_account,
+            rent,
+            remaining,
+            ..
+        } = InitDelegatedEscrowAccounts::parse(program_id, accounts)?;
+        let rent = &Rent::from_account_info(rent)?;
+
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        // a delegated amount of 0 means the escrow has a vault
+        if offered_amount == 0 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+
+        let mut escrow_info: Escrow = loader::load_uninitialized(escrow_account, program_id)?;
+        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let offered = TokenAccount::unpack(&initializers_sending_token_account.try_borrow_data()?)?;
+        Self::check_delegation(&offered, &pda, offered_amount)?;
+
+        escrow_info.is_initialized = true;
+        escrow_info.initializer_pubkey = *initializer.key;
+        escrow_info.temp_token_account_pubkey = *initializers_sending_token_account.key;
+        escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key;
+        escrow_info.expected_amount = amount;
+        escrow_info.delegated_amount = offered_amount;
+        escrow_info.pda_bump = nonce;
+        let clock = Clock::get()?;
+        (escrow_info.unlock_time, escrow_info.time_out) =
+            Self::lock_window(clock.slot, unlock_slots, time_out_slots)?;
+
+        let requested = TokenAccount::unpack(&token_to_receive_account.try_borrow_data()?)?;
+        let event = EscrowEvent::Created {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            offered_mint: offered.mint,
+            offered_amount,
+            requested_mint: requested.mint,
+            requested_amount: amount,
+            unlock_time: escrow_info.unlock_time,
+            time_out: escrow_info.time_out,
+            clock: EventClock::from(&clock),
+        };
+        let bond_event = Self::take_bond(
+            &mut escrow_info,
+            bond,
+            initializer,
+            escrow_account,
+            remaining,
+            rent,
+            &pda,
+            &clock,
+        )?;
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        event.emit();
+        if let Some(bond_event) = bond_event {
+            bond_event.emit();
+        }
+        Ok(())
+    }
+
+    fn process_init_hash_lock(
+        accounts: &[AccountInfo],
+        hash_kind: HashKind,
+        hash: [u8; 32],
+        counterparty: Pubkey,
+        unlock_slots: u64,
+        time_out_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let InitHashLockAccounts {
+            initializer,
+            temp_token_account,
+            escrow_account,
+            rent,
+            token_program,
+            ..
+        } = InitHashLockAccounts::parse(program_id, accounts)?;
+        let rent = &Rent::from_account_info(rent)?;
+
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let mut escrow_info: Escrow = loader::load_uninitialized(escrow_account, program_id)?;
+
+        escrow_info.is_initialized = true;
+        escrow_info.initializer_pubkey = *initializer.key;
+        escrow_info.temp_token_account_pubkey = *temp_token_account.key;
+        escrow_info.mode = EscrowMode::HashLock;
+        escrow_info.hash_kind = hash_kind;
+        escrow_info.hash = hash;
+        escrow_info.counterparty = counterparty;
+        let clock = Clock::get()?;
+        (escrow_info.unlock_time, escrow_info.time_out) =
+            Self::lock_window(clock.slot, unlock_slots, time_out_slots)?;
+
+        let offered = TokenAccount::unpack(&temp_token_account.try_borrow_data()?)?;
+        let event = EscrowEvent::HashLocked {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            counterparty,
+            offered_mint: offered.mint,
+            offered_amount: offered.amount,
+            hash_kind,
+            hash,
+            unlock_time: escrow_info.unlock_time,
+            time_out: escrow_info.time_out,
+            clock: EventClock::from(&clock),
+        };
+
+        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        escrow_info.pda_bump = nonce;
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        Self::hand_vault_to_pda(initializer, temp_token_account, token_program, &pda)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_init_dead_mans_switch(
+        accounts: &[AccountInfo],
+        beneficiary: Pubkey,
+        time_out_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let InitDeadMansSwitchAccounts {
+            initializer,
+            temp_token_account,
+            escrow_account,
+            rent,
+            token_program,
+            ..
+        } = InitDeadMansSwitchAccounts::parse(program_id, accounts)?;
+        let rent = &Rent::from_account_info(rent)?;
+
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let mut escrow_info: Escrow = loader::load_uninitialized(escrow_account, program_id)?;
+
+        escrow_info.is_initialized = true;
+        escrow_info.initializer_pubkey = *initializer.key;
+        escrow_info.temp_token_account_pubkey = *temp_token_account.key;
+        escrow_info.mode = EscrowMode::DeadMansSwitch;
+        escrow_info.counterparty = beneficiary;
+        let clock = Clock::get()?;
+        (escrow_info.unlock_time, escrow_info.time_out) =
+            Self::lock_window(clock.slot, 0, time_out_slots)?;
+
+        let offered = TokenAccount::unpack(&temp_token_account.try_borrow_data()?)?;
+        let event = EscrowEvent::SwitchArmed {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            beneficiary,
+            offered_mint: offered.mint,
+            offered_amount: offered.amount,
+            time_out: escrow_info.time_out,
+            clock: EventClock::from(&clock),
+        };
+
+        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        escrow_info.pda_bump = nonce;
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        Self::hand_vault_to_pda(initializer, temp_token_account, token_program, &pda)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_lock(
+        accounts: &[AccountInfo],
+        unlock_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let LockAccounts {
+            initializer,
+            temp_token_account,
+            escrow_account,
+            rent,
+            token_program,
+            ..
+        } = LockAccounts::parse(program_id, accounts)?;
+        let rent = &Rent::from_account_info(rent)?;
+
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let mut escrow_info: Escrow = loader::load_uninitialized(escrow_account, program_id)?;
+
+        escrow_info.is_initialized = true;
+        escrow_info.initializer_pubkey = *initializer.key;
+        escrow_info.temp_token_account_pubkey = *temp_token_account.key;
+        escrow_info.mode = EscrowMode::SavingsLock;
+        let clock = Clock::get()?;
+        escrow_info.unlock_time = clock
+            .slot
+            .checked_add(unlock_slots)
+            .ok_or(EscrowError::AmountOverflow)?;
+        // a savings lock never times out
+        escrow_info.time_out = u64::MAX;
+
+        let offered = TokenAccount::unpack(&temp_token_account.try_borrow_data()?)?;
+        let event = EscrowEvent::Locked {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            offered_mint: offered.mint,
+            offered_amount: offered.amount,
+            unlock_time: escrow_info.unlock_time,
+            clock: EventClock::from(&clock),
+        };
+
+        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        escrow_info.pda_bump = nonce;
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        Self::hand_vault_to_pda(initializer, temp_token_account, token_program, &pda)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_init_arbitrated(
+        accounts: &[AccountInfo],
+        taker: Pubkey,
+        arbiter: Pubkey,
+        default_outcome: DefaultOutcome,
+        time_out_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let InitArbitratedAccounts {
+            initializer,
+            temp_token_account,
+            escrow_account,
+            rent,
+            token_program,
+            ..
+        } = InitArbitratedAccounts::parse(program_id, accounts)?;
+        let rent = &Rent::from_account_info(rent)?;
+
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let mut escrow_info: Escrow = loader::load_uninitialized(escrow_account, program_id)?;
+
+        escrow_info.is_initialized = true;
+        escrow_info.initializer_pubkey = *initializer.key;
+        escrow_info.temp_token_account_pubkey = *temp_token_account.key;
+        escrow_info.mode = EscrowMode::Arbitrated;
+        escrow_info.counterparty = taker;
+        escrow_info.arbiter = arbiter;
+        escrow_info.default_outcome = default_outcome;
+        let clock = Clock::get()?;
+        (escrow_info.unlock_time, escrow_info.time_out) =
+            Self::lock_window(clock.slot, 0, time_out_slots)?;
+
+        let offered = TokenAccount::unpack(&temp_token_account.try_borrow_data()?)?;
+        let event = EscrowEvent::Arbitrated {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            taker,
+            arbiter,
+            offered_mint: offered.mint,
+            offered_amount: offered.amount,
+            default_outcome,
+            time_out: escrow_info.time_out,
+            clock: EventClock::from(&clock),
+        };
+
+        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        escrow_info.pda_bump = nonce;
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        Self::hand_vault_to_pda(initializer, temp_token_account, token_program, &pda)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_exchange(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        proof: &[[u8; 32]],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let ExchangeAccounts {
+            taker,
+            takers_sending_token_account,
+            takers_token_to_receive_account,
+            pdas_temp_token_account,
+            initializers_main_account,
+            initializers_token_to_receive_account,
+            escrow_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            remaining,
+        } = ExchangeAccounts::parse(program_id, accounts)?;
+        let pdas_temp_token_account_info =
+            TokenAccount::unpack(&pdas_temp_token_account.try_borrow_data()?)?;
+        let (pda, nonce) = (*pda_account.key, bumps.get("pda")?);
+
+        let escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        let clock = Clock::get()?;
+        let offered_amount = Self::check_takeable(
+            &escrow_info,
+            taker.key,
+            pdas_temp_token_account,
+            &pdas_temp_token_account_info,
+            initializers_main_account,
+            initializers_token_to_receive_account,
+            &pda,
+            Some(amount_expected_by_taker),
+            proof,
+            &clock,
+        )?;
+        Self::check_price_condition(&escrow_info, remaining, program_id, &clock)?;
+
+        let oracle_accounts = escrow_info.has_price_condition() as usize;
+        Self::settle_swap(
+            &escrow_info,
+            offered_amount,
+            &pdas_temp_token_account_info.mint,
+            taker,
+            takers_sending_token_account,
+            takers_token_to_receive_account,
+            pdas_temp_token_account,
+            initializers_main_account,
+            initializers_token_to_receive_account,
+            escrow_account,
+            token_program,
+            pda_account,
+            nonce,
+            remaining,
+            remaining
+                .get(Self::bond_account_count(&escrow_info, false) + oracle_accounts..)
+                .unwrap_or(&[]),
+            program_id,
+            &clock,
+        )
+    }
+
+    fn process_exchange_many(
+        accounts: &[AccountInfo],
+        max_total: u64,
+        skip_unfillable: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let ExchangeManyAccounts {
+            taker,
+            takers_sending_token_account,
+            takers_token_to_receive_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            remaining,
+        } = ExchangeManyAccounts::parse(program_id, accounts)?;
+        let (pda, nonce) = (*pda_account.key, bumps.get("pda")?);
+        let sending_mint =
+            TokenAccount::unpack(&takers_sending_token_account.try_borrow_data()?)?.mint;
+        let receiving_mint =
+            TokenAccount::unpack(&takers_token_to_receive_account.try_borrow_data()?)?.mint;
+        let clock = Clock::get()?;
+
+        let mut takeable: Vec<(EscrowToTakeAccounts, Escrow, TokenAccount, u64)> = Vec::new();
+        let mut rest = remaining;
+        while !rest.is_empty() {
+            let group = EscrowToTakeAccounts::parse(program_id, rest)?;
+            rest = group.remaining;
+            let checked = (|| {
+                // settling the same escrow twice would pay the taker twice
+                if takeable
+                    .iter()
+                    .any(|(taken, ..)| taken.escrow_account.key == group.escrow_account.key)
+                {
+                    return Err(EscrowError::InvalidInstruction.into());
+                }
+                let escrow_info: Escrow = loader::load(group.escrow_account, program_id, true)?;
+                Self::check_sweepable(&escrow_info)?;
+                let vault_info = TokenAccount::unpack(&group.vault.try_borrow_data()?)?;
+                let offered_amount = Self::check_takeable(
+                    &escrow_info,
+                    taker.key,
+                    group.vault,
+                    &vault_info,
+                    group.initializers_main_account,
+                    group.initializers_token_to_receive_account,
+                    &pda,
+                    None,
+                    &[],
+                    &clock,
+                )?;
+                check::mint("vault", group.vault, &receiving_mint)?;
+                check::mint(
+                    "initializers_token_to_receive_account",
+                    group.initializers_token_to_receive_account,
+                    &sending_mint,
+                )?;
+                Ok::<_, ProgramError>((escrow_info, vault_info, offered_amount))
+            })();
+            match checked {
+                Ok((escrow_info, vault_info, offered_amount)) => {
+                    takeable.push((group, escrow_info, vault_info, offered_amount))
+                }
+                Err(err) if skip_unfillable => {
+                    msg!("Skipping escrow {}: {}", group.escrow_account.key, err)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        // cheapest first: the least asked per token offered
+        takeable.sort_by(|(_, a, _, a_offered), (_, b, _, b_offered)| {
+            (a.expected_amount as u128 * *b_offered as u128)
+                .cmp(&(b.expected_amount as u128 * *a_offered as u128))
+        });
+
+        let mut total = 0u64;
+        let mut filled = 0;
+        // delegated escrows can share a source account, whose allowance and
+        // balance every fill from it uses up
+        let mut drawn: BTreeMap<Pubkey, u64> = BTreeMap::new();
+        for (group, escrow_info, vault_info, offered_amount) in &takeable {
+            let new_total = total
+                .checked_add(escrow_info.expected_amount)
+                .ok_or(EscrowError::AmountOverflow)?;
+            if new_total > max_total {
+                if skip_unfillable {
+                    msg!(
+                        "Skipping escrow {}: over the price limit",
+                        group.escrow_account.key
+                    );
+                    continue;
+                }
+                return Err(EscrowError::PriceLimitExceeded.into());
+            }
+            if escrow_info.is_delegated() {
+                let source_drawn = drawn
+                    .get(group.vault.key)
+                    .copied()
+                    .unwrap_or(0)
+                    .checked_add(*offered_amount)
+                    .ok_or(EscrowError::AmountOverflow)?;
+                match Self::check_delegation(vault_info, &pda, source_drawn) {
+                    Ok(()) => {
+                        drawn.insert(*group.vault.key, source_drawn);
+                    }
+                    Err(err) if skip_unfillable => {
+                        msg!("Skipping escrow {}: {}", group.escrow_account.key, err);
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            total = new_total;
+            Self::settle_swap(
+                escrow_info,
+                *offered_amount,
+                &receiving_mint,
+                taker,
+                takers_sending_token_account,
+                takers_token_to_receive_account,
+                group.vault,
+                group.initializers_main_account,
+                group.initializers_token_to_receive_account,
+                group.escrow_account,
+                token_program,
+                pda_account,
+                nonce,
+                &[],
+                &[],
+                program_id,
+                &clock,
+            )?;
+            filled += 1;
+        }
+        if filled == 0 {
+            return Err(EscrowError::NothingFilled.into());
+        }
+        msg!("Took {} escrows for {} in total", filled, total);
+        Ok(())
+    }
+
+    fn process_settle_ring(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let SettleRingAccounts {
+            settler,
+            token_program,
+            pda: pda_account,
+            bumps,
+            remaining,
+        } = SettleRingAccounts::parse(program_id, accounts)?;
+        let (pda, nonce) = (*pda_account.key, bumps.get("pda")?);
+        let clock = Clock::get()?;
+
+        let mut ring: Vec<(EscrowToTakeAccounts, Escrow, TokenAccount, u64)> = Vec::new();
+        let mut rest = remaining;
+        while !rest.is_empty() {
+            let group = EscrowToTakeAccounts::parse(program_id, rest)?;
+            rest = group.remaining;
+            // settling the same escrow twice would pay out its vault twice
+            if ring
+                .iter()
+                .any(|(other, ..)| other.escrow_account.key == group.escrow_account.key)
+            {
+                return Err(EscrowError::InvalidInstruction.into());
+            }
+            let escrow_info: Escrow = loader::load(group.escrow_account, program_id, true)?;
+            Self::check_sweepable(&escrow_info)?;
+            let vault_info = TokenAccount::unpack(&group.vault.try_borrow_data()?)?;
+            // a reserved swap only joins a ring its reserving taker settles
+            let offered_amount = Self::check_takeable(
+                &escrow_info,
+                settler.key,
+                group.vault,
+                &vault_info,
+                group.initializers_main_account,
+                group.initializers_token_to_receive_account,
+                &pda,
+                None,
+                &[],
+                &clock,
+            )?;
+            ring.push((group, escrow_info, vault_info, offered_amount));
+        }
+        if ring.len() < 2 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+
+        // every maker is paid by the next one, and the last by the first
+        for (i, (group, escrow_info, ..)) in ring.iter().enumerate() {
+            let (_, _, next_vault, next_offered) = &ring[(i + 1) % ring.len()];
+            let requested_mint = TokenAccount::unpack(
+                &group
+                    .initializers_token_to_receive_account
+                    .try_borrow_data()?,
+            )?
+            .mint;
+            if next_vault.mint != requested_mint || *next_offered < escrow_info.expected_amount {
+                msg!(
+                    "Escrow {} is not paid by the next one in the ring",
+                    group.escrow_account.key
+                );
+                return Err(EscrowError::RingMismatch.into());
+            }
+        }
+
+        // each maker gives what they offered, which may be more than the
+        // previous maker asked for
+        for (i, (group, ..)) in ring.iter().enumerate() {
+            let (next, _, _, next_offered) = &ring[(i + 1) % ring.len()];
+            msg!("Calling the token program to transfer tokens to the escrow's initializer...");
+            Self::pay_from_vault(
+                next.vault,
+                group.initializers_token_to_receive_account,
+                *next_offered,
+                token_program,
+                pda_account,
+                nonce,
+            )?;
+        }
+
+        for (i, (group, escrow_info, vault_info, offered_amount)) in ring.iter().enumerate() {
+            let (previous, ..) = &ring[(i + ring.len() - 1) % ring.len()];
+            Self::close_swap(
+                escrow_info,
+                group.vault,
+                group.initializers_main_account,
+                group.escrow_account,
+                token_program,
+                pda_account,
+                nonce,
+                &[],
+                &clock,
+            )?;
+            EscrowEvent::Exchanged {
+                escrow: *group.escrow_account.key,
+                maker: escrow_info.initializer_pubkey,
+                taker: *previous.initializers_main_account.key,
+                offered_mint: vault_info.mint,
+                offered_amount: *offered_amount,
+                requested_mint: ring[(i + 1) % ring.len()].2.mint,
+                requested_amount: escrow_info.expected_amount,
+                clock: EventClock::from(&clock),
+            }
+            .emit();
+        }
+        msg!("Settled a ring of {} escrows", ring.len());
+        Ok(())
+    }
+
+    fn process_cancel(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let CancelAccounts {
+            initializer,
+            pda_temp_token_account,
+            initializer_sent_token_account,
+            escrow_account,
+            token_program,
+            pda: pda_account_info,
+            bumps,
+            remaining,
+        } = CancelAccounts::parse(program_id, accounts)?;
+
+        let escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+
+        //check if initializer pubkey is equal to the one that submitted the transaction
+        check::address("initializer", initializer, &escrow_info.initializer_pubkey)?;
+        check::address(
+            "pda_temp_token_account",
+            pda_temp_token_account,
+            &escrow_info.temp_token_account_pubkey,
+        )?;
+
+        // a savings lock only opens through Unlock, once it unlocks, an
+        // arbitrated deal only pays out through Release, Resolve or Settle,
+        // and a milestone escrow one tranche at a time
+        if matches!(
+            escrow_info.mode,
+            EscrowMode::SavingsLock | EscrowMode::Arbitrated | EscrowMode::Milestones
+        ) {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        // the counterparty may still redeem until then
+        let clock = Clock::get()?;
+        if escrow_info.mode == EscrowMode::HashLock && clock.slot <= escrow_info.time_out {
+            return Err(EscrowError::EscrowNotExpired.into());
+        }
+        // the taker paid for the right to exchange until then
+        if escrow_info.is_reserved(clock.slot) {
+            return Err(EscrowError::EscrowReserved.into());
+        }
+
+        let pda_token_account_info =
+            TokenAccount::unpack(&pda_temp_token_account.try_borrow_data()?)?;
+        let (pda, nonce) = (*pda_account_info.key, bumps.get("pda")?); //nonce is what you get back when you call this "find_PA", need for invoke_signed.
+
+        // a delegate-approval escrow never took the maker's tokens, so closing
+        // it is all there is to cancelling it
+        let offered_amount = if escrow_info.is_delegated() {
+            escrow_info.delegated_amount
+        } else {
+            check::authority("pda_temp_token_account", pda_temp_token_account, &pda)?;
+
+            //transfer tokens back to initializer
+            let transfer_to_initializer_ix = spl_token::instruction::transfer(
+                token_program.key,
+                pda_temp_token_account.key,
+                initializer_sent_token_account.key,
+                &pda,
+                &[&pda],
+                pda_token_account_info.amount,
+            )?;
+            msg!("Calling token program to transfer tokens back to initializer");
+            invoke_signed(
+                &transfer_to_initializer_ix,
+                &[
+                    pda_temp_token_account.clone(),
+                    initializer_sent_token_account.clone(),
+                    pda_account_info.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[nonce]]], //verifies PDA token account generated/PDA account sent it are correct.
+            )?;
+
+            //close the escrow account
+            let close_escrow_token_acct_ix = spl_token::instruction::close_account(
+                token_program.key, //include program ID anytime you're doing anything with `spl-token`
+                pda_temp_token_account.key,
+                initializer.key,
+                &pda,
+                &[&pda],
+            )?;
+
+            msg!("Calling token program to close escrow token account");
+            invoke_signed(
+                &close_escrow_token_acct_ix,
+                &[
+                    pda_temp_token_account.clone(),
+                    initializer.clone(),
+                    pda_account_info.clone(),
+                    token_program.clone(),
+                ],
+                &[&[&b"escrow"[..], &[nonce]]],
+            )?;
+            pda_token_account_info.amount
+        };
+
+        // backing out while a taker may be counting on the deal costs the bond
+        let forfeit = (escrow_info.unlock_time..=escrow_info.time_out).contains(&clock.slot);
+        let expired = clock.slot > escrow_info.time_out;
+        Self::call_back(
+            &escrow_info,
+            &Settlement {
+                kind: if expired {
+                    SettlementKind::Expired
+                } else {
+                    SettlementKind::Cancelled
+                },
+                escrow: *escrow_account.key,
+                maker: *initializer.key,
+                taker: Pubkey::default(),
+                offered_mint: pda_token_account_info.mint,
+                offered_amount,
+                requested_mint: Pubkey::default(),
+                requested_amount: escrow_info.expected_amount,
+                slot: clock.slot,
+                unix_timestamp: clock.unix_timestamp,
+            },
+            remaining
+                .get(Self::bond_account_count(&escrow_info, forfeit)..)
+                .unwrap_or(&[]),
+            escrow_account,
+            program_id,
+        )?;
+        let bond_event = Self::settle_bond(
+            &escrow_info,
+            forfeit,
+            remaining,
+            escrow_account,
+            initializer,
+            token_program,
+            pda_account_info,
+            nonce,
+            &clock,
+        )?;
+
+        msg!("Closing the escrow account...");
+        **initializer.try_borrow_mut_lamports()? = initializer
+            .lamports()
+            .checked_add(escrow_account.lamports())
+            .ok_or(EscrowError::AmountOverflow)?; //check that there is no overflow (u64)
+        **escrow_account.try_borrow_mut_lamports()? = 0;
+        *escrow_account.try_borrow_mut_data()? = &mut [];
+
+        if let Some(event) = bond_event {
+            event.emit();
+        }
+        if expired {
+            EscrowEvent::Expired {
+                escrow: *escrow_account.key,
+                maker: *initializer.key,
+                offered_mint: pda_token_account_info.mint,
+                offered_amount,
+                time_out: escrow_info.time_out,
+                clock: EventClock::from(&clock),
+            }
+            .emit();
+        } else {
+            EscrowEvent::Cancelled {
+                escrow: *escrow_account.key,
+                maker: *initializer.key,
+                offered_mint: pda_token_account_info.mint,
+                offered_amount,
+                clock: EventClock::from(&clock),
+            }
+            .emit();
+        }
+        Ok(())
+    }
+
+    /* Way of resetting rather than having to cancel once the escrow times out*/
+    //must be called by initiator
+    //load the escrow state
+    //get the clock slot
+    //set unlock_time to current_slot + unlock_slots
+    //set the stored time out to unlock_time + time_out_slots
+    fn process_reset_time_lock(
+        accounts: &[AccountInfo],
+        unlock_slots: u64,
+        time_out_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let ResetTimeLockAccounts {
+            initializer,
+            escrow_account,
+            ..
+        } = ResetTimeLockAccounts::parse(program_id, accounts)?;
+
+        let mut escrow_info = Self::load_current(escrow_account, program_id)?;
+
+        check::address("initializer", initializer, &escrow_info.initializer_pubkey)?;
+        // moving a hash lock's time-out could let the maker reclaim the vault
+        // after the counterparty has paid out on the other chain. Resetting a
+        // dead man's switch is how its maker keeps it from the beneficiary.
+        // Savings locks can only be extended, with ExtendLock, an arbitrated
+        // deal's time out is part of what the taker agreed to, and milestone
+        // escrows have a deadline per tranche instead.
+        if !matches!(
+            escrow_info.mode,
+            EscrowMode::Swap | EscrowMode::DeadMansSwitch
+        ) {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+
+        let clock = Clock::get()?;
+        // otherwise the maker could push the window out and cancel for free
+        if escrow_info.bond > 0
+            && (escrow_info.unlock_time..=escrow_info.time_out).contains(&clock.slot)
+        {
+            return Err(EscrowError::BondAtStake.into());
+        }
+        // a reservation's term is the time out the taker paid for; once it
+        // has passed, the reservation lapses with the old window
+        if escrow_info.is_reserved(clock.slot) {
+            return Err(EscrowError::EscrowReserved.into());
+        }
+        if escrow_info.mode == EscrowMode::Swap {
+            escrow_info.counterparty = Pubkey::default();
+        }
+        let exclusive_span = escrow_info
+            .exclusive_until
+            .saturating_sub(escrow_info.unlock_time);
+        (escrow_info.unlock_time, escrow_info.time_out) =
+            Self::lock_window(clock.slot, unlock_slots, time_out_slots)?;
+        // the designated taker keeps a period as long as before, from the new
+        // unlock
+        if escrow_info.designated_taker != Pubkey::default() {
+            escrow_info.exclusive_until = escrow_info
+                .unlock_time
+                .checked_add(exclusive_span)
+                .ok_or(EscrowError::AmountOverflow)?;
+            Self::check_exclusive_period(&escrow_info, escrow_info.exclusive_until)?;
+        }
+
+        let event = EscrowEvent::LockReset {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            unlock_time: escrow_info.unlock_time,
+            time_out: escrow_info.time_out,
+            clock: EventClock::from(&clock),
+        };
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    // Older escrows can still be taken and cancelled as they are, only writing
+    // to them needs the current layout. Migrating twice is a no-op.
+    fn process_migrate(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let MigrateAccounts {
+            initializer,
+            escrow_account,
+            system_program,
+            ..
+        } = MigrateAccounts::parse(program_id, accounts)?;
+
+        let mut escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+
+        check::address("initializer", initializer, &escrow_info.initializer_pubkey)?;
+
+        let version = Escrow::version(&escrow_account.try_borrow_data()?)?;
+        if version == ESCROW_VERSION {
+            msg!("Escrow is already at version {}", ESCROW_VERSION);
+            return Ok(());
+        }
+
+        msg!(
+            "Migrating escrow from version {} to {}",
+            version,
+            ESCROW_VERSION
+        );
+        escrow_info.pda_bump = Pubkey::find_program_address(&[b"escrow"], program_id).1;
+        Self::store_resized(escrow_info, escrow_account, initializer, system_program)
+    }
+
+    fn process_redeem(
+        accounts: &[AccountInfo],
+        preimage: Vec<u8>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let RedeemAccounts {
+            counterparty,
+            counterparty_token_account,
+            pdas_temp_token_account,
+            initializers_main_account,
+            escrow_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = RedeemAccounts::parse(program_id, accounts)?;
+        let nonce = bumps.get("pda")?;
+
+        let escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::HashLock {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+
+        check::address("counterparty", counterparty, &escrow_info.counterparty)?;
+        check::address(
+            "pdas_temp_token_account",
+            pdas_temp_token_account,
+            &escrow_info.temp_token_account_pubkey,
+        )?;
+        check::address(
+            "initializers_main_account",
+            initializers_main_account,
+            &escrow_info.initializer_pubkey,
+        )?;
+
+        let clock = Clock::get()?;
+        if clock.slot < escrow_info.unlock_time {
+            return Err(EscrowError::EscrowTimeUnlock.into());
+        }
+        if clock.slot > escrow_info.time_out {
+            return Err(EscrowError::EscrowTimeOut.into());
+        }
+        if escrow_info.hash_kind.hash(&preimage) != escrow_info.hash {
+            return Err(EscrowError::PreimageMismatch.into());
+        }
+
+        let vault = TokenAccount::unpack(&pdas_temp_token_account.try_borrow_data()?)?;
+        msg!("Calling the token program to transfer tokens to the counterparty...");
+        Self::release_vault(
+            pdas_temp_token_account,
+            &[(counterparty_token_account, vault.amount)],
+            initializers_main_account,
+            escrow_account,
+            token_program,
+            pda_account,
+            nonce,
+        )?;
+
+        EscrowEvent::Redeemed {
+            escrow: *escrow_account.key,
+            maker: escrow_info.initializer_pubkey,
+            counterparty: *counterparty.key,
+            offered_mint: vault.mint,
+            offered_amount: vault.amount,
+            preimage,
+            clock: EventClock::from(&clock),
+        }
+        .emit();
+        Ok(())
+    }
+
+    fn process_claim(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let ClaimAccounts {
+            beneficiary,
+            beneficiary_token_account,
+            pdas_temp_token_account,
+            initializers_main_account,
+            escrow_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = ClaimAccounts::parse(program_id, accounts)?;
+
+        let escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::DeadMansSwitch {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+
+        check::address("beneficiary", beneficiary, &escrow_info.counterparty)?;
+        check::address(
+            "pdas_temp_token_account",
+            pdas_temp_token_account,
+            &escrow_info.temp_token_account_pubkey,
+        )?;
+        check::address(
+            "initializers_main_account",
+            initializers_main_account,
+            &escrow_info.initializer_pubkey,
+        )?;
+
+        let clock = Clock::get()?;
+        if clock.slot <= escrow_info.time_out {
+            return Err(EscrowError::EscrowNotExpired.into());
+        }
+
+        let vault = TokenAccount::unpack(&pdas_temp_token_account.try_borrow_data()?)?;
+        msg!("Calling the token program to transfer tokens to the beneficiary...");
+        Self::release_vault(
+            pdas_temp_token_account,
+            &[(beneficiary_token_account, vault.amount)],
+            initializers_main_account,
+            escrow_account,
+            token_program,
+            pda_account,
+            bumps.get("pda")?,
+        )?;
+
+        EscrowEvent::Claimed {
+            escrow: *escrow_account.key,
+            maker: escrow_info.initializer_pubkey,
+            beneficiary: *beneficiary.key,
+            offered_mint: vault.mint,
+            offered_amount: vault.amount,
+            time_out: escrow_info.time_out,
+            clock: EventClock::from(&clock),
+        }
+        .emit();
+        Ok(())
+    }
+
+    fn process_unlock(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let UnlockAccounts {
+            initializer,
+            pda_temp_token_account,
+            initializer_token_account,
+            escrow_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = UnlockAccounts::parse(program_id, accounts)?;
+
+        let escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::SavingsLock {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+
+        check::address("initializer", initializer, &escrow_info.initializer_pubkey)?;
+        check::address(
+            "pda_temp_token_account",
+            pda_temp_token_account,
+            &escrow_info.temp_token_account_pubkey,
+        )?;
+
+        let clock = Clock::get()?;
+        if clock.slot < escrow_info.unlock_time {
+            return Err(EscrowError::EscrowTimeUnlock.into());
+        }
+
+        let vault = TokenAccount::unpack(&pda_temp_token_account.try_borrow_data()?)?;
+        msg!("Calling token program to transfer tokens back to initializer");
+        Self::release_vault(
+            pda_temp_token_account,
+            &[(initializer_token_account, vault.amount)],
+            initializer,
+            escrow_account,
+            token_program,
+            pda_account,
+            bumps.get("pda")?,
+        )?;
+
+        EscrowEvent::Unlocked {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            offered_mint: vault.mint,
+            offered_amount: vault.amount,
+            clock: EventClock::from(&clock),
+        }
+        .emit();
+        Ok(())
+    }
+
+    fn process_extend_lock(
+        accounts: &[AccountInfo],
+        extra_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let ExtendLockAccounts {
+            initializer,
+            escrow_account,
+            ..
+        } = ExtendLockAccounts::parse(program_id, accounts)?;
+
+        let mut escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+
+        check::address("initializer", initializer, &escrow_info.initializer_pubkey)?;
+        if escrow_info.mode != EscrowMode::SavingsLock {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+
+        escrow_info.unlock_time = escrow_info
+            .unlock_time
+            .checked_add(extra_slots)
+            .ok_or(EscrowError::AmountOverflow)?;
+
+        let event = EscrowEvent::LockReset {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            unlock_time: escrow_info.unlock_time,
+            time_out: escrow_info.time_out,
+            clock: EventClock::from(&Clock::get()?),
+        };
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_mark_delivered(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let MarkDeliveredAccounts {
+            taker,
+            escrow_account,
+            ..
+        } = MarkDeliveredAccounts::parse(program_id, accounts)?;
+
+        let mut escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::Arbitrated {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        check::address("taker", taker, &escrow_info.counterparty)?;
+        if escrow_info.deal_state == DealState::Disputed {
+            return Err(EscrowError::DealDisputed.into());
+        }
+        let clock = Clock::get()?;
+        if clock.slot > escrow_info.time_out {
+            return Err(EscrowError::EscrowTimeOut.into());
+        }
+
+        escrow_info.deal_state = DealState::Delivered;
+        let event = EscrowEvent::Delivered {
+            escrow: *escrow_account.key,
+            maker: escrow_info.initializer_pubkey,
+            taker: *taker.key,
+            clock: EventClock::from(&clock),
+        };
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_open_dispute(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let OpenDisputeAccounts {
+            party,
+            escrow_account,
+            ..
+        } = OpenDisputeAccounts::parse(program_id, accounts)?;
+
+        let mut escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::Arbitrated {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if *party.key != escrow_info.initializer_pubkey {
+            check::address("party", party, &escrow_info.counterparty)?;
+        }
+        if escrow_info.deal_state == DealState::Disputed {
+            return Err(EscrowError::DealDisputed.into());
+        }
+        // once it timed out the deal settles the agreed way
+        let clock = Clock::get()?;
+        if clock.slot > escrow_info.time_out {
+            return Err(EscrowError::EscrowTimeOut.into());
+        }
+
+        escrow_info.deal_state = DealState::Disputed;
+        let event = EscrowEvent::Disputed {
+            escrow: *escrow_account.key,
+            maker: escrow_info.initializer_pubkey,
+            party: *party.key,
+            clock: EventClock::from(&clock),
+        };
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_release(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let ReleaseAccounts {
+            initializer,
+            taker_token_account,
+            pdas_temp_token_account,
+            escrow_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = ReleaseAccounts::parse(program_id, accounts)?;
+
+        let escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::Arbitrated {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        check::address("initializer", initializer, &escrow_info.initializer_pubkey)?;
+        check::address(
+            "pdas_temp_token_account",
+            pdas_temp_token_account,
+            &escrow_info.temp_token_account_pubkey,
+        )?;
+        check::authority(
+            "taker_token_account",
+            taker_token_account,
+            &escrow_info.counterparty,
+        )?;
+        if escrow_info.deal_state == DealState::Disputed {
+            return Err(EscrowError::DealDisputed.into());
+        }
+
+        let vault = TokenAccount::unpack(&pdas_temp_token_account.try_borrow_data()?)?;
+        msg!("Calling the token program to transfer tokens to the taker...");
+        Self::release_vault(
+            pdas_temp_token_account,
+            &[(taker_token_account, vault.amount)],
+            initializer,
+            escrow_account,
+            token_program,
+            pda_account,
+            bumps.get("pda")?,
+        )?;
+
+        EscrowEvent::Settled {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            taker: escrow_info.counterparty,
+            offered_mint: vault.mint,
+            to_taker: vault.amount,
+            to_maker: 0,
+            clock: EventClock::from(&Clock::get()?),
+        }
+        .emit();
+        Ok(())
+    }
+
+    fn process_resolve(
+        accounts: &[AccountInfo],
+        taker_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let ResolveAccounts {
+            arbiter,
+            taker_token_account,
+            maker_token_account,
+            initializers_main_account,
+            pdas_temp_token_account,
+            escrow_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = ResolveAccounts::parse(program_id, accounts)?;
+
+        let escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::Arbitrated {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        check::address("arbiter", arbiter, &escrow_info.arbiter)?;
+        if escrow_info.deal_state != DealState::Disputed {
+            return Err(EscrowError::DealNotDisputed.into());
+        }
+        if taker_bps > 10_000 {
+            return Err(EscrowError::InvalidBasisPoints.into());
+        }
+
+        let vault = TokenAccount::unpack(&pdas_temp_token_account.try_borrow_data()?)?;
+        // u128 so the product can't overflow; the quotient fits back in a u64
+        let to_taker = (vault.amount as u128 * taker_bps as u128 / 10_000) as u64;
+        Self::split_vault(
+            &escrow_info,
+            &vault,
+            to_taker,
+            taker_token_account,
+            maker_token_account,
+            initializers_main_account,
+            pdas_temp_token_account,
+            escrow_account,
+            token_program,
+            pda_account,
+            bumps.get("pda")?,
+        )
+    }
+
+    fn process_settle(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let SettleAccounts {
+            taker_token_account,
+            maker_token_account,
+            initializers_main_account,
+            pdas_temp_token_account,
+            escrow_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = SettleAccounts::parse(program_id, accounts)?;
+
+        let escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::Arbitrated {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        // an arbiter who never rules mustn't lock the tokens up for good, so
+        // a dispute only holds the deal up for a grace period past the time out
+        let slot = Clock::get()?.slot;
+        if escrow_info.deal_state == DealState::Disputed {
+            if slot <= escrow_info.time_out.saturating_add(DISPUTE_GRACE_SLOTS) {
+                return Err(EscrowError::DealDisputed.into());
+            }
+        } else if slot <= escrow_info.time_out {
+            return Err(EscrowError::EscrowNotExpired.into());
+        }
+
+        let vault = TokenAccount::unpack(&pdas_temp_token_account.try_borrow_data()?)?;
+        let pay_taker = escrow_info.deal_state == DealState::Delivered
+            || escrow_info.default_outcome == DefaultOutcome::PayTaker;
+        Self::split_vault(
+            &escrow_info,
+            &vault,
+            if pay_taker { vault.amount } else { 0 },
+            taker_token_account,
+            maker_token_account,
+            initializers_main_account,
+            pdas_temp_token_account,
+            escrow_account,
+            token_program,
+            pda_account,
+            bumps.get("pda")?,
+        )
+    }
+
+    /// Pays `to_taker` of an arbitrated deal's vault to the taker and the rest
+    /// back to the maker, after checking the accounts belong to them
+    #[allow(clippy::too_many_arguments)]
+    fn split_vault<'a>(
+        escrow_info: &Escrow,
+        vault: &TokenAccount,
+        to_taker: u64,
+        taker_token_account: &AccountInfo<'a>,
+        maker_token_account: &AccountInfo<'a>,
+        initializers_main_account: &AccountInfo<'a>,
+        pdas_temp_token_account: &AccountInfo<'a>,
+        escrow_account: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        nonce: u8,
+    ) -> ProgramResult {
+        check::address(
+            "pdas_temp_token_account",
+            pdas_temp_token_account,
+            &escrow_info.temp_token_account_pubkey,
+        )?;
+        check::address(
+            "initializers_main_account",
+            initializers_main_account,
+            &escrow_info.initializer_pubkey,
+        )?;
+        check::authority(
+            "taker_token_account",
+            taker_token_account,
+            &escrow_info.counterparty,
+        )?;
+        check::authority(
+            "maker_token_account",
+            maker_token_account,
+            &escrow_info.initializer_pubkey,
+        )?;
+
+        let to_maker = vault.amount - to_taker;
+        msg!(
+            "Paying {} to the taker and {} to the maker...",
+            to_taker,
+            to_maker
+        );
+        Self::release_vault(
+            pdas_temp_token_account,
+            &[
+                (taker_token_account, to_taker),
+                (maker_token_account, to_maker),
+            ],
+            initializers_main_account,
+            escrow_account,
+            token_program,
+            pda_account,
+            nonce,
+        )?;
+
+        EscrowEvent::Settled {
+            escrow: *escrow_account.key,
+            maker: escrow_info.initializer_pubkey,
+            taker: escrow_info.counterparty,
+            offered_mint: vault.mint,
+            to_taker,
+            to_maker,
+            clock: EventClock::from(&Clock::get()?),
+        }
+        .emit();
+        Ok(())
+    }
+
+    fn process_init_milestones(
+        accounts: &[AccountInfo],
+        payee: Pubkey,
+        milestones: &[MilestoneTerms],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let InitMilestonesAccounts {
+            initializer,
+            temp_token_account,
+            escrow_account,
+            rent,
+            token_program,
+            ..
+        } = InitMilestonesAccounts::parse(program_id, accounts)?;
+        let rent = &Rent::from_account_info(rent)?;
+
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let mut escrow_info: Escrow = loader::load_uninitialized(escrow_account, program_id)?;
+        if milestones.is_empty() || milestones.len() > MAX_MILESTONES {
+            return Err(EscrowError::InvalidMilestones.into());
+        }
+
+        let clock = Clock::get()?;
+        let mut total: u64 = 0;
+        for (milestone, terms) in escrow_info.milestones.iter_mut().zip(milestones) {
+            if terms.amount == 0 {
+                return Err(EscrowError::InvalidMilestones.into());
+            }
+            total = total
+                .checked_add(terms.amount)
+                .ok_or(EscrowError::AmountOverflow)?;
+            let deadline = match terms.deadline_slots {
+                Some(slots) => Some(
+                    clock
+                        .slot
+                        .checked_add(slots)
+                        .ok_or(EscrowError::AmountOverflow)?,
+                ),
+                None => None,
+            };
+            *milestone = Milestone {
+                amount: terms.amount,
+                deadline,
+                state: MilestoneState::Pending,
+            };
+        }
+        // anything left over would be stuck in the vault
+        let offered = TokenAccount::unpack(&temp_token_account.try_borrow_data()?)?;
+        if offered.amount != total {
+            return Err(EscrowError::InvalidMilestones.into());
+        }
+
+        escrow_info.is_initialized = true;
+        escrow_info.initializer_pubkey = *initializer.key;
+        escrow_info.temp_token_account_pubkey = *temp_token_account.key;
+        escrow_info.mode = EscrowMode::Milestones;
+        escrow_info.counterparty = payee;
+        escrow_info.milestone_count = milestones.len() as u8;
+        escrow_info.remaining = total;
+        // each tranche has its own deadline, if any
+        escrow_info.unlock_time = clock.slot;
+        escrow_info.time_out = u64::MAX;
+
+        let event = EscrowEvent::MilestonesOpened {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            payee,
+            offered_mint: offered.mint,
+            offered_amount: offered.amount,
+            milestone_count: escrow_info.milestone_count,
+            clock: EventClock::from(&clock),
+        };
+
+        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        escrow_info.pda_bump = nonce;
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        Self::hand_vault_to_pda(initializer, temp_token_account, token_program, &pda)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_approve_milestone(
+        accounts: &[AccountInfo],
+        index: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let ApproveMilestoneAccounts {
+            initializer,
+            payee_token_account,
+            pdas_temp_token_account,
+            escrow_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = ApproveMilestoneAccounts::parse(program_id, accounts)?;
+
+        let mut escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::Milestones {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        check::address("initializer", initializer, &escrow_info.initializer_pubkey)?;
+        check::address(
+            "pdas_temp_token_account",
+            pdas_temp_token_account,
+            &escrow_info.temp_token_account_pubkey,
+        )?;
+        check::authority(
+            "payee_token_account",
+            payee_token_account,
+            &escrow_info.counterparty,
+        )?;
+
+        let count = escrow_info.milestone_count as usize;
+        let milestone = escrow_info.milestones[..count]
+            .get_mut(index as usize)
+            .ok_or(EscrowError::InvalidMilestones)?;
+        if milestone.state != MilestoneState::Pending {
+            return Err(EscrowError::MilestoneSettled.into());
+        }
+        milestone.state = MilestoneState::Approved;
+        let amount = milestone.amount;
+        escrow_info.remaining = escrow_info
+            .remaining
+            .checked_sub(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+
+        let vault = TokenAccount::unpack(&pdas_temp_token_account.try_borrow_data()?)?;
+        let event = EscrowEvent::MilestoneApproved {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            payee: escrow_info.counterparty,
+            offered_mint: vault.mint,
+            index,
+            amount,
+            remaining: escrow_info.remaining,
+            clock: EventClock::from(&Clock::get()?),
+        };
+        msg!("Calling the token program to pay the milestone...");
+        if escrow_info.remaining == 0 {
+            // whatever else ended up in the vault goes along, so it can close
+            Self::release_vault(
+                pdas_temp_token_account,
+                &[(payee_token_account, vault.amount)],
+                initializer,
+                escrow_account,
+                token_program,
+                pda_account,
+                bumps.get("pda")?,
+            )?;
+        } else {
+            Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+            Self::pay_from_vault(
+                pdas_temp_token_account,
+                payee_token_account,
+                amount,
+                token_program,
+                pda_account,
+                bumps.get("pda")?,
+            )?;
+        }
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_refund_unapproved(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let RefundUnapprovedAccounts {
+            initializer,
+            initializer_token_account,
+            pdas_temp_token_account,
+            escrow_account,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = RefundUnapprovedAccounts::parse(program_id, accounts)?;
+
+        let mut escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if escrow_info.mode != EscrowMode::Milestones {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        check::address("initializer", initializer, &escrow_info.initializer_pubkey)?;
+        check::address(
+            "pdas_temp_token_account",
+            pdas_temp_token_account,
+            &escrow_info.temp_token_account_pubkey,
+        )?;
+
+        let clock = Clock::get()?;
+        let count = escrow_info.milestone_count as usize;
+        let mut amount: u64 = 0;
+        for milestone in escrow_info.milestones[..count]
+            .iter_mut()
+            .filter(|milestone| milestone.is_expired(clock.slot))
+        {
+            milestone.state = MilestoneState::Refunded;
+            amount = amount
+                .checked_add(milestone.amount)
+                .ok_or(EscrowError::AmountOverflow)?;
+        }
+        if amount == 0 {
+            return Err(EscrowError::NoExpiredMilestones.into());
+        }
+        escrow_info.remaining = escrow_info
+            .remaining
+            .checked_sub(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+
+        let vault = TokenAccount::unpack(&pdas_temp_token_account.try_borrow_data()?)?;
+        let event = EscrowEvent::MilestonesRefunded {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            offered_mint: vault.mint,
+            amount,
+            remaining: escrow_info.remaining,
+            clock: EventClock::from(&clock),
+        };
+        msg!("Calling token program to transfer tokens back to initializer");
+        if escrow_info.remaining == 0 {
+            Self::release_vault(
+                pdas_temp_token_account,
+                &[(initializer_token_account, vault.amount)],
+                initializer,
+                escrow_account,
+                token_program,
+                pda_account,
+                bumps.get("pda")?,
+            )?;
+        } else {
+            Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+            Self::pay_from_vault(
+                pdas_temp_token_account,
+                initializer_token_account,
+                amount,
+                token_program,
+                pda_account,
+                bumps.get("pda")?,
+            )?;
+        }
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_set_premium(
+        accounts: &[AccountInfo],
+        premium: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let SetPremiumAccounts {
+            initializer,
+            escrow_account,
+            system_program,
+            ..
+        } = SetPremiumAccounts::parse(program_id, accounts)?;
+
+        let (mut escrow_info, clock) =
+            Self::load_mutable_terms(initializer, escrow_account, program_id)?;
+        escrow_info.premium = premium;
+
+        Self::store_resized(escrow_info, escrow_account, initializer, system_program)?;
+
+        EscrowEvent::PremiumSet {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            premium,
+            clock: EventClock::from(&clock),
+        }
+        .emit();
+        Ok(())
+    }
+
+    fn process_set_exclusive_taker(
+        accounts: &[AccountInfo],
+        taker: &Pubkey,
+        exclusive_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let SetExclusiveTakerAccounts {
+            initializer,
+            escrow_account,
+            system_program,
+            ..
+        } = SetExclusiveTakerAccounts::parse(program_id, accounts)?;
+
+        let (mut escrow_info, clock) =
+            Self::load_mutable_terms(initializer, escrow_account, program_id)?;
+        // once the swap unlocks, the designated taker may already be counting
+        // on their period
+        if clock.slot >= escrow_info.unlock_time {
+            return Err(EscrowError::ExclusivityStarted.into());
+        }
+        (escrow_info.designated_taker, escrow_info.exclusive_until) = if *taker == Pubkey::default()
+        {
+            (Pubkey::default(), 0)
+        } else {
+            let last_slot = exclusive_slots
+                .checked_sub(1)
+                .ok_or(EscrowError::InvalidInstruction)?;
+            let exclusive_until = escrow_info
+                .unlock_time
+                .checked_add(last_slot)
+                .ok_or(EscrowError::AmountOverflow)?;
+            Self::check_exclusive_period(&escrow_info, exclusive_until)?;
+            (*taker, exclusive_until)
+        };
+
+        let event = EscrowEvent::ExclusiveTakerSet {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            taker: escrow_info.designated_taker,
+            exclusive_until: escrow_info.exclusive_until,
+            clock: EventClock::from(&clock),
+        };
+        Self::store_resized(escrow_info, escrow_account, initializer, system_program)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_set_allow_list(
+        accounts: &[AccountInfo],
+        root: &[u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let SetAllowListAccounts {
+            initializer,
+            escrow_account,
+            system_program,
+            ..
+        } = SetAllowListAccounts::parse(program_id, accounts)?;
+
+        let (mut escrow_info, clock) =
+            Self::load_mutable_terms(initializer, escrow_account, program_id)?;
+        // takers left off the list after the unlock would have been racing
+        // for a swap they could no longer take
+        if clock.slot >= escrow_info.unlock_time {
+            return Err(EscrowError::ExclusivityStarted.into());
+        }
+        escrow_info.allow_list_root = *root;
+
+        Self::store_resized(escrow_info, escrow_account, initializer, system_program)?;
+
+        EscrowEvent::AllowListSet {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            root: *root,
+            clock: EventClock::from(&clock),
+        }
+        .emit();
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_set_price_condition(
+        accounts: &[AccountInfo],
+        oracle_kind: OracleKind,
+        condition: PriceCondition,
+        trigger_price: i64,
+        trigger_expo: i32,
+        max_staleness: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let SetPriceConditionAccounts {
+            initializer,
+            escrow_account,
+            oracle,
+            system_program,
+            ..
+        } = SetPriceConditionAccounts::parse(program_id, accounts)?;
+
+        let (mut escrow_info, clock) =
+            Self::load_mutable_terms(initializer, escrow_account, program_id)?;
+        if condition == PriceCondition::None {
+            escrow_info.oracle = Pubkey::default();
+            escrow_info.oracle_kind = OracleKind::default();
+            escrow_info.trigger_price = 0;
+            escrow_info.trigger_expo = 0;
+            escrow_info.max_staleness = 0;
+        } else {
+            // a swap nobody can take is better caught now than at exchange
+            oracle_kind.read_price(&oracle.try_borrow_data()?)?;
+            escrow_info.oracle = *oracle.key;
+            escrow_info.oracle_kind = oracle_kind;
+            escrow_info.trigger_price = trigger_price;
+            escrow_info.trigger_expo = trigger_expo;
+            escrow_info.max_staleness = max_staleness;
+        }
+        escrow_info.price_condition = condition;
+
+        let event = EscrowEvent::PriceConditionSet {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            oracle: escrow_info.oracle,
+            oracle_kind: escrow_info.oracle_kind,
+            condition,
+            trigger_price: escrow_info.trigger_price,
+            trigger_expo: escrow_info.trigger_expo,
+            max_staleness: escrow_info.max_staleness,
+            clock: EventClock::from(&clock),
+        };
+        Self::store_resized(escrow_info, escrow_account, initializer, system_program)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_set_callback(
+        accounts: &[AccountInfo],
+        callback_program_key: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let SetCallbackAccounts {
+            initializer,
+            escrow_account,
+            callback_program,
+            system_program,
+            ..
+        } = SetCallbackAccounts::parse(program_id, accounts)?;
+
+        let (mut escrow_info, clock) =
+            Self::load_mutable_terms(initializer, escrow_account, program_id)?;
+        if *callback_program_key != Pubkey::default() {
+            check::address("callback_program", callback_program, callback_program_key)?;
+            if callback_program_key == program_id {
+                return Err(EscrowError::CallbackReentrancy.into());
+            }
+            if !callback_program.executable {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+        }
+        escrow_info.callback_program = *callback_program_key;
+
+        let event = EscrowEvent::CallbackSet {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            callback_program: *callback_program_key,
+            clock: EventClock::from(&clock),
+        };
+        Self::store_resized(escrow_info, escrow_account, initializer, system_program)?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_reserve(
+        accounts: &[AccountInfo],
+        premium_expected_by_taker: u64,
+        proof: &[[u8; 32]],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let ReserveAccounts {
+            taker,
+            takers_sending_token_account,
+            initializers_token_to_receive_account,
+            escrow_account,
+            token_program,
+            ..
+        } = ReserveAccounts::parse(program_id, accounts)?;
+
+        let mut escrow_info = Self::load_current(escrow_account, program_id)?;
+        if escrow_info.mode != EscrowMode::Swap {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if escrow_info.premium == 0 {
+            return Err(EscrowError::NotReservable.into());
+        }
+        if premium_expected_by_taker != escrow_info.premium {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+        check::address(
+            "initializers_token_to_receive_account",
+            initializers_token_to_receive_account,
+            &escrow_info.initializer_token_to_receive_account_pubkey,
+        )?;
+
+        let clock = Clock::get()?;
+        if clock.slot > escrow_info.time_out {
+            return Err(EscrowError::EscrowTimeOut.into());
+        }
+        if escrow_info.is_reserved(clock.slot) {
+            return Err(EscrowError::EscrowReserved.into());
+        }
+        // a reservation would lock the designated taker out of their period
+        if escrow_info.designated_taker != *taker.key
+            && escrow_info.designated_taker != Pubkey::default()
+            && clock.slot <= escrow_info.exclusive_until
+        {
+            return Err(EscrowError::NotYetPublic.into());
+        }
+        Self::check_allowed(&escrow_info, taker.key, proof)?;
+        escrow_info.counterparty = *taker.key;
+
+        let premium_mint =
+            TokenAccount::unpack(&initializers_token_to_receive_account.try_borrow_data()?)?.mint;
+        let event = EscrowEvent::Reserved {
+            escrow: *escrow_account.key,
+            maker: escrow_info.initializer_pubkey,
+            taker: *taker.key,
+            premium_mint,
+            premium: escrow_info.premium,
+            time_out: escrow_info.time_out,
+            clock: EventClock::from(&clock),
+        };
+        let premium = escrow_info.premium;
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+
+        let transfer_ix = spl_token::instruction::transfer(
+            token_program.key,
+            takers_sending_token_account.key,
+            initializers_token_to_receive_account.key,
+            taker.key,
+            &[taker.key],
+            premium,
+        )?;
+        msg!("Calling the token program to pay the premium to the escrow's initializer...");
+        invoke(
+            &transfer_ix,
+            &[
+                takers_sending_token_account.clone(),
+                initializers_token_to_receive_account.clone(),
+                taker.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_fill_signed_order(
+        accounts: &[AccountInfo],
+        order: &Order,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let FillSignedOrderAccounts {
+            taker,
+            takers_sending_token_account,
+            takers_token_to_receive_account,
+            makers_sending_token_account,
+            makers_token_to_receive_account,
+            order_nonce,
+            order_receipt,
+            instructions: instructions_sysvar,
+            system_program,
+            token_program,
+            pda,
+            bumps,
+            ..
+        } = FillSignedOrderAccounts::parse(program_id, accounts)?;
+
+        check::address(
+            "makers_sending_token_account",
+            makers_sending_token_account,
+            &order.maker_send_account,
+        )?;
+        check::address(
+            "makers_token_to_receive_account",
+            makers_token_to_receive_account,
+            &order.maker_receive_account,
+        )?;
+        check::authority(
+            "makers_sending_token_account",
+            makers_sending_token_account,
+            &order.maker,
+        )?;
+        if order.taker != Pubkey::default() && order.taker != *taker.key {
+            return Err(EscrowError::OrderNotForTaker.into());
+        }
+        let clock = Clock::get()?;
+        if clock.slot > order.expires_at {
+            return Err(EscrowError::OrderExpired.into());
+        }
+
+        check::seeds(
+            "order_nonce",
+            order_nonce,
+            &[b"nonce", order.maker.as_ref()],
+            program_id,
+        )?;
+        // a maker who never cancelled has no nonce account yet
+        let nonce = if order_nonce.owner == program_id {
+            loader::load::<OrderNonce>(order_nonce, program_id, false)?.nonce
+        } else {
+            0
+        };
+        if order.nonce != nonce {
+            return Err(EscrowError::StaleOrderNonce.into());
+        }
+
+        let message = order.message(program_id);
+        let current = instructions::load_current_index_checked(instructions_sysvar)?;
+        let previous = current
+            .checked_sub(1)
+            .ok_or(EscrowError::InvalidOrderSignature)?;
+        let ed25519_ix =
+            instructions::load_instruction_at_checked(previous as usize, instructions_sysvar)?;
+        order::verify_ed25519(&ed25519_ix, &order.maker, &message)?;
+
+        let hash = order.hash(program_id);
+        let receipt_bump = check::seeds(
+            "order_receipt",
+            order_receipt,
+            &[b"order", &hash],
+            program_id,
+        )?;
+        if order_receipt.owner == program_id {
+            return Err(EscrowError::OrderAlreadyFilled.into());
+        }
+        Self::create_pda_account(
+            taker,
+            order_receipt,
+            ORDER_RECEIPT_LEN,
+            &[b"order", &hash, &[receipt_bump]],
+            system_program,
+            program_id,
+        )?;
+        order_receipt.try_borrow_mut_data()?[0] = AccountType::OrderReceipt as u8;
+
+        let makers_sending =
+            TokenAccount::unpack(&makers_sending_token_account.try_borrow_data()?)?;
+        Self::check_delegation(&makers_sending, pda.key, order.offered_amount)?;
+        let requested_mint =
+            TokenAccount::unpack(&makers_token_to_receive_account.try_borrow_data()?)?.mint;
+        let event = EscrowEvent::OrderFilled {
+            escrow: *order_receipt.key,
+            maker: order.maker,
+            taker: *taker.key,
+            offered_mint: makers_sending.mint,
+            offered_amount: order.offered_amount,
+            requested_mint,
+            requested_amount: order.requested_amount,
+            nonce: order.nonce,
+            clock: EventClock::from(&clock),
+        };
+
+        let transfer_to_maker_ix = spl_token::instruction::transfer(
+            token_program.key,
+            takers_sending_token_account.key,
+            makers_token_to_receive_account.key,
+            taker.key,
+            &[taker.key],
+            order.requested_amount,
+        )?;
+        msg!("Calling the token program to transfer tokens to the order's maker...");
+        invoke(
+            &transfer_to_maker_ix,
+            &[
+                takers_sending_token_account.clone(),
+                makers_token_to_receive_account.clone(),
+                taker.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
+        msg!("Calling the token program to transfer the maker's tokens to the taker...");
+        Self::pay_from_vault(
+            makers_sending_token_account,
+            takers_token_to_receive_account,
+            order.offered_amount,
+            token_program,
+            pda,
+            bumps.get("pda")?,
+        )?;
+
+        event.emit();
+        Ok(())
+    }
+
+    fn process_cancel_all_orders(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let CancelAllOrdersAccounts {
+            maker,
+            order_nonce,
+            system_program,
+            bumps,
+            ..
+        } = CancelAllOrdersAccounts::parse(program_id, accounts)?;
+
+        let bump = bumps.get("order_nonce")?;
+        let mut nonce_info = if order_nonce.owner == program_id {
+            loader::load::<OrderNonce>(order_nonce, program_id, true)?
+        } else {
+            Self::create_pda_account(
+                maker,
+                order_nonce,
+                OrderNonce::LEN,
+                &[b"nonce", maker.key.as_ref(), &[bump]],
+                system_program,
+                program_id,
+            )?;
+            OrderNonce {
+                is_initialized: true,
+                maker: *maker.key,
+                nonce: 0,
+                bump,
+            }
+        };
+        nonce_info.nonce = nonce_info
+            .nonce
+            .checked_add(1)
+            .ok_or(EscrowError::AmountOverflow)?;
+        let nonce = nonce_info.nonce;
+        OrderNonce::pack(nonce_info, &mut order_nonce.try_borrow_mut_data()?)?;
+
+        EscrowEvent::OrdersCancelled {
+            escrow: *order_nonce.key,
+            maker: *maker.key,
+            nonce,
+            clock: EventClock::from(&Clock::get()?),
+        }
+        .emit();
+        Ok(())
+    }
+
+    fn process_init_pool(
+        accounts: &[AccountInfo],
+        offered_per_lot: u64,
+        requested_per_lot: u64,
+        unlock_slots: u64,
+        time_out_slots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let InitPoolAccounts {
+            creator,
+            pool,
+            vault,
+            proceeds_vault,
+            rent,
+            token_program,
+            ..
+        } = InitPoolAccounts::parse(program_id, accounts)?;
+        let rent = &Rent::from_account_info(rent)?;
+
+        if !rent.is_exempt(pool.lamports(), pool.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        if offered_per_lot == 0 || requested_per_lot == 0 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        let _: Pool = loader::load_uninitialized(pool, program_id)?;
+
+        // tokens already in a vault would belong to nobody's shares, and a
+        // close authority could take the vault away from the makers
+        if vault.key == proceeds_vault.key {
+            return Err(EscrowError::UnsuitablePoolVault.into());
+        }
+        let offered = TokenAccount::unpack(&vault.try_borrow_data()?)?;
+        let requested = TokenAccount::unpack(&proceeds_vault.try_borrow_data()?)?;
+        for account in [&offered, &requested] {
+            if account.amount != 0 || account.close_authority.is_some() {
+                return Err(EscrowError::UnsuitablePoolVault.into());
+            }
+        }
+
+        let clock = Clock::get()?;
+        let (unlock_time, time_out) = Self::lock_window(clock.slot, unlock_slots, time_out_slots)?;
+        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let pool_info = Pool {
+            is_initialized: true,
+            creator: *creator.key,
+            vault: *vault.key,
+            proceeds_vault: *proceeds_vault.key,
+            offered_per_lot,
+            requested_per_lot,
+            unlock_time,
+            time_out,
+            pda_bump: nonce,
+            ..Pool::default()
+        };
+        Pool::pack(pool_info, &mut pool.try_borrow_mut_data()?)?;
+
+        Self::hand_vault_to_pda(creator, vault, token_program, &pda)?;
+        Self::hand_vault_to_pda(creator, proceeds_vault, token_program, &pda)?;
+
+        EscrowEvent::PoolOpened {
+            escrow: *pool.key,
+            creator: *creator.key,
+            offered_mint: offered.mint,
+            offered_per_lot,
+            requested_mint: requested.mint,
+            requested_per_lot,
+            unlock_time,
+            time_out,
+            clock: EventClock::from(&clock),
         }
+        .emit();
+        Ok(())
     }
 
-    fn process_init_escrow(
+    fn process_deposit_pool(
         accounts: &[AccountInfo],
         amount: u64,
         program_id: &Pubkey,
     ) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let initializer = next_account_info(account_info_iter)?;
+        let DepositPoolAccounts {
+            maker,
+            makers_token_account,
+            pool,
+            vault,
+            share,
+            token_program,
+            system_program,
+            bumps,
+            ..
+        } = DepositPoolAccounts::parse(program_id, accounts)?;
 
-        if !initializer.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+        let mut pool_info: Pool = loader::load(pool, program_id, true)?;
+        check::address("vault", vault, &pool_info.vault)?;
+        if amount == 0 {
+            return Err(EscrowError::InvalidInstruction.into());
         }
-
-        let temp_token_account = next_account_info(account_info_iter)?;
-
-        let token_to_receive_account = next_account_info(account_info_iter)?;
-        if *token_to_receive_account.owner != spl_token::id() {
-            return Err(ProgramError::IncorrectProgramId);
+        // shares are only worth their deposit while nothing has been filled
+        let clock = Clock::get()?;
+        if clock.slot > pool_info.time_out {
+            return Err(EscrowError::EscrowTimeOut.into());
+        }
+        if clock.slot >= pool_info.unlock_time {
+            return Err(EscrowError::PoolWindowOpen.into());
         }
 
-        let escrow_account = next_account_info(account_info_iter)?;
-        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let bump = bumps.get("share")?;
+        let mut share_info = if share.owner == program_id {
+            loader::load::<PoolShare>(share, program_id, true)?
+        } else {
+            Self::create_pda_account(
+                maker,
+                share,
+                PoolShare::LEN,
+                &[b"share", pool.key.as_ref(), maker.key.as_ref(), &[bump]],
+                system_program,
+                program_id,
+            )?;
+            PoolShare {
+                is_initialized: true,
+                pool: *pool.key,
+                maker: *maker.key,
+                shares: 0,
+                bump,
+            }
+        };
+        share_info.shares = share_info
+            .shares
+            .checked_add(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        pool_info.total_shares = pool_info
+            .total_shares
+            .checked_add(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        pool_info.unfilled = pool_info
+            .unfilled
+            .checked_add(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        PoolShare::pack(share_info, &mut share.try_borrow_mut_data()?)?;
+        Pool::pack(pool_info, &mut pool.try_borrow_mut_data()?)?;
 
-        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
-            return Err(EscrowError::NotRentExempt.into());
-        }
+        let transfer_ix = spl_token::instruction::transfer(
+            token_program.key,
+            makers_token_account.key,
+            vault.key,
+            maker.key,
+            &[maker.key],
+            amount,
+        )?;
+        msg!("Calling the token program to transfer tokens to the pool...");
+        invoke(
+            &transfer_ix,
+            &[
+                makers_token_account.clone(),
+                vault.clone(),
+                maker.clone(),
+                token_program.clone(),
+            ],
+        )?;
 
-        let mut escrow_info = Escrow::unpack_unchecked(&escrow_account.try_borrow_data()?)?;
-        if escrow_info.is_initialized() {
-            return Err(ProgramError::AccountAlreadyInitialized);
+        EscrowEvent::PoolDeposited {
+            escrow: *pool.key,
+            maker: *maker.key,
+            amount,
+            clock: EventClock::from(&clock),
         }
+        .emit();
+        Ok(())
+    }
 
-        escrow_info.is_initialized = true;
-        escrow_info.initializer_pubkey = *initializer.key;
-        escrow_info.temp_token_account_pubkey = *temp_token_account.key;
-        escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key;
-        escrow_info.expected_amount = amount;
+    fn process_fill_pool(
+        accounts: &[AccountInfo],
+        lots: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let FillPoolAccounts {
+            taker,
+            takers_sending_token_account,
+            takers_token_to_receive_account,
+            pool,
+            vault,
+            proceeds_vault,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = FillPoolAccounts::parse(program_id, accounts)?;
+        let nonce = bumps.get("pda")?;
 
-        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
-        let (pda, _nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let mut pool_info: Pool = loader::load(pool, program_id, true)?;
+        check::address("vault", vault, &pool_info.vault)?;
+        check::address("proceeds_vault", proceeds_vault, &pool_info.proceeds_vault)?;
+        if lots == 0 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        let clock = Clock::get()?;
+        if clock.slot < pool_info.unlock_time {
+            return Err(EscrowError::EscrowTimeUnlock.into());
+        }
+        if clock.slot > pool_info.time_out {
+            return Err(EscrowError::EscrowTimeOut.into());
+        }
+        let (offered_amount, requested_amount) = pool_info.fill(lots)?;
+        Pool::pack(pool_info, &mut pool.try_borrow_mut_data()?)?;
 
-        let token_program = next_account_info(account_info_iter)?;
-        let owner_change_ix = spl_token::instruction::set_authority(
+        let transfer_to_pool_ix = spl_token::instruction::transfer(
             token_program.key,
-            temp_token_account.key,
-            Some(&pda),
-            spl_token::instruction::AuthorityType::AccountOwner,
-            initializer.key,
-            &[&initializer.key],
+            takers_sending_token_account.key,
+            proceeds_vault.key,
+            taker.key,
+            &[taker.key],
+            requested_amount,
         )?;
-
-        msg!("Calling the token program to transfer token account ownership...");
+        msg!("Calling the token program to transfer tokens to the pool...");
         invoke(
-            &owner_change_ix,
+            &transfer_to_pool_ix,
             &[
-                temp_token_account.clone(),
-                initializer.clone(),
+                takers_sending_token_account.clone(),
+                proceeds_vault.clone(),
+                taker.clone(),
                 token_program.clone(),
             ],
         )?;
+        msg!("Calling the token program to transfer tokens to the taker...");
+        Self::pay_from_vault(
+            vault,
+            takers_token_to_receive_account,
+            offered_amount,
+            token_program,
+            pda_account,
+            nonce,
+        )?;
 
+        EscrowEvent::PoolFilled {
+            escrow: *pool.key,
+            taker: *taker.key,
+            offered_amount,
+            requested_amount,
+            clock: EventClock::from(&clock),
+        }
+        .emit();
         Ok(())
     }
 
-    fn process_exchange(
-        accounts: &[AccountInfo],
-        amount_expected_by_taker: u64,
-        program_id: &Pubkey,
-    ) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let taker = next_account_info(account_info_iter)?;
+    fn process_withdraw_pool(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let WithdrawPoolAccounts {
+            maker,
+            share,
+            pool,
+            vault,
+            proceeds_vault,
+            makers_offered_account,
+            makers_requested_account,
+            creator,
+            token_program,
+            pda: pda_account,
+            bumps,
+            ..
+        } = WithdrawPoolAccounts::parse(program_id, accounts)?;
+        let nonce = bumps.get("pda")?;
 
-        if !taker.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+        let mut pool_info: Pool = loader::load(pool, program_id, true)?;
+        check::address("vault", vault, &pool_info.vault)?;
+        check::address("proceeds_vault", proceeds_vault, &pool_info.proceeds_vault)?;
+        check::address("creator", creator, &pool_info.creator)?;
+        // nobody has a share of a pool nobody deposited into, so its creator
+        // closes it without one
+        let shares = if pool_info.total_shares == 0 {
+            check::address("maker", maker, &pool_info.creator)?;
+            0
+        } else {
+            let share_info: PoolShare = loader::load(share, program_id, true)?;
+            check::address("pool", pool, &share_info.pool)?;
+            check::address("maker", maker, &share_info.maker)?;
+            share_info.shares
+        };
+
+        let clock = Clock::get()?;
+        if clock.slot >= pool_info.unlock_time && clock.slot <= pool_info.time_out {
+            return Err(EscrowError::PoolWindowOpen.into());
+        }
+        let (mut unfilled, mut proceeds) = pool_info.withdraw(shares)?;
+        let last = pool_info.total_shares == 0;
+        if last {
+            // including anything sent to the vaults besides deposits and
+            // fills, so they can be closed
+            unfilled = TokenAccount::unpack(&vault.try_borrow_data()?)?.amount;
+            proceeds = TokenAccount::unpack(&proceeds_vault.try_borrow_data()?)?.amount;
+        } else {
+            Pool::pack(pool_info, &mut pool.try_borrow_mut_data()?)?;
         }
 
-        let takers_sending_token_account = next_account_info(account_info_iter)?;
+        msg!("Calling the token program to pay out the maker's share...");
+        Self::pay_from_vault(
+            vault,
+            makers_offered_account,
+            unfilled,
+            token_program,
+            pda_account,
+            nonce,
+        )?;
+        Self::pay_from_vault(
+            proceeds_vault,
+            makers_requested_account,
+            proceeds,
+            token_program,
+            pda_account,
+            nonce,
+        )?;
+
+        if last {
+            Self::close_vault(vault, creator, token_program, pda_account, nonce)?;
+            Self::close_vault(proceeds_vault, creator, token_program, pda_account, nonce)?;
+            msg!("Closing the pool account...");
+            Self::close_account(pool, creator)?;
+        }
+        if shares > 0 {
+            Self::close_account(share, maker)?;
+        }
 
-        let takers_token_to_receive_account = next_account_info(account_info_iter)?;
+        EscrowEvent::PoolWithdrawn {
+            escrow: *pool.key,
+            maker: *maker.key,
+            unfilled,
+            proceeds,
+            clock: EventClock::from(&clock),
+        }
+        .emit();
+        Ok(())
+    }
 
-        let pdas_temp_token_account = next_account_info(account_info_iter)?;
-        let pdas_temp_token_account_info =
-            TokenAccount::unpack(&pdas_temp_token_account.try_borrow_data()?)?;
-        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id);
+    /// Closes a program account, sending its rent to `destination`.
+    ///
+    /// The account only goes away at the end of the transaction, and rent
+    /// sent back to it before then keeps it, data and all. Zeroing the data
+    /// leaves an uninitialized account with no shares that nothing loads.
+    fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+        account.try_borrow_mut_data()?.fill(0);
+        **destination.try_borrow_mut_lamports()? = destination
+            .lamports()
+            .checked_add(account.lamports())
+            .ok_or(EscrowError::AmountOverflow)?;
+        **account.try_borrow_mut_lamports()? = 0;
+        Ok(())
+    }
 
-        if amount_expected_by_taker != pdas_temp_token_account_info.amount {
+    /// Checks `taker` may take the swap in `escrow_info` out of `vault`
+    /// right now, for `amount_expected_by_taker` if given, and returns how much
+    /// the swap offers
+    #[allow(clippy::too_many_arguments)]
+    fn check_takeable(
+        escrow_info: &Escrow,
+        taker: &Pubkey,
+        vault: &AccountInfo,
+        vault_info: &TokenAccount,
+        initializers_main_account: &AccountInfo,
+        initializers_token_to_receive_account: &AccountInfo,
+        pda: &Pubkey,
+        amount_expected_by_taker: Option<u64>,
+        proof: &[[u8; 32]],
+        clock: &Clock,
+    ) -> Result<u64, ProgramError> {
+        if escrow_info.mode != EscrowMode::Swap {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        let offered_amount = if escrow_info.is_delegated() {
+            check::authority(
+                "pdas_temp_token_account",
+                vault,
+                &escrow_info.initializer_pubkey,
+            )?;
+            Self::check_delegation(vault_info, pda, escrow_info.delegated_amount)?;
+            escrow_info.delegated_amount
+        } else {
+            check::authority("pdas_temp_token_account", vault, pda)?;
+            vault_info.amount
+        };
+        if amount_expected_by_taker.is_some_and(|amount| amount != offered_amount) {
             return Err(EscrowError::ExpectedAmountMismatch.into());
         }
+        // a reservation lasts until the time out, after which nobody can take
+        // the swap anyway
+        if escrow_info.counterparty != Pubkey::default() && *taker != escrow_info.counterparty {
+            return Err(EscrowError::EscrowReserved.into());
+        }
+
+        check::address(
+            "pdas_temp_token_account",
+            vault,
+            &escrow_info.temp_token_account_pubkey,
+        )?;
+        check::address(
+            "initializers_main_account",
+            initializers_main_account,
+            &escrow_info.initializer_pubkey,
+        )?;
+        check::address(
+            "initializers_token_to_receive_account",
+            initializers_token_to_receive_account,
+            &escrow_info.initializer_token_to_receive_account_pubkey,
+        )?;
+
+        if clock.slot < escrow_info.unlock_time {
+            return Err(EscrowError::EscrowTimeUnlock.into());
+        }
+        if clock.slot > escrow_info.time_out {
+            return Err(EscrowError::EscrowTimeOut.into());
+        }
+        if escrow_info.is_exclusive(clock.slot) && *taker != escrow_info.designated_taker {
+            return Err(EscrowError::NotYetPublic.into());
+        }
+        Self::check_allowed(escrow_info, taker, proof)?;
+        Ok(offered_amount)
+    }
+
+    /// Checks the swap in `escrow_info` can be taken alongside others, which
+    /// leaves no room for the accounts a token bond's vault and destination,
+    /// an oracle or a callback program would need
+    fn check_sweepable(escrow_info: &Escrow) -> ProgramResult {
+        if escrow_info.bond_vault != Pubkey::default()
+            || escrow_info.has_price_condition()
+            || escrow_info.has_callback()
+        {
+            msg!("Escrows with a token bond, a price condition or a callback can only be taken through Exchange");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        Ok(())
+    }
+
+    /// Checks an exclusive period ending at `exclusive_until` leaves the swap
+    /// public for at least its last slot
+    fn check_exclusive_period(escrow_info: &Escrow, exclusive_until: u64) -> ProgramResult {
+        if exclusive_until >= escrow_info.time_out {
+            return Err(EscrowError::ExclusivePeriodTooLong.into());
+        }
+        Ok(())
+    }
 
-        let initializers_main_account = next_account_info(account_info_iter)?;
-        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
-        let escrow_account = next_account_info(account_info_iter)?;
+    /// Checks the oracle's price is fresh and meets the swap's condition, if
+    /// it has one. The oracle comes after any token bond's accounts.
+    fn check_price_condition(
+        escrow_info: &Escrow,
+        remaining: &[AccountInfo],
+        program_id: &Pubkey,
+        clock: &Clock,
+    ) -> ProgramResult {
+        if !escrow_info.has_price_condition() {
+            return Ok(());
+        }
+        let bond_accounts = Self::bond_account_count(escrow_info, false);
+        let OracleAccounts { oracle, .. } =
+            OracleAccounts::parse(program_id, remaining.get(bond_accounts..).unwrap_or(&[]))?;
+        check::address("oracle", oracle, &escrow_info.oracle)?;
 
-        let escrow_info = Escrow::unpack(&escrow_account.try_borrow_data()?)?;
+        let price = escrow_info
+            .oracle_kind
+            .read_price(&oracle.try_borrow_data()?)?;
+        if price.is_stale(clock.slot, escrow_info.max_staleness) {
+            return Err(EscrowError::StaleOraclePrice.into());
+        }
+        if !escrow_info.price_condition.is_met(
+            &price,
+            escrow_info.trigger_price,
+            escrow_info.trigger_expo,
+        )? {
+            return Err(EscrowError::PriceConditionNotMet.into());
+        }
+        Ok(())
+    }
 
-        if escrow_info.temp_token_account_pubkey != *pdas_temp_token_account.key {
-            return Err(ProgramError::InvalidAccountData);
+    /// How many of the accounts after `Exchange`'s or `Cancel`'s own settle
+    /// the escrow's bond, which depends on whether it is forfeited
+    fn bond_account_count(escrow_info: &Escrow, forfeit: bool) -> usize {
+        if escrow_info.bond == 0 {
+            0
+        } else if escrow_info.bond_vault == Pubkey::default() {
+            forfeit as usize
+        } else {
+            2
         }
+    }
 
-        if escrow_info.initializer_pubkey != *initializers_main_account.key {
-            return Err(ProgramError::InvalidAccountData);
+    /// Calls the swap's callback program, if it has one, with `settlement`,
+    /// once the tokens have moved. `remaining` starts with the callback
+    /// program and its authority; the accounts after them go to the callback
+    /// program as they are.
+    ///
+    /// This runs before the bond is settled and the escrow closed, since the
+    /// runtime rejects a call made while lamports moved by this program have
+    /// yet to balance. The escrow goes to the callback read-only. The runtime
+    /// only lets a program be reentered through a direct call to itself, so
+    /// refusing to call this program, and to hand the escrow over writable,
+    /// keeps the callback from settling the same escrow again.
+    fn call_back<'a>(
+        escrow_info: &Escrow,
+        settlement: &Settlement,
+        remaining: &[AccountInfo<'a>],
+        escrow_account: &AccountInfo<'a>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if !escrow_info.has_callback() {
+            return Ok(());
+        }
+        let CallbackAccounts {
+            callback_program,
+            callback_authority,
+            remaining: passed,
+            ..
+        } = CallbackAccounts::parse(program_id, remaining)?;
+        check::address(
+            "callback_program",
+            callback_program,
+            &escrow_info.callback_program,
+        )?;
+        if callback_program.key == program_id
+            || passed
+                .iter()
+                .any(|account| account.key == escrow_account.key)
+        {
+            return Err(EscrowError::CallbackReentrancy.into());
         }
+        let (authority, bump) =
+            Pubkey::find_program_address(&[b"callback", escrow_account.key.as_ref()], program_id);
+        check::address("callback_authority", callback_authority, &authority)?;
+
+        let mut metas = vec![
+            AccountMeta::new_readonly(authority, true),
+            AccountMeta::new_readonly(*escrow_account.key, false),
+        ];
+        // the taker's or maker's signature is for this instruction, not for a
+        // program the maker picked, so no passed account signs the callback
+        metas.extend(passed.iter().map(|account| match account.is_writable {
+            true => AccountMeta::new(*account.key, false),
+            false => AccountMeta::new_readonly(*account.key, false),
+        }));
+        let mut infos = vec![callback_authority.clone(), escrow_account.clone()];
+        infos.extend(passed.iter().cloned());
+        infos.push(callback_program.clone());
+
+        msg!("Calling the swap's callback program...");
+        invoke_signed(
+            &Instruction {
+                program_id: *callback_program.key,
+                accounts: metas,
+                data: settlement.pack(),
+            },
+            &infos,
+            &[&[b"callback", escrow_account.key.as_ref(), &[bump]]],
+        )
+    }
 
-        if escrow_info.initializer_token_to_receive_account_pubkey
-            != *initializers_token_to_receive_account.key
+    /// Checks `proof` puts the taker on the escrow's allow list, if it has one
+    fn check_allowed(escrow_info: &Escrow, taker: &Pubkey, proof: &[[u8; 32]]) -> ProgramResult {
+        if escrow_info.has_allow_list()
+            && !merkle::verify(&escrow_info.allow_list_root, taker, proof)
         {
-            return Err(ProgramError::InvalidAccountData);
+            return Err(EscrowError::TakerNotAllowed.into());
         }
+        Ok(())
+    }
 
-        let token_program = next_account_info(account_info_iter)?;
+    /// Swaps the taker's tokens for the ones offered, calls the swap's
+    /// callback program back, then closes the escrow, returning its rent and
+    /// any lamport bond to the maker
+    #[allow(clippy::too_many_arguments)]
+    fn settle_swap<'a>(
+        escrow_info: &Escrow,
+        offered_amount: u64,
+        offered_mint: &Pubkey,
+        taker: &AccountInfo<'a>,
+        takers_sending_token_account: &AccountInfo<'a>,
+        takers_token_to_receive_account: &AccountInfo<'a>,
+        vault: &AccountInfo<'a>,
+        initializers_main_account: &AccountInfo<'a>,
+        initializers_token_to_receive_account: &AccountInfo<'a>,
+        escrow_account: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        nonce: u8,
+        remaining: &[AccountInfo<'a>],
+        callback_accounts: &[AccountInfo<'a>],
+        program_id: &Pubkey,
+        clock: &Clock,
+    ) -> ProgramResult {
+        let pda = pda_account.key;
+        let requested_mint =
+            TokenAccount::unpack(&initializers_token_to_receive_account.try_borrow_data()?)?.mint;
 
         let transfer_to_initializer_ix = spl_token::instruction::transfer(
             token_program.key,
             takers_sending_token_account.key,
             initializers_token_to_receive_account.key,
             taker.key,
-            &[&taker.key],
+            &[taker.key],
             escrow_info.expected_amount,
         )?;
         msg!("Calling the token program to transfer tokens to the escrow's initializer...");
@@ -172,21 +3088,19 @@ impl Processor {
             ],
         )?;
 
-        let pda_account = next_account_info(account_info_iter)?;
-
         let transfer_to_taker_ix = spl_token::instruction::transfer(
             token_program.key,
-            pdas_temp_token_account.key,
+            vault.key,
             takers_token_to_receive_account.key,
-            &pda,
-            &[&pda],
-            pdas_temp_token_account_info.amount,
+            pda,
+            &[pda],
+            offered_amount,
         )?;
         msg!("Calling the token program to transfer tokens to the taker...");
         invoke_signed(
             &transfer_to_taker_ix,
             &[
-                pdas_temp_token_account.clone(),
+                vault.clone(),
                 takers_token_to_receive_account.clone(),
                 pda_account.clone(),
                 token_program.clone(),
@@ -194,23 +3108,84 @@ impl Processor {
             &[&[&b"escrow"[..], &[nonce]]],
         )?;
 
-        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
-            token_program.key,
-            pdas_temp_token_account.key,
-            initializers_main_account.key,
-            &pda,
-            &[&pda],
+        Self::call_back(
+            escrow_info,
+            &Settlement {
+                kind: SettlementKind::Exchanged,
+                escrow: *escrow_account.key,
+                maker: escrow_info.initializer_pubkey,
+                taker: *taker.key,
+                offered_mint: *offered_mint,
+                offered_amount,
+                requested_mint,
+                requested_amount: escrow_info.expected_amount,
+                slot: clock.slot,
+                unix_timestamp: clock.unix_timestamp,
+            },
+            callback_accounts,
+            escrow_account,
+            program_id,
         )?;
-        msg!("Calling the token program to close pda's temp account...");
-        invoke_signed(
-            &close_pdas_temp_acc_ix,
-            &[
-                pdas_temp_token_account.clone(),
-                initializers_main_account.clone(),
-                pda_account.clone(),
-                token_program.clone(),
-            ],
-            &[&[&b"escrow"[..], &[nonce]]],
+        Self::close_swap(
+            escrow_info,
+            vault,
+            initializers_main_account,
+            escrow_account,
+            token_program,
+            pda_account,
+            nonce,
+            remaining,
+            clock,
+        )?;
+        EscrowEvent::Exchanged {
+            escrow: *escrow_account.key,
+            maker: escrow_info.initializer_pubkey,
+            taker: *taker.key,
+            offered_mint: *offered_mint,
+            offered_amount,
+            requested_mint,
+            requested_amount: escrow_info.expected_amount,
+            clock: EventClock::from(clock),
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Closes a swap whose tokens have changed hands, along with its vault,
+    /// returning their rent and the bond to the maker
+    #[allow(clippy::too_many_arguments)]
+    fn close_swap<'a>(
+        escrow_info: &Escrow,
+        vault: &AccountInfo<'a>,
+        initializers_main_account: &AccountInfo<'a>,
+        escrow_account: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        nonce: u8,
+        remaining: &[AccountInfo<'a>],
+        clock: &Clock,
+    ) -> ProgramResult {
+        // the maker's own account stays theirs
+        if !escrow_info.is_delegated() {
+            Self::close_vault(
+                vault,
+                initializers_main_account,
+                token_program,
+                pda_account,
+                nonce,
+            )?;
+        }
+
+        let bond_event = Self::settle_bond(
+            escrow_info,
+            false,
+            remaining,
+            escrow_account,
+            initializers_main_account,
+            token_program,
+            pda_account,
+            nonce,
+            clock,
         )?;
 
         msg!("Closing the escrow account...");
@@ -221,126 +3196,433 @@ impl Processor {
         **escrow_account.try_borrow_mut_lamports()? = 0; //no money
         *escrow_account.try_borrow_mut_data()? = &mut []; //no data (the moment this epoch ends, this account is gone! Might not see on block explorer anymore?)
 
+        if let Some(event) = bond_event {
+            event.emit();
+        }
         Ok(())
     }
 
-    fn process_cancel(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let initializer = next_account_info(account_info_iter)?;
+    /// Checks the PDA may still move `amount` out of a maker's token account
+    /// as its delegate, and that the account still holds that much
+    fn check_delegation(account: &TokenAccount, pda: &Pubkey, amount: u64) -> ProgramResult {
+        if account.delegate != Some(*pda).into() {
+            return Err(EscrowError::DelegationRevoked.into());
+        }
+        if account.delegated_amount < amount {
+            return Err(EscrowError::InsufficientDelegation.into());
+        }
+        // an approval doesn't keep the maker from spending the tokens
+        if account.amount < amount {
+            return Err(EscrowError::InsufficientBalance.into());
+        }
+        Ok(())
+    }
+
+    /// Creates a rent-exempt account owned by the program at a PDA, paid by
+    /// `payer`. Lamports someone sent to the address beforehand count toward
+    /// the rent instead of blocking the account from being created.
+    fn create_pda_account<'a>(
+        payer: &AccountInfo<'a>,
+        account: &AccountInfo<'a>,
+        space: usize,
+        seeds: &[&[u8]],
+        system_program: &AccountInfo<'a>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let rent = Rent::get()?.minimum_balance(space);
+        let shortfall = rent.saturating_sub(account.lamports());
+        if shortfall > 0 {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, shortfall),
+                &[payer.clone(), account.clone(), system_program.clone()],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(account.key, space as u64),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account.key, program_id),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )
+    }
+
+    /// Packs the escrow, first growing the account if the extensions it now
+    /// uses don't fit. The initializer tops up the rent, and the lamports of
+    /// a bond in lamports must already be in the account.
+    fn store_resized<'a>(
+        escrow_info: Escrow,
+        escrow_account: &AccountInfo<'a>,
+        initializer: &AccountInfo<'a>,
+        system_program: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        let len = escrow_info.packed_len();
+        if len > escrow_account.data_len() {
+            // a bond in lamports sits on top of the rent
+            let bond = match escrow_info.bond_vault == Pubkey::default() {
+                true => escrow_info.bond,
+                false => 0,
+            };
+            let top_up = Rent::get()?
+                .minimum_balance(len)
+                .saturating_add(bond)
+                .saturating_sub(escrow_account.lamports());
+            if top_up > 0 {
+                msg!("Calling the system program to top up the escrow's rent...");
+                invoke(
+                    &system_instruction::transfer(initializer.key, escrow_account.key, top_up),
+                    &[
+                        initializer.clone(),
+                        escrow_account.clone(),
+                        system_program.clone(),
+                    ],
+                )?;
+            }
+            escrow_account.realloc(len, true)?;
+        }
+        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)
+    }
+
+    /// Loads an escrow already migrated to the current layout, the only one
+    /// whose terms can still change
+    fn load_current(
+        escrow_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Escrow, ProgramError> {
+        let escrow_info: Escrow = loader::load(escrow_account, program_id, true)?;
+        if Escrow::version(&escrow_account.try_borrow_data()?)? != ESCROW_VERSION {
+            return Err(EscrowError::OutdatedEscrow.into());
+        }
+        Ok(escrow_info)
+    }
+
+    /// Loads the swap whose terms `initializer` is changing, along with the
+    /// clock. Nothing about it may change while it is reserved: the reserving
+    /// taker paid for the deal as it stood.
+    fn load_mutable_terms(
+        initializer: &AccountInfo,
+        escrow_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<(Escrow, Clock), ProgramError> {
+        let escrow_info = Self::load_current(escrow_account, program_id)?;
 
-        if !initializer.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+        check::address("initializer", initializer, &escrow_info.initializer_pubkey)?;
+        if escrow_info.mode != EscrowMode::Swap {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        let clock = Clock::get()?;
+        if escrow_info.is_reserved(clock.slot) {
+            return Err(EscrowError::EscrowReserved.into());
         }
+        Ok((escrow_info, clock))
+    }
+
+    /// Takes the bond a swap is being opened with, if any, and returns the
+    /// event recording it. A bond in lamports must already be in the escrow
+    /// account on top of its rent. A token bond's account comes after the
+    /// instruction's own and is handed to the PDA, so the swap never opens
+    /// without its bond.
+    #[allow(clippy::too_many_arguments)]
+    fn take_bond<'a>(
+        escrow_info: &mut Escrow,
+        bond: Option<BondTerms>,
+        initializer: &AccountInfo<'a>,
+        escrow_account: &AccountInfo<'a>,
+        remaining: &[AccountInfo<'a>],
+        rent: &Rent,
+        pda: &Pubkey,
+        clock: &Clock,
+    ) -> Result<Option<EscrowEvent>, ProgramError> {
+        let Some(bond) = bond else {
+            return Ok(None);
+        };
+        escrow_info.bond_recipient = bond.recipient;
+        let mut bond_mint = Pubkey::default();
+        if bond.lamports > 0 {
+            let spare = escrow_account
+                .lamports()
+                .saturating_sub(rent.minimum_balance(escrow_account.data_len()));
+            if spare < bond.lamports {
+                return Err(EscrowError::InvalidBond.into());
+            }
+            escrow_info.bond = bond.lamports;
+        } else {
+            let InitTokenBondAccounts {
+                bond_token_account,
+                token_program,
+                ..
+            } = InitTokenBondAccounts::parse(escrow_account.owner, remaining)?;
+            check::authority("bond_token_account", bond_token_account, initializer.key)?;
+            let vault = TokenAccount::unpack(&bond_token_account.try_borrow_data()?)?;
+            if vault.amount == 0 {
+                return Err(EscrowError::InvalidBond.into());
+            }
+            escrow_info.bond = vault.amount;
+            escrow_info.bond_vault = *bond_token_account.key;
+            bond_mint = vault.mint;
 
-        let pda_temp_token_account = next_account_info(account_info_iter)?;
-        let initializer_main_account = next_account_info(account_info_iter)?;
-        let initializer_sent_token_account = next_account_info(account_info_iter)?;
-        let escrow_account = next_account_info(account_info_iter)?;
+            Self::hand_vault_to_pda(initializer, bond_token_account, token_program, pda)?;
+        }
 
-        if escrow_account.owner != program_id || escrow_account.is_writable == false {
-            return Err(ProgramError::IllegalOwner);
-        } //wont need to do this with Anchor (make sure escrow account's owner is the program ID! Make sure it's also writable!)
+        Ok(Some(EscrowEvent::BondPosted {
+            escrow: *escrow_account.key,
+            maker: *initializer.key,
+            recipient: Self::bond_recipient(escrow_info, escrow_account.owner),
+            bond_mint,
+            amount: escrow_info.bond,
+            clock: EventClock::from(clock),
+        }))
+    }
 
-        let escrow_info = Escrow::unpack(&escrow_account.try_borrow_data()?)?;
+    /// Who a forfeited bond goes to: its recipient, or the fee vault if the
+    /// maker named none
+    fn bond_recipient(escrow_info: &Escrow, program_id: &Pubkey) -> Pubkey {
+        match escrow_info.bond_recipient == Pubkey::default() {
+            true => instruction::fee_vault(program_id),
+            false => escrow_info.bond_recipient,
+        }
+    }
 
-        if escrow_info.initializer_pubkey != *initializer.key {
-            //check if initializer pubkey is equal to the one that submitted the transaction
-            return Err(ProgramError::InvalidAccountData);
+    /// Hands a swap's bond to its recipient if `forfeit`, or back to the
+    /// maker otherwise, from the accounts after the instruction's own. A bond
+    /// in lamports goes back with the escrow's rent when it closes, so only a
+    /// forfeited one moves here.
+    #[allow(clippy::too_many_arguments)]
+    fn settle_bond<'a>(
+        escrow_info: &Escrow,
+        forfeit: bool,
+        remaining: &[AccountInfo<'a>],
+        escrow_account: &AccountInfo<'a>,
+        initializers_main_account: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        nonce: u8,
+        clock: &Clock,
+    ) -> Result<Option<EscrowEvent>, ProgramError> {
+        if escrow_info.bond == 0 {
+            return Ok(None);
         }
+        let program_id = escrow_account.owner;
+        let recipient = Self::bond_recipient(escrow_info, program_id);
+        let mut bond_mint = Pubkey::default();
+        if escrow_info.bond_vault == Pubkey::default() {
+            if forfeit {
+                let LamportBondAccounts { bond_recipient, .. } =
+                    LamportBondAccounts::parse(program_id, remaining)?;
+                check::address("bond_recipient", bond_recipient, &recipient)?;
 
-        let token_program = next_account_info(account_info_iter)?;
-        let pda_account_info = next_account_info(account_info_iter)?;
-        let pda_token_account_info =
-            TokenAccount::unpack(&pda_temp_token_account.try_borrow_data()?)?;
-        let (pda, nonce) = Pubkey::find_program_address(&[b"escrow"], program_id); //nonce is what you get back when you call this "find_PA", need for invoke_signed.
+                msg!("Forfeiting the bond...");
+                **escrow_account.try_borrow_mut_lamports()? = escrow_account
+                    .lamports()
+                    .checked_sub(escrow_info.bond)
+                    .ok_or(EscrowError::AmountOverflow)?;
+                **bond_recipient.try_borrow_mut_lamports()? = bond_recipient
+                    .lamports()
+                    .checked_add(escrow_info.bond)
+                    .ok_or(EscrowError::AmountOverflow)?;
+            }
+        } else {
+            let TokenBondAccounts {
+                bond_vault,
+                bond_destination,
+                ..
+            } = TokenBondAccounts::parse(program_id, remaining)?;
+            check::address("bond_vault", bond_vault, &escrow_info.bond_vault)?;
+            let owner = if forfeit {
+                &recipient
+            } else {
+                &escrow_info.initializer_pubkey
+            };
+            check::authority("bond_destination", bond_destination, owner)?;
 
-        //transfer tokens back to initializer
-        let transfer_to_initializer_ix = spl_token::instruction::transfer(
+            let vault = TokenAccount::unpack(&bond_vault.try_borrow_data()?)?;
+            bond_mint = vault.mint;
+            msg!("Calling the token program to settle the bond...");
+            Self::pay_from_vault(
+                bond_vault,
+                bond_destination,
+                vault.amount,
+                token_program,
+                pda_account,
+                nonce,
+            )?;
+            Self::close_vault(
+                bond_vault,
+                initializers_main_account,
+                token_program,
+                pda_account,
+                nonce,
+            )?;
+        }
+
+        let escrow = *escrow_account.key;
+        let maker = escrow_info.initializer_pubkey;
+        let amount = escrow_info.bond;
+        let clock = EventClock::from(clock);
+        Ok(Some(if forfeit {
+            EscrowEvent::BondForfeited {
+                escrow,
+                maker,
+                recipient,
+                bond_mint,
+                amount,
+                clock,
+            }
+        } else {
+            EscrowEvent::BondRefunded {
+                escrow,
+                maker,
+                bond_mint,
+                amount,
+                clock,
+            }
+        }))
+    }
+
+    /// Pays the vault out, each destination getting its amount, then closes
+    /// the vault and the escrow, returning their rent to the maker
+    fn release_vault<'a>(
+        vault: &AccountInfo<'a>,
+        payouts: &[(&AccountInfo<'a>, u64)],
+        initializers_main_account: &AccountInfo<'a>,
+        escrow_account: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        nonce: u8,
+    ) -> ProgramResult {
+        for (destination, amount) in payouts {
+            if *amount == 0 {
+                continue;
+            }
+            Self::pay_from_vault(
+                vault,
+                destination,
+                *amount,
+                token_program,
+                pda_account,
+                nonce,
+            )?;
+        }
+
+        Self::close_vault(
+            vault,
+            initializers_main_account,
+            token_program,
+            pda_account,
+            nonce,
+        )?;
+
+        msg!("Closing the escrow account...");
+        **initializers_main_account.try_borrow_mut_lamports()? = initializers_main_account
+            .lamports()
+            .checked_add(escrow_account.lamports())
+            .ok_or(EscrowError::AmountOverflow)?;
+        **escrow_account.try_borrow_mut_lamports()? = 0;
+        *escrow_account.try_borrow_mut_data()? = &mut [];
+        Ok(())
+    }
+
+    /// Closes an emptied vault, returning its rent to the maker
+    fn close_vault<'a>(
+        vault: &AccountInfo<'a>,
+        initializers_main_account: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        nonce: u8,
+    ) -> ProgramResult {
+        let pda = pda_account.key;
+        let close_ix = spl_token::instruction::close_account(
             token_program.key,
-            pda_temp_token_account.key,
-            initializer_sent_token_account.key,
-            &pda,
-            &[&pda],
-            pda_token_account_info.amount,
+            vault.key,
+            initializers_main_account.key,
+            pda,
+            &[pda],
         )?;
-        msg!("Calling token program to transfer tokens back to initializer");
+        msg!("Calling the token program to close pda's temp account...");
         invoke_signed(
-            &transfer_to_initializer_ix,
+            &close_ix,
             &[
-                pda_temp_token_account.clone(),
-                initializer_sent_token_account.clone(),
-                pda_account_info.clone(),
+                vault.clone(),
+                initializers_main_account.clone(),
+                pda_account.clone(),
                 token_program.clone(),
             ],
-            &[&[&b"escrow"[..], &[nonce]]], //verifies PDA token account generated/PDA account sent it are correct.
-        )?;
+            &[&[&b"escrow"[..], &[nonce]]],
+        )
+    }
 
-        //close the escrow account
-        let close_escrow_token_acct_ix = spl_token::instruction::close_account(
-            token_program.key, //include program ID anytime you're doing anything with `spl-token`
-            pda_temp_token_account.key,
-            initializer_main_account.key,
-            &pda,
-            &[&pda],
+    /// Transfers `amount` out of a vault, signing as the PDA
+    fn pay_from_vault<'a>(
+        vault: &AccountInfo<'a>,
+        destination: &AccountInfo<'a>,
+        amount: u64,
+        token_program: &AccountInfo<'a>,
+        pda_account: &AccountInfo<'a>,
+        nonce: u8,
+    ) -> ProgramResult {
+        let pda = pda_account.key;
+        let transfer_ix = spl_token::instruction::transfer(
+            token_program.key,
+            vault.key,
+            destination.key,
+            pda,
+            &[pda],
+            amount,
         )?;
-
-        msg!("Calling token program to close escrow token account");
         invoke_signed(
-            &close_escrow_token_acct_ix,
+            &transfer_ix,
             &[
-                pda_temp_token_account.clone(),
-                initializer_main_account.clone(),
-                pda_account_info.clone(),
+                vault.clone(),
+                destination.clone(),
+                pda_account.clone(),
                 token_program.clone(),
             ],
             &[&[&b"escrow"[..], &[nonce]]],
-        )?;
-
-        msg!("Closing the escrow account...");
-        **initializer.try_borrow_mut_lamports()? = initializer_main_account
-            .lamports()
-            .checked_add(escrow_account.lamports())
-            .ok_or(EscrowError::AmountOverflow)?; //check that there is no overflow (u64)
-        **escrow_account.try_borrow_mut_lamports()? = 0;
-        *escrow_account.try_borrow_mut_data()? = &mut [];
-
-        Ok(())
+        )
     }
 
-    /* Way of resetting rather than having to cancel after 1000 seconds*/
-    //write the reset time lock function
-    //must be called by initiator and
-    //load the escrow state
-    //get the clock slot
-    //set the stroed time out to current_slot + 100
-    //set unlock_time to current_slot + 1000
-    fn process_reset_time_lock(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let initializer = next_account_info(account_info_iter)?;
-
-        let slot = Clock::get()?.slot;
-        let unlock_time = slot.checked_add(100).unwrap();
-        let time_out = unlock_time.checked_add(1000).unwrap();
-
-        if !initializer.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-
-        let escrow_account = next_account_info(account_info_iter)?;
-
-        if escrow_account.owner != program_id || escrow_account.is_writable == false {
-            return Err(ProgramError::IllegalOwner);
-        }
-
-        let escrow_info = &mut Escrow::unpack(&escrow_account.try_borrow_data()?)?;
-
-        if escrow_info.initializer_pubkey != *initializer.key {
-            return Err(ProgramError::InvalidAccountData);
-        }
+    /// Makes the PDA the owner of a maker's temp token account, which turns it
+    /// into the escrow's vault
+    fn hand_vault_to_pda<'a>(
+        initializer: &AccountInfo<'a>,
+        temp_token_account: &AccountInfo<'a>,
+        token_program: &AccountInfo<'a>,
+        pda: &Pubkey,
+    ) -> ProgramResult {
+        let owner_change_ix = spl_token::instruction::set_authority(
+            token_program.key,
+            temp_token_account.key,
+            Some(pda),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            initializer.key,
+            &[initializer.key],
+        )?;
 
-        escrow_info.unlock_time = unlock_time;
-        escrow_info.time_out = time_out;
+        msg!("Calling the token program to transfer token account ownership...");
+        invoke(
+            &owner_change_ix,
+            &[
+                temp_token_account.clone(),
+                initializer.clone(),
+                token_program.clone(),
+            ],
+        )
+    }
 
-        Ok(())
+    /// Turns the requested durations into the absolute (unlock_time, time_out) slots
+    fn lock_window(
+        slot: u64,
+        unlock_slots: u64,
+        time_out_slots: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        let unlock_time = slot
+            .checked_add(unlock_slots)
+            .ok_or(EscrowError::AmountOverflow)?;
+        let time_out = unlock_time
+            .checked_add(time_out_slots)
+            .ok_or(EscrowError::AmountOverflow)?;
+        Ok((unlock_time, time_out))
     }
 }
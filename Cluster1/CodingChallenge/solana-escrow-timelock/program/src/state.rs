@@ -1,4 +1,8 @@
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
 use solana_program::{
+    entrypoint::ProgramResult,
+    hash, keccak,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
@@ -6,6 +10,213 @@ use solana_program::{
 
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 
+use crate::{
+    error::EscrowError,
+    oracle::{OracleKind, PriceCondition},
+};
+
+/// First byte of every account the program owns, telling the types apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AccountType {
+    Uninitialized = 0,
+    Escrow = 1,
+    OrderNonce = 2,
+    /// Marks a signed order as filled. Nothing else is stored; the address,
+    /// derived from the order's hash, is the record.
+    OrderReceipt = 3,
+    Pool = 4,
+    PoolShare = 5,
+}
+
+/// Implemented by every account type the program owns, so
+/// [`loader`](crate::loader) can tell them apart
+pub trait ProgramAccount: Pack + IsInitialized {
+    const ACCOUNT_TYPE: AccountType;
+}
+
+/// Layout version new escrows are written with
+pub const ESCROW_VERSION: u8 = 1;
+
+/// Size of the escrows written before the layout had a header. Those start
+/// straight away with the `is_initialized` flag and count as version 0.
+pub const LEGACY_ESCROW_LEN: usize = 121;
+
+/// How many milestones a milestone escrow can hold
+pub const MAX_MILESTONES: usize = 8;
+
+/// Slots after an arbitrated deal's time out that its arbiter has to rule on
+/// a dispute, about a day, before anyone can settle it the default way
+pub const DISPUTE_GRACE_SLOTS: u64 = 216_000;
+
+/// `account_type` and `version`
+const HEADER_LEN: usize = 2;
+
+/// Offset of `pda_bump`, right after the fields legacy escrows have
+const PDA_BUMP_OFFSET: usize = HEADER_LEN + 120;
+
+/// `amount`, `deadline` and `state`
+const MILESTONE_LEN: usize = 8 + 8 + 1;
+
+/// `tag` and `len` of an [`Extension`]
+const EXTENSION_HEADER_LEN: usize = 1 + 2;
+
+/// The entries an escrow's tail can hold, past the fields every escrow has.
+/// Each is written as its tag, the length of its data as a `u16` and the
+/// data, and only if its fields aren't all at their defaults. Zeroes fill
+/// the rest of the account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum Extension {
+    /// `hash_kind` and `hash`
+    HashLock = 1,
+    /// `arbiter`, `deal_state` and `default_outcome`
+    Arbitration = 2,
+    /// `milestone_count`, `remaining` and the milestones in use
+    Milestones = 3,
+    /// `bond`, `bond_vault` and `bond_recipient`
+    Bond = 4,
+    /// `premium`
+    Premium = 5,
+    /// `delegated_amount`
+    Delegation = 6,
+    /// `designated_taker` and `exclusive_until`
+    ExclusiveTaker = 7,
+    /// `allow_list_root`
+    AllowList = 8,
+    /// `oracle`, `oracle_kind`, `price_condition`, `trigger_price`,
+    /// `trigger_expo` and `max_staleness`
+    PriceCondition = 9,
+    /// `callback_program`
+    Callback = 10,
+}
+
+impl Extension {
+    const ALL: [Extension; 10] = [
+        Self::HashLock,
+        Self::Arbitration,
+        Self::Milestones,
+        Self::Bond,
+        Self::Premium,
+        Self::Delegation,
+        Self::ExclusiveTaker,
+        Self::AllowList,
+        Self::PriceCondition,
+        Self::Callback,
+    ];
+}
+
+/// What releases an escrow's vault to a taker
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum EscrowMode {
+    /// Anyone who pays `expected_amount` with `Exchange`
+    #[default]
+    Swap = 0,
+    /// The counterparty, with `Redeem` and a preimage of `hash`
+    HashLock = 1,
+    /// The counterparty, with `Claim`, once the maker let it time out
+    DeadMansSwitch = 2,
+    /// Nobody; the maker withdraws with `Unlock` once it unlocks
+    SavingsLock = 3,
+    /// The counterparty, when the maker releases it, the arbiter rules for
+    /// them or the deal settles in their favour after `time_out`
+    Arbitrated = 4,
+    /// The counterparty, one milestone at a time as the maker approves them
+    Milestones = 5,
+}
+
+/// How far an arbitrated deal got
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum DealState {
+    #[default]
+    Open = 0,
+    /// The taker says they delivered
+    Delivered = 1,
+    /// Only the arbiter can settle it now
+    Disputed = 2,
+}
+
+/// Where an undelivered, undisputed arbitrated deal's vault goes once it
+/// times out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum DefaultOutcome {
+    #[default]
+    RefundMaker = 0,
+    PayTaker = 1,
+}
+
+/// Whether a milestone has been paid out yet, and to whom
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum MilestoneState {
+    #[default]
+    Pending = 0,
+    /// Paid to the payee
+    Approved = 1,
+    /// Returned to the maker after its deadline
+    Refunded = 2,
+}
+
+/// One tranche of a milestone escrow
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Milestone {
+    pub amount: u64,
+    /// Slot after which the maker can take the tranche back, if it has one.
+    /// Packed as 0 when it doesn't.
+    pub deadline: Option<u64>,
+    pub state: MilestoneState,
+}
+
+impl Milestone {
+    fn unpack(src: &[u8; MILESTONE_LEN]) -> Result<Self, ProgramError> {
+        let (amount, deadline, state) = array_refs![src, 8, 8, 1];
+        Ok(Milestone {
+            amount: u64::from_le_bytes(*amount),
+            deadline: Some(u64::from_le_bytes(*deadline)).filter(|deadline| *deadline != 0),
+            state: MilestoneState::from_u8(state[0]).ok_or(ProgramError::InvalidAccountData)?,
+        })
+    }
+
+    fn pack(&self, dst: &mut [u8; MILESTONE_LEN]) {
+        let (amount_dst, deadline_dst, state_dst) = mut_array_refs![dst, 8, 8, 1];
+        *amount_dst = self.amount.to_le_bytes();
+        *deadline_dst = self.deadline.unwrap_or(0).to_le_bytes();
+        state_dst[0] = self.state as u8;
+    }
+
+    /// Whether the maker can take the tranche back at `slot`
+    pub fn is_expired(&self, slot: u64) -> bool {
+        self.state == MilestoneState::Pending
+            && self.deadline.is_some_and(|deadline| slot > deadline)
+    }
+}
+
+/// The longest preimage `Redeem` takes, which keeps the `Redeemed` event
+/// bounded
+pub const MAX_PREIMAGE_LEN: usize = 64;
+
+/// How a hash-lock escrow's `hash` was computed from the preimage
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum HashKind {
+    #[default]
+    Sha256 = 0,
+    Keccak256 = 1,
+}
+
+impl HashKind {
+    pub fn hash(self, preimage: &[u8]) -> [u8; 32] {
+        match self {
+            Self::Sha256 => hash::hashv(&[preimage]).to_bytes(),
+            Self::Keccak256 => keccak::hashv(&[preimage]).to_bytes(),
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct Escrow {
     pub is_initialized: bool,
     pub initializer_pubkey: Pubkey,
@@ -18,37 +229,362 @@ pub struct Escrow {
     //unlock and lock times
     pub unlock_time: u64,
     pub time_out: u64,
+
+    /// Bump of the program's `escrow` PDA, so taking and cancelling don't
+    /// have to search for it. 0 in legacy escrows.
+    pub pda_bump: u8,
+
+    /// How the escrow is taken. Legacy escrows are swaps.
+    pub mode: EscrowMode,
+    /// Hash-lock escrows only
+    pub hash_kind: HashKind,
+    /// Hash-lock escrows only: the hash the taker has to reveal a preimage of
+    pub hash: [u8; 32],
+    /// The only account that may take the escrow, in modes that name one
+    pub counterparty: Pubkey,
+
+    /// Arbitrated escrows only: who settles disputes
+    pub arbiter: Pubkey,
+    /// Arbitrated escrows only
+    pub deal_state: DealState,
+    /// Arbitrated escrows only
+    pub default_outcome: DefaultOutcome,
+
+    /// Milestone escrows only: how many of `milestones` are in use
+    pub milestone_count: u8,
+    /// Milestone escrows only: what is still pending in the vault
+    pub remaining: u64,
+    /// Milestone escrows only
+    pub milestones: [Milestone; MAX_MILESTONES],
+
+    /// What the maker put up as a bond, 0 if nothing. The maker loses it by
+    /// cancelling between `unlock_time` and `time_out`.
+    pub bond: u64,
+    /// The PDA-owned token account holding a token bond. `Pubkey::default()`
+    /// for a bond in lamports, which the escrow account itself holds on top
+    /// of its rent.
+    pub bond_vault: Pubkey,
+    /// Who gets a forfeited bond, the fee vault if `Pubkey::default()`
+    pub bond_recipient: Pubkey,
+
+    /// Swaps only: what a taker pays the maker, in the requested mint, to
+    /// reserve the swap until `time_out`. 0 if the maker doesn't offer
+    /// reservations. The taker holding the reservation is the
+    /// `counterparty`.
+    pub premium: u64,
+
+    /// Swaps only: what the PDA pulls out of the maker's own token account
+    /// as its delegate on exchange. Non-zero makes the escrow a
+    /// delegate-approval one, where `temp_token_account_pubkey` is that
+    /// account and the maker keeps it. 0 for escrows holding their tokens in
+    /// a PDA-owned vault.
+    pub delegated_amount: u64,
+
+    /// Swaps only: the only taker who may exchange from `unlock_time`
+    /// through `exclusive_until`. `Pubkey::default()` if the swap is public
+    /// as soon as it unlocks.
+    pub designated_taker: Pubkey,
+    /// Last slot of the designated taker's exclusive period
+    pub exclusive_until: u64,
+
+    /// Swaps only: root of the Merkle tree of takers allowed to exchange, see
+    /// [`merkle`](crate::merkle). All zeroes if anyone may.
+    pub allow_list_root: [u8; 32],
+
+    /// Swaps only: the oracle account whose price has to meet
+    /// `price_condition` for the swap to be taken, see
+    /// [`oracle`](crate::oracle)
+    pub oracle: Pubkey,
+    /// The format of `oracle`
+    pub oracle_kind: OracleKind,
+    /// `PriceCondition::None` if the swap doesn't read an oracle
+    pub price_condition: PriceCondition,
+    /// The condition's trigger, worth `trigger_price * 10^trigger_expo`
+    pub trigger_price: i64,
+    pub trigger_expo: i32,
+    /// How many slots old the oracle's price may be
+    pub max_staleness: u64,
+
+    /// Swaps only: the program `Exchange` and `Cancel` call with a
+    /// [`Settlement`](crate::callback::Settlement) once the swap has
+    /// settled. `Pubkey::default()` if nothing is called.
+    pub callback_program: Pubkey,
 }
 
-impl Sealed for Escrow {}
+impl Escrow {
+    /// The milestones in use
+    pub fn milestones(&self) -> &[Milestone] {
+        &self.milestones[..self.milestone_count as usize]
+    }
 
-impl IsInitialized for Escrow {
-    fn is_initialized(&self) -> bool {
-        self.is_initialized
+    /// Whether the maker's tokens stay in their own account until exchange
+    pub fn is_delegated(&self) -> bool {
+        self.delegated_amount > 0
     }
-}
 
-impl Pack for Escrow {
-    const LEN: usize = 121; //105 + 8 + 8 --> for unlock_time and time_out
-    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, Escrow::LEN];
+    /// Whether a taker holds a reservation on the swap at `slot`
+    pub fn is_reserved(&self, slot: u64) -> bool {
+        self.mode == EscrowMode::Swap
+            && self.counterparty != Pubkey::default()
+            && slot <= self.time_out
+    }
+
+    /// Whether only the designated taker may exchange at `slot`
+    pub fn is_exclusive(&self, slot: u64) -> bool {
+        self.mode == EscrowMode::Swap
+            && self.designated_taker != Pubkey::default()
+            && slot <= self.exclusive_until
+    }
+
+    /// Whether only the takers on an allow list may exchange
+    pub fn has_allow_list(&self) -> bool {
+        self.mode == EscrowMode::Swap && self.allow_list_root != [0; 32]
+    }
+
+    /// Whether the swap can only be taken while its oracle's price meets a
+    /// condition
+    pub fn has_price_condition(&self) -> bool {
+        self.mode == EscrowMode::Swap && self.price_condition != PriceCondition::None
+    }
+
+    /// Whether settling the swap calls a program
+    pub fn has_callback(&self) -> bool {
+        self.mode == EscrowMode::Swap && self.callback_program != Pubkey::default()
+    }
+
+    /// Layout version of a packed escrow
+    pub fn version(src: &[u8]) -> Result<u8, ProgramError> {
+        if src.len() == LEGACY_ESCROW_LEN {
+            return Ok(0);
+        }
+        match src {
+            [account_type, version, ..] if *account_type == AccountType::Escrow as u8 => {
+                Ok(*version)
+            }
+            [account_type, ..] if *account_type == AccountType::Uninitialized as u8 => {
+                Ok(ESCROW_VERSION)
+            }
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// The stored PDA bump, if the escrow isn't a legacy one. Reads just that
+    /// byte rather than the whole escrow.
+    pub fn pda_bump(src: &[u8]) -> Result<Option<u8>, ProgramError> {
+        Ok(match Self::version(src)? {
+            0 => None,
+            _ => Some(
+                *src.get(PDA_BUMP_OFFSET)
+                    .ok_or(ProgramError::InvalidAccountData)?,
+            ),
+        })
+    }
+
+    /// Bytes the escrow takes packed: the fields every escrow has and the
+    /// extensions in use. The account may be longer.
+    pub fn packed_len(&self) -> usize {
+        Self::LEN
+            + Extension::ALL
+                .iter()
+                .filter_map(|extension| self.pack_extension(*extension))
+                .map(|data| EXTENSION_HEADER_LEN + data.len())
+                .sum::<usize>()
+    }
+
+    /// The data of `extension`, or `None` if its fields are all at their
+    /// defaults and it can be left out
+    fn pack_extension(&self, extension: Extension) -> Option<Vec<u8>> {
+        let data = match extension {
+            Extension::HashLock => [&[self.hash_kind as u8][..], &self.hash].concat(),
+            Extension::Arbitration => [
+                self.arbiter.as_ref(),
+                &[self.deal_state as u8, self.default_outcome as u8],
+            ]
+            .concat(),
+            Extension::Milestones => {
+                let mut data =
+                    [&[self.milestone_count][..], &self.remaining.to_le_bytes()].concat();
+                for milestone in self.milestones() {
+                    let mut packed = [0; MILESTONE_LEN];
+                    milestone.pack(&mut packed);
+                    data.extend_from_slice(&packed);
+                }
+                data
+            }
+            Extension::Bond => [
+                &self.bond.to_le_bytes()[..],
+                self.bond_vault.as_ref(),
+                self.bond_recipient.as_ref(),
+            ]
+            .concat(),
+            Extension::Premium => self.premium.to_le_bytes().to_vec(),
+            Extension::Delegation => self.delegated_amount.to_le_bytes().to_vec(),
+            Extension::ExclusiveTaker => [
+                self.designated_taker.as_ref(),
+                &self.exclusive_until.to_le_bytes(),
+            ]
+            .concat(),
+            Extension::AllowList => self.allow_list_root.to_vec(),
+            Extension::PriceCondition => [
+                self.oracle.as_ref(),
+                &[self.oracle_kind as u8, self.price_condition as u8],
+                &self.trigger_price.to_le_bytes(),
+                &self.trigger_expo.to_le_bytes(),
+                &self.max_staleness.to_le_bytes(),
+            ]
+            .concat(),
+            Extension::Callback => self.callback_program.as_ref().to_vec(),
+        };
+        Some(data).filter(|data| data.iter().any(|byte| *byte != 0))
+    }
+
+    fn unpack_extension(&mut self, extension: Extension, src: &[u8]) -> ProgramResult {
+        match extension {
+            Extension::HashLock => {
+                let (hash_kind, hash) = array_refs![exact::<33>(src)?, 1, 32];
+                self.hash_kind =
+                    HashKind::from_u8(hash_kind[0]).ok_or(ProgramError::InvalidAccountData)?;
+                self.hash = *hash;
+            }
+            Extension::Arbitration => {
+                let (arbiter, deal_state, default_outcome) =
+                    array_refs![exact::<34>(src)?, 32, 1, 1];
+                self.arbiter = Pubkey::new_from_array(*arbiter);
+                self.deal_state =
+                    DealState::from_u8(deal_state[0]).ok_or(ProgramError::InvalidAccountData)?;
+                self.default_outcome = DefaultOutcome::from_u8(default_outcome[0])
+                    .ok_or(ProgramError::InvalidAccountData)?;
+            }
+            Extension::Milestones => {
+                if src.len() < 1 + 8 {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                let (head, milestones) = src.split_at(1 + 8);
+                let (milestone_count, remaining) = array_refs![exact::<9>(head)?, 1, 8];
+                if milestone_count[0] as usize > MAX_MILESTONES
+                    || milestones.len() != milestone_count[0] as usize * MILESTONE_LEN
+                {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                self.milestone_count = milestone_count[0];
+                self.remaining = u64::from_le_bytes(*remaining);
+                for (milestone, src) in self
+                    .milestones
+                    .iter_mut()
+                    .zip(milestones.chunks_exact(MILESTONE_LEN))
+                {
+                    *milestone = Milestone::unpack(exact(src)?)?;
+                }
+            }
+            Extension::Bond => {
+                let (bond, bond_vault, bond_recipient) = array_refs![exact::<72>(src)?, 8, 32, 32];
+                self.bond = u64::from_le_bytes(*bond);
+                self.bond_vault = Pubkey::new_from_array(*bond_vault);
+                self.bond_recipient = Pubkey::new_from_array(*bond_recipient);
+            }
+            Extension::Premium => self.premium = u64::from_le_bytes(*exact(src)?),
+            Extension::Delegation => self.delegated_amount = u64::from_le_bytes(*exact(src)?),
+            Extension::ExclusiveTaker => {
+                let (designated_taker, exclusive_until) = array_refs![exact::<40>(src)?, 32, 8];
+                self.designated_taker = Pubkey::new_from_array(*designated_taker);
+                self.exclusive_until = u64::from_le_bytes(*exclusive_until);
+            }
+            Extension::AllowList => self.allow_list_root = *exact(src)?,
+            Extension::PriceCondition => {
+                let (
+                    oracle,
+                    oracle_kind,
+                    price_condition,
+                    trigger_price,
+                    trigger_expo,
+                    max_staleness,
+                ) = array_refs![exact::<54>(src)?, 32, 1, 1, 8, 4, 8];
+                self.oracle = Pubkey::new_from_array(*oracle);
+                self.oracle_kind =
+                    OracleKind::from_u8(oracle_kind[0]).ok_or(ProgramError::InvalidAccountData)?;
+                self.price_condition = PriceCondition::from_u8(price_condition[0])
+                    .ok_or(ProgramError::InvalidAccountData)?;
+                self.trigger_price = i64::from_le_bytes(*trigger_price);
+                self.trigger_expo = i32::from_le_bytes(*trigger_expo);
+                self.max_staleness = u64::from_le_bytes(*max_staleness);
+            }
+            Extension::Callback => self.callback_program = Pubkey::new_from_array(*exact(src)?),
+        }
+        Ok(())
+    }
+
+    fn unpack_legacy(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, LEGACY_ESCROW_LEN];
+        let (is_initialized, body) = array_refs![src, 1, 120];
+        let mut escrow = Self::unpack_body(body);
+        escrow.is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        // the old program packed `unlock_time` big-endian, and only wrote the
+        // window on ResetTimeLock. It never enforced one, so an escrow that was
+        // never reset stays takeable until it is cancelled.
+        escrow.unlock_time = u64::from_be_bytes(*array_ref![body, 104, 8]);
+        if escrow.time_out == 0 {
+            escrow.time_out = u64::MAX;
+        }
+        Ok(escrow)
+    }
+
+    /// The fields every escrow has, then its extensions up to the padding
+    fn unpack_current(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (core, mut tail) = src.split_at(Self::LEN);
+        let core = array_ref![core, 0, Escrow::LEN];
+        let (account_type, version, body, pda_bump, mode, counterparty) =
+            array_refs![core, 1, 1, 120, 1, 1, 32];
+        let mut escrow = Self::unpack_body(body);
+        // a fresh account has no version yet
+        escrow.is_initialized = match (account_type, version) {
+            ([0], _) => false,
+            ([1], [ESCROW_VERSION]) => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        escrow.pda_bump = pda_bump[0];
+        escrow.mode = EscrowMode::from_u8(mode[0]).ok_or(ProgramError::InvalidAccountData)?;
+        escrow.counterparty = Pubkey::new_from_array(*counterparty);
+
+        while let [tag, rest @ ..] = tail {
+            if *tag == 0 {
+                break;
+            }
+            let extension = Extension::from_u8(*tag).ok_or(ProgramError::InvalidAccountData)?;
+            let [len_lo, len_hi, rest @ ..] = rest else {
+                return Err(ProgramError::InvalidAccountData);
+            };
+            let len = u16::from_le_bytes([*len_lo, *len_hi]) as usize;
+            if rest.len() < len {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let (data, rest) = rest.split_at(len);
+            escrow.unpack_extension(extension, data)?;
+            tail = rest;
+        }
+        Ok(escrow)
+    }
+
+    /// The fields every escrow has had since before the header, in the
+    /// order they were introduced
+    fn unpack_body(src: &[u8; 120]) -> Self {
         let (
-            is_initialized,
             initializer_pubkey,
             temp_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
             expected_amount,
             unlock_time,
             time_out,
-        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 8];
-        let is_initialized = match is_initialized {
-            [0] => false,
-            [1] => true,
-            _ => return Err(ProgramError::InvalidAccountData),
-        };
+        ) = array_refs![src, 32, 32, 32, 8, 8, 8];
 
-        Ok(Escrow {
-            is_initialized,
+        Escrow {
+            is_initialized: true,
             initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
             temp_token_account_pubkey: Pubkey::new_from_array(*temp_token_account_pubkey),
             initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(
@@ -57,38 +593,369 @@ impl Pack for Escrow {
             expected_amount: u64::from_le_bytes(*expected_amount),
             unlock_time: u64::from_le_bytes(*unlock_time),
             time_out: u64::from_le_bytes(*time_out),
-        })
+            ..Escrow::default()
+        }
+    }
+}
+
+/// `src` as an array, if it is exactly `N` bytes long
+fn exact<const N: usize>(src: &[u8]) -> Result<&[u8; N], ProgramError> {
+    src.try_into().map_err(|_| ProgramError::InvalidAccountData)
+}
+
+impl Sealed for Escrow {}
+
+impl ProgramAccount for Escrow {
+    const ACCOUNT_TYPE: AccountType = AccountType::Escrow;
+}
+
+impl IsInitialized for Escrow {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Escrow {
+    /// Size of the fields every escrow has, and of a plain swap. See
+    /// [`packed_len`](Escrow::packed_len) for the size of any other escrow.
+    const LEN: usize = HEADER_LEN + 32 * 3 + 8 * 3 + 1 + 1 + 32;
+
+    /// Reads legacy escrows as well as current ones
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        match Self::version(input)? {
+            0 => Self::unpack_legacy(input),
+            ESCROW_VERSION => Self::unpack_current(input),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let escrow = Self::unpack_unchecked(input)?;
+        if escrow.is_initialized() {
+            Ok(escrow)
+        } else {
+            Err(ProgramError::UninitializedAccount)
+        }
+    }
+
+    /// Writes into any account of at least [`packed_len`](Escrow::packed_len)
+    /// bytes, as escrows grow and shrink with the extensions they use
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() < src.packed_len() {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        src.pack_into_slice(dst);
+        Ok(())
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        Self::unpack_current(src)
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, Escrow::LEN];
+        let (core, tail) = dst.split_at_mut(Escrow::LEN);
+        let core = array_mut_ref![core, 0, Escrow::LEN];
         let (
-            is_initialized_dst,
+            account_type_dst,
+            version_dst,
             initializer_pubkey_dst,
             temp_token_account_pubkey_dst,
             initializer_token_to_receive_account_pubkey_dst,
             expected_amount_dst,
             unlock_time_dst,
             time_out_dst,
-        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 8];
+            pda_bump_dst,
+            mode_dst,
+            counterparty_dst,
+        ) = mut_array_refs![core, 1, 1, 32, 32, 32, 8, 8, 8, 1, 1, 32];
 
-        let Escrow {
-            is_initialized,
-            initializer_pubkey,
-            temp_token_account_pubkey,
-            initializer_token_to_receive_account_pubkey,
-            expected_amount,
+        account_type_dst[0] = if self.is_initialized {
+            AccountType::Escrow
+        } else {
+            AccountType::Uninitialized
+        } as u8;
+        version_dst[0] = ESCROW_VERSION;
+        initializer_pubkey_dst.copy_from_slice(self.initializer_pubkey.as_ref());
+        temp_token_account_pubkey_dst.copy_from_slice(self.temp_token_account_pubkey.as_ref());
+        initializer_token_to_receive_account_pubkey_dst
+            .copy_from_slice(self.initializer_token_to_receive_account_pubkey.as_ref());
+        *expected_amount_dst = self.expected_amount.to_le_bytes();
+        *unlock_time_dst = self.unlock_time.to_le_bytes();
+        *time_out_dst = self.time_out.to_le_bytes();
+        pda_bump_dst[0] = self.pda_bump;
+        mode_dst[0] = self.mode as u8;
+        counterparty_dst.copy_from_slice(self.counterparty.as_ref());
+
+        let mut offset = 0;
+        for extension in Extension::ALL {
+            let Some(data) = self.pack_extension(extension) else {
+                continue;
+            };
+            tail[offset] = extension as u8;
+            tail[offset + 1..offset + EXTENSION_HEADER_LEN]
+                .copy_from_slice(&(data.len() as u16).to_le_bytes());
+            offset += EXTENSION_HEADER_LEN;
+            tail[offset..offset + data.len()].copy_from_slice(&data);
+            offset += data.len();
+        }
+        tail[offset..].fill(0);
+    }
+}
+
+/// Size of an order receipt, which is only its account type
+pub const ORDER_RECEIPT_LEN: usize = 1;
+
+/// A maker's current nonce for signed orders, at the PDA
+/// `["nonce", maker]`. Orders signed with an older nonce can't be filled.
+/// Makers who never cancelled have no account yet and are at nonce 0.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OrderNonce {
+    pub is_initialized: bool,
+    pub maker: Pubkey,
+    pub nonce: u64,
+    pub bump: u8,
+}
+
+impl Sealed for OrderNonce {}
+
+impl ProgramAccount for OrderNonce {
+    const ACCOUNT_TYPE: AccountType = AccountType::OrderNonce;
+}
+
+impl IsInitialized for OrderNonce {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for OrderNonce {
+    const LEN: usize = 1 + 32 + 8 + 1;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, OrderNonce::LEN];
+        let (account_type, maker, nonce, bump) = array_refs![src, 1, 32, 8, 1];
+        Ok(OrderNonce {
+            is_initialized: account_type[0] == AccountType::OrderNonce as u8,
+            maker: Pubkey::new_from_array(*maker),
+            nonce: u64::from_le_bytes(*nonce),
+            bump: bump[0],
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, OrderNonce::LEN];
+        let (account_type_dst, maker_dst, nonce_dst, bump_dst) = mut_array_refs![dst, 1, 32, 8, 1];
+        account_type_dst[0] = if self.is_initialized {
+            AccountType::OrderNonce
+        } else {
+            AccountType::Uninitialized
+        } as u8;
+        maker_dst.copy_from_slice(self.maker.as_ref());
+        *nonce_dst = self.nonce.to_le_bytes();
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Makers selling the same mint at the same price, pooled in one vault.
+/// Makers deposit until `unlock_time`, takers fill between `unlock_time` and
+/// `time_out`, and makers withdraw their share of what is left and of the
+/// proceeds outside that window.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Pool {
+    pub is_initialized: bool,
+    /// Paid the rent of the pool and its vaults, and gets it back
+    pub creator: Pubkey,
+    /// PDA-owned token account holding what hasn't been filled
+    pub vault: Pubkey,
+    /// PDA-owned token account holding what takers paid
+    pub proceeds_vault: Pubkey,
+    /// Takers fill whole lots: `requested_per_lot` for `offered_per_lot`
+    pub offered_per_lot: u64,
+    pub requested_per_lot: u64,
+    pub unlock_time: u64,
+    pub time_out: u64,
+    /// Shares are minted 1:1 with deposits, all before any fill
+    pub total_shares: u64,
+    pub unfilled: u64,
+    pub proceeds: u64,
+    pub pda_bump: u8,
+}
+
+impl Sealed for Pool {}
+
+impl ProgramAccount for Pool {
+    const ACCOUNT_TYPE: AccountType = AccountType::Pool;
+}
+
+impl IsInitialized for Pool {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pool {
+    /// What filling `lots` takes out of the pool and pays into it, as
+    /// (offered, requested)
+    pub fn fill(&mut self, lots: u64) -> Result<(u64, u64), ProgramError> {
+        let offered = lots
+            .checked_mul(self.offered_per_lot)
+            .ok_or(EscrowError::AmountOverflow)?;
+        let requested = lots
+            .checked_mul(self.requested_per_lot)
+            .ok_or(EscrowError::AmountOverflow)?;
+        self.unfilled = self
+            .unfilled
+            .checked_sub(offered)
+            .ok_or(EscrowError::PoolExhausted)?;
+        self.proceeds = self
+            .proceeds
+            .checked_add(requested)
+            .ok_or(EscrowError::AmountOverflow)?;
+        Ok((offered, requested))
+    }
+
+    /// Redeems `shares` for their part of what is unfilled and of the
+    /// proceeds, as (unfilled, proceeds). Each withdrawal is worked out from
+    /// what is still in the pool, rounding down, so the rounding stays in the
+    /// pool and the last shares out take exactly what is left. A pool
+    /// nobody deposited into redeems no shares for nothing, so it can be
+    /// closed.
+    pub fn withdraw(&mut self, shares: u64) -> Result<(u64, u64), ProgramError> {
+        if shares > self.total_shares || (shares == 0 && self.total_shares > 0) {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        if shares == 0 {
+            return Ok((0, 0));
+        }
+        let part =
+            |amount: u64| (amount as u128 * shares as u128 / self.total_shares as u128) as u64;
+        let (unfilled, proceeds) = (part(self.unfilled), part(self.proceeds));
+        self.total_shares -= shares;
+        self.unfilled -= unfilled;
+        self.proceeds -= proceeds;
+        Ok((unfilled, proceeds))
+    }
+}
+
+impl Pack for Pool {
+    const LEN: usize = 1 + 32 * 3 + 8 * 7 + 1;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Pool::LEN];
+        let (
+            account_type,
+            creator,
+            vault,
+            proceeds_vault,
+            offered_per_lot,
+            requested_per_lot,
             unlock_time,
             time_out,
-        } = self;
+            total_shares,
+            unfilled,
+            proceeds,
+            pda_bump,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 8, 8, 8, 8, 8, 1];
+        Ok(Pool {
+            is_initialized: account_type[0] == AccountType::Pool as u8,
+            creator: Pubkey::new_from_array(*creator),
+            vault: Pubkey::new_from_array(*vault),
+            proceeds_vault: Pubkey::new_from_array(*proceeds_vault),
+            offered_per_lot: u64::from_le_bytes(*offered_per_lot),
+            requested_per_lot: u64::from_le_bytes(*requested_per_lot),
+            unlock_time: u64::from_le_bytes(*unlock_time),
+            time_out: u64::from_le_bytes(*time_out),
+            total_shares: u64::from_le_bytes(*total_shares),
+            unfilled: u64::from_le_bytes(*unfilled),
+            proceeds: u64::from_le_bytes(*proceeds),
+            pda_bump: pda_bump[0],
+        })
+    }
 
-        is_initialized_dst[0] = *is_initialized as u8;
-        initializer_pubkey_dst.copy_from_slice(initializer_pubkey.as_ref());
-        temp_token_account_pubkey_dst.copy_from_slice(temp_token_account_pubkey.as_ref());
-        initializer_token_to_receive_account_pubkey_dst
-            .copy_from_slice(initializer_token_to_receive_account_pubkey.as_ref());
-        *expected_amount_dst = expected_amount.to_le_bytes();
-        *unlock_time_dst = unlock_time.to_be_bytes();
-        *time_out_dst = time_out.to_le_bytes();
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Pool::LEN];
+        let (
+            account_type_dst,
+            creator_dst,
+            vault_dst,
+            proceeds_vault_dst,
+            offered_per_lot_dst,
+            requested_per_lot_dst,
+            unlock_time_dst,
+            time_out_dst,
+            total_shares_dst,
+            unfilled_dst,
+            proceeds_dst,
+            pda_bump_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 8, 8, 8, 8, 8, 1];
+        account_type_dst[0] = if self.is_initialized {
+            AccountType::Pool
+        } else {
+            AccountType::Uninitialized
+        } as u8;
+        creator_dst.copy_from_slice(self.creator.as_ref());
+        vault_dst.copy_from_slice(self.vault.as_ref());
+        proceeds_vault_dst.copy_from_slice(self.proceeds_vault.as_ref());
+        *offered_per_lot_dst = self.offered_per_lot.to_le_bytes();
+        *requested_per_lot_dst = self.requested_per_lot.to_le_bytes();
+        *unlock_time_dst = self.unlock_time.to_le_bytes();
+        *time_out_dst = self.time_out.to_le_bytes();
+        *total_shares_dst = self.total_shares.to_le_bytes();
+        *unfilled_dst = self.unfilled.to_le_bytes();
+        *proceeds_dst = self.proceeds.to_le_bytes();
+        pda_bump_dst[0] = self.pda_bump;
+    }
+}
+
+/// A maker's shares in a pool, at the PDA `["share", pool, maker]`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PoolShare {
+    pub is_initialized: bool,
+    pub pool: Pubkey,
+    pub maker: Pubkey,
+    pub shares: u64,
+    pub bump: u8,
+}
+
+impl Sealed for PoolShare {}
+
+impl ProgramAccount for PoolShare {
+    const ACCOUNT_TYPE: AccountType = AccountType::PoolShare;
+}
+
+impl IsInitialized for PoolShare {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for PoolShare {
+    const LEN: usize = 1 + 32 + 32 + 8 + 1;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, PoolShare::LEN];
+        let (account_type, pool, maker, shares, bump) = array_refs![src, 1, 32, 32, 8, 1];
+        Ok(PoolShare {
+            is_initialized: account_type[0] == AccountType::PoolShare as u8,
+            pool: Pubkey::new_from_array(*pool),
+            maker: Pubkey::new_from_array(*maker),
+            shares: u64::from_le_bytes(*shares),
+            bump: bump[0],
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, PoolShare::LEN];
+        let (account_type_dst, pool_dst, maker_dst, shares_dst, bump_dst) =
+            mut_array_refs![dst, 1, 32, 32, 8, 1];
+        account_type_dst[0] = if self.is_initialized {
+            AccountType::PoolShare
+        } else {
+            AccountType::Uninitialized
+        } as u8;
+        pool_dst.copy_from_slice(self.pool.as_ref());
+        maker_dst.copy_from_slice(self.maker.as_ref());
+        *shares_dst = self.shares.to_le_bytes();
+        bump_dst[0] = self.bump;
     }
 }
@@ -0,0 +1,108 @@
+mod common;
+
+use common::{escrow_failure, program_test, send, start, token_balance, Swap, OFFERED};
+use solana_escrow::{
+    error::EscrowError,
+    instruction::{self, EscrowInstruction},
+    merkle::{self, MAX_PROOF_LEN},
+    state::Escrow,
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{instruction::Instruction, signature::Signer};
+
+/// A swap only the taker and `other` may take, and the taker's proof
+async fn listed_swap(other: &Pubkey) -> (ProgramTestContext, Swap, Vec<[u8; 32]>) {
+    let swap = Swap::new();
+    let sibling = merkle::leaf(other);
+    let mut test = program_test();
+    swap.add_to(
+        &mut test,
+        Escrow {
+            allow_list_root: merkle::parent(&merkle::leaf(&swap.taker.pubkey()), &sibling),
+            ..swap.escrow_info(0, u64::MAX)
+        },
+    );
+    (start(test).await, swap, vec![sibling])
+}
+
+/// The taker's `Exchange`, proving they are listed with `proof`
+fn exchange(swap: &Swap, proof: &[[u8; 32]]) -> Instruction {
+    instruction::exchange(
+        &solana_escrow::id(),
+        &swap.taker.pubkey(),
+        &swap.taker_send,
+        &swap.taker_receive,
+        &swap.vault,
+        &swap.maker.pubkey(),
+        &swap.maker_receive,
+        &swap.escrow,
+        &spl_token::id(),
+        OFFERED,
+        proof,
+    )
+    .unwrap()
+}
+
+#[test]
+fn exchanges_without_a_proof_keep_their_old_data() {
+    let instruction = EscrowInstruction::Exchange {
+        amount: 100,
+        proof: vec![],
+    };
+    let data = instruction.pack();
+    assert_eq!(data, [&[1][..], &100u64.to_le_bytes()].concat());
+    assert_eq!(EscrowInstruction::unpack(&data).unwrap(), instruction);
+}
+
+#[test]
+fn proofs_longer_than_the_maximum_are_rejected() {
+    let proof = vec![[7; 32]; MAX_PROOF_LEN];
+    let instruction = EscrowInstruction::Reserve {
+        premium: 5,
+        proof: proof.clone(),
+    };
+    let data = instruction.pack();
+    assert_eq!(data.len(), 1 + 8 + 4 + 32 * MAX_PROOF_LEN);
+    assert_eq!(EscrowInstruction::unpack(&data).unwrap(), instruction);
+
+    let mut too_long = EscrowInstruction::Reserve {
+        premium: 5,
+        proof: [proof, vec![[7; 32]]].concat(),
+    }
+    .pack();
+    assert_eq!(
+        EscrowInstruction::unpack(&too_long).unwrap_err(),
+        EscrowError::InvalidInstruction.into()
+    );
+    too_long.truncate(1 + 8 + 4 + 32);
+    assert_eq!(
+        EscrowInstruction::unpack(&too_long).unwrap_err(),
+        EscrowError::InvalidInstruction.into()
+    );
+}
+
+#[tokio::test]
+async fn listed_takers_exchange_with_their_proof() {
+    let (mut context, swap, proof) = listed_swap(&Pubkey::new_unique()).await;
+    send(&mut context, &[exchange(&swap, &proof)], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+}
+
+#[tokio::test]
+async fn exchanges_with_a_wrong_or_missing_proof_are_rejected() {
+    let (mut context, swap, _) = listed_swap(&Pubkey::new_unique()).await;
+    let wrong = [merkle::leaf(&Pubkey::new_unique())];
+    for proof in [&wrong[..], &[]] {
+        assert_eq!(
+            send(&mut context, &[exchange(&swap, proof)], &[&swap.taker]).await,
+            Err(escrow_failure(EscrowError::TakerNotAllowed))
+        );
+    }
+    assert_eq!(token_balance(&mut context, &swap.vault).await, OFFERED);
+}
@@ -0,0 +1,291 @@
+mod common;
+
+use common::{
+    account, escrow, escrow_failure, failure, program_test, send, start, token_balance, Swap,
+    OFFERED, SLOT,
+};
+use native_accounts::AccountError;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{DealState, DefaultOutcome, Escrow, EscrowMode, DISPUTE_GRACE_SLOTS},
+};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+};
+
+const TIME_OUT: u64 = SLOT + 100;
+
+/// A deal paying [`OFFERED`] for the swap's taker's work, timing out at
+/// [`TIME_OUT`], with `arbiter` on call
+async fn deal(arbiter: &Keypair, default_outcome: DefaultOutcome) -> (ProgramTestContext, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_to(
+        &mut test,
+        Escrow {
+            mode: EscrowMode::Arbitrated,
+            counterparty: swap.taker.pubkey(),
+            arbiter: arbiter.pubkey(),
+            default_outcome,
+            ..swap.escrow_info(SLOT, TIME_OUT)
+        },
+    );
+    (start(test).await, swap)
+}
+
+fn open_dispute(swap: &Swap, party: &Keypair) -> Instruction {
+    instruction::open_dispute(&solana_escrow::id(), &party.pubkey(), &swap.escrow).unwrap()
+}
+
+/// `signer`'s `Resolve`, paying the taker into `taker_receive` and the maker
+/// into `maker_refund`
+fn resolve(swap: &Swap, signer: &Keypair, taker_bps: u16) -> Instruction {
+    instruction::resolve(
+        &solana_escrow::id(),
+        &signer.pubkey(),
+        &swap.taker_receive,
+        &swap.maker_refund,
+        &swap.maker.pubkey(),
+        &swap.vault,
+        &swap.escrow,
+        &spl_token::id(),
+        taker_bps,
+    )
+    .unwrap()
+}
+
+fn settle(swap: &Swap) -> Instruction {
+    instruction::settle(
+        &solana_escrow::id(),
+        &swap.taker_receive,
+        &swap.maker_refund,
+        &swap.maker.pubkey(),
+        &swap.vault,
+        &swap.escrow,
+        &spl_token::id(),
+    )
+    .unwrap()
+}
+
+/// What the taker and the maker were paid out of the vault
+async fn payouts(context: &mut ProgramTestContext, swap: &Swap) -> (u64, u64) {
+    (
+        token_balance(context, &swap.taker_receive).await,
+        token_balance(context, &swap.maker_refund).await,
+    )
+}
+
+#[tokio::test]
+async fn the_maker_can_release_the_vault_to_the_taker() {
+    let arbiter = Keypair::new();
+    let (mut context, swap) = deal(&arbiter, DefaultOutcome::RefundMaker).await;
+    let release = instruction::release(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.taker_receive,
+        &swap.vault,
+        &swap.escrow,
+        &spl_token::id(),
+    )
+    .unwrap();
+    send(&mut context, &[release], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn only_the_arbiter_resolves_a_dispute() {
+    let arbiter = Keypair::new();
+    let (mut context, swap) = deal(&arbiter, DefaultOutcome::RefundMaker).await;
+    assert_eq!(
+        send(
+            &mut context,
+            &[resolve(&swap, &arbiter, 5_000)],
+            &[&arbiter]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::DealNotDisputed))
+    );
+
+    // outsiders can't dispute a deal
+    let stranger = Keypair::new();
+    assert_eq!(
+        send(
+            &mut context,
+            &[open_dispute(&swap, &stranger)],
+            &[&stranger]
+        )
+        .await,
+        Err(failure(AccountError::ConstraintAddress as u32))
+    );
+    send(
+        &mut context,
+        &[open_dispute(&swap, &swap.taker)],
+        &[&swap.taker],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        escrow(&mut context, &swap.escrow).await.deal_state,
+        DealState::Disputed
+    );
+    let release = instruction::release(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.taker_receive,
+        &swap.vault,
+        &swap.escrow,
+        &spl_token::id(),
+    )
+    .unwrap();
+    assert_eq!(
+        send(&mut context, &[release], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::DealDisputed))
+    );
+    assert_eq!(
+        send(
+            &mut context,
+            &[resolve(&swap, &swap.maker, 0)],
+            &[&swap.maker]
+        )
+        .await,
+        Err(failure(AccountError::ConstraintAddress as u32))
+    );
+
+    send(
+        &mut context,
+        &[resolve(&swap, &arbiter, 2_500)],
+        &[&arbiter],
+    )
+    .await
+    .unwrap();
+    assert_eq!(payouts(&mut context, &swap).await, (250, 750));
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn resolving_rounds_the_takers_share_down() {
+    for (taker_bps, to_taker) in [
+        (0, 0),
+        (1, 0),
+        (3_333, 333),
+        (9_999, 999),
+        (10_000, OFFERED),
+    ] {
+        let arbiter = Keypair::new();
+        let (mut context, swap) = deal(&arbiter, DefaultOutcome::RefundMaker).await;
+        send(
+            &mut context,
+            &[open_dispute(&swap, &swap.maker)],
+            &[&swap.maker],
+        )
+        .await
+        .unwrap();
+        send(
+            &mut context,
+            &[resolve(&swap, &arbiter, taker_bps)],
+            &[&arbiter],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            payouts(&mut context, &swap).await,
+            (to_taker, OFFERED - to_taker),
+            "{} basis points",
+            taker_bps
+        );
+    }
+}
+
+#[tokio::test]
+async fn shares_over_the_whole_vault_are_rejected() {
+    let arbiter = Keypair::new();
+    let (mut context, swap) = deal(&arbiter, DefaultOutcome::RefundMaker).await;
+    send(
+        &mut context,
+        &[open_dispute(&swap, &swap.maker)],
+        &[&swap.maker],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        send(
+            &mut context,
+            &[resolve(&swap, &arbiter, 10_001)],
+            &[&arbiter]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::InvalidBasisPoints))
+    );
+}
+
+#[tokio::test]
+async fn undisputed_deals_settle_after_the_time_out() {
+    let arbiter = Keypair::new();
+    let (mut context, swap) = deal(&arbiter, DefaultOutcome::RefundMaker).await;
+    let delivered =
+        instruction::mark_delivered(&solana_escrow::id(), &swap.taker.pubkey(), &swap.escrow)
+            .unwrap();
+    send(&mut context, &[delivered], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        send(&mut context, &[settle(&swap)], &[]).await,
+        Err(escrow_failure(EscrowError::EscrowNotExpired))
+    );
+
+    // a delivered deal pays the taker whatever the default
+    context.warp_to_slot(TIME_OUT + 1).unwrap();
+    send(&mut context, &[settle(&swap)], &[]).await.unwrap();
+    assert_eq!(payouts(&mut context, &swap).await, (OFFERED, 0));
+
+    let (mut context, swap) = deal(&arbiter, DefaultOutcome::RefundMaker).await;
+    context.warp_to_slot(TIME_OUT + 1).unwrap();
+    assert_eq!(
+        send(
+            &mut context,
+            &[open_dispute(&swap, &swap.taker)],
+            &[&swap.taker]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::EscrowTimeOut))
+    );
+    send(&mut context, &[settle(&swap)], &[]).await.unwrap();
+    assert_eq!(payouts(&mut context, &swap).await, (0, OFFERED));
+}
+
+#[tokio::test]
+async fn a_dispute_the_arbiter_ignores_settles_by_default_after_the_grace_period() {
+    let arbiter = Keypair::new();
+    let (mut context, swap) = deal(&arbiter, DefaultOutcome::PayTaker).await;
+    send(
+        &mut context,
+        &[open_dispute(&swap, &swap.maker)],
+        &[&swap.maker],
+    )
+    .await
+    .unwrap();
+
+    context
+        .warp_to_slot(TIME_OUT + DISPUTE_GRACE_SLOTS)
+        .unwrap();
+    assert_eq!(
+        send(&mut context, &[settle(&swap)], &[]).await,
+        Err(escrow_failure(EscrowError::DealDisputed))
+    );
+
+    context
+        .warp_to_slot(TIME_OUT + DISPUTE_GRACE_SLOTS + 1)
+        .unwrap();
+    send(&mut context, &[settle(&swap)], &[]).await.unwrap();
+    assert_eq!(payouts(&mut context, &swap).await, (OFFERED, 0));
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
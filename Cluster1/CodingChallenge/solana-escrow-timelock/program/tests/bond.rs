@@ -0,0 +1,292 @@
+mod common;
+
+use common::{
+    account, escrow, escrow_account, escrow_failure, lamports, pda, program_test, rent_exempt,
+    send, start, token_account, token_balance, wallet, Swap, OFFERED, REQUESTED, SLOT,
+};
+use solana_escrow::{
+    error::EscrowError,
+    instruction::{self, BondTerms},
+    state::Escrow,
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    rent::Rent,
+    signature::Signer,
+};
+use spl_token::state::Account as TokenAccount;
+
+const BOND: u64 = 5_000_000;
+
+/// A swap open from slot 150 through 250, backed by a bond of [`BOND`]
+/// lamports for `recipient`, or the fee vault if that is the default key
+async fn bonded_swap(recipient: &Pubkey) -> (ProgramTestContext, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_accounts_to(&mut test);
+    if *recipient != Pubkey::default() {
+        test.add_account(*recipient, wallet());
+    }
+    let mut escrow = escrow_account(Escrow {
+        bond: BOND,
+        bond_recipient: *recipient,
+        ..swap.escrow_info(SLOT + 50, SLOT + 150)
+    });
+    escrow.lamports += BOND;
+    test.add_account(swap.escrow, escrow);
+    (start(test).await, swap)
+}
+
+/// The maker's `Cancel`, handing a forfeited bond to `recipient`
+fn forfeiting_cancel(swap: &Swap, recipient: &Pubkey) -> Instruction {
+    let mut cancel = swap.cancel();
+    cancel.accounts.push(AccountMeta::new(*recipient, false));
+    cancel
+}
+
+#[tokio::test]
+async fn cancelling_inside_the_window_forfeits_the_bond() {
+    let recipient = Pubkey::new_unique();
+    let (mut context, swap) = bonded_swap(&recipient).await;
+    let maker = swap.maker.pubkey();
+    let maker_before = lamports(&mut context, &maker).await;
+    let escrow_lamports = lamports(&mut context, &swap.escrow).await;
+    context.warp_to_slot(SLOT + 100).unwrap();
+
+    // without the recipient there's nowhere for the bond to go
+    assert!(send(&mut context, &[swap.cancel()], &[&swap.maker])
+        .await
+        .is_err());
+    send(
+        &mut context,
+        &[forfeiting_cancel(&swap, &recipient)],
+        &[&swap.maker],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        lamports(&mut context, &recipient).await,
+        wallet().lamports + BOND
+    );
+    assert_eq!(
+        lamports(&mut context, &maker).await,
+        maker_before + escrow_lamports - BOND + token_rent()
+    );
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_refund).await,
+        OFFERED
+    );
+}
+
+#[tokio::test]
+async fn exchanging_refunds_the_bond() {
+    let recipient = Pubkey::new_unique();
+    let (mut context, swap) = bonded_swap(&recipient).await;
+    let maker = swap.maker.pubkey();
+    let maker_before = lamports(&mut context, &maker).await;
+    let escrow_lamports = lamports(&mut context, &swap.escrow).await;
+    context.warp_to_slot(SLOT + 100).unwrap();
+
+    send(&mut context, &[swap.exchange()], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        lamports(&mut context, &maker).await,
+        maker_before + escrow_lamports + token_rent()
+    );
+    assert_eq!(lamports(&mut context, &recipient).await, wallet().lamports);
+}
+
+#[tokio::test]
+async fn cancelling_after_the_time_out_refunds_the_bond() {
+    let recipient = Pubkey::new_unique();
+    let (mut context, swap) = bonded_swap(&recipient).await;
+    let maker = swap.maker.pubkey();
+    let maker_before = lamports(&mut context, &maker).await;
+    let escrow_lamports = lamports(&mut context, &swap.escrow).await;
+    context.warp_to_slot(SLOT + 151).unwrap();
+
+    send(&mut context, &[swap.cancel()], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        lamports(&mut context, &maker).await,
+        maker_before + escrow_lamports + token_rent()
+    );
+    assert_eq!(lamports(&mut context, &recipient).await, wallet().lamports);
+}
+
+#[tokio::test]
+async fn a_token_bond_is_forfeited_to_the_recipients_account() {
+    let swap = Swap::new();
+    let (recipient, bond_vault, bond_destination) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let mut test = program_test();
+    swap.add_accounts_to(&mut test);
+    test.add_account(bond_vault, token_account(&swap.offered_mint, &pda().0, 7));
+    test.add_account(
+        bond_destination,
+        token_account(&swap.offered_mint, &recipient, 0),
+    );
+    test.add_account(
+        swap.escrow,
+        escrow_account(Escrow {
+            bond: 7,
+            bond_vault,
+            bond_recipient: recipient,
+            ..swap.escrow_info(0, SLOT + 150)
+        }),
+    );
+    let mut context = start(test).await;
+
+    let mut cancel = swap.cancel();
+    cancel.accounts.extend([
+        AccountMeta::new(bond_vault, false),
+        AccountMeta::new(bond_destination, false),
+    ]);
+    send(&mut context, &[cancel], &[&swap.maker]).await.unwrap();
+    assert_eq!(token_balance(&mut context, &bond_destination).await, 7);
+    assert!(account(&mut context, &bond_vault).await.is_none());
+}
+
+/// Room for an escrow carrying a bond
+fn bonded_len() -> usize {
+    Escrow {
+        bond: BOND,
+        ..Escrow::default()
+    }
+    .packed_len()
+}
+
+/// A validator with `swap`'s parties, a vault of [`OFFERED`] still the
+/// maker's, and a blank escrow account with room for a bond, holding
+/// `lamports`
+async fn unopened_swap(swap: &Swap, lamports: u64) -> ProgramTestContext {
+    let mut test = program_test();
+    swap.add_parties_to(&mut test);
+    test.add_account(
+        swap.vault,
+        token_account(&swap.offered_mint, &swap.maker.pubkey(), OFFERED),
+    );
+    test.add_account(
+        swap.escrow,
+        Account {
+            lamports,
+            ..rent_exempt(vec![0; bonded_len()], solana_escrow::id())
+        },
+    );
+    start(test).await
+}
+
+/// The maker's `InitEscrow` for `swap` with `bond`, open from slot 150
+/// through 250
+fn bonded_init(swap: &Swap, bond: BondTerms, bond_token_account: Option<&Pubkey>) -> Instruction {
+    instruction::init_escrow(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.vault,
+        &swap.maker_receive,
+        &swap.escrow,
+        &spl_token::id(),
+        REQUESTED,
+        50,
+        150,
+        Some(bond),
+        bond_token_account,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn init_escrow_takes_a_lamport_bond_on_top_of_rent() {
+    let rent = Rent::default().minimum_balance(bonded_len());
+    for (lamports, opened) in [(rent + BOND, true), (rent + BOND - 1, false)] {
+        let swap = Swap::new();
+        let mut context = unopened_swap(&swap, lamports).await;
+        let bond = BondTerms {
+            lamports: BOND,
+            recipient: Pubkey::new_unique(),
+        };
+
+        let result = send(
+            &mut context,
+            &[bonded_init(&swap, bond, None)],
+            &[&swap.maker],
+        )
+        .await;
+        if !opened {
+            assert_eq!(result, Err(escrow_failure(EscrowError::InvalidBond)));
+            continue;
+        }
+        result.unwrap();
+        let escrow_info = escrow(&mut context, &swap.escrow).await;
+        assert_eq!(escrow_info.bond, BOND);
+        assert_eq!(escrow_info.bond_vault, Pubkey::default());
+        assert_eq!(escrow_info.bond_recipient, bond.recipient);
+    }
+}
+
+#[tokio::test]
+async fn init_escrow_hands_a_token_bond_to_the_pda() {
+    let swap = Swap::new();
+    let bond_vault = Pubkey::new_unique();
+    let mut test = program_test();
+    swap.add_parties_to(&mut test);
+    test.add_account(
+        swap.vault,
+        token_account(&swap.offered_mint, &swap.maker.pubkey(), OFFERED),
+    );
+    test.add_account(
+        bond_vault,
+        token_account(&swap.offered_mint, &swap.maker.pubkey(), 7),
+    );
+    test.add_account(
+        swap.escrow,
+        rent_exempt(vec![0; bonded_len()], solana_escrow::id()),
+    );
+    let mut context = start(test).await;
+    let bond = BondTerms {
+        lamports: 0,
+        recipient: Pubkey::default(),
+    };
+
+    send(
+        &mut context,
+        &[bonded_init(&swap, bond, Some(&bond_vault))],
+        &[&swap.maker],
+    )
+    .await
+    .unwrap();
+    let escrow_info = escrow(&mut context, &swap.escrow).await;
+    assert_eq!(escrow_info.bond, 7);
+    assert_eq!(escrow_info.bond_vault, bond_vault);
+    let vault = account(&mut context, &bond_vault).await.unwrap();
+    assert_eq!(TokenAccount::unpack(&vault.data).unwrap().owner, pda().0);
+}
+
+#[tokio::test]
+async fn a_bond_without_a_recipient_is_forfeited_to_the_fee_vault() {
+    let (mut context, swap) = bonded_swap(&Pubkey::default()).await;
+    let fee_vault = instruction::fee_vault(&solana_escrow::id());
+    context.warp_to_slot(SLOT + 100).unwrap();
+
+    send(
+        &mut context,
+        &[forfeiting_cancel(&swap, &fee_vault)],
+        &[&swap.maker],
+    )
+    .await
+    .unwrap();
+    assert_eq!(lamports(&mut context, &fee_vault).await, BOND);
+}
+
+/// What the maker gets back for the closed vault
+fn token_rent() -> u64 {
+    token_account(&Pubkey::default(), &Pubkey::default(), 0).lamports
+}
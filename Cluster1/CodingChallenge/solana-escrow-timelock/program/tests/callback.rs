@@ -0,0 +1,295 @@
+//! Settlement callbacks, run natively under `solana-program-test` against a
+//! callback program that records what it is called with.
+
+mod common;
+
+use common::{
+    account, escrow_failure, failure, program_test, rent_exempt, send, start, Swap, OFFERED,
+    REQUESTED,
+};
+use native_accounts::AccountError;
+use solana_escrow::{
+    callback::{Settlement, SettlementKind, CALLBACK_VERSION},
+    error::EscrowError,
+    instruction::{self, EscrowInstruction},
+    state::Escrow,
+};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, instruction::AccountMeta,
+    program::invoke, program_error::ProgramError, pubkey::Pubkey,
+};
+use solana_program_test::{processor, tokio, ProgramTestContext};
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    signature::Signer,
+    system_instruction, system_program,
+    transaction::TransactionError,
+};
+
+/// A callback program that checks it was called by the escrow it is told
+/// about and copies the settlement into the account after the escrow
+fn record_settlement(_: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let settlement = Settlement::unpack(data)?;
+    let [authority, escrow, record, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if !authority.is_signer
+        || *authority.key != instruction::callback_authority(&solana_escrow::id(), escrow.key)
+        || *escrow.key != settlement.escrow
+    {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    record.try_borrow_mut_data()?[..data.len()].copy_from_slice(data);
+    Ok(())
+}
+
+/// A callback program that spends the first passed account's lamports if it
+/// signed for the callback. Natively, `invoke` panics rather than fails when
+/// it lacks the signature, so it checks first.
+fn steal_from_signers(_: &Pubkey, accounts: &[AccountInfo], _: &[u8]) -> ProgramResult {
+    let [_, _, victim, thief, system_program, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if !victim.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    invoke(
+        &system_instruction::transfer(victim.key, thief.key, 1),
+        &[victim.clone(), thief.clone(), system_program.clone()],
+    )
+}
+
+struct Keys {
+    swap: Swap,
+    callback_program: Pubkey,
+    thief_program: Pubkey,
+    record: Pubkey,
+}
+
+/// Starts a validator with an open swap whose callback is `record_settlement`
+async fn open_swap() -> (ProgramTestContext, Keys) {
+    let keys = Keys {
+        swap: Swap::new(),
+        callback_program: Pubkey::new_unique(),
+        thief_program: Pubkey::new_unique(),
+        record: Pubkey::new_unique(),
+    };
+    let mut test = program_test();
+    test.add_program(
+        "record_settlement",
+        keys.callback_program,
+        processor!(record_settlement),
+    );
+    test.add_program(
+        "steal_from_signers",
+        keys.thief_program,
+        processor!(steal_from_signers),
+    );
+    keys.swap.add_to(
+        &mut test,
+        Escrow {
+            callback_program: keys.callback_program,
+            ..keys.swap.escrow_info(0, 1_000)
+        },
+    );
+    test.add_account(
+        keys.record,
+        rent_exempt(vec![0; Settlement::LEN], keys.callback_program),
+    );
+    (start(test).await, keys)
+}
+
+fn exchange(keys: &Keys, passed: &[AccountMeta]) -> Instruction {
+    let mut ix = keys.swap.exchange();
+    ix.accounts.extend([
+        AccountMeta::new_readonly(keys.callback_program, false),
+        AccountMeta::new_readonly(
+            instruction::callback_authority(&solana_escrow::id(), &keys.swap.escrow),
+            false,
+        ),
+    ]);
+    ix.accounts.extend_from_slice(passed);
+    ix
+}
+
+async fn recorded(context: &mut ProgramTestContext, keys: &Keys) -> Settlement {
+    let record = account(context, &keys.record).await.unwrap();
+    Settlement::unpack(&record.data).unwrap()
+}
+
+#[tokio::test]
+async fn exchanges_call_back_with_the_settlement() {
+    let (mut context, keys) = open_swap().await;
+    let ix = exchange(&keys, &[AccountMeta::new(keys.record, false)]);
+    send(&mut context, &[ix], &[&keys.swap.taker])
+        .await
+        .unwrap();
+
+    let settlement = recorded(&mut context, &keys).await;
+    assert_eq!(settlement.kind, SettlementKind::Exchanged);
+    assert_eq!(settlement.escrow, keys.swap.escrow);
+    assert_eq!(settlement.maker, keys.swap.maker.pubkey());
+    assert_eq!(settlement.taker, keys.swap.taker.pubkey());
+    assert_eq!(settlement.offered_mint, keys.swap.offered_mint);
+    assert_eq!(settlement.offered_amount, OFFERED);
+    assert_eq!(settlement.requested_mint, keys.swap.requested_mint);
+    assert_eq!(settlement.requested_amount, REQUESTED);
+}
+
+#[tokio::test]
+async fn cancels_call_back_with_the_settlement() {
+    let (mut context, keys) = open_swap().await;
+    let mut ix = keys.swap.cancel();
+    ix.accounts.extend([
+        AccountMeta::new_readonly(keys.callback_program, false),
+        AccountMeta::new_readonly(
+            instruction::callback_authority(&solana_escrow::id(), &keys.swap.escrow),
+            false,
+        ),
+        AccountMeta::new(keys.record, false),
+    ]);
+    send(&mut context, &[ix], &[&keys.swap.maker])
+        .await
+        .unwrap();
+
+    let settlement = recorded(&mut context, &keys).await;
+    assert_eq!(settlement.kind, SettlementKind::Cancelled);
+    assert_eq!(settlement.taker, Pubkey::default());
+    assert_eq!(settlement.offered_amount, OFFERED);
+    assert_eq!(settlement.requested_mint, Pubkey::default());
+}
+
+#[tokio::test]
+async fn the_escrow_cannot_be_passed_to_its_callback() {
+    let (mut context, keys) = open_swap().await;
+    let ix = exchange(
+        &keys,
+        &[
+            AccountMeta::new(keys.record, false),
+            AccountMeta::new(keys.swap.escrow, false),
+        ],
+    );
+    assert_eq!(
+        send(&mut context, &[ix], &[&keys.swap.taker]).await,
+        Err(escrow_failure(EscrowError::CallbackReentrancy))
+    );
+}
+
+#[tokio::test]
+async fn passed_accounts_do_not_sign_the_callback() {
+    let (mut context, mut keys) = open_swap().await;
+    let ix = instruction::set_callback(
+        &solana_escrow::id(),
+        &keys.swap.maker.pubkey(),
+        &keys.swap.escrow,
+        &keys.thief_program,
+    )
+    .unwrap();
+    send(&mut context, &[ix], &[&keys.swap.maker])
+        .await
+        .unwrap();
+
+    keys.callback_program = keys.thief_program;
+    let ix = exchange(
+        &keys,
+        &[
+            AccountMeta::new(keys.swap.taker.pubkey(), true),
+            AccountMeta::new(keys.record, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    assert_eq!(
+        send(&mut context, &[ix], &[&keys.swap.taker]).await,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::MissingRequiredSignature
+        ))
+    );
+}
+
+#[tokio::test]
+async fn only_the_escrows_callback_program_is_called() {
+    let (mut context, keys) = open_swap().await;
+    let mut ix = exchange(&keys, &[AccountMeta::new(keys.record, false)]);
+    ix.accounts[9].pubkey = spl_token::id();
+    assert_eq!(
+        send(&mut context, &[ix], &[&keys.swap.taker]).await,
+        Err(failure(AccountError::ConstraintAddress as u32))
+    );
+}
+
+#[tokio::test]
+async fn the_escrow_program_cannot_be_its_own_callback() {
+    let (mut context, keys) = open_swap().await;
+    let ix = instruction::set_callback(
+        &solana_escrow::id(),
+        &keys.swap.maker.pubkey(),
+        &keys.swap.escrow,
+        &solana_escrow::id(),
+    )
+    .unwrap();
+    assert_eq!(
+        send(&mut context, &[ix], &[&keys.swap.maker]).await,
+        Err(escrow_failure(EscrowError::CallbackReentrancy))
+    );
+}
+
+#[test]
+fn later_versions_read_as_version_1() {
+    let settlement = Settlement {
+        kind: SettlementKind::Expired,
+        escrow: Pubkey::new_unique(),
+        maker: Pubkey::new_unique(),
+        taker: Pubkey::default(),
+        offered_mint: Pubkey::new_unique(),
+        offered_amount: 1,
+        requested_mint: Pubkey::default(),
+        requested_amount: 2,
+        slot: 3,
+        unix_timestamp: 4,
+    };
+    let data = settlement.pack();
+    assert_eq!(data[8], CALLBACK_VERSION);
+    assert_eq!(Settlement::unpack(&data).unwrap(), settlement);
+
+    let mut v2 = [data.clone(), vec![5; 16]].concat();
+    v2[8] = 2;
+    assert_eq!(Settlement::unpack(&v2).unwrap(), settlement);
+
+    let invalid = ProgramError::InvalidInstructionData;
+    assert_eq!(
+        Settlement::unpack(&data[..Settlement::LEN - 1]),
+        Err(invalid.clone())
+    );
+    let mut v0 = data.clone();
+    v0[8] = 0;
+    assert_eq!(Settlement::unpack(&v0), Err(invalid.clone()));
+    let mut other = data;
+    other[..8].copy_from_slice(b"notescrw");
+    assert_eq!(Settlement::unpack(&other), Err(invalid));
+}
+
+#[test]
+fn each_escrow_has_its_own_callback_authority() {
+    let program_id = solana_escrow::id();
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    assert_ne!(
+        instruction::callback_authority(&program_id, &a),
+        instruction::callback_authority(&program_id, &b)
+    );
+    // the vault authority must never sign a callback
+    assert_ne!(
+        instruction::callback_authority(&program_id, &a),
+        instruction::escrow_authority(&program_id)
+    );
+    let data = EscrowInstruction::SetCallback {
+        callback_program: a,
+    }
+    .pack();
+    assert_eq!(
+        EscrowInstruction::unpack(&data).unwrap(),
+        EscrowInstruction::SetCallback {
+            callback_program: a
+        }
+    );
+}
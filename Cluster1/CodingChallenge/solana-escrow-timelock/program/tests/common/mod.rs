@@ -0,0 +1,343 @@
+//! What the processor tests share: a validator running the program natively,
+//! accounts to seed it with, and an open swap between a maker and a taker.
+
+#![allow(dead_code)]
+
+use solana_escrow::{
+    error::EscrowError,
+    instruction::{self, EscrowToTake},
+    processor::Processor,
+    state::{Escrow, EscrowMode},
+};
+use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    instruction::{Instruction, InstructionError},
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_program,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+/// Slot every test starts at, so windows can open before and after it
+pub const SLOT: u64 = 100;
+pub const OFFERED: u64 = 1_000;
+pub const REQUESTED: u64 = 2_000;
+
+/// A validator running the program natively, without the SBF build
+pub fn program_test() -> ProgramTest {
+    let mut test = ProgramTest::new(
+        "solana_escrow",
+        solana_escrow::id(),
+        processor!(Processor::process),
+    );
+    test.prefer_bpf(false);
+    test
+}
+
+/// Starts the validator at [`SLOT`]
+pub async fn start(test: ProgramTest) -> ProgramTestContext {
+    let mut context = test.start_with_context().await;
+    context.warp_to_slot(SLOT).unwrap();
+    context
+}
+
+/// The PDA owning every vault, and its bump
+pub fn pda() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"escrow"], &solana_escrow::id())
+}
+
+pub fn rent_exempt(data: Vec<u8>, owner: Pubkey) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+pub fn wallet() -> Account {
+    Account {
+        lamports: 1_000_000_000,
+        ..rent_exempt(vec![], system_program::id())
+    }
+}
+
+pub fn mint() -> Account {
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: u64::MAX / 2,
+            decimals: 0,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+    rent_exempt(data, spl_token::id())
+}
+
+pub fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Account {
+    delegated_token_account(mint, owner, amount, None, 0)
+}
+
+pub fn delegated_token_account(
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    delegate: Option<Pubkey>,
+    delegated_amount: u64,
+) -> Account {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            delegate: delegate.into(),
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount,
+            close_authority: COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+    rent_exempt(data, spl_token::id())
+}
+
+pub fn escrow_account(escrow: Escrow) -> Account {
+    let mut data = vec![0; escrow.packed_len()];
+    Escrow::pack(escrow, &mut data).unwrap();
+    rent_exempt(data, solana_escrow::id())
+}
+
+/// Sends `instructions` signed by the fee payer and `signers`
+pub async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(instructions, Some(&context.payer.pubkey()));
+    transaction.partial_sign(&[&context.payer], blockhash);
+    transaction.sign(signers, blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|err| err.unwrap())
+}
+
+/// How the first instruction of a transaction fails with custom error `code`
+pub fn failure(code: u32) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(code))
+}
+
+/// [`failure`] with one of the program's own errors
+pub fn escrow_failure(error: EscrowError) -> TransactionError {
+    failure(error as u32)
+}
+
+pub async fn account(context: &mut ProgramTestContext, key: &Pubkey) -> Option<Account> {
+    context.banks_client.get_account(*key).await.unwrap()
+}
+
+pub async fn lamports(context: &mut ProgramTestContext, key: &Pubkey) -> u64 {
+    account(context, key)
+        .await
+        .map_or(0, |account| account.lamports)
+}
+
+pub async fn token_balance(context: &mut ProgramTestContext, key: &Pubkey) -> u64 {
+    let account = account(context, key).await.unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+pub async fn escrow(context: &mut ProgramTestContext, key: &Pubkey) -> Escrow {
+    Escrow::unpack(&account(context, key).await.unwrap().data).unwrap()
+}
+
+/// An open swap of [`OFFERED`] tokens for [`REQUESTED`], with the accounts
+/// of its maker and a taker
+pub struct Swap {
+    pub maker: Keypair,
+    pub taker: Keypair,
+    pub escrow: Pubkey,
+    pub vault: Pubkey,
+    pub offered_mint: Pubkey,
+    pub requested_mint: Pubkey,
+    pub maker_receive: Pubkey,
+    pub maker_refund: Pubkey,
+    pub taker_send: Pubkey,
+    pub taker_receive: Pubkey,
+}
+
+impl Swap {
+    pub fn new() -> Self {
+        Swap {
+            maker: Keypair::new(),
+            taker: Keypair::new(),
+            escrow: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            offered_mint: Pubkey::new_unique(),
+            requested_mint: Pubkey::new_unique(),
+            maker_receive: Pubkey::new_unique(),
+            maker_refund: Pubkey::new_unique(),
+            taker_send: Pubkey::new_unique(),
+            taker_receive: Pubkey::new_unique(),
+        }
+    }
+
+    /// The escrow as `InitEscrow` leaves it, open from `unlock_time` through
+    /// `time_out`
+    pub fn escrow_info(&self, unlock_time: u64, time_out: u64) -> Escrow {
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey: self.maker.pubkey(),
+            temp_token_account_pubkey: self.vault,
+            initializer_token_to_receive_account_pubkey: self.maker_receive,
+            expected_amount: REQUESTED,
+            unlock_time,
+            time_out,
+            pda_bump: pda().1,
+            mode: EscrowMode::Swap,
+            ..Escrow::default()
+        }
+    }
+
+    /// Adds the wallets, mints and token accounts, the vault holding
+    /// [`OFFERED`] for the PDA, and the escrow as `escrow_info`
+    pub fn add_to(&self, test: &mut ProgramTest, escrow_info: Escrow) {
+        self.add_accounts_to(test);
+        test.add_account(self.escrow, escrow_account(escrow_info));
+    }
+
+    /// Adds everything [`add_to`](Self::add_to) does but the escrow
+    pub fn add_accounts_to(&self, test: &mut ProgramTest) {
+        self.add_parties_to(test);
+        test.add_account(
+            self.vault,
+            token_account(&self.offered_mint, &pda().0, OFFERED),
+        );
+    }
+
+    /// Adds the wallets, mints and token accounts of the maker and the taker
+    pub fn add_parties_to(&self, test: &mut ProgramTest) {
+        let (maker, taker) = (self.maker.pubkey(), self.taker.pubkey());
+        test.add_account(maker, wallet());
+        test.add_account(taker, wallet());
+        test.add_account(self.offered_mint, mint());
+        test.add_account(self.requested_mint, mint());
+        test.add_account(
+            self.maker_receive,
+            token_account(&self.requested_mint, &maker, 0),
+        );
+        test.add_account(
+            self.maker_refund,
+            token_account(&self.offered_mint, &maker, 0),
+        );
+        test.add_account(
+            self.taker_send,
+            token_account(&self.requested_mint, &taker, REQUESTED),
+        );
+        test.add_account(
+            self.taker_receive,
+            token_account(&self.offered_mint, &taker, 0),
+        );
+    }
+
+    /// The taker's `Exchange`, expecting all of [`OFFERED`]
+    pub fn exchange(&self) -> Instruction {
+        instruction::exchange(
+            &solana_escrow::id(),
+            &self.taker.pubkey(),
+            &self.taker_send,
+            &self.taker_receive,
+            &self.vault,
+            &self.maker.pubkey(),
+            &self.maker_receive,
+            &self.escrow,
+            &spl_token::id(),
+            OFFERED,
+            &[],
+        )
+        .unwrap()
+    }
+
+    /// The maker's `Cancel`, refunding into `maker_refund`
+    pub fn cancel(&self) -> Instruction {
+        instruction::cancel(
+            &solana_escrow::id(),
+            &self.maker.pubkey(),
+            &self.vault,
+            &self.maker_refund,
+            &self.escrow,
+            &spl_token::id(),
+        )
+        .unwrap()
+    }
+}
+
+/// A maker's swap among others for the same mints, as `ExchangeMany` and
+/// `SettleRing` take them
+pub struct Offer {
+    pub maker: Keypair,
+    pub escrow: Pubkey,
+    pub vault: Pubkey,
+    pub maker_receive: Pubkey,
+}
+
+impl Offer {
+    /// Adds a new maker's swap of `offered` tokens of `offered_mint` for
+    /// `requested` of `requested_mint`, open from slot 0 through `time_out`
+    pub fn add_to(
+        test: &mut ProgramTest,
+        (offered_mint, offered): (&Pubkey, u64),
+        (requested_mint, requested): (&Pubkey, u64),
+        time_out: u64,
+    ) -> Self {
+        let offer = Offer {
+            maker: Keypair::new(),
+            escrow: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            maker_receive: Pubkey::new_unique(),
+        };
+        let maker = offer.maker.pubkey();
+        test.add_account(maker, wallet());
+        test.add_account(offer.vault, token_account(offered_mint, &pda().0, offered));
+        test.add_account(
+            offer.maker_receive,
+            token_account(requested_mint, &maker, 0),
+        );
+        test.add_account(
+            offer.escrow,
+            escrow_account(Escrow {
+                is_initialized: true,
+                initializer_pubkey: maker,
+                temp_token_account_pubkey: offer.vault,
+                initializer_token_to_receive_account_pubkey: offer.maker_receive,
+                expected_amount: requested,
+                unlock_time: 0,
+                time_out,
+                pda_bump: pda().1,
+                ..Escrow::default()
+            }),
+        );
+        offer
+    }
+
+    pub fn to_take(&self) -> EscrowToTake {
+        EscrowToTake {
+            escrow: self.escrow,
+            vault: self.vault,
+            maker: self.maker.pubkey(),
+            maker_receive_account: self.maker_receive,
+        }
+    }
+}
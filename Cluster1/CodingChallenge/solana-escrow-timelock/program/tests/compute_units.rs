@@ -0,0 +1,541 @@
+//! Compute units each instruction consumes, per code path, against the
+//! budgets in `compute_units.json`.
+//!
+//! Only the SBF build meters compute units, so the benchmark is ignored by
+//! a plain `cargo test`; `cargo test-sbf --test compute_units -- --ignored`
+//! runs it, and it fails if `solana_escrow.so` hasn't been built. A path
+//! that costs more than `threshold_percent` over its recorded budget fails,
+//! and so does one without a budget; `UPDATE_COMPUTE_UNITS=1 cargo test-sbf
+//! --test compute_units -- --ignored` records the current numbers instead.
+
+mod common;
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use common::{
+    mint, pda, rent_exempt, token_account, wallet, Offer, Swap, OFFERED, REQUESTED, SLOT,
+};
+use serde_json::{json, Value};
+use solana_escrow::{
+    instruction::{self, BondTerms, EscrowToTake},
+    state::{
+        Escrow, EscrowMode, Milestone, MilestoneState, Pool, LEGACY_ESCROW_LEN, MAX_MILESTONES,
+    },
+};
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::{find_file, tokio, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+/// The layout the escrow under test was written with
+#[derive(Clone, Copy)]
+enum Layout {
+    /// The current layout, with the stored PDA bump
+    Current,
+    /// The layout from before the header, which makes the processor search
+    /// for the bump
+    Legacy,
+}
+
+/// Keys of the accounts an open escrow involves
+struct Keys {
+    program_id: Pubkey,
+    maker: Keypair,
+    taker: Keypair,
+    escrow: Pubkey,
+    vault: Pubkey,
+    maker_receive: Pubkey,
+    maker_refund: Pubkey,
+    taker_send: Pubkey,
+    taker_receive: Pubkey,
+}
+
+fn escrow_account(keys: &Keys, pda_bump: u8, time_out: u64, layout: Layout) -> Account {
+    let mut data = vec![0; Escrow::LEN];
+    Escrow::pack(
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey: keys.maker.pubkey(),
+            temp_token_account_pubkey: keys.vault,
+            initializer_token_to_receive_account_pubkey: keys.maker_receive,
+            expected_amount: REQUESTED,
+            unlock_time: 0,
+            time_out,
+            pda_bump,
+            ..Escrow::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    if let Layout::Legacy = layout {
+        // the body after the `is_initialized` flag is the same, as the unlock
+        // time of 0 reads the same either way round
+        data = [&[1], &data[2..1 + LEGACY_ESCROW_LEN]].concat();
+    }
+    rent_exempt(data, keys.program_id)
+}
+
+/// Starts a validator with the program, two mints and the accounts of the
+/// maker and taker. With `escrow` the escrow is already open, its vault
+/// owned by the PDA; without it the vault still belongs to the maker and the
+/// escrow account is allocated but empty.
+async fn start(escrow: Option<(Layout, u64)>) -> (ProgramTestContext, Keys) {
+    let program_id = Pubkey::new_unique();
+    let mut test = ProgramTest::new("solana_escrow", program_id, None);
+    test.prefer_bpf(true);
+
+    let (pda, pda_bump) = Pubkey::find_program_address(&[b"escrow"], &program_id);
+    let (offered_mint, requested_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let keys = Keys {
+        program_id,
+        maker: Keypair::new(),
+        taker: Keypair::new(),
+        escrow: Pubkey::new_unique(),
+        vault: Pubkey::new_unique(),
+        maker_receive: Pubkey::new_unique(),
+        maker_refund: Pubkey::new_unique(),
+        taker_send: Pubkey::new_unique(),
+        taker_receive: Pubkey::new_unique(),
+    };
+    let (maker, taker) = (keys.maker.pubkey(), keys.taker.pubkey());
+
+    test.add_account(maker, wallet());
+    test.add_account(taker, wallet());
+    test.add_account(offered_mint, mint());
+    test.add_account(requested_mint, mint());
+    test.add_account(
+        keys.maker_receive,
+        token_account(&requested_mint, &maker, 0),
+    );
+    test.add_account(keys.maker_refund, token_account(&offered_mint, &maker, 0));
+    test.add_account(
+        keys.taker_send,
+        token_account(&requested_mint, &taker, REQUESTED),
+    );
+    test.add_account(keys.taker_receive, token_account(&offered_mint, &taker, 0));
+    match escrow {
+        Some((layout, time_out)) => {
+            test.add_account(keys.vault, token_account(&offered_mint, &pda, OFFERED));
+            test.add_account(
+                keys.escrow,
+                escrow_account(&keys, pda_bump, time_out, layout),
+            );
+        }
+        None => {
+            test.add_account(keys.vault, token_account(&offered_mint, &maker, OFFERED));
+            test.add_account(keys.escrow, rent_exempt(vec![0; Escrow::LEN], program_id));
+        }
+    }
+
+    let mut context = test.start_with_context().await;
+    context.warp_to_slot(SLOT).unwrap();
+    (context, keys)
+}
+
+/// Compute units `instruction` consumes, signed by the fee payer and `signer`
+async fn measure(
+    context: &mut ProgramTestContext,
+    instruction: Instruction,
+    signer: &Keypair,
+) -> u64 {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, signer],
+        context.last_blockhash,
+    );
+    let simulation = context
+        .banks_client
+        .simulate_transaction(transaction)
+        .await
+        .unwrap();
+    let details = simulation.simulation_details.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())), "{:#?}", details.logs);
+    details.units_consumed
+}
+
+async fn init_escrow() -> u64 {
+    let (mut context, keys) = start(None).await;
+    let ix = instruction::init_escrow(
+        &keys.program_id,
+        &keys.maker.pubkey(),
+        &keys.vault,
+        &keys.maker_receive,
+        &keys.escrow,
+        &spl_token::id(),
+        REQUESTED,
+        0,
+        1_000,
+        None,
+        None,
+    )
+    .unwrap();
+    measure(&mut context, ix, &keys.maker).await
+}
+
+async fn exchange(layout: Layout) -> u64 {
+    let (mut context, keys) = start(Some((layout, SLOT + 1_000))).await;
+    let ix = instruction::exchange(
+        &keys.program_id,
+        &keys.taker.pubkey(),
+        &keys.taker_send,
+        &keys.taker_receive,
+        &keys.vault,
+        &keys.maker.pubkey(),
+        &keys.maker_receive,
+        &keys.escrow,
+        &spl_token::id(),
+        OFFERED,
+        &[],
+    )
+    .unwrap();
+    measure(&mut context, ix, &keys.taker).await
+}
+
+async fn cancel(layout: Layout, time_out: u64) -> u64 {
+    let (mut context, keys) = start(Some((layout, time_out))).await;
+    let ix = instruction::cancel(
+        &keys.program_id,
+        &keys.maker.pubkey(),
+        &keys.vault,
+        &keys.maker_refund,
+        &keys.escrow,
+        &spl_token::id(),
+    )
+    .unwrap();
+    measure(&mut context, ix, &keys.maker).await
+}
+
+async fn reset_time_lock() -> u64 {
+    let (mut context, keys) = start(Some((Layout::Current, SLOT + 1_000))).await;
+    let ix = instruction::reset_time_lock(
+        &keys.program_id,
+        &keys.maker.pubkey(),
+        &keys.escrow,
+        10,
+        1_000,
+    )
+    .unwrap();
+    measure(&mut context, ix, &keys.maker).await
+}
+
+async fn migrate_legacy() -> u64 {
+    let (mut context, keys) = start(Some((Layout::Legacy, SLOT + 1_000))).await;
+    let ix = instruction::migrate(&keys.program_id, &keys.maker.pubkey(), &keys.escrow).unwrap();
+    measure(&mut context, ix, &keys.maker).await
+}
+
+/// A validator loading the SBF build under the program's own id, for the
+/// paths set up with the shared test accounts
+fn sbf_program_test() -> ProgramTest {
+    let mut test = ProgramTest::new("solana_escrow", solana_escrow::id(), None);
+    test.prefer_bpf(true);
+    test
+}
+
+/// An `InitEscrow` taking a bond of a million lamports
+async fn init_escrow_bonded() -> u64 {
+    let swap = Swap::new();
+    let bond = BondTerms {
+        lamports: 1_000_000,
+        recipient: Pubkey::new_unique(),
+    };
+    let mut test = sbf_program_test();
+    swap.add_parties_to(&mut test);
+    test.add_account(
+        swap.vault,
+        token_account(&swap.offered_mint, &swap.maker.pubkey(), OFFERED),
+    );
+    let mut escrow = rent_exempt(
+        vec![
+            0;
+            Escrow {
+                bond: bond.lamports,
+                ..Escrow::default()
+            }
+            .packed_len()
+        ],
+        solana_escrow::id(),
+    );
+    escrow.lamports += bond.lamports;
+    test.add_account(swap.escrow, escrow);
+    let mut context = common::start(test).await;
+    let ix = instruction::init_escrow(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.vault,
+        &swap.maker_receive,
+        &swap.escrow,
+        &spl_token::id(),
+        REQUESTED,
+        0,
+        1_000,
+        Some(bond),
+        None,
+    )
+    .unwrap();
+    measure(&mut context, ix, &swap.maker).await
+}
+
+/// A `Cancel` inside the window, forfeiting a bond in lamports
+async fn cancel_forfeiting_bond() -> u64 {
+    let swap = Swap::new();
+    let recipient = Pubkey::new_unique();
+    let mut test = sbf_program_test();
+    swap.add_accounts_to(&mut test);
+    test.add_account(recipient, wallet());
+    let mut escrow = common::escrow_account(Escrow {
+        bond: 1_000_000,
+        bond_recipient: recipient,
+        ..swap.escrow_info(0, SLOT + 1_000)
+    });
+    escrow.lamports += 1_000_000;
+    test.add_account(swap.escrow, escrow);
+    let mut context = common::start(test).await;
+    let mut ix = swap.cancel();
+    ix.accounts.push(AccountMeta::new(recipient, false));
+    measure(&mut context, ix, &swap.maker).await
+}
+
+/// An `ExchangeMany` taking three swaps of 10 tokens for 20 each
+async fn exchange_many() -> u64 {
+    let (offered_mint, requested_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (taker, taker_send, taker_receive) =
+        (Keypair::new(), Pubkey::new_unique(), Pubkey::new_unique());
+    let mut test = sbf_program_test();
+    test.add_account(offered_mint, mint());
+    test.add_account(requested_mint, mint());
+    test.add_account(taker.pubkey(), wallet());
+    test.add_account(
+        taker_send,
+        token_account(&requested_mint, &taker.pubkey(), 60),
+    );
+    test.add_account(
+        taker_receive,
+        token_account(&offered_mint, &taker.pubkey(), 0),
+    );
+    let escrows: Vec<EscrowToTake> = (0..3)
+        .map(|_| {
+            Offer::add_to(
+                &mut test,
+                (&offered_mint, 10),
+                (&requested_mint, 20),
+                SLOT + 1_000,
+            )
+            .to_take()
+        })
+        .collect();
+    let mut context = common::start(test).await;
+    let ix = instruction::exchange_many(
+        &solana_escrow::id(),
+        &taker.pubkey(),
+        &taker_send,
+        &taker_receive,
+        &spl_token::id(),
+        &escrows,
+        60,
+        false,
+    )
+    .unwrap();
+    measure(&mut context, ix, &taker).await
+}
+
+/// A `SettleRing` of two makers each asking for what the other offers
+async fn settle_ring() -> u64 {
+    let (x, y) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let settler = Keypair::new();
+    let mut test = sbf_program_test();
+    test.add_account(x, mint());
+    test.add_account(y, mint());
+    test.add_account(settler.pubkey(), wallet());
+    let ring = [
+        Offer::add_to(&mut test, (&x, 10), (&y, 20), SLOT + 1_000).to_take(),
+        Offer::add_to(&mut test, (&y, 20), (&x, 10), SLOT + 1_000).to_take(),
+    ];
+    let mut context = common::start(test).await;
+    let ix = instruction::settle_ring(
+        &solana_escrow::id(),
+        &settler.pubkey(),
+        &spl_token::id(),
+        &ring,
+    )
+    .unwrap();
+    measure(&mut context, ix, &settler).await
+}
+
+/// A `FillPool` of 5 lots of 3 for 2 from a pool holding 30
+async fn fill_pool() -> u64 {
+    let (offered_mint, requested_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (pool, vault, proceeds_vault) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let (taker, taker_send, taker_receive) =
+        (Keypair::new(), Pubkey::new_unique(), Pubkey::new_unique());
+    let mut test = sbf_program_test();
+    test.add_account(offered_mint, mint());
+    test.add_account(requested_mint, mint());
+    test.add_account(taker.pubkey(), wallet());
+    test.add_account(
+        taker_send,
+        token_account(&requested_mint, &taker.pubkey(), 10),
+    );
+    test.add_account(
+        taker_receive,
+        token_account(&offered_mint, &taker.pubkey(), 0),
+    );
+    test.add_account(vault, token_account(&offered_mint, &pda().0, 30));
+    test.add_account(proceeds_vault, token_account(&requested_mint, &pda().0, 0));
+    let mut data = vec![0; Pool::LEN];
+    Pool::pack(
+        Pool {
+            is_initialized: true,
+            creator: Pubkey::new_unique(),
+            vault,
+            proceeds_vault,
+            offered_per_lot: 3,
+            requested_per_lot: 2,
+            unlock_time: 0,
+            time_out: SLOT + 1_000,
+            total_shares: 30,
+            unfilled: 30,
+            proceeds: 0,
+            pda_bump: pda().1,
+        },
+        &mut data,
+    )
+    .unwrap();
+    test.add_account(pool, rent_exempt(data, solana_escrow::id()));
+    let mut context = common::start(test).await;
+    let ix = instruction::fill_pool(
+        &solana_escrow::id(),
+        &taker.pubkey(),
+        &taker_send,
+        &taker_receive,
+        &pool,
+        &vault,
+        &proceeds_vault,
+        &spl_token::id(),
+        5,
+    )
+    .unwrap();
+    measure(&mut context, ix, &taker).await
+}
+
+/// An `ApproveMilestone` paying the first of three tranches
+async fn approve_milestone() -> u64 {
+    let swap = Swap::new();
+    let mut milestones = [Milestone::default(); MAX_MILESTONES];
+    for (milestone, amount) in milestones.iter_mut().zip([200, 300, 500]) {
+        *milestone = Milestone {
+            amount,
+            deadline: None,
+            state: MilestoneState::Pending,
+        };
+    }
+    let mut test = sbf_program_test();
+    swap.add_to(
+        &mut test,
+        Escrow {
+            mode: EscrowMode::Milestones,
+            counterparty: swap.taker.pubkey(),
+            milestone_count: 3,
+            remaining: OFFERED,
+            milestones,
+            ..swap.escrow_info(0, u64::MAX)
+        },
+    );
+    let mut context = common::start(test).await;
+    let ix = instruction::approve_milestone(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.taker_receive,
+        &swap.vault,
+        &swap.escrow,
+        &spl_token::id(),
+        0,
+    )
+    .unwrap();
+    measure(&mut context, ix, &swap.maker).await
+}
+
+fn baseline_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/compute_units.json")
+}
+
+#[tokio::test]
+#[ignore = "needs the SBF build, run with `cargo test-sbf --test compute_units -- --ignored`"]
+async fn compute_units_stay_within_budget() {
+    assert!(
+        find_file("solana_escrow.so").is_some(),
+        "solana_escrow.so not found, build it with `cargo build-sbf` to run the benchmarks"
+    );
+
+    let mut measured = BTreeMap::new();
+    measured.insert("init_escrow", init_escrow().await);
+    measured.insert("exchange", exchange(Layout::Current).await);
+    measured.insert("exchange_legacy", exchange(Layout::Legacy).await);
+    measured.insert("cancel", cancel(Layout::Current, SLOT + 1_000).await);
+    measured.insert("cancel_legacy", cancel(Layout::Legacy, SLOT + 1_000).await);
+    measured.insert("cancel_expired", cancel(Layout::Current, SLOT - 1).await);
+    measured.insert("reset_time_lock", reset_time_lock().await);
+    measured.insert("migrate_legacy", migrate_legacy().await);
+    measured.insert("init_escrow_bonded", init_escrow_bonded().await);
+    measured.insert("cancel_forfeiting_bond", cancel_forfeiting_bond().await);
+    measured.insert("exchange_many", exchange_many().await);
+    measured.insert("settle_ring", settle_ring().await);
+    measured.insert("fill_pool", fill_pool().await);
+    measured.insert("approve_milestone", approve_milestone().await);
+
+    for (path, legacy) in [("exchange", "exchange_legacy"), ("cancel", "cancel_legacy")] {
+        println!(
+            "{}: {} CU with the stored bump, {} CU searching for it ({} saved)",
+            path,
+            measured[path],
+            measured[legacy],
+            measured[legacy] as i64 - measured[path] as i64
+        );
+    }
+
+    let mut baseline: Value =
+        serde_json::from_str(&fs::read_to_string(baseline_path()).unwrap()).unwrap();
+    if std::env::var_os("UPDATE_COMPUTE_UNITS").is_some() {
+        baseline["units"] = json!(measured);
+        fs::write(
+            baseline_path(),
+            serde_json::to_string_pretty(&baseline).unwrap() + "\n",
+        )
+        .unwrap();
+        return;
+    }
+
+    let threshold = baseline["threshold_percent"].as_u64().unwrap();
+    let mut regressions = vec![];
+    for (path, units) in &measured {
+        // a path without a budget could regress unnoticed
+        let Some(budget) = baseline["units"][path].as_u64() else {
+            regressions.push(format!(
+                "{}: {} CU, no budget recorded, run with UPDATE_COMPUTE_UNITS=1",
+                path, units
+            ));
+            continue;
+        };
+        println!("{}: {} CU, budget {}", path, units, budget);
+        if *units * 100 > budget * (100 + threshold) {
+            regressions.push(format!(
+                "{}: {} CU, budget {} +{}%",
+                path, units, budget, threshold
+            ));
+        }
+    }
+    assert!(
+        regressions.is_empty(),
+        "compute units regressed:\n{}",
+        regressions.join("\n")
+    );
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use common::{
+    account, escrow, escrow_failure, failure, program_test, send, start, token_balance, Swap,
+    OFFERED, SLOT,
+};
+use native_accounts::AccountError;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{Escrow, EscrowMode},
+};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+};
+
+const TIME_OUT: u64 = SLOT + 100;
+
+/// A switch handing the vault to the swap's taker after [`TIME_OUT`]
+async fn switch() -> (ProgramTestContext, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_to(
+        &mut test,
+        Escrow {
+            mode: EscrowMode::DeadMansSwitch,
+            counterparty: swap.taker.pubkey(),
+            initializer_token_to_receive_account_pubkey: Default::default(),
+            expected_amount: 0,
+            ..swap.escrow_info(SLOT, TIME_OUT)
+        },
+    );
+    (start(test).await, swap)
+}
+
+/// `beneficiary`'s `Claim`, paying into the taker's receiving account
+fn claim(swap: &Swap, beneficiary: &Keypair) -> Instruction {
+    instruction::claim(
+        &solana_escrow::id(),
+        &beneficiary.pubkey(),
+        &swap.taker_receive,
+        &swap.vault,
+        &swap.maker.pubkey(),
+        &swap.escrow,
+        &spl_token::id(),
+    )
+    .unwrap()
+}
+
+/// The maker's heartbeat, arming the switch for another `time_out_slots`
+fn heartbeat(swap: &Swap, time_out_slots: u64) -> Instruction {
+    instruction::reset_time_lock(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.escrow,
+        0,
+        time_out_slots,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn the_beneficiary_claims_only_after_the_time_out() {
+    let (mut context, swap) = switch().await;
+    context.warp_to_slot(TIME_OUT).unwrap();
+    assert_eq!(
+        send(&mut context, &[claim(&swap, &swap.taker)], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowNotExpired))
+    );
+
+    context.warp_to_slot(TIME_OUT + 1).unwrap();
+    let stranger = Keypair::new();
+    assert_eq!(
+        send(&mut context, &[claim(&swap, &stranger)], &[&stranger]).await,
+        Err(failure(AccountError::ConstraintAddress as u32))
+    );
+    send(&mut context, &[claim(&swap, &swap.taker)], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn a_heartbeat_pushes_the_claim_back() {
+    let (mut context, swap) = switch().await;
+    context.warp_to_slot(TIME_OUT).unwrap();
+    send(&mut context, &[heartbeat(&swap, 100)], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        escrow(&mut context, &swap.escrow).await.time_out,
+        TIME_OUT + 100
+    );
+
+    // the beneficiary stays the same, only the deadline moves
+    context.warp_to_slot(TIME_OUT + 1).unwrap();
+    assert_eq!(
+        send(&mut context, &[claim(&swap, &swap.taker)], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowNotExpired))
+    );
+    context.warp_to_slot(TIME_OUT + 101).unwrap();
+    send(&mut context, &[claim(&swap, &swap.taker)], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+}
+
+#[tokio::test]
+async fn the_maker_can_withdraw_until_it_is_claimed() {
+    let (mut context, swap) = switch().await;
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::WrongEscrowMode))
+    );
+
+    context.warp_to_slot(TIME_OUT + 1).unwrap();
+    send(&mut context, &[swap.cancel()], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_refund).await,
+        OFFERED
+    );
+}
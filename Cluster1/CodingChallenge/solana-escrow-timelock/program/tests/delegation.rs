@@ -0,0 +1,98 @@
+mod common;
+
+use common::{
+    account, delegated_token_account, escrow_failure, pda, program_test, send, start,
+    token_balance, Swap, OFFERED, REQUESTED,
+};
+use solana_escrow::{error::EscrowError, state::Escrow};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::signature::Signer;
+
+/// A swap of [`OFFERED`] tokens left in the maker's own account, which holds
+/// `balance` of them with `delegated` approved to `delegate`
+async fn delegated_swap(
+    balance: u64,
+    delegate: Option<Pubkey>,
+    delegated: u64,
+) -> (ProgramTestContext, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_parties_to(&mut test);
+    test.add_account(
+        swap.vault,
+        delegated_token_account(
+            &swap.offered_mint,
+            &swap.maker.pubkey(),
+            balance,
+            delegate,
+            delegated,
+        ),
+    );
+    test.add_account(
+        swap.escrow,
+        common::escrow_account(Escrow {
+            delegated_amount: OFFERED,
+            ..swap.escrow_info(0, u64::MAX)
+        }),
+    );
+    (start(test).await, swap)
+}
+
+#[tokio::test]
+async fn the_pda_takes_the_offered_amount_from_the_makers_account() {
+    let (mut context, swap) = delegated_swap(OFFERED + 5, Some(pda().0), OFFERED).await;
+    send(&mut context, &[swap.exchange()], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_receive).await,
+        REQUESTED
+    );
+    // the maker's account stays open with the rest
+    assert_eq!(token_balance(&mut context, &swap.vault).await, 5);
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn a_revoked_approval_fails_the_exchange_but_not_the_cancel() {
+    let (mut context, swap) = delegated_swap(OFFERED, None, 0).await;
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::DelegationRevoked))
+    );
+
+    send(&mut context, &[swap.cancel()], &[&swap.maker])
+        .await
+        .unwrap();
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+    assert_eq!(token_balance(&mut context, &swap.vault).await, OFFERED);
+}
+
+#[tokio::test]
+async fn a_lowered_approval_fails_the_exchange() {
+    let (mut context, swap) = delegated_swap(OFFERED, Some(pda().0), OFFERED - 1).await;
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::InsufficientDelegation))
+    );
+}
+
+#[tokio::test]
+async fn spending_the_tokens_fails_the_exchange_but_not_the_cancel() {
+    let (mut context, swap) = delegated_swap(OFFERED - 1, Some(pda().0), OFFERED).await;
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::InsufficientBalance))
+    );
+    assert_eq!(token_balance(&mut context, &swap.taker_receive).await, 0);
+
+    send(&mut context, &[swap.cancel()], &[&swap.maker])
+        .await
+        .unwrap();
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
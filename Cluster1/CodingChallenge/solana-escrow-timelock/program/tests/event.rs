@@ -0,0 +1,329 @@
+use solana_escrow::{
+    error::EscrowError,
+    event::{EscrowEvent, EventClock, EVENT_VERSION},
+    oracle::{OracleKind, PriceCondition},
+    state::{DefaultOutcome, HashKind},
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+fn key(n: u8) -> Pubkey {
+    Pubkey::new_from_array([n; 32])
+}
+
+fn clock() -> EventClock {
+    EventClock {
+        slot: 1_234,
+        unix_timestamp: -5_678,
+    }
+}
+
+/// One of every event in tag order, with every field set to a different
+/// value so that swapped fields don't round-trip
+fn every_event() -> Vec<EscrowEvent> {
+    vec![
+        EscrowEvent::Created {
+            escrow: key(1),
+            maker: key(2),
+            offered_mint: key(3),
+            offered_amount: 4,
+            requested_mint: key(5),
+            requested_amount: 6,
+            unlock_time: 7,
+            time_out: 8,
+            clock: clock(),
+        },
+        EscrowEvent::Exchanged {
+            escrow: key(9),
+            maker: key(10),
+            taker: key(11),
+            offered_mint: key(12),
+            offered_amount: 13,
+            requested_mint: key(14),
+            requested_amount: 15,
+            clock: clock(),
+        },
+        EscrowEvent::Cancelled {
+            escrow: key(16),
+            maker: key(17),
+            offered_mint: key(18),
+            offered_amount: 19,
+            clock: clock(),
+        },
+        EscrowEvent::LockReset {
+            escrow: key(20),
+            maker: key(21),
+            unlock_time: 22,
+            time_out: 23,
+            clock: clock(),
+        },
+        EscrowEvent::Expired {
+            escrow: key(24),
+            maker: key(25),
+            offered_mint: key(26),
+            offered_amount: 27,
+            time_out: 28,
+            clock: clock(),
+        },
+        EscrowEvent::HashLocked {
+            escrow: key(29),
+            maker: key(30),
+            counterparty: key(31),
+            offered_mint: key(32),
+            offered_amount: 33,
+            hash_kind: HashKind::Keccak256,
+            hash: [34; 32],
+            unlock_time: 35,
+            time_out: 36,
+            clock: clock(),
+        },
+        EscrowEvent::Redeemed {
+            escrow: key(37),
+            maker: key(38),
+            counterparty: key(39),
+            offered_mint: key(40),
+            offered_amount: 41,
+            preimage: vec![42; 5],
+            clock: clock(),
+        },
+        EscrowEvent::SwitchArmed {
+            escrow: key(43),
+            maker: key(44),
+            beneficiary: key(45),
+            offered_mint: key(46),
+            offered_amount: 47,
+            time_out: 48,
+            clock: clock(),
+        },
+        EscrowEvent::Claimed {
+            escrow: key(49),
+            maker: key(50),
+            beneficiary: key(51),
+            offered_mint: key(52),
+            offered_amount: 53,
+            time_out: 54,
+            clock: clock(),
+        },
+        EscrowEvent::Locked {
+            escrow: key(55),
+            maker: key(56),
+            offered_mint: key(57),
+            offered_amount: 58,
+            unlock_time: 59,
+            clock: clock(),
+        },
+        EscrowEvent::Unlocked {
+            escrow: key(60),
+            maker: key(61),
+            offered_mint: key(62),
+            offered_amount: 63,
+            clock: clock(),
+        },
+        EscrowEvent::Arbitrated {
+            escrow: key(64),
+            maker: key(65),
+            taker: key(66),
+            arbiter: key(67),
+            offered_mint: key(68),
+            offered_amount: 69,
+            default_outcome: DefaultOutcome::PayTaker,
+            time_out: 70,
+            clock: clock(),
+        },
+        EscrowEvent::Delivered {
+            escrow: key(71),
+            maker: key(72),
+            taker: key(73),
+            clock: clock(),
+        },
+        EscrowEvent::Disputed {
+            escrow: key(74),
+            maker: key(75),
+            party: key(76),
+            clock: clock(),
+        },
+        EscrowEvent::Settled {
+            escrow: key(77),
+            maker: key(78),
+            taker: key(79),
+            offered_mint: key(80),
+            to_taker: 81,
+            to_maker: 82,
+            clock: clock(),
+        },
+        EscrowEvent::MilestonesOpened {
+            escrow: key(83),
+            maker: key(84),
+            payee: key(85),
+            offered_mint: key(86),
+            offered_amount: 87,
+            milestone_count: 88,
+            clock: clock(),
+        },
+        EscrowEvent::MilestoneApproved {
+            escrow: key(89),
+            maker: key(90),
+            payee: key(91),
+            offered_mint: key(92),
+            index: 93,
+            amount: 94,
+            remaining: 95,
+            clock: clock(),
+        },
+        EscrowEvent::MilestonesRefunded {
+            escrow: key(96),
+            maker: key(97),
+            offered_mint: key(98),
+            amount: 99,
+            remaining: 100,
+            clock: clock(),
+        },
+        EscrowEvent::BondPosted {
+            escrow: key(101),
+            maker: key(102),
+            recipient: key(103),
+            bond_mint: key(104),
+            amount: 105,
+            clock: clock(),
+        },
+        EscrowEvent::BondRefunded {
+            escrow: key(106),
+            maker: key(107),
+            bond_mint: key(108),
+            amount: 109,
+            clock: clock(),
+        },
+        EscrowEvent::BondForfeited {
+            escrow: key(110),
+            maker: key(111),
+            recipient: key(112),
+            bond_mint: key(113),
+            amount: 114,
+            clock: clock(),
+        },
+        EscrowEvent::PremiumSet {
+            escrow: key(115),
+            maker: key(116),
+            premium: 117,
+            clock: clock(),
+        },
+        EscrowEvent::Reserved {
+            escrow: key(118),
+            maker: key(119),
+            taker: key(120),
+            premium_mint: key(121),
+            premium: 122,
+            time_out: 123,
+            clock: clock(),
+        },
+        EscrowEvent::OrderFilled {
+            escrow: key(124),
+            maker: key(125),
+            taker: key(126),
+            offered_mint: key(127),
+            offered_amount: 128,
+            requested_mint: key(129),
+            requested_amount: 130,
+            nonce: 131,
+            clock: clock(),
+        },
+        EscrowEvent::OrdersCancelled {
+            escrow: key(132),
+            maker: key(133),
+            nonce: 134,
+            clock: clock(),
+        },
+        EscrowEvent::PoolOpened {
+            escrow: key(135),
+            creator: key(136),
+            offered_mint: key(137),
+            offered_per_lot: 138,
+            requested_mint: key(139),
+            requested_per_lot: 140,
+            unlock_time: 141,
+            time_out: 142,
+            clock: clock(),
+        },
+        EscrowEvent::PoolDeposited {
+            escrow: key(143),
+            maker: key(144),
+            amount: 145,
+            clock: clock(),
+        },
+        EscrowEvent::PoolFilled {
+            escrow: key(146),
+            taker: key(147),
+            offered_amount: 148,
+            requested_amount: 149,
+            clock: clock(),
+        },
+        EscrowEvent::PoolWithdrawn {
+            escrow: key(150),
+            maker: key(151),
+            unfilled: 152,
+            proceeds: 153,
+            clock: clock(),
+        },
+        EscrowEvent::ExclusiveTakerSet {
+            escrow: key(154),
+            maker: key(155),
+            taker: key(156),
+            exclusive_until: 157,
+            clock: clock(),
+        },
+        EscrowEvent::AllowListSet {
+            escrow: key(158),
+            maker: key(159),
+            root: [160; 32],
+            clock: clock(),
+        },
+        EscrowEvent::PriceConditionSet {
+            escrow: key(161),
+            maker: key(162),
+            oracle: key(163),
+            oracle_kind: OracleKind::Mock,
+            condition: PriceCondition::AtOrBelow,
+            trigger_price: -164,
+            trigger_expo: -165,
+            max_staleness: 166,
+            clock: clock(),
+        },
+        EscrowEvent::CallbackSet {
+            escrow: key(167),
+            maker: key(168),
+            callback_program: key(169),
+            clock: clock(),
+        },
+    ]
+}
+
+#[test]
+fn every_event_round_trips() {
+    for (tag, event) in every_event().into_iter().enumerate() {
+        let data = event.pack();
+        assert_eq!(data[..2], [EVENT_VERSION, tag as u8], "{:?}", event);
+        assert_eq!(EscrowEvent::unpack(&data).unwrap(), event);
+    }
+}
+
+#[test]
+fn malformed_events_are_rejected() {
+    let invalid: ProgramError = EscrowError::InvalidEvent.into();
+    for event in every_event() {
+        let data = event.pack();
+        let cut_off = &data[..data.len() - 1];
+        let trailing = [&data[..], &[0]].concat();
+        let mut other_version = data.clone();
+        other_version[0] = EVENT_VERSION + 1;
+        for malformed in [cut_off, &trailing, &other_version] {
+            assert_eq!(
+                EscrowEvent::unpack(malformed).unwrap_err(),
+                invalid,
+                "{:?}",
+                event
+            );
+        }
+    }
+    let unknown_tag = [EVENT_VERSION, every_event().len() as u8];
+    assert_eq!(EscrowEvent::unpack(&unknown_tag).unwrap_err(), invalid);
+    assert_eq!(EscrowEvent::unpack(&[]).unwrap_err(), invalid);
+}
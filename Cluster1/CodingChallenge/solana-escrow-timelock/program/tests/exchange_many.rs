@@ -0,0 +1,333 @@
+mod common;
+
+use common::{
+    account, delegated_token_account, escrow_account, escrow_failure, mint, pda, program_test,
+    send, start, token_account, token_balance, wallet, Offer, SLOT,
+};
+use solana_escrow::{
+    error::EscrowError,
+    instruction::{self, EscrowInstruction, EscrowToTake},
+    state::Escrow,
+};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::{tokio, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+};
+
+#[test]
+fn the_skip_flag_is_a_single_byte_bool() {
+    for skip_unfillable in [false, true] {
+        let instruction = EscrowInstruction::ExchangeMany {
+            max_total: 100,
+            skip_unfillable,
+        };
+        let data = instruction.pack();
+        assert_eq!(data.len(), 1 + 8 + 1);
+        assert_eq!(EscrowInstruction::unpack(&data).unwrap(), instruction);
+    }
+
+    let mut data = EscrowInstruction::ExchangeMany {
+        max_total: 100,
+        skip_unfillable: true,
+    }
+    .pack();
+    *data.last_mut().unwrap() = 2;
+    assert_eq!(
+        EscrowInstruction::unpack(&data).unwrap_err(),
+        EscrowError::InvalidInstruction.into()
+    );
+    assert_eq!(
+        EscrowInstruction::unpack(&data[..data.len() - 1]).unwrap_err(),
+        EscrowError::InvalidInstruction.into()
+    );
+}
+
+#[test]
+fn every_escrow_adds_its_four_writable_accounts() {
+    let program_id = solana_escrow::id();
+    let escrows: Vec<EscrowToTake> = (0..3)
+        .map(|_| EscrowToTake {
+            escrow: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            maker_receive_account: Pubkey::new_unique(),
+        })
+        .collect();
+    let ix = instruction::exchange_many(
+        &program_id,
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        &Pubkey::new_unique(),
+        &spl_token::id(),
+        &escrows,
+        10,
+        false,
+    )
+    .unwrap();
+
+    let (fixed, groups) = ix.accounts.split_at(5);
+    assert_eq!(fixed[4].pubkey, instruction::escrow_authority(&program_id));
+    assert_eq!(groups.len(), 4 * escrows.len());
+    for (group, escrow) in groups.chunks(4).zip(&escrows) {
+        let keys: Vec<Pubkey> = group.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(
+            keys,
+            [
+                escrow.vault,
+                escrow.maker,
+                escrow.maker_receive_account,
+                escrow.escrow
+            ]
+        );
+        assert!(group.iter().all(|meta| meta.is_writable && !meta.is_signer));
+    }
+}
+
+/// Swaps of 10 tokens each, for what `prices` asks, open through
+/// `time_out`, and a taker with 100 tokens to pay with
+struct Book {
+    offers: Vec<Offer>,
+    taker: Keypair,
+    taker_send: Pubkey,
+    taker_receive: Pubkey,
+}
+
+impl Book {
+    fn take(&self, offers: &[usize], max_total: u64, skip_unfillable: bool) -> Instruction {
+        let escrows: Vec<EscrowToTake> = offers.iter().map(|i| self.offers[*i].to_take()).collect();
+        instruction::exchange_many(
+            &solana_escrow::id(),
+            &self.taker.pubkey(),
+            &self.taker_send,
+            &self.taker_receive,
+            &spl_token::id(),
+            &escrows,
+            max_total,
+            skip_unfillable,
+        )
+        .unwrap()
+    }
+
+    /// Whether each offer is still open
+    async fn open(&self, context: &mut ProgramTestContext) -> Vec<bool> {
+        let mut open = vec![];
+        for offer in &self.offers {
+            open.push(account(context, &offer.escrow).await.is_some());
+        }
+        open
+    }
+}
+
+async fn book(prices: &[(u64, u64)]) -> (ProgramTestContext, Book) {
+    let (offered_mint, requested_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut test = program_test();
+    let offers = prices
+        .iter()
+        .map(|(price, time_out)| {
+            Offer::add_to(
+                &mut test,
+                (&offered_mint, 10),
+                (&requested_mint, *price),
+                *time_out,
+            )
+        })
+        .collect();
+    open_book(test, offers, &offered_mint, &requested_mint).await
+}
+
+/// Swaps of 10 tokens each, for 10 and for 20, that one maker left in the
+/// same account of theirs, which holds 20 with `delegated` approved to the PDA
+async fn shared_source(delegated: u64) -> (ProgramTestContext, Book) {
+    let (offered_mint, requested_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut test = program_test();
+    let (maker, source) = (Keypair::new(), Pubkey::new_unique());
+    test.add_account(maker.pubkey(), wallet());
+    test.add_account(
+        source,
+        delegated_token_account(&offered_mint, &maker.pubkey(), 20, Some(pda().0), delegated),
+    );
+    let offers = [10, 20]
+        .into_iter()
+        .map(|price| {
+            let offer = Offer {
+                maker: maker.insecure_clone(),
+                escrow: Pubkey::new_unique(),
+                vault: source,
+                maker_receive: Pubkey::new_unique(),
+            };
+            test.add_account(
+                offer.maker_receive,
+                token_account(&requested_mint, &maker.pubkey(), 0),
+            );
+            test.add_account(
+                offer.escrow,
+                escrow_account(Escrow {
+                    is_initialized: true,
+                    initializer_pubkey: maker.pubkey(),
+                    temp_token_account_pubkey: source,
+                    initializer_token_to_receive_account_pubkey: offer.maker_receive,
+                    expected_amount: price,
+                    unlock_time: 0,
+                    time_out: OPEN,
+                    delegated_amount: 10,
+                    pda_bump: pda().1,
+                    ..Escrow::default()
+                }),
+            );
+            offer
+        })
+        .collect();
+    open_book(test, offers, &offered_mint, &requested_mint).await
+}
+
+/// Adds the mints and a taker with 100 tokens to pay with to `offers`
+async fn open_book(
+    mut test: ProgramTest,
+    offers: Vec<Offer>,
+    offered_mint: &Pubkey,
+    requested_mint: &Pubkey,
+) -> (ProgramTestContext, Book) {
+    test.add_account(*offered_mint, mint());
+    test.add_account(*requested_mint, mint());
+    let book = Book {
+        offers,
+        taker: Keypair::new(),
+        taker_send: Pubkey::new_unique(),
+        taker_receive: Pubkey::new_unique(),
+    };
+    let taker = book.taker.pubkey();
+    test.add_account(taker, wallet());
+    test.add_account(book.taker_send, token_account(requested_mint, &taker, 100));
+    test.add_account(book.taker_receive, token_account(offered_mint, &taker, 0));
+    (start(test).await, book)
+}
+
+const OPEN: u64 = SLOT + 1_000;
+
+#[tokio::test]
+async fn takes_the_cheapest_escrows_up_to_the_price_limit() {
+    let (mut context, book) = book(&[(30, OPEN), (10, OPEN), (20, OPEN)]).await;
+    let ix = book.take(&[0, 1, 2], 30, true);
+    send(&mut context, &[ix], &[&book.taker]).await.unwrap();
+
+    assert_eq!(book.open(&mut context).await, [true, false, false]);
+    assert_eq!(token_balance(&mut context, &book.taker_receive).await, 20);
+    assert_eq!(token_balance(&mut context, &book.taker_send).await, 70);
+    for (offer, paid) in book.offers[1..].iter().zip([10, 20]) {
+        assert_eq!(
+            token_balance(&mut context, &offer.maker_receive).await,
+            paid
+        );
+    }
+}
+
+#[tokio::test]
+async fn an_atomic_sweep_takes_everything_or_nothing() {
+    let (mut context, book) = book(&[(30, OPEN), (10, OPEN), (20, OPEN)]).await;
+    assert_eq!(
+        send(
+            &mut context,
+            &[book.take(&[0, 1, 2], 59, false)],
+            &[&book.taker]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::PriceLimitExceeded))
+    );
+    assert_eq!(book.open(&mut context).await, [true, true, true]);
+    assert_eq!(token_balance(&mut context, &book.taker_send).await, 100);
+
+    send(
+        &mut context,
+        &[book.take(&[0, 1, 2], 60, false)],
+        &[&book.taker],
+    )
+    .await
+    .unwrap();
+    assert_eq!(book.open(&mut context).await, [false, false, false]);
+    assert_eq!(token_balance(&mut context, &book.taker_receive).await, 30);
+    assert_eq!(token_balance(&mut context, &book.taker_send).await, 40);
+}
+
+#[tokio::test]
+async fn unfillable_escrows_fail_the_sweep_unless_skipped() {
+    // the first has timed out
+    let (mut context, book) = book(&[(10, SLOT - 1), (10, OPEN)]).await;
+    assert_eq!(
+        send(
+            &mut context,
+            &[book.take(&[0, 1], 100, false)],
+            &[&book.taker]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::EscrowTimeOut))
+    );
+    assert_eq!(book.open(&mut context).await, [true, true]);
+
+    send(
+        &mut context,
+        &[book.take(&[0, 1], 100, true)],
+        &[&book.taker],
+    )
+    .await
+    .unwrap();
+    assert_eq!(book.open(&mut context).await, [true, false]);
+    assert_eq!(token_balance(&mut context, &book.taker_receive).await, 10);
+
+    assert_eq!(
+        send(&mut context, &[book.take(&[0], 100, true)], &[&book.taker]).await,
+        Err(escrow_failure(EscrowError::NothingFilled))
+    );
+}
+
+#[tokio::test]
+async fn an_escrow_is_only_taken_once_per_sweep() {
+    let (mut context, book) = book(&[(10, OPEN)]).await;
+    assert_eq!(
+        send(
+            &mut context,
+            &[book.take(&[0, 0], 100, false)],
+            &[&book.taker]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::InvalidInstruction))
+    );
+
+    send(
+        &mut context,
+        &[book.take(&[0, 0], 100, true)],
+        &[&book.taker],
+    )
+    .await
+    .unwrap();
+    assert_eq!(token_balance(&mut context, &book.taker_receive).await, 10);
+    assert_eq!(token_balance(&mut context, &book.taker_send).await, 90);
+}
+
+#[tokio::test]
+async fn escrows_sharing_a_source_share_its_allowance() {
+    // enough is approved for one of the two
+    let (mut context, book) = shared_source(15).await;
+    assert_eq!(
+        send(
+            &mut context,
+            &[book.take(&[0, 1], 100, false)],
+            &[&book.taker]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::InsufficientDelegation))
+    );
+    assert_eq!(book.open(&mut context).await, [true, true]);
+
+    send(
+        &mut context,
+        &[book.take(&[0, 1], 100, true)],
+        &[&book.taker],
+    )
+    .await
+    .unwrap();
+    assert_eq!(book.open(&mut context).await, [false, true]);
+    assert_eq!(token_balance(&mut context, &book.taker_receive).await, 10);
+    assert_eq!(token_balance(&mut context, &book.offers[0].vault).await, 10);
+}
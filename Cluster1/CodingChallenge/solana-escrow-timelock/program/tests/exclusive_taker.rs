@@ -0,0 +1,102 @@
+mod common;
+
+use common::{escrow, escrow_failure, program_test, send, start, token_balance, Swap, OFFERED};
+use solana_escrow::{error::EscrowError, instruction, state::Escrow};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::{tokio, ProgramTest};
+use solana_sdk::signature::Signer;
+
+/// A swap open from slot 50 through 200 whose first 100 slots belong to
+/// someone other than its taker
+fn exclusive_swap() -> (ProgramTest, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_to(
+        &mut test,
+        Escrow {
+            designated_taker: Pubkey::new_unique(),
+            exclusive_until: 149,
+            ..swap.escrow_info(50, 200)
+        },
+    );
+    (test, swap)
+}
+
+#[tokio::test]
+async fn the_swap_turns_public_once_the_period_ends() {
+    let (test, swap) = exclusive_swap();
+    let mut context = start(test).await;
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::NotYetPublic))
+    );
+
+    context.warp_to_slot(150).unwrap();
+    send(&mut context, &[swap.exchange()], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+}
+
+#[tokio::test]
+async fn nobody_can_take_the_swap_after_its_time_out() {
+    let (test, swap) = exclusive_swap();
+    let mut context = start(test).await;
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::NotYetPublic))
+    );
+
+    context.warp_to_slot(201).unwrap();
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowTimeOut))
+    );
+}
+
+#[tokio::test]
+async fn exclusive_periods_end_before_the_time_out() {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_to(&mut test, swap.escrow_info(150, 200));
+    let mut context = start(test).await;
+    let set = |exclusive_slots| {
+        instruction::set_exclusive_taker(
+            &solana_escrow::id(),
+            &swap.maker.pubkey(),
+            &swap.escrow,
+            &swap.taker.pubkey(),
+            exclusive_slots,
+        )
+        .unwrap()
+    };
+
+    assert_eq!(
+        send(&mut context, &[set(51)], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::ExclusivePeriodTooLong))
+    );
+    send(&mut context, &[set(50)], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        escrow(&mut context, &swap.escrow).await.exclusive_until,
+        199
+    );
+
+    // the 50 slot period moves along, but no longer fits the new window
+    let reset = instruction::reset_time_lock(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.escrow,
+        10,
+        49,
+    )
+    .unwrap();
+    assert_eq!(
+        send(&mut context, &[reset], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::ExclusivePeriodTooLong))
+    );
+}
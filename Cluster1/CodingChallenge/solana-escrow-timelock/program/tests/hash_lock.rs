@@ -0,0 +1,119 @@
+mod common;
+
+use common::{
+    account, escrow_failure, program_test, send, start, token_balance, Swap, OFFERED, SLOT,
+};
+use solana_escrow::{
+    error::EscrowError,
+    instruction::{self, EscrowInstruction},
+    state::{Escrow, EscrowMode, HashKind, MAX_PREIMAGE_LEN},
+};
+use solana_program::program_error::ProgramError;
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{instruction::Instruction, signature::Signer};
+
+/// A secret of another chain's length rather than 32 bytes
+const SECRET: &[u8] = b"twenty byte secret!!";
+
+/// A Keccak hash lock of [`SECRET`] for the swap's taker, open through
+/// slot 200
+async fn hash_lock() -> (ProgramTestContext, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_to(
+        &mut test,
+        Escrow {
+            mode: EscrowMode::HashLock,
+            hash_kind: HashKind::Keccak256,
+            hash: HashKind::Keccak256.hash(SECRET),
+            counterparty: swap.taker.pubkey(),
+            ..swap.escrow_info(SLOT, 200)
+        },
+    );
+    (start(test).await, swap)
+}
+
+fn redeem(swap: &Swap, preimage: &[u8]) -> Instruction {
+    instruction::redeem(
+        &solana_escrow::id(),
+        &swap.taker.pubkey(),
+        &swap.taker_receive,
+        &swap.vault,
+        &swap.maker.pubkey(),
+        &swap.escrow,
+        &spl_token::id(),
+        preimage,
+    )
+    .unwrap()
+}
+
+#[test]
+fn preimages_are_length_prefixed_and_bounded() {
+    let data = EscrowInstruction::Redeem {
+        preimage: SECRET.to_vec(),
+    }
+    .pack();
+    assert_eq!(data[1..5], (SECRET.len() as u32).to_le_bytes());
+    assert_eq!(&data[5..], SECRET);
+    assert_eq!(
+        EscrowInstruction::unpack(&data).unwrap(),
+        EscrowInstruction::Redeem {
+            preimage: SECRET.to_vec()
+        }
+    );
+
+    let invalid: ProgramError = EscrowError::InvalidInstruction.into();
+    // cut short, and longer than allowed
+    assert_eq!(EscrowInstruction::unpack(&data[..10]), Err(invalid.clone()));
+    let too_long = EscrowInstruction::Redeem {
+        preimage: vec![1; MAX_PREIMAGE_LEN + 1],
+    }
+    .pack();
+    assert_eq!(EscrowInstruction::unpack(&too_long), Err(invalid));
+}
+
+#[tokio::test]
+async fn the_preimage_releases_the_vault_to_the_counterparty() {
+    let (mut context, swap) = hash_lock().await;
+    send(&mut context, &[redeem(&swap, SECRET)], &[&swap.taker])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+    assert!(account(&mut context, &swap.vault).await.is_none());
+}
+
+#[tokio::test]
+async fn a_wrong_preimage_releases_nothing() {
+    let (mut context, swap) = hash_lock().await;
+    for preimage in [&SECRET[..19], b"twenty byte secret!?", &[0; 32]] {
+        assert_eq!(
+            send(&mut context, &[redeem(&swap, preimage)], &[&swap.taker]).await,
+            Err(escrow_failure(EscrowError::PreimageMismatch))
+        );
+    }
+    assert_eq!(token_balance(&mut context, &swap.vault).await, OFFERED);
+}
+
+#[tokio::test]
+async fn after_the_time_out_only_the_maker_gets_the_vault() {
+    let (mut context, swap) = hash_lock().await;
+    context.warp_to_slot(201).unwrap();
+    assert_eq!(
+        send(&mut context, &[redeem(&swap, SECRET)], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowTimeOut))
+    );
+
+    send(&mut context, &[swap.cancel()], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_refund).await,
+        OFFERED
+    );
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
@@ -0,0 +1,743 @@
+//! Keeps `idl/solana_escrow.json` in sync with the Rust types it describes.
+
+use num_traits::FromPrimitive;
+use serde_json::Value;
+use solana_escrow::{
+    callback::{Settlement, SettlementKind, CALLBACK_DISCRIMINATOR, CALLBACK_VERSION},
+    error::EscrowError,
+    instruction::{self, EscrowInstruction, EscrowToTake, MilestoneTerms},
+    oracle::{OracleKind, PriceCondition},
+    order::Order,
+    state::{
+        DealState, DefaultOutcome, Escrow, EscrowMode, Extension, HashKind, Milestone,
+        MilestoneState, Pool, ESCROW_VERSION, MAX_MILESTONES,
+    },
+};
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+
+/// Field names with the bytes each should pack to, in order
+type Fields<'a> = Vec<(&'a str, Vec<u8>)>;
+
+fn idl() -> Value {
+    serde_json::from_str(include_str!("../idl/solana_escrow.json")).unwrap()
+}
+
+fn key(n: u8) -> Pubkey {
+    Pubkey::new_from_array([n; 32])
+}
+
+/// `InitEscrow { .. }` -> `initEscrow`
+fn idl_name(instruction: &EscrowInstruction) -> String {
+    let debug = format!("{:?}", instruction);
+    let variant = debug.split([' ', '{', '(']).next().unwrap();
+    variant[..1].to_lowercase() + &variant[1..]
+}
+
+/// One of the IDL's `types`
+fn defined_type<'a>(idl: &'a Value, name: &Value) -> &'a Value {
+    &idl["types"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|ty| ty["name"] == *name)
+        .unwrap_or_else(|| panic!("{} is missing from the IDL types", name))["type"]
+}
+
+/// The fields of one of the IDL's struct `types`
+fn defined_fields<'a>(idl: &'a Value, name: &Value) -> &'a Vec<Value> {
+    defined_type(idl, name)["fields"].as_array().unwrap()
+}
+
+/// How many variants one of the IDL's enum `types` has, `None` for a struct
+fn defined_variants(idl: &Value, name: &Value) -> Option<usize> {
+    defined_type(idl, name)["variants"].as_array().map(Vec::len)
+}
+
+fn encode_arg(idl: &Value, ty: &Value, n: u64) -> Vec<u8> {
+    match ty.as_str() {
+        Some("u8") => vec![n as u8],
+        Some("bool") => vec![(n % 2) as u8],
+        Some("u16") => (n as u16).to_le_bytes().to_vec(),
+        Some("u64") => n.to_le_bytes().to_vec(),
+        Some("i64") => (n as i64).to_le_bytes().to_vec(),
+        Some("i32") => (n as i32).to_le_bytes().to_vec(),
+        Some("publicKey") => key(n as u8).to_bytes().to_vec(),
+        None if ty["array"][0] == "u8" => vec![n as u8; ty["array"][1].as_u64().unwrap() as usize],
+        // Borsh, as Anchor encodes them: a u32 length, a u8 tag
+        None if !ty["vec"].is_null() => {
+            [1u32.to_le_bytes().to_vec(), encode_arg(idl, &ty["vec"], n)].concat()
+        }
+        None if !ty["option"].is_null() => [vec![1], encode_arg(idl, &ty["option"], n)].concat(),
+        // a unit enum is just its u8 tag, kept in range
+        None if !ty["defined"].is_null() && defined_variants(idl, &ty["defined"]).is_some() => {
+            vec![(n % defined_variants(idl, &ty["defined"]).unwrap() as u64) as u8]
+        }
+        None if !ty["defined"].is_null() => defined_fields(idl, &ty["defined"])
+            .iter()
+            .flat_map(|field| encode_arg(idl, &field["type"], n))
+            .collect(),
+        _ => panic!("the IDL test does not know how to encode {}", ty),
+    }
+}
+
+/// Packed size of a fixed-size IDL type
+fn type_size(idl: &Value, ty: &Value) -> usize {
+    match ty.as_str() {
+        Some("bool" | "u8") => 1,
+        Some("i32") => 4,
+        Some("u64" | "i64") => 8,
+        Some("publicKey") => 32,
+        None if !ty["array"].is_null() => {
+            type_size(idl, &ty["array"][0]) * ty["array"][1].as_u64().unwrap() as usize
+        }
+        None if !ty["defined"].is_null() && defined_variants(idl, &ty["defined"]).is_some() => 1,
+        None if !ty["defined"].is_null() => defined_fields(idl, &ty["defined"])
+            .iter()
+            .map(|field| type_size(idl, &field["type"]))
+            .sum(),
+        _ => panic!("the IDL test does not know the size of {}", ty),
+    }
+}
+
+fn idl_instruction<'a>(idl: &'a Value, name: &str) -> &'a Value {
+    idl["instructions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|ix| ix["name"] == name)
+        .unwrap_or_else(|| panic!("{} is missing from the IDL", name))
+}
+
+#[test]
+fn address_matches_declared_id() {
+    assert_eq!(
+        idl()["metadata"]["address"],
+        solana_escrow::id().to_string()
+    );
+}
+
+#[test]
+fn errors_match_escrow_error() {
+    let idl = idl();
+    let errors = idl["errors"].as_array().unwrap();
+    for (code, entry) in errors.iter().enumerate() {
+        let error = EscrowError::from_usize(code)
+            .unwrap_or_else(|| panic!("IDL error {} has no EscrowError", entry["name"]));
+        assert_eq!(entry["code"], code);
+        assert_eq!(entry["name"], format!("{:?}", error));
+        assert_eq!(entry["msg"], error.to_string());
+    }
+    assert!(
+        EscrowError::from_usize(errors.len()).is_none(),
+        "EscrowError has variants missing from the IDL"
+    );
+}
+
+#[test]
+fn instruction_args_match_unpack() {
+    let idl = idl();
+    for entry in idl["instructions"].as_array().unwrap() {
+        let mut data = vec![entry["discriminant"]["value"].as_u64().unwrap() as u8];
+        for (i, arg) in entry["args"].as_array().unwrap().iter().enumerate() {
+            data.extend(encode_arg(&idl, &arg["type"], i as u64 + 1));
+        }
+        let unpacked = EscrowInstruction::unpack(&data)
+            .unwrap_or_else(|e| panic!("{} does not unpack: {:?}", entry["name"], e));
+        assert_eq!(entry["name"], idl_name(&unpacked));
+        assert_eq!(unpacked.pack(), data, "{} args differ", entry["name"]);
+    }
+}
+
+#[test]
+fn instruction_accounts_match_builders() {
+    let program_id = solana_escrow::id();
+    let builders: Vec<Instruction> = vec![
+        instruction::init_escrow(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &spl_token::id(),
+            1,
+            2,
+            3,
+            None,
+            None,
+        )
+        .unwrap(),
+        instruction::exchange(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &key(5),
+            &key(6),
+            &key(7),
+            &spl_token::id(),
+            1,
+            &[[2; 32]],
+        )
+        .unwrap(),
+        instruction::reset_time_lock(&program_id, &key(1), &key(2), 1, 2).unwrap(),
+        instruction::cancel(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &spl_token::id(),
+        )
+        .unwrap(),
+        instruction::migrate(&program_id, &key(1), &key(2)).unwrap(),
+        instruction::init_hash_lock(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &spl_token::id(),
+            HashKind::Sha256,
+            [4; 32],
+            &key(5),
+            1,
+            2,
+        )
+        .unwrap(),
+        instruction::redeem(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &key(5),
+            &spl_token::id(),
+            &[6; 32],
+        )
+        .unwrap(),
+        instruction::init_dead_mans_switch(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &spl_token::id(),
+            &key(4),
+            1,
+        )
+        .unwrap(),
+        instruction::claim(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &key(5),
+            &spl_token::id(),
+        )
+        .unwrap(),
+        instruction::lock(&program_id, &key(1), &key(2), &key(3), &spl_token::id(), 1).unwrap(),
+        instruction::unlock(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &spl_token::id(),
+        )
+        .unwrap(),
+        instruction::extend_lock(&program_id, &key(1), &key(2), 1).unwrap(),
+        instruction::init_arbitrated(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &spl_token::id(),
+            &key(4),
+            &key(5),
+            DefaultOutcome::PayTaker,
+            1,
+        )
+        .unwrap(),
+        instruction::mark_delivered(&program_id, &key(1), &key(2)).unwrap(),
+        instruction::open_dispute(&program_id, &key(1), &key(2)).unwrap(),
+        instruction::release(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &spl_token::id(),
+        )
+        .unwrap(),
+        instruction::resolve(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &key(5),
+            &key(6),
+            &spl_token::id(),
+            5_000,
+        )
+        .unwrap(),
+        instruction::settle(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &key(5),
+            &spl_token::id(),
+        )
+        .unwrap(),
+        instruction::init_milestones(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &spl_token::id(),
+            &key(4),
+            &[MilestoneTerms {
+                amount: 1,
+                deadline_slots: None,
+            }],
+        )
+        .unwrap(),
+        instruction::approve_milestone(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &spl_token::id(),
+            0,
+        )
+        .unwrap(),
+        instruction::refund_unapproved(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &spl_token::id(),
+        )
+        .unwrap(),
+        instruction::set_premium(&program_id, &key(1), &key(2), 3).unwrap(),
+        instruction::reserve(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &spl_token::id(),
+            5,
+            &[[6; 32]],
+        )
+        .unwrap(),
+        instruction::fill_signed_order(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &Order::default(),
+            &spl_token::id(),
+        )
+        .unwrap(),
+        instruction::cancel_all_orders(&program_id, &key(1)).unwrap(),
+        instruction::init_delegated_escrow(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            1,
+            2,
+            3,
+            4,
+            None,
+            None,
+        )
+        .unwrap(),
+        instruction::exchange_many(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &[],
+            5,
+            true,
+        )
+        .unwrap(),
+        {
+            let escrow = EscrowToTake {
+                escrow: key(3),
+                vault: key(4),
+                maker: key(5),
+                maker_receive_account: key(6),
+            };
+            let mut ix =
+                instruction::settle_ring(&program_id, &key(1), &key(2), &[escrow; 2]).unwrap();
+            // the IDL only lists the accounts before the ring's
+            ix.accounts.truncate(3);
+            ix
+        },
+        instruction::init_pool(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &key(5),
+            6,
+            7,
+            8,
+            9,
+        )
+        .unwrap(),
+        instruction::deposit_pool(&program_id, &key(1), &key(2), &key(3), &key(4), &key(5), 6)
+            .unwrap(),
+        instruction::fill_pool(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &key(5),
+            &key(6),
+            &key(7),
+            8,
+        )
+        .unwrap(),
+        instruction::withdraw_pool(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            &key(4),
+            &key(5),
+            &key(6),
+            &key(7),
+            &key(8),
+        )
+        .unwrap(),
+        instruction::set_exclusive_taker(&program_id, &key(1), &key(2), &key(3), 4).unwrap(),
+        instruction::set_allow_list(&program_id, &key(1), &key(2), &[3; 32]).unwrap(),
+        instruction::set_price_condition(
+            &program_id,
+            &key(1),
+            &key(2),
+            &key(3),
+            OracleKind::Mock,
+            PriceCondition::AtOrAbove,
+            4,
+            -5,
+            6,
+        )
+        .unwrap(),
+        instruction::set_callback(&program_id, &key(1), &key(2), &key(3)).unwrap(),
+    ];
+
+    let idl = idl();
+    assert_eq!(
+        builders.len(),
+        idl["instructions"].as_array().unwrap().len(),
+        "every IDL instruction needs a builder here"
+    );
+    for ix in builders {
+        let name = idl_name(&EscrowInstruction::unpack(&ix.data).unwrap());
+        let accounts = idl_instruction(&idl, &name)["accounts"].as_array().unwrap();
+        assert_eq!(accounts.len(), ix.accounts.len(), "{} account count", name);
+        for (meta, account) in ix.accounts.iter().zip(accounts) {
+            assert_eq!(
+                account["isMut"], meta.is_writable,
+                "{} {}",
+                name, account["name"]
+            );
+            assert_eq!(
+                account["isSigner"], meta.is_signer,
+                "{} {}",
+                name, account["name"]
+            );
+        }
+    }
+}
+
+#[test]
+fn escrow_layout_matches_pack() {
+    let escrow = Escrow {
+        is_initialized: true,
+        initializer_pubkey: key(1),
+        temp_token_account_pubkey: key(2),
+        initializer_token_to_receive_account_pubkey: key(3),
+        expected_amount: 4,
+        unlock_time: 5,
+        time_out: 6,
+        pda_bump: 7,
+        mode: EscrowMode::HashLock,
+        hash_kind: HashKind::Keccak256,
+        hash: [8; 32],
+        counterparty: key(9),
+        arbiter: key(10),
+        deal_state: DealState::Delivered,
+        default_outcome: DefaultOutcome::PayTaker,
+        milestone_count: 1,
+        remaining: 11,
+        milestones: [Milestone {
+            amount: 12,
+            deadline: Some(13),
+            state: MilestoneState::Refunded,
+        }; MAX_MILESTONES],
+        bond: 14,
+        bond_vault: key(15),
+        bond_recipient: key(16),
+        premium: 17,
+        delegated_amount: 18,
+        designated_taker: key(19),
+        exclusive_until: 20,
+        allow_list_root: [21; 32],
+        oracle: key(22),
+        oracle_kind: OracleKind::Mock,
+        price_condition: PriceCondition::AtOrBelow,
+        trigger_price: -23,
+        trigger_expo: -24,
+        max_staleness: 25,
+        callback_program: key(26),
+    };
+    let core: Fields = vec![
+        ("accountType", vec![1]),
+        ("version", vec![ESCROW_VERSION]),
+        ("initializerPubkey", key(1).to_bytes().to_vec()),
+        ("tempTokenAccountPubkey", key(2).to_bytes().to_vec()),
+        (
+            "initializerTokenToReceiveAccountPubkey",
+            key(3).to_bytes().to_vec(),
+        ),
+        ("expectedAmount", 4u64.to_le_bytes().to_vec()),
+        ("unlockTime", 5u64.to_le_bytes().to_vec()),
+        ("timeOut", 6u64.to_le_bytes().to_vec()),
+        ("pdaBump", vec![7]),
+        ("mode", vec![1]),
+        ("counterparty", key(9).to_bytes().to_vec()),
+    ];
+    let milestone = [
+        12u64.to_le_bytes().to_vec(),
+        13u64.to_le_bytes().to_vec(),
+        vec![2],
+    ]
+    .concat();
+    // in the order of their tags, with what follows their fields
+    let extensions: Vec<(Extension, &str, Fields, Vec<u8>)> = vec![
+        (
+            Extension::HashLock,
+            "HashLockExtension",
+            vec![("hashKind", vec![1]), ("hash", vec![8; 32])],
+            vec![],
+        ),
+        (
+            Extension::Arbitration,
+            "ArbitrationExtension",
+            vec![
+                ("arbiter", key(10).to_bytes().to_vec()),
+                ("dealState", vec![1]),
+                ("defaultOutcome", vec![1]),
+            ],
+            vec![],
+        ),
+        (
+            Extension::Milestones,
+            "MilestonesExtension",
+            vec![
+                ("milestoneCount", vec![1]),
+                ("remaining", 11u64.to_le_bytes().to_vec()),
+            ],
+            milestone,
+        ),
+        (
+            Extension::Bond,
+            "BondExtension",
+            vec![
+                ("bond", 14u64.to_le_bytes().to_vec()),
+                ("bondVault", key(15).to_bytes().to_vec()),
+                ("bondRecipient", key(16).to_bytes().to_vec()),
+            ],
+            vec![],
+        ),
+        (
+            Extension::Premium,
+            "PremiumExtension",
+            vec![("premium", 17u64.to_le_bytes().to_vec())],
+            vec![],
+        ),
+        (
+            Extension::Delegation,
+            "DelegationExtension",
+            vec![("delegatedAmount", 18u64.to_le_bytes().to_vec())],
+            vec![],
+        ),
+        (
+            Extension::ExclusiveTaker,
+            "ExclusiveTakerExtension",
+            vec![
+                ("designatedTaker", key(19).to_bytes().to_vec()),
+                ("exclusiveUntil", 20u64.to_le_bytes().to_vec()),
+            ],
+            vec![],
+        ),
+        (
+            Extension::AllowList,
+            "AllowListExtension",
+            vec![("allowListRoot", vec![21; 32])],
+            vec![],
+        ),
+        (
+            Extension::PriceCondition,
+            "PriceConditionExtension",
+            vec![
+                ("oracle", key(22).to_bytes().to_vec()),
+                ("oracleKind", vec![0]),
+                ("priceCondition", vec![2]),
+                ("triggerPrice", (-23i64).to_le_bytes().to_vec()),
+                ("triggerExpo", (-24i32).to_le_bytes().to_vec()),
+                ("maxStaleness", 25u64.to_le_bytes().to_vec()),
+            ],
+            vec![],
+        ),
+        (
+            Extension::Callback,
+            "CallbackExtension",
+            vec![("callbackProgram", key(26).to_bytes().to_vec())],
+            vec![],
+        ),
+    ];
+    let mut data = vec![0; escrow.packed_len()];
+    Escrow::pack(escrow, &mut data).unwrap();
+
+    let idl = idl();
+    let check_fields = |fields: &Vec<Value>, expected: Fields, offset: &mut usize| {
+        assert_eq!(fields.len(), expected.len());
+        for (field, (name, bytes)) in fields.iter().zip(expected) {
+            assert_eq!(field["name"], name);
+            let size = type_size(&idl, &field["type"]);
+            assert_eq!(&data[*offset..*offset + size], &bytes[..], "{}", name);
+            *offset += size;
+        }
+    };
+    assert_eq!(idl["accounts"][0]["name"], "Escrow");
+    let mut offset = 0;
+    check_fields(
+        idl["accounts"][0]["type"]["fields"].as_array().unwrap(),
+        core,
+        &mut offset,
+    );
+    assert_eq!(offset, Escrow::LEN);
+
+    for (extension, name, fields, rest) in extensions {
+        assert_eq!(data[offset], extension as u8, "{}", name);
+        let len = u16::from_le_bytes([data[offset + 1], data[offset + 2]]) as usize;
+        offset += 3;
+        let end = offset + len;
+        check_fields(defined_fields(&idl, &name.into()), fields, &mut offset);
+        assert_eq!(&data[offset..end], &rest[..], "{}", name);
+        offset = end;
+    }
+    assert_eq!(offset, data.len());
+}
+
+#[test]
+fn pool_layout_matches_pack() {
+    let pool = Pool {
+        is_initialized: true,
+        creator: key(1),
+        vault: key(2),
+        proceeds_vault: key(3),
+        offered_per_lot: 4,
+        requested_per_lot: 5,
+        unlock_time: 6,
+        time_out: 7,
+        total_shares: 8,
+        unfilled: 9,
+        proceeds: 10,
+        pda_bump: 11,
+    };
+    let expected: Fields = vec![
+        ("accountType", vec![4]),
+        ("creator", key(1).to_bytes().to_vec()),
+        ("vault", key(2).to_bytes().to_vec()),
+        ("proceedsVault", key(3).to_bytes().to_vec()),
+        ("offeredPerLot", 4u64.to_le_bytes().to_vec()),
+        ("requestedPerLot", 5u64.to_le_bytes().to_vec()),
+        ("unlockTime", 6u64.to_le_bytes().to_vec()),
+        ("timeOut", 7u64.to_le_bytes().to_vec()),
+        ("totalShares", 8u64.to_le_bytes().to_vec()),
+        ("unfilled", 9u64.to_le_bytes().to_vec()),
+        ("proceeds", 10u64.to_le_bytes().to_vec()),
+        ("pdaBump", vec![11]),
+    ];
+    let mut data = vec![0; Pool::LEN];
+    Pool::pack(pool, &mut data).unwrap();
+
+    let idl = idl();
+    let fields = idl["accounts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|account| account["name"] == "Pool")
+        .expect("the IDL lists the Pool account")["type"]["fields"]
+        .as_array()
+        .unwrap();
+    assert_eq!(fields.len(), expected.len());
+    let mut offset = 0;
+    for (field, (name, bytes)) in fields.iter().zip(expected) {
+        assert_eq!(field["name"], name);
+        let size = type_size(&idl, &field["type"]);
+        assert_eq!(&data[offset..offset + size], &bytes[..], "{}", name);
+        offset += size;
+    }
+    assert_eq!(offset, Pool::LEN);
+}
+
+#[test]
+fn settlement_layout_matches_pack() {
+    let settlement = Settlement {
+        kind: SettlementKind::Expired,
+        escrow: key(1),
+        maker: key(2),
+        taker: key(3),
+        offered_mint: key(4),
+        offered_amount: 5,
+        requested_mint: key(6),
+        requested_amount: 7,
+        slot: 8,
+        unix_timestamp: -9,
+    };
+    let expected: Fields = vec![
+        ("discriminator", CALLBACK_DISCRIMINATOR.to_vec()),
+        ("version", vec![CALLBACK_VERSION]),
+        ("kind", vec![2]),
+        ("escrow", key(1).to_bytes().to_vec()),
+        ("maker", key(2).to_bytes().to_vec()),
+        ("taker", key(3).to_bytes().to_vec()),
+        ("offeredMint", key(4).to_bytes().to_vec()),
+        ("offeredAmount", 5u64.to_le_bytes().to_vec()),
+        ("requestedMint", key(6).to_bytes().to_vec()),
+        ("requestedAmount", 7u64.to_le_bytes().to_vec()),
+        ("slot", 8u64.to_le_bytes().to_vec()),
+        ("unixTimestamp", (-9i64).to_le_bytes().to_vec()),
+    ];
+    let data = settlement.pack();
+
+    let idl = idl();
+    let fields = defined_fields(&idl, &"Settlement".into());
+    assert_eq!(fields.len(), expected.len());
+    let mut offset = 0;
+    for (field, (name, bytes)) in fields.iter().zip(expected) {
+        assert_eq!(field["name"], name);
+        let size = type_size(&idl, &field["type"]);
+        assert_eq!(&data[offset..offset + size], &bytes[..], "{}", name);
+        offset += size;
+    }
+    assert_eq!(offset, Settlement::LEN);
+    assert_eq!(data.len(), Settlement::LEN);
+}
@@ -0,0 +1,85 @@
+use solana_escrow::{error::EscrowError, loader, state::Escrow};
+use solana_program::{
+    account_info::AccountInfo, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+};
+
+fn escrow_data() -> Vec<u8> {
+    let mut data = vec![0; Escrow::LEN];
+    Escrow::pack(
+        Escrow {
+            is_initialized: true,
+            initializer_pubkey: Pubkey::new_unique(),
+            temp_token_account_pubkey: Pubkey::new_unique(),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_unique(),
+            expected_amount: 1,
+            unlock_time: 2,
+            time_out: 3,
+            ..Escrow::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    data
+}
+
+fn load(
+    data: &mut [u8],
+    owner: &Pubkey,
+    is_writable: bool,
+    writable: bool,
+) -> Result<Escrow, ProgramError> {
+    let key = Pubkey::new_unique();
+    let mut lamports = 0;
+    let account = AccountInfo::new(
+        &key,
+        false,
+        is_writable,
+        &mut lamports,
+        data,
+        owner,
+        false,
+        0,
+    );
+    loader::load(&account, &solana_escrow::id(), writable)
+}
+
+#[test]
+fn loads_escrows_owned_by_the_program() {
+    let mut data = escrow_data();
+    assert!(load(&mut data, &solana_escrow::id(), true, true).is_ok());
+    assert!(load(&mut data, &solana_escrow::id(), false, false).is_ok());
+}
+
+#[test]
+fn rejects_foreign_owners() {
+    let mut data = escrow_data();
+    assert_eq!(
+        load(&mut data, &Pubkey::new_unique(), true, true).err(),
+        Some(ProgramError::IllegalOwner)
+    );
+}
+
+#[test]
+fn rejects_read_only_accounts_when_writing() {
+    let mut data = escrow_data();
+    assert_eq!(
+        load(&mut data, &solana_escrow::id(), false, true).err(),
+        Some(EscrowError::AccountNotWritable.into())
+    );
+}
+
+#[test]
+fn rejects_other_account_types() {
+    let mut data = escrow_data();
+    data[0] = 7;
+    assert_eq!(
+        load(&mut data, &solana_escrow::id(), true, true).err(),
+        Some(EscrowError::WrongAccountType.into())
+    );
+
+    let mut data = vec![0; Escrow::LEN];
+    assert_eq!(
+        load(&mut data, &solana_escrow::id(), true, true).err(),
+        Some(EscrowError::WrongAccountType.into())
+    );
+}
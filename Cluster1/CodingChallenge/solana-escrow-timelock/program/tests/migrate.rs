@@ -0,0 +1,124 @@
+mod common;
+
+use common::{
+    account, escrow, escrow_failure, lamports, program_test, rent_exempt, send, start,
+    token_balance, Swap, OFFERED, REQUESTED,
+};
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{Escrow, ESCROW_VERSION, LEGACY_ESCROW_LEN},
+};
+use solana_program::program_pack::Pack;
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{rent::Rent, signature::Signer};
+
+/// The swap's escrow as the program wrote it before the layout had a header,
+/// with `unlock_time` big-endian
+fn legacy_escrow(swap: &Swap, unlock_time: u64, time_out: u64) -> Vec<u8> {
+    let mut data = vec![1];
+    data.extend_from_slice(swap.maker.pubkey().as_ref());
+    data.extend_from_slice(swap.vault.as_ref());
+    data.extend_from_slice(swap.maker_receive.as_ref());
+    data.extend_from_slice(&REQUESTED.to_le_bytes());
+    data.extend_from_slice(&unlock_time.to_be_bytes());
+    data.extend_from_slice(&time_out.to_le_bytes());
+    assert_eq!(data.len(), LEGACY_ESCROW_LEN);
+    data
+}
+
+async fn legacy_swap(unlock_time: u64, time_out: u64) -> (ProgramTestContext, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_accounts_to(&mut test);
+    test.add_account(
+        swap.escrow,
+        rent_exempt(
+            legacy_escrow(&swap, unlock_time, time_out),
+            solana_escrow::id(),
+        ),
+    );
+    (start(test).await, swap)
+}
+
+#[tokio::test]
+async fn a_migrated_legacy_escrow_can_be_taken() {
+    // never reset, so the old program left the window zeroed
+    let (mut context, swap) = legacy_swap(0, 0).await;
+    let maker = swap.maker.pubkey();
+    let maker_before = lamports(&mut context, &maker).await;
+
+    let migrate = instruction::migrate(&solana_escrow::id(), &maker, &swap.escrow).unwrap();
+    send(&mut context, &[migrate], &[&swap.maker])
+        .await
+        .unwrap();
+
+    let migrated = account(&mut context, &swap.escrow).await.unwrap();
+    assert_eq!(migrated.data.len(), Escrow::LEN);
+    assert_eq!(Escrow::version(&migrated.data).unwrap(), ESCROW_VERSION);
+    let rent = Rent::default();
+    assert_eq!(migrated.lamports, rent.minimum_balance(Escrow::LEN));
+    assert_eq!(
+        maker_before - lamports(&mut context, &maker).await,
+        rent.minimum_balance(Escrow::LEN) - rent.minimum_balance(LEGACY_ESCROW_LEN)
+    );
+    let escrow_info = escrow(&mut context, &swap.escrow).await;
+    assert_eq!(escrow_info.unlock_time, 0);
+    assert_eq!(escrow_info.time_out, u64::MAX);
+
+    send(&mut context, &[swap.exchange()], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_receive).await,
+        REQUESTED
+    );
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn a_reset_legacy_escrow_keeps_its_window() {
+    let (mut context, swap) = legacy_swap(150, 1_150).await;
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowTimeUnlock))
+    );
+
+    context.warp_to_slot(150).unwrap();
+    send(&mut context, &[swap.exchange()], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+}
+
+#[tokio::test]
+async fn legacy_terms_only_change_once_migrated() {
+    let (mut context, swap) = legacy_swap(0, 1_000).await;
+    let program_id = solana_escrow::id();
+    let maker = swap.maker.pubkey();
+    let set_premium = instruction::set_premium(&program_id, &maker, &swap.escrow, 5).unwrap();
+    let reset_time_lock =
+        instruction::reset_time_lock(&program_id, &maker, &swap.escrow, 10, 100).unwrap();
+    for ix in [set_premium.clone(), reset_time_lock] {
+        assert_eq!(
+            send(&mut context, &[ix], &[&swap.maker]).await,
+            Err(escrow_failure(EscrowError::OutdatedEscrow))
+        );
+    }
+
+    let migrate = instruction::migrate(&program_id, &maker, &swap.escrow).unwrap();
+    send(&mut context, &[migrate], &[&swap.maker])
+        .await
+        .unwrap();
+    send(&mut context, &[set_premium], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(escrow(&mut context, &swap.escrow).await.premium, 5);
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use common::{
+    account, escrow, escrow_failure, program_test, send, start, token_balance, Swap, OFFERED, SLOT,
+};
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{Escrow, EscrowMode, Milestone, MilestoneState, MAX_MILESTONES},
+};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{instruction::Instruction, signature::Signer};
+
+const DEADLINE: u64 = SLOT + 10;
+
+/// A contract paying the swap's taker [`OFFERED`] in tranches of 200 and 300,
+/// which the maker can take back after [`DEADLINE`], and 500 without a
+/// deadline
+async fn contract() -> (ProgramTestContext, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    let mut milestones = [Milestone::default(); MAX_MILESTONES];
+    for (milestone, (amount, deadline)) in
+        milestones
+            .iter_mut()
+            .zip([(200, Some(DEADLINE)), (300, Some(DEADLINE)), (500, None)])
+    {
+        *milestone = Milestone {
+            amount,
+            deadline,
+            state: MilestoneState::Pending,
+        };
+    }
+    swap.add_to(
+        &mut test,
+        Escrow {
+            mode: EscrowMode::Milestones,
+            counterparty: swap.taker.pubkey(),
+            milestone_count: 3,
+            remaining: OFFERED,
+            milestones,
+            ..swap.escrow_info(0, u64::MAX)
+        },
+    );
+    (start(test).await, swap)
+}
+
+fn approve(swap: &Swap, index: u8) -> Instruction {
+    instruction::approve_milestone(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.taker_receive,
+        &swap.vault,
+        &swap.escrow,
+        &spl_token::id(),
+        index,
+    )
+    .unwrap()
+}
+
+fn refund_unapproved(swap: &Swap) -> Instruction {
+    instruction::refund_unapproved(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.maker_refund,
+        &swap.vault,
+        &swap.escrow,
+        &spl_token::id(),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn each_approval_pays_its_own_tranche() {
+    let (mut context, swap) = contract().await;
+    for (index, paid, remaining) in [(1, 300, 700), (0, 500, 500)] {
+        send(&mut context, &[approve(&swap, index)], &[&swap.maker])
+            .await
+            .unwrap();
+        assert_eq!(token_balance(&mut context, &swap.taker_receive).await, paid);
+        let escrow_info = escrow(&mut context, &swap.escrow).await;
+        assert_eq!(escrow_info.remaining, remaining);
+        assert_eq!(
+            escrow_info.milestones[index as usize].state,
+            MilestoneState::Approved
+        );
+    }
+
+    // the last tranche closes the escrow
+    send(&mut context, &[approve(&swap, 2)], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn a_tranche_is_approved_only_once() {
+    let (mut context, swap) = contract().await;
+    send(&mut context, &[approve(&swap, 1)], &[&swap.maker])
+        .await
+        .unwrap();
+    context.get_new_latest_blockhash().await.unwrap();
+    assert_eq!(
+        send(&mut context, &[approve(&swap, 1)], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::MilestoneSettled))
+    );
+    assert_eq!(token_balance(&mut context, &swap.taker_receive).await, 300);
+
+    // nor can a tranche past the ones in use be approved
+    assert_eq!(
+        send(&mut context, &[approve(&swap, 3)], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::InvalidMilestones))
+    );
+}
+
+#[tokio::test]
+async fn only_unapproved_tranches_past_their_deadline_are_refunded() {
+    let (mut context, swap) = contract().await;
+    send(&mut context, &[approve(&swap, 0)], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        send(&mut context, &[refund_unapproved(&swap)], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::NoExpiredMilestones))
+    );
+
+    context.warp_to_slot(DEADLINE + 1).unwrap();
+    send(&mut context, &[refund_unapproved(&swap)], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut context, &swap.maker_refund).await, 300);
+    let escrow_info = escrow(&mut context, &swap.escrow).await;
+    assert_eq!(escrow_info.remaining, 500);
+    assert_eq!(
+        escrow_info
+            .milestones()
+            .iter()
+            .map(|milestone| milestone.state)
+            .collect::<Vec<_>>(),
+        [
+            MilestoneState::Approved,
+            MilestoneState::Refunded,
+            MilestoneState::Pending
+        ]
+    );
+
+    // a refunded tranche can't be approved, one without a deadline still can
+    assert_eq!(
+        send(&mut context, &[approve(&swap, 1)], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::MilestoneSettled))
+    );
+    send(&mut context, &[approve(&swap, 2)], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut context, &swap.taker_receive).await, 700);
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
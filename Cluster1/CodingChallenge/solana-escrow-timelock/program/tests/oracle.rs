@@ -0,0 +1,225 @@
+mod common;
+
+use common::{
+    escrow_failure, failure, program_test, rent_exempt, send, start, token_balance, Swap, OFFERED,
+    SLOT,
+};
+use native_accounts::AccountError;
+use solana_escrow::{
+    error::EscrowError,
+    instruction::EscrowInstruction,
+    oracle::{MockOracle, OracleKind, OraclePrice, PriceCondition, PriceFeed},
+    state::Escrow,
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::instruction::{AccountMeta, Instruction};
+
+const MAX_STALENESS: u64 = 10;
+
+fn mock_account(price: i64, expo: i32, publish_slot: u64) -> Vec<u8> {
+    let mut data = vec![0; MockOracle::LEN];
+    MockOracle {
+        price,
+        expo,
+        publish_slot,
+    }
+    .pack(&mut data);
+    data
+}
+
+#[test]
+fn mock_oracles_read_back_their_price() {
+    let data = mock_account(-1_234, -2, 77);
+    let price = OracleKind::Mock.read_price(&data).unwrap();
+    assert_eq!(
+        price,
+        OraclePrice {
+            price: -1_234,
+            expo: -2,
+            publish_slot: 77,
+        }
+    );
+    // trailing bytes are the feed owner's business
+    let longer = [data, vec![9; 8]].concat();
+    assert_eq!(MockOracle::read_price(&longer).unwrap(), price);
+}
+
+#[test]
+fn other_accounts_are_not_mock_oracles() {
+    let mut data = mock_account(100, 0, 1);
+    assert_eq!(
+        MockOracle::read_price(&data[..MockOracle::LEN - 1]).unwrap_err(),
+        ProgramError::InvalidAccountData
+    );
+    data[..4].copy_from_slice(b"pyth");
+    assert_eq!(
+        MockOracle::read_price(&data).unwrap_err(),
+        ProgramError::InvalidAccountData
+    );
+}
+
+#[test]
+fn conditions_compare_across_exponents() {
+    // 2.50 against a trigger of 25 * 10^-1
+    let price = OracleKind::Mock
+        .read_price(&mock_account(250, -2, 0))
+        .unwrap();
+    for (condition, trigger_price, trigger_expo, met) in [
+        (PriceCondition::AtOrAbove, 25, -1, true),
+        (PriceCondition::AtOrBelow, 25, -1, true),
+        (PriceCondition::AtOrAbove, 2_501, -3, false),
+        (PriceCondition::AtOrBelow, 2_499, -3, false),
+        (PriceCondition::AtOrAbove, 2, 0, true),
+        (PriceCondition::AtOrBelow, 3, 0, true),
+        (PriceCondition::AtOrBelow, -3, 0, false),
+        (PriceCondition::None, i64::MAX, 0, true),
+    ] {
+        assert_eq!(
+            condition
+                .is_met(&price, trigger_price, trigger_expo)
+                .unwrap(),
+            met,
+            "{:?} {}e{}",
+            condition,
+            trigger_price,
+            trigger_expo
+        );
+    }
+}
+
+#[test]
+fn exponents_too_far_apart_overflow() {
+    let price = OraclePrice {
+        price: i64::MAX,
+        expo: 30,
+        publish_slot: 0,
+    };
+    assert_eq!(
+        PriceCondition::AtOrAbove
+            .is_met(&price, 1, -30)
+            .unwrap_err(),
+        EscrowError::AmountOverflow.into()
+    );
+}
+
+#[test]
+fn prices_go_stale_after_max_staleness_slots() {
+    let price = OracleKind::Mock
+        .read_price(&mock_account(1, 0, 100))
+        .unwrap();
+    assert!(!price.is_stale(100, 0));
+    assert!(price.is_stale(101, 0));
+    assert!(!price.is_stale(125, 25));
+    assert!(price.is_stale(126, 25));
+    // a price from a later slot than the clock's isn't stale
+    assert!(!price.is_stale(50, 0));
+}
+
+#[test]
+fn negative_triggers_round_trip_through_instruction_data() {
+    let instruction = EscrowInstruction::SetPriceCondition {
+        oracle_kind: OracleKind::Mock,
+        condition: PriceCondition::AtOrBelow,
+        trigger_price: -5,
+        trigger_expo: -8,
+        max_staleness: 25,
+    };
+    let data = instruction.pack();
+    assert_eq!(data.len(), 1 + 1 + 1 + 8 + 4 + 8);
+    assert_eq!(EscrowInstruction::unpack(&data).unwrap(), instruction);
+
+    let mut unknown_kind = data;
+    unknown_kind[1] = 1;
+    assert_eq!(
+        EscrowInstruction::unpack(&unknown_kind).unwrap_err(),
+        EscrowError::InvalidInstruction.into()
+    );
+}
+
+/// A swap that can only be taken while `oracle` prices the offered tokens at
+/// 2.50 or more, published at most [`MAX_STALENESS`] slots ago. The oracle
+/// publishes `price * 10^-2` at `publish_slot`, and `decoy` is a fresh oracle
+/// at 3.00 the swap doesn't use.
+async fn limit_order(price: i64, publish_slot: u64) -> (ProgramTestContext, Swap, Pubkey, Pubkey) {
+    let swap = Swap::new();
+    let (oracle, decoy) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut test = program_test();
+    swap.add_to(
+        &mut test,
+        Escrow {
+            oracle,
+            oracle_kind: OracleKind::Mock,
+            price_condition: PriceCondition::AtOrAbove,
+            trigger_price: 25,
+            trigger_expo: -1,
+            max_staleness: MAX_STALENESS,
+            ..swap.escrow_info(0, u64::MAX)
+        },
+    );
+    for (key, price) in [(oracle, price), (decoy, 300)] {
+        test.add_account(
+            key,
+            rent_exempt(mock_account(price, -2, publish_slot), Pubkey::new_unique()),
+        );
+    }
+    (start(test).await, swap, oracle, decoy)
+}
+
+/// The taker's `Exchange`, reading the price from `oracle`
+fn exchange_at(swap: &Swap, oracle: &Pubkey) -> Instruction {
+    let mut exchange = swap.exchange();
+    exchange
+        .accounts
+        .push(AccountMeta::new_readonly(*oracle, false));
+    exchange
+}
+
+#[tokio::test]
+async fn swaps_are_taken_while_the_price_meets_the_condition() {
+    for price in [250, 300] {
+        let (mut context, swap, oracle, _) = limit_order(price, SLOT).await;
+        send(&mut context, &[exchange_at(&swap, &oracle)], &[&swap.taker])
+            .await
+            .unwrap();
+        assert_eq!(
+            token_balance(&mut context, &swap.taker_receive).await,
+            OFFERED
+        );
+    }
+}
+
+#[tokio::test]
+async fn swaps_are_not_taken_while_the_price_misses_the_condition() {
+    let (mut context, swap, oracle, _) = limit_order(249, SLOT).await;
+    assert_eq!(
+        send(&mut context, &[exchange_at(&swap, &oracle)], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::PriceConditionNotMet))
+    );
+}
+
+#[tokio::test]
+async fn stale_prices_are_rejected() {
+    for (publish_slot, stale) in [
+        (SLOT - MAX_STALENESS, false),
+        (SLOT - MAX_STALENESS - 1, true),
+    ] {
+        let (mut context, swap, oracle, _) = limit_order(300, publish_slot).await;
+        let result = send(&mut context, &[exchange_at(&swap, &oracle)], &[&swap.taker]).await;
+        if stale {
+            assert_eq!(result, Err(escrow_failure(EscrowError::StaleOraclePrice)));
+        } else {
+            result.unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn only_the_swaps_own_oracle_is_read() {
+    let (mut context, swap, _, decoy) = limit_order(200, SLOT).await;
+    assert_eq!(
+        send(&mut context, &[exchange_at(&swap, &decoy)], &[&swap.taker]).await,
+        Err(failure(AccountError::ConstraintAddress as u32))
+    );
+    assert_eq!(token_balance(&mut context, &swap.vault).await, OFFERED);
+}
@@ -0,0 +1,313 @@
+mod common;
+
+use common::{
+    account, delegated_token_account, escrow_failure, program_test, send, start, token_account,
+    token_balance, wallet, OFFERED, REQUESTED, SLOT,
+};
+use solana_escrow::{
+    error::EscrowError,
+    instruction::{self, EscrowInstruction},
+    order::{self, Order},
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{
+    ed25519_instruction,
+    feature_set::FeatureSet,
+    instruction::{Instruction, InstructionError},
+    signature::Keypair,
+    signer::Signer,
+    transaction::TransactionError,
+};
+
+fn signed_order() -> (Keypair, Order, Instruction) {
+    let program_id = solana_escrow::id();
+    let maker = Keypair::new();
+    let order = Order {
+        maker: maker.pubkey(),
+        maker_send_account: Pubkey::new_unique(),
+        maker_receive_account: Pubkey::new_unique(),
+        offered_amount: 1,
+        requested_amount: 2,
+        nonce: 3,
+        expires_at: 4,
+        taker: Pubkey::new_unique(),
+    };
+    let message = order.message(&program_id);
+    let signature = maker.sign_message(&message);
+    let ix = order::ed25519_instruction(
+        &maker.pubkey(),
+        signature.as_ref().try_into().unwrap(),
+        &message,
+    );
+    (maker, order, ix)
+}
+
+#[test]
+fn orders_round_trip_through_instruction_data() {
+    let (_, order, _) = signed_order();
+    let data = EscrowInstruction::FillSignedOrder { order }.pack();
+    assert_eq!(data.len(), 1 + Order::LEN);
+    match EscrowInstruction::unpack(&data).unwrap() {
+        EscrowInstruction::FillSignedOrder { order: unpacked } => assert_eq!(unpacked, order),
+        other => panic!("unpacked {:?}", other),
+    }
+    assert_eq!(
+        EscrowInstruction::unpack(&data[..data.len() - 1]).unwrap_err(),
+        EscrowError::InvalidInstruction.into()
+    );
+}
+
+#[test]
+fn the_ed25519_program_accepts_the_built_instruction() {
+    let (_, _, ix) = signed_order();
+    ed25519_instruction::verify(&ix.data, &[&ix.data], &FeatureSet::all_enabled()).unwrap();
+
+    let mut tampered = ix.data.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(
+        ed25519_instruction::verify(&tampered, &[&tampered], &FeatureSet::all_enabled()).is_err()
+    );
+}
+
+#[test]
+fn verification_only_accepts_the_makers_signature_of_the_order() {
+    let program_id = solana_escrow::id();
+    let (maker, order, ix) = signed_order();
+    let message = order.message(&program_id);
+    order::verify_ed25519(&ix, &maker.pubkey(), &message).unwrap();
+
+    let invalid: ProgramError = EscrowError::InvalidOrderSignature.into();
+    let other_key = Pubkey::new_unique();
+    assert_eq!(
+        order::verify_ed25519(&ix, &other_key, &message).unwrap_err(),
+        invalid
+    );
+    let other_order = Order {
+        offered_amount: 100,
+        ..order
+    };
+    assert_eq!(
+        order::verify_ed25519(&ix, &maker.pubkey(), &other_order.message(&program_id)).unwrap_err(),
+        invalid
+    );
+    // an order signed for another deployment of the program
+    assert_eq!(
+        order::verify_ed25519(&ix, &maker.pubkey(), &order.message(&Pubkey::new_unique()))
+            .unwrap_err(),
+        invalid
+    );
+
+    let mut wrong_program = ix.clone();
+    wrong_program.program_id = Pubkey::new_unique();
+    assert_eq!(
+        order::verify_ed25519(&wrong_program, &maker.pubkey(), &message).unwrap_err(),
+        invalid
+    );
+    // a key read from another instruction of the transaction can't be trusted
+    let mut other_instruction = ix;
+    other_instruction.data[8..10].copy_from_slice(&0u16.to_le_bytes());
+    assert_eq!(
+        order::verify_ed25519(&other_instruction, &maker.pubkey(), &message).unwrap_err(),
+        invalid
+    );
+}
+
+/// A maker who approved the PDA to sell tokens from their account, and a
+/// taker with [`REQUESTED`] tokens to pay for them
+struct Book {
+    maker: Keypair,
+    taker: Keypair,
+    maker_send: Pubkey,
+    maker_receive: Pubkey,
+    taker_send: Pubkey,
+    taker_receive: Pubkey,
+}
+
+impl Book {
+    /// The maker's account holds `balance` tokens, `delegated` of them
+    /// approved to `delegate`
+    async fn open(
+        balance: u64,
+        delegate: Option<Pubkey>,
+        delegated: u64,
+    ) -> (ProgramTestContext, Self) {
+        let book = Book {
+            maker: Keypair::new(),
+            taker: Keypair::new(),
+            maker_send: Pubkey::new_unique(),
+            maker_receive: Pubkey::new_unique(),
+            taker_send: Pubkey::new_unique(),
+            taker_receive: Pubkey::new_unique(),
+        };
+        let (offered_mint, requested_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (maker, taker) = (book.maker.pubkey(), book.taker.pubkey());
+        let mut test = program_test();
+        test.add_account(maker, wallet());
+        test.add_account(taker, wallet());
+        test.add_account(offered_mint, common::mint());
+        test.add_account(requested_mint, common::mint());
+        test.add_account(
+            book.maker_send,
+            delegated_token_account(&offered_mint, &maker, balance, delegate, delegated),
+        );
+        test.add_account(
+            book.maker_receive,
+            token_account(&requested_mint, &maker, 0),
+        );
+        test.add_account(
+            book.taker_send,
+            token_account(&requested_mint, &taker, REQUESTED),
+        );
+        test.add_account(book.taker_receive, token_account(&offered_mint, &taker, 0));
+        (start(test).await, book)
+    }
+
+    /// The maker has [`OFFERED`] tokens, all approved to the PDA
+    async fn approved() -> (ProgramTestContext, Self) {
+        Self::open(OFFERED, Some(common::pda().0), OFFERED).await
+    }
+
+    /// An order of [`OFFERED`] for [`REQUESTED`] at `nonce`, for any taker
+    fn order(&self, nonce: u64) -> Order {
+        Order {
+            maker: self.maker.pubkey(),
+            maker_send_account: self.maker_send,
+            maker_receive_account: self.maker_receive,
+            offered_amount: OFFERED,
+            requested_amount: REQUESTED,
+            nonce,
+            expires_at: SLOT + 100,
+            taker: Pubkey::default(),
+        }
+    }
+
+    /// The maker's signature of `order`, checked by the ed25519 program
+    fn signature(&self, order: &Order) -> Instruction {
+        let message = order.message(&solana_escrow::id());
+        let signature = self.maker.sign_message(&message);
+        order::ed25519_instruction(
+            &self.maker.pubkey(),
+            signature.as_ref().try_into().unwrap(),
+            &message,
+        )
+    }
+
+    fn fill(&self, order: &Order) -> Instruction {
+        instruction::fill_signed_order(
+            &solana_escrow::id(),
+            &self.taker.pubkey(),
+            &self.taker_send,
+            &self.taker_receive,
+            order,
+            &spl_token::id(),
+        )
+        .unwrap()
+    }
+
+    async fn send_fill(
+        &self,
+        context: &mut ProgramTestContext,
+        order: &Order,
+    ) -> Result<(), TransactionError> {
+        let instructions = [self.signature(order), self.fill(order)];
+        send(context, &instructions, &[&self.taker]).await
+    }
+}
+
+/// How a fill after its ed25519 instruction fails
+fn fill_failure(error: EscrowError) -> TransactionError {
+    TransactionError::InstructionError(1, InstructionError::Custom(error as u32))
+}
+
+#[tokio::test]
+async fn a_signed_order_fills_once() {
+    let (mut context, book) = Book::approved().await;
+    let order = book.order(0);
+    book.send_fill(&mut context, &order).await.unwrap();
+    assert_eq!(
+        token_balance(&mut context, &book.taker_receive).await,
+        OFFERED
+    );
+    assert_eq!(
+        token_balance(&mut context, &book.maker_receive).await,
+        REQUESTED
+    );
+    let receipt = instruction::order_receipt_address(&solana_escrow::id(), &order);
+    assert_eq!(
+        account(&mut context, &receipt).await.unwrap().owner,
+        solana_escrow::id()
+    );
+
+    context.get_new_latest_blockhash().await.unwrap();
+    assert_eq!(
+        book.send_fill(&mut context, &order).await,
+        Err(fill_failure(EscrowError::OrderAlreadyFilled))
+    );
+}
+
+#[tokio::test]
+async fn cancelling_all_orders_makes_older_ones_unfillable() {
+    let (mut context, book) = Book::approved().await;
+    let cancel =
+        instruction::cancel_all_orders(&solana_escrow::id(), &book.maker.pubkey()).unwrap();
+    send(&mut context, &[cancel], &[&book.maker]).await.unwrap();
+
+    assert_eq!(
+        book.send_fill(&mut context, &book.order(0)).await,
+        Err(fill_failure(EscrowError::StaleOrderNonce))
+    );
+    // orders signed after cancelling still fill
+    book.send_fill(&mut context, &book.order(1)).await.unwrap();
+    assert_eq!(
+        token_balance(&mut context, &book.taker_receive).await,
+        OFFERED
+    );
+}
+
+#[tokio::test]
+async fn a_fill_needs_the_makers_signature_right_before_it() {
+    let (mut context, book) = Book::approved().await;
+    let order = book.order(0);
+    assert_eq!(
+        send(&mut context, &[book.fill(&order)], &[&book.taker]).await,
+        Err(escrow_failure(EscrowError::InvalidOrderSignature))
+    );
+
+    // a signature of another order doesn't cover this one
+    let other = Order {
+        offered_amount: 1,
+        ..order
+    };
+    assert_eq!(
+        send(
+            &mut context,
+            &[book.signature(&other), book.fill(&order)],
+            &[&book.taker]
+        )
+        .await,
+        Err(fill_failure(EscrowError::InvalidOrderSignature))
+    );
+    assert_eq!(token_balance(&mut context, &book.taker_receive).await, 0);
+}
+
+#[tokio::test]
+async fn fills_need_the_whole_order_approved_and_held() {
+    let (mut context, book) = Book::open(OFFERED, None, 0).await;
+    assert_eq!(
+        book.send_fill(&mut context, &book.order(0)).await,
+        Err(fill_failure(EscrowError::DelegationRevoked))
+    );
+
+    let (mut context, book) = Book::open(OFFERED, Some(common::pda().0), OFFERED - 1).await;
+    assert_eq!(
+        book.send_fill(&mut context, &book.order(0)).await,
+        Err(fill_failure(EscrowError::InsufficientDelegation))
+    );
+
+    let (mut context, book) = Book::open(OFFERED - 1, Some(common::pda().0), OFFERED).await;
+    assert_eq!(
+        book.send_fill(&mut context, &book.order(0)).await,
+        Err(fill_failure(EscrowError::InsufficientBalance))
+    );
+}
@@ -0,0 +1,391 @@
+mod common;
+
+use common::{
+    account, lamports, program_test, rent_exempt, send, start, token_account, token_balance, wallet,
+};
+use native_accounts::AccountError;
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{Pool, PoolShare},
+};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::TransactionError,
+};
+
+/// A pool the makers deposited `deposits` into, selling 3 for 2
+fn pool(deposits: &[u64]) -> Pool {
+    let total: u64 = deposits.iter().sum();
+    Pool {
+        is_initialized: true,
+        offered_per_lot: 3,
+        requested_per_lot: 2,
+        total_shares: total,
+        unfilled: total,
+        ..Pool::default()
+    }
+}
+
+#[test]
+fn fills_take_whole_lots_until_the_pool_runs_out() {
+    let mut pool = pool(&[10]);
+    assert_eq!(pool.fill(2).unwrap(), (6, 4));
+    assert_eq!((pool.unfilled, pool.proceeds), (4, 4));
+
+    let exhausted: ProgramError = EscrowError::PoolExhausted.into();
+    assert_eq!(pool.fill(2).unwrap_err(), exhausted);
+    assert_eq!(pool.fill(1).unwrap(), (3, 2));
+    assert_eq!((pool.unfilled, pool.proceeds), (1, 6));
+    assert_eq!(pool.fill(1).unwrap_err(), exhausted);
+}
+
+#[test]
+fn withdrawals_pay_out_exactly_what_the_pool_holds() {
+    let deposits = [1, 1, 1, 7, 1_000_003, u32::MAX as u64];
+    let mut filled = pool(&deposits);
+    filled.fill(filled.unfilled / 3 / 2).unwrap();
+    let (unfilled, proceeds) = (filled.unfilled, filled.proceeds);
+    let total_shares = filled.total_shares;
+
+    // in every order, the payouts are each maker's part rounded down, or up
+    // by whatever rounding the makers before left behind
+    for order in [[0, 1, 2, 3, 4, 5], [5, 4, 3, 2, 1, 0], [3, 0, 5, 1, 4, 2]] {
+        let mut pool = pool(&deposits);
+        pool.fill(pool.unfilled / 3 / 2).unwrap();
+        let (mut paid_unfilled, mut paid_proceeds) = (0, 0);
+        for maker in order {
+            let shares = deposits[maker];
+            let (maker_unfilled, maker_proceeds) = pool.withdraw(shares).unwrap();
+            let fair =
+                |amount: u64| (amount as u128 * shares as u128 / total_shares as u128) as u64;
+            let slack = deposits.len() as u64;
+            assert!(maker_unfilled >= fair(unfilled) && maker_unfilled <= fair(unfilled) + slack);
+            assert!(maker_proceeds >= fair(proceeds) && maker_proceeds <= fair(proceeds) + slack);
+            paid_unfilled += maker_unfilled;
+            paid_proceeds += maker_proceeds;
+        }
+        assert_eq!((paid_unfilled, paid_proceeds), (unfilled, proceeds));
+        assert_eq!((pool.total_shares, pool.unfilled, pool.proceeds), (0, 0, 0));
+    }
+}
+
+#[test]
+fn withdrawing_before_any_fill_returns_the_deposit() {
+    let mut pool = pool(&[5, 8]);
+    assert_eq!(pool.withdraw(8).unwrap(), (8, 0));
+    assert_eq!(pool.withdraw(5).unwrap(), (5, 0));
+
+    let invalid: ProgramError = EscrowError::InvalidInstruction.into();
+    assert_eq!(pool.withdraw(1).unwrap_err(), invalid);
+}
+
+#[test]
+fn only_an_empty_pool_redeems_no_shares() {
+    let invalid: ProgramError = EscrowError::InvalidInstruction.into();
+    assert_eq!(pool(&[5]).withdraw(0).unwrap_err(), invalid);
+
+    let mut empty = pool(&[]);
+    assert_eq!(empty.withdraw(0).unwrap(), (0, 0));
+    assert_eq!(empty.withdraw(1).unwrap_err(), invalid);
+}
+
+#[test]
+fn share_accounts_round_trip() {
+    let share = PoolShare {
+        is_initialized: true,
+        pool: Pubkey::new_unique(),
+        maker: Pubkey::new_unique(),
+        shares: 42,
+        bump: 254,
+    };
+    let mut data = vec![0; PoolShare::LEN];
+    let (pool, maker) = (share.pool, share.maker);
+    PoolShare::pack(share, &mut data).unwrap();
+    assert_eq!(data[0], 5);
+    let unpacked = PoolShare::unpack(&data).unwrap();
+    assert_eq!(
+        (unpacked.pool, unpacked.maker, unpacked.shares),
+        (pool, maker, 42)
+    );
+    assert_eq!(unpacked.bump, 254);
+}
+
+/// A pool selling 3 of one mint for 2 of another, open to takers from slot
+/// 110 through 160, with two makers and a taker
+struct Market {
+    creator: Keypair,
+    /// The creator's accounts of the offered and requested mint
+    creator_accounts: (Pubkey, Pubkey),
+    pool: Pubkey,
+    vault: Pubkey,
+    proceeds_vault: Pubkey,
+    /// Each maker with their accounts of the offered and requested mint
+    makers: Vec<(Keypair, Pubkey, Pubkey)>,
+    taker: Keypair,
+    taker_send: Pubkey,
+    taker_receive: Pubkey,
+}
+
+impl Market {
+    fn withdraw(&self, maker: usize) -> Instruction {
+        let (maker, offered, requested) = &self.makers[maker];
+        self.withdraw_into(maker, offered, requested)
+    }
+
+    /// `signer`'s `WithdrawPool`, paying into `offered` and `requested`
+    fn withdraw_into(&self, signer: &Keypair, offered: &Pubkey, requested: &Pubkey) -> Instruction {
+        instruction::withdraw_pool(
+            &solana_escrow::id(),
+            &signer.pubkey(),
+            &self.pool,
+            &self.vault,
+            &self.proceeds_vault,
+            offered,
+            requested,
+            &self.creator.pubkey(),
+            &spl_token::id(),
+        )
+        .unwrap()
+    }
+}
+
+/// Opens the pool and has the makers deposit `deposits`, leaving the
+/// validator before the pool opens
+async fn open_market(deposits: &[u64]) -> (ProgramTestContext, Market) {
+    let (offered_mint, requested_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let market = Market {
+        creator: Keypair::new(),
+        creator_accounts: (Pubkey::new_unique(), Pubkey::new_unique()),
+        pool: Pubkey::new_unique(),
+        vault: Pubkey::new_unique(),
+        proceeds_vault: Pubkey::new_unique(),
+        makers: deposits
+            .iter()
+            .map(|_| (Keypair::new(), Pubkey::new_unique(), Pubkey::new_unique()))
+            .collect(),
+        taker: Keypair::new(),
+        taker_send: Pubkey::new_unique(),
+        taker_receive: Pubkey::new_unique(),
+    };
+    let creator = market.creator.pubkey();
+    let mut test = program_test();
+    test.add_account(offered_mint, common::mint());
+    test.add_account(requested_mint, common::mint());
+    test.add_account(creator, wallet());
+    test.add_account(
+        market.creator_accounts.0,
+        token_account(&offered_mint, &creator, 0),
+    );
+    test.add_account(
+        market.creator_accounts.1,
+        token_account(&requested_mint, &creator, 0),
+    );
+    test.add_account(
+        market.pool,
+        rent_exempt(vec![0; Pool::LEN], solana_escrow::id()),
+    );
+    test.add_account(market.vault, token_account(&offered_mint, &creator, 0));
+    test.add_account(
+        market.proceeds_vault,
+        token_account(&requested_mint, &creator, 0),
+    );
+    for ((maker, offered, requested), deposit) in market.makers.iter().zip(deposits) {
+        test.add_account(maker.pubkey(), wallet());
+        test.add_account(
+            *offered,
+            token_account(&offered_mint, &maker.pubkey(), *deposit),
+        );
+        test.add_account(
+            *requested,
+            token_account(&requested_mint, &maker.pubkey(), 0),
+        );
+    }
+    let taker = market.taker.pubkey();
+    test.add_account(taker, wallet());
+    test.add_account(
+        market.taker_send,
+        token_account(&requested_mint, &taker, 1_000),
+    );
+    test.add_account(
+        market.taker_receive,
+        token_account(&offered_mint, &taker, 0),
+    );
+    let mut context = start(test).await;
+
+    let program_id = solana_escrow::id();
+    let init = instruction::init_pool(
+        &program_id,
+        &creator,
+        &market.pool,
+        &market.vault,
+        &market.proceeds_vault,
+        &spl_token::id(),
+        3,
+        2,
+        10,
+        50,
+    )
+    .unwrap();
+    send(&mut context, &[init], &[&market.creator])
+        .await
+        .unwrap();
+    for ((maker, offered, _), deposit) in market.makers.iter().zip(deposits) {
+        let deposit = instruction::deposit_pool(
+            &program_id,
+            &maker.pubkey(),
+            offered,
+            &market.pool,
+            &market.vault,
+            &spl_token::id(),
+            *deposit,
+        )
+        .unwrap();
+        send(&mut context, &[deposit], &[maker]).await.unwrap();
+    }
+    (context, market)
+}
+
+/// Opens the pool, has the makers deposit `deposits` and the taker fill
+/// `lots`, and leaves the validator after the pool's time out
+async fn filled_market(deposits: &[u64], lots: u64) -> (ProgramTestContext, Market) {
+    let (mut context, market) = open_market(deposits).await;
+    context.warp_to_slot(110).unwrap();
+    let fill = instruction::fill_pool(
+        &solana_escrow::id(),
+        &market.taker.pubkey(),
+        &market.taker_send,
+        &market.taker_receive,
+        &market.pool,
+        &market.vault,
+        &market.proceeds_vault,
+        &spl_token::id(),
+        lots,
+    )
+    .unwrap();
+    send(&mut context, &[fill], &[&market.taker]).await.unwrap();
+
+    context.warp_to_slot(161).unwrap();
+    (context, market)
+}
+
+#[tokio::test]
+async fn makers_withdraw_their_part_of_a_filled_pool_once() {
+    let (mut context, market) = filled_market(&[30, 60], 10).await;
+    assert_eq!(token_balance(&mut context, &market.taker_receive).await, 30);
+    assert_eq!(token_balance(&mut context, &market.taker_send).await, 980);
+
+    // paying the closed share's rent back must not bring its shares back
+    let (maker, offered, requested) = &market.makers[0];
+    let share =
+        instruction::pool_share_address(&solana_escrow::id(), &market.pool, &maker.pubkey());
+    let revive = system_instruction::transfer(
+        &context.payer.pubkey(),
+        &share,
+        Rent::default().minimum_balance(PoolShare::LEN),
+    );
+    assert_eq!(
+        send(
+            &mut context,
+            &[market.withdraw(0), revive, market.withdraw(0)],
+            &[maker]
+        )
+        .await,
+        Err(TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(EscrowError::WrongAccountType as u32)
+        ))
+    );
+
+    // a third of the 60 left and of the 20 paid in, rounded down
+    send(&mut context, &[market.withdraw(0)], &[maker])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut context, offered).await, 20);
+    assert_eq!(token_balance(&mut context, requested).await, 6);
+    assert!(account(&mut context, &share).await.is_none());
+
+    context.warp_to_slot(162).unwrap();
+    assert_eq!(
+        send(&mut context, &[market.withdraw(0)], &[maker]).await,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::IllegalOwner
+        ))
+    );
+
+    // the last maker out takes the rest and closes the pool
+    let (last, offered, requested) = &market.makers[1];
+    send(&mut context, &[market.withdraw(1)], &[last])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut context, offered).await, 40);
+    assert_eq!(token_balance(&mut context, requested).await, 14);
+    for closed in [market.pool, market.vault, market.proceeds_vault] {
+        assert!(account(&mut context, &closed).await.is_none());
+    }
+}
+
+#[tokio::test]
+async fn a_closed_pool_cannot_be_revived() {
+    let (mut context, market) = filled_market(&[30], 10).await;
+    let (maker, ..) = &market.makers[0];
+    let revive = system_instruction::transfer(
+        &context.payer.pubkey(),
+        &market.pool,
+        Rent::default().minimum_balance(Pool::LEN),
+    );
+    assert_eq!(
+        send(
+            &mut context,
+            &[market.withdraw(0), revive, market.withdraw(0)],
+            &[maker]
+        )
+        .await,
+        Err(TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(EscrowError::WrongAccountType as u32)
+        ))
+    );
+}
+
+#[tokio::test]
+async fn the_creator_closes_a_pool_nobody_deposited_into() {
+    let (mut context, market) = open_market(&[]).await;
+    let (offered, requested) = &market.creator_accounts;
+    let stranger = Keypair::new();
+    assert_eq!(
+        send(
+            &mut context,
+            &[market.withdraw_into(&stranger, offered, requested)],
+            &[&stranger]
+        )
+        .await,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(AccountError::ConstraintAddress as u32)
+        ))
+    );
+
+    let creator = market.creator.pubkey();
+    let before = lamports(&mut context, &creator).await;
+    let rent = lamports(&mut context, &market.pool).await
+        + lamports(&mut context, &market.vault).await
+        + lamports(&mut context, &market.proceeds_vault).await;
+    send(
+        &mut context,
+        &[market.withdraw_into(&market.creator, offered, requested)],
+        &[&market.creator],
+    )
+    .await
+    .unwrap();
+    for closed in [market.pool, market.vault, market.proceeds_vault] {
+        assert!(account(&mut context, &closed).await.is_none());
+    }
+    assert_eq!(lamports(&mut context, &creator).await, before + rent);
+}
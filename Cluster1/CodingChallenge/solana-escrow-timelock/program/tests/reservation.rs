@@ -0,0 +1,193 @@
+mod common;
+
+use common::{
+    escrow, escrow_failure, program_test, send, start, token_account, token_balance, wallet, Swap,
+    OFFERED, REQUESTED, SLOT,
+};
+use solana_escrow::{error::EscrowError, instruction, state::Escrow};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+};
+
+const PREMIUM: u64 = 100;
+const TIME_OUT: u64 = SLOT + 100;
+
+/// A swap the taker can reserve for [`PREMIUM`] through [`TIME_OUT`], paying
+/// it out of `premium_account`, and a rival taker with their own accounts
+struct Reservable {
+    swap: Swap,
+    premium_account: Pubkey,
+    rival: Keypair,
+    rival_send: Pubkey,
+    rival_receive: Pubkey,
+}
+
+async fn reservable() -> (ProgramTestContext, Reservable) {
+    let swap = Swap::new();
+    let reservable = Reservable {
+        premium_account: Pubkey::new_unique(),
+        rival: Keypair::new(),
+        rival_send: Pubkey::new_unique(),
+        rival_receive: Pubkey::new_unique(),
+        swap,
+    };
+    let Reservable { swap, rival, .. } = &reservable;
+    let mut test = program_test();
+    swap.add_to(
+        &mut test,
+        Escrow {
+            premium: PREMIUM,
+            ..swap.escrow_info(0, TIME_OUT)
+        },
+    );
+    test.add_account(
+        reservable.premium_account,
+        token_account(&swap.requested_mint, &swap.taker.pubkey(), PREMIUM),
+    );
+    test.add_account(rival.pubkey(), wallet());
+    test.add_account(
+        reservable.rival_send,
+        token_account(&swap.requested_mint, &rival.pubkey(), REQUESTED),
+    );
+    test.add_account(
+        reservable.rival_receive,
+        token_account(&swap.offered_mint, &rival.pubkey(), 0),
+    );
+    (start(test).await, reservable)
+}
+
+impl Reservable {
+    /// The taker's `Reserve`, offering `premium`
+    fn reserve(&self, premium: u64) -> Instruction {
+        instruction::reserve(
+            &solana_escrow::id(),
+            &self.swap.taker.pubkey(),
+            &self.premium_account,
+            &self.swap.maker_receive,
+            &self.swap.escrow,
+            &spl_token::id(),
+            premium,
+            &[],
+        )
+        .unwrap()
+    }
+
+    /// The rival's `Exchange`, expecting all of [`OFFERED`]
+    fn rival_exchange(&self) -> Instruction {
+        let swap = &self.swap;
+        instruction::exchange(
+            &solana_escrow::id(),
+            &self.rival.pubkey(),
+            &self.rival_send,
+            &self.rival_receive,
+            &swap.vault,
+            &swap.maker.pubkey(),
+            &swap.maker_receive,
+            &swap.escrow,
+            &spl_token::id(),
+            OFFERED,
+            &[],
+        )
+        .unwrap()
+    }
+}
+
+#[tokio::test]
+async fn a_reservation_pays_the_premium_and_blocks_the_makers_cancel() {
+    let (mut context, reservable) = reservable().await;
+    let swap = &reservable.swap;
+    assert_eq!(
+        send(
+            &mut context,
+            &[reservable.reserve(PREMIUM - 1)],
+            &[&swap.taker]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::ExpectedAmountMismatch))
+    );
+    send(&mut context, &[reservable.reserve(PREMIUM)], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_receive).await,
+        PREMIUM
+    );
+    assert_eq!(
+        escrow(&mut context, &swap.escrow).await.counterparty,
+        swap.taker.pubkey()
+    );
+
+    assert_eq!(
+        send(&mut context, &[swap.cancel()], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::EscrowReserved))
+    );
+    send(&mut context, &[swap.exchange()], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_receive).await,
+        PREMIUM + REQUESTED
+    );
+}
+
+#[tokio::test]
+async fn other_takers_are_turned_away_while_reserved() {
+    let (mut context, reservable) = reservable().await;
+    let (swap, rival) = (&reservable.swap, &reservable.rival);
+    send(&mut context, &[reservable.reserve(PREMIUM)], &[&swap.taker])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        send(&mut context, &[reservable.rival_exchange()], &[rival]).await,
+        Err(escrow_failure(EscrowError::EscrowReserved))
+    );
+    // nor can the reservation be taken over, even by the same taker
+    context.get_new_latest_blockhash().await.unwrap();
+    assert_eq!(
+        send(&mut context, &[reservable.reserve(PREMIUM)], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowReserved))
+    );
+    assert_eq!(
+        token_balance(&mut context, &reservable.rival_send).await,
+        REQUESTED
+    );
+}
+
+#[tokio::test]
+async fn a_reservation_lapses_at_the_time_out() {
+    let (mut context, reservable) = reservable().await;
+    let swap = &reservable.swap;
+    send(&mut context, &[reservable.reserve(PREMIUM)], &[&swap.taker])
+        .await
+        .unwrap();
+
+    context.warp_to_slot(TIME_OUT + 1).unwrap();
+    assert!(!escrow(&mut context, &swap.escrow)
+        .await
+        .is_reserved(TIME_OUT + 1));
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowTimeOut))
+    );
+
+    // the maker gets the tokens back and keeps the premium
+    send(&mut context, &[swap.cancel()], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_refund).await,
+        OFFERED
+    );
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_receive).await,
+        PREMIUM
+    );
+}
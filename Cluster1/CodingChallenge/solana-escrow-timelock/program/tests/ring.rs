@@ -0,0 +1,150 @@
+mod common;
+
+use common::{
+    account, escrow_failure, mint, program_test, send, start, token_balance, wallet, Offer, SLOT,
+};
+use solana_escrow::{
+    error::EscrowError,
+    instruction::{self, EscrowInstruction, EscrowToTake},
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+};
+
+fn escrow_to_take() -> EscrowToTake {
+    EscrowToTake {
+        escrow: Pubkey::new_unique(),
+        vault: Pubkey::new_unique(),
+        maker: Pubkey::new_unique(),
+        maker_receive_account: Pubkey::new_unique(),
+    }
+}
+
+#[test]
+fn a_ring_needs_at_least_two_escrows() {
+    let program_id = solana_escrow::id();
+    let settler = Pubkey::new_unique();
+    let invalid: ProgramError = EscrowError::InvalidInstruction.into();
+    for ring in [vec![], vec![escrow_to_take()]] {
+        assert_eq!(
+            instruction::settle_ring(&program_id, &settler, &spl_token::id(), &ring).unwrap_err(),
+            invalid
+        );
+    }
+
+    let ring = [escrow_to_take(), escrow_to_take(), escrow_to_take()];
+    let ix = instruction::settle_ring(&program_id, &settler, &spl_token::id(), &ring).unwrap();
+    assert_eq!(
+        EscrowInstruction::unpack(&ix.data).unwrap(),
+        EscrowInstruction::SettleRing {}
+    );
+    let (fixed, groups) = ix.accounts.split_at(3);
+    assert!(fixed[0].is_signer && !fixed[0].is_writable);
+    assert_eq!(fixed[2].pubkey, instruction::escrow_authority(&program_id));
+    // the ring's order is kept, since each maker is paid by the next
+    let escrows: Vec<Pubkey> = groups.chunks(4).map(|group| group[3].pubkey).collect();
+    assert_eq!(escrows, ring.map(|escrow| escrow.escrow));
+}
+
+/// Three makers each offering what the previous one asks for: 10 X for
+/// 20 Y, 20 Y for 30 Z, and 30 Z for what the last asks
+struct Ring {
+    settler: Keypair,
+    offers: Vec<Offer>,
+}
+
+impl Ring {
+    fn settle(&self, offers: &[usize]) -> Instruction {
+        let ring: Vec<EscrowToTake> = offers.iter().map(|i| self.offers[*i].to_take()).collect();
+        instruction::settle_ring(
+            &solana_escrow::id(),
+            &self.settler.pubkey(),
+            &spl_token::id(),
+            &ring,
+        )
+        .unwrap()
+    }
+}
+
+/// A ring whose last maker asks for `last_requested` tokens of its own
+/// mint, or of X when it has none
+async fn ring(last_requested: (Option<Pubkey>, u64)) -> (ProgramTestContext, Ring) {
+    let mints = [
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    ];
+    let (x, y, z) = (&mints[0], &mints[1], &mints[2]);
+    let last_mint = last_requested.0.unwrap_or(*x);
+    let time_out = SLOT + 1_000;
+    let mut test = program_test();
+    for key in mints.iter().chain([&last_mint]) {
+        test.add_account(*key, mint());
+    }
+    let offers = vec![
+        Offer::add_to(&mut test, (x, 10), (y, 20), time_out),
+        Offer::add_to(&mut test, (y, 20), (z, 30), time_out),
+        Offer::add_to(&mut test, (z, 30), (&last_mint, last_requested.1), time_out),
+    ];
+    let settler = Keypair::new();
+    test.add_account(settler.pubkey(), wallet());
+    (start(test).await, Ring { settler, offers })
+}
+
+#[tokio::test]
+async fn every_maker_is_paid_by_the_next_one() {
+    let (mut context, ring) = ring((None, 10)).await;
+    send(&mut context, &[ring.settle(&[0, 1, 2])], &[&ring.settler])
+        .await
+        .unwrap();
+
+    for (offer, paid) in ring.offers.iter().zip([20, 30, 10]) {
+        assert_eq!(
+            token_balance(&mut context, &offer.maker_receive).await,
+            paid
+        );
+        assert!(account(&mut context, &offer.escrow).await.is_none());
+        assert!(account(&mut context, &offer.vault).await.is_none());
+    }
+}
+
+#[tokio::test]
+async fn a_maker_paid_in_the_wrong_mint_breaks_the_ring() {
+    let (mut context, ring) = ring((Some(Pubkey::new_unique()), 10)).await;
+    assert_eq!(
+        send(&mut context, &[ring.settle(&[0, 1, 2])], &[&ring.settler]).await,
+        Err(escrow_failure(EscrowError::RingMismatch))
+    );
+}
+
+#[tokio::test]
+async fn a_maker_paid_too_little_breaks_the_ring() {
+    let (mut context, ring) = ring((None, 11)).await;
+    assert_eq!(
+        send(&mut context, &[ring.settle(&[0, 1, 2])], &[&ring.settler]).await,
+        Err(escrow_failure(EscrowError::RingMismatch))
+    );
+    for offer in &ring.offers {
+        assert_eq!(token_balance(&mut context, &offer.maker_receive).await, 0);
+    }
+}
+
+#[tokio::test]
+async fn an_escrow_only_joins_the_ring_once() {
+    let (mut context, ring) = ring((None, 10)).await;
+    assert_eq!(
+        send(
+            &mut context,
+            &[ring.settle(&[0, 1, 2, 0])],
+            &[&ring.settler]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::InvalidInstruction))
+    );
+    for offer in &ring.offers {
+        assert!(account(&mut context, &offer.escrow).await.is_some());
+    }
+}
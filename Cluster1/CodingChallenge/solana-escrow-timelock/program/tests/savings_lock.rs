@@ -0,0 +1,126 @@
+mod common;
+
+use common::{
+    account, escrow, escrow_failure, program_test, send, start, token_balance, Swap, OFFERED, SLOT,
+};
+use solana_escrow::{
+    error::EscrowError,
+    instruction,
+    state::{Escrow, EscrowMode},
+};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{instruction::Instruction, signature::Signer};
+
+const UNLOCK: u64 = SLOT + 100;
+
+/// The swap's maker's savings, locked until [`UNLOCK`]
+async fn savings() -> (ProgramTestContext, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_to(
+        &mut test,
+        Escrow {
+            mode: EscrowMode::SavingsLock,
+            initializer_token_to_receive_account_pubkey: Default::default(),
+            expected_amount: 0,
+            ..swap.escrow_info(UNLOCK, u64::MAX)
+        },
+    );
+    (start(test).await, swap)
+}
+
+/// The maker's `Unlock`, paying into `maker_refund`
+fn unlock(swap: &Swap) -> Instruction {
+    instruction::unlock(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.vault,
+        &swap.maker_refund,
+        &swap.escrow,
+        &spl_token::id(),
+    )
+    .unwrap()
+}
+
+fn extend_lock(swap: &Swap, extra_slots: u64) -> Instruction {
+    instruction::extend_lock(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.escrow,
+        extra_slots,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn savings_unlock_only_once_the_lock_is_up() {
+    let (mut context, swap) = savings().await;
+    context.warp_to_slot(UNLOCK - 1).unwrap();
+    assert_eq!(
+        send(&mut context, &[unlock(&swap)], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::EscrowTimeUnlock))
+    );
+
+    context.warp_to_slot(UNLOCK).unwrap();
+    send(&mut context, &[unlock(&swap)], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_refund).await,
+        OFFERED
+    );
+    assert!(account(&mut context, &swap.escrow).await.is_none());
+}
+
+#[tokio::test]
+async fn the_lock_only_moves_forward() {
+    let (mut context, swap) = savings().await;
+    send(&mut context, &[extend_lock(&swap, 50)], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        escrow(&mut context, &swap.escrow).await.unlock_time,
+        UNLOCK + 50
+    );
+    assert_eq!(
+        send(
+            &mut context,
+            &[extend_lock(&swap, u64::MAX)],
+            &[&swap.maker]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::AmountOverflow))
+    );
+
+    // resetting the window could bring the unlock closer
+    let reset = instruction::reset_time_lock(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.escrow,
+        0,
+        0,
+    )
+    .unwrap();
+    assert_eq!(
+        send(&mut context, &[reset], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::WrongEscrowMode))
+    );
+
+    context.warp_to_slot(UNLOCK).unwrap();
+    assert_eq!(
+        send(&mut context, &[unlock(&swap)], &[&swap.maker]).await,
+        Err(escrow_failure(EscrowError::EscrowTimeUnlock))
+    );
+}
+
+#[tokio::test]
+async fn savings_cannot_be_cancelled_or_taken() {
+    let (mut context, swap) = savings().await;
+    for (instruction, signer) in [(swap.cancel(), &swap.maker), (swap.exchange(), &swap.taker)] {
+        assert_eq!(
+            send(&mut context, &[instruction], &[signer]).await,
+            Err(escrow_failure(EscrowError::WrongEscrowMode))
+        );
+    }
+    assert_eq!(token_balance(&mut context, &swap.vault).await, OFFERED);
+}
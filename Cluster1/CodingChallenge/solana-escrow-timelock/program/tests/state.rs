@@ -0,0 +1,358 @@
+use solana_escrow::{
+    oracle::{OracleKind, PriceCondition},
+    state::{
+        DealState, DefaultOutcome, Escrow, EscrowMode, Extension, HashKind, Milestone,
+        MilestoneState, ESCROW_VERSION, LEGACY_ESCROW_LEN,
+    },
+};
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+fn key(n: u8) -> Pubkey {
+    Pubkey::new_from_array([n; 32])
+}
+
+/// An escrow as the program wrote it before the layout had a header
+fn legacy_escrow() -> Vec<u8> {
+    let mut data = vec![1];
+    data.extend_from_slice(key(1).as_ref());
+    data.extend_from_slice(key(2).as_ref());
+    data.extend_from_slice(key(3).as_ref());
+    data.extend_from_slice(&4u64.to_le_bytes());
+    // the only big-endian field
+    data.extend_from_slice(&5u64.to_be_bytes());
+    data.extend_from_slice(&6u64.to_le_bytes());
+    assert_eq!(data.len(), LEGACY_ESCROW_LEN);
+    data
+}
+
+#[test]
+fn legacy_escrows_unpack_as_version_0() {
+    let data = legacy_escrow();
+    assert_eq!(Escrow::version(&data).unwrap(), 0);
+
+    let escrow = Escrow::unpack(&data).unwrap();
+    assert!(escrow.is_initialized);
+    assert_eq!(escrow.initializer_pubkey, key(1));
+    assert_eq!(escrow.temp_token_account_pubkey, key(2));
+    assert_eq!(escrow.initializer_token_to_receive_account_pubkey, key(3));
+    assert_eq!(escrow.expected_amount, 4);
+    assert_eq!(escrow.unlock_time, 5);
+    assert_eq!(escrow.time_out, 6);
+}
+
+#[test]
+fn legacy_escrows_that_were_never_reset_have_no_window() {
+    let mut data = legacy_escrow();
+    data[105..].fill(0);
+
+    let escrow = Escrow::unpack(&data).unwrap();
+    assert_eq!(escrow.unlock_time, 0);
+    assert_eq!(escrow.time_out, u64::MAX);
+}
+
+/// The legacy escrow as a current swap would hold it
+fn swap() -> Escrow {
+    Escrow::unpack(&legacy_escrow()).unwrap()
+}
+
+fn pack(escrow: Escrow) -> Vec<u8> {
+    let mut data = vec![0; escrow.packed_len()];
+    Escrow::pack(escrow, &mut data).unwrap();
+    data
+}
+
+#[test]
+fn migrated_escrows_keep_their_fields() {
+    let mut data = vec![0; Escrow::LEN];
+    Escrow::pack(Escrow::unpack(&legacy_escrow()).unwrap(), &mut data).unwrap();
+    assert_eq!(Escrow::version(&data).unwrap(), ESCROW_VERSION);
+
+    let escrow = Escrow::unpack(&data).unwrap();
+    assert_eq!(escrow.initializer_pubkey, key(1));
+    assert_eq!(escrow.time_out, 6);
+}
+
+#[test]
+fn current_escrows_store_the_pda_bump() {
+    let mut escrow = swap();
+    escrow.pda_bump = 254;
+    let data = pack(escrow);
+
+    assert_eq!(Escrow::pda_bump(&data).unwrap(), Some(254));
+    assert_eq!(Escrow::unpack(&data).unwrap().pda_bump, 254);
+}
+
+#[test]
+fn fresh_accounts_are_uninitialized() {
+    let data = vec![0; Escrow::LEN];
+    assert!(!Escrow::unpack_unchecked(&data).unwrap().is_initialized);
+    assert_eq!(
+        Escrow::unpack(&data).err(),
+        Some(ProgramError::UninitializedAccount)
+    );
+}
+
+#[test]
+fn unknown_versions_are_rejected() {
+    let mut data = vec![0; Escrow::LEN];
+    data[0] = 1;
+    data[1] = ESCROW_VERSION + 1;
+    assert_eq!(
+        Escrow::unpack(&data).err(),
+        Some(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn plain_swaps_have_no_extensions() {
+    assert_eq!(pack(swap()).len(), Escrow::LEN);
+}
+
+#[test]
+fn extensions_are_tagged_after_the_fields_every_escrow_has() {
+    let mut escrow = swap();
+    escrow.premium = 25;
+    let data = pack(escrow);
+
+    assert_eq!(data.len(), Escrow::LEN + 3 + 8);
+    assert_eq!(data[Escrow::LEN], Extension::Premium as u8);
+    assert_eq!(data[Escrow::LEN + 1..Escrow::LEN + 3], 8u16.to_le_bytes());
+    assert_eq!(data[Escrow::LEN + 3..], 25u64.to_le_bytes());
+}
+
+#[test]
+fn escrows_pack_into_longer_accounts() {
+    let mut escrow = swap();
+    escrow.premium = 25;
+    escrow.callback_program = key(11);
+    let mut data = vec![0; escrow.packed_len() + 40];
+    Escrow::pack(escrow, &mut data).unwrap();
+    assert_eq!(Escrow::unpack(&data).unwrap().callback_program, key(11));
+
+    // dropping an extension leaves zeroes behind it
+    let mut escrow = Escrow::unpack(&data).unwrap();
+    escrow.premium = 0;
+    Escrow::pack(escrow, &mut data).unwrap();
+    let escrow = Escrow::unpack(&data).unwrap();
+    assert_eq!(escrow.premium, 0);
+    assert_eq!(escrow.callback_program, key(11));
+    assert!(data[Escrow::LEN + 3 + 32..].iter().all(|byte| *byte == 0));
+}
+
+#[test]
+fn escrows_dont_pack_into_accounts_without_room_for_their_extensions() {
+    let mut escrow = swap();
+    escrow.premium = 25;
+    let mut data = vec![0; Escrow::LEN];
+    assert_eq!(
+        Escrow::pack(escrow, &mut data),
+        Err(ProgramError::AccountDataTooSmall)
+    );
+}
+
+#[test]
+fn unknown_or_cut_off_extensions_are_rejected() {
+    let mut data = pack(swap());
+    data.extend_from_slice(&[99, 0, 0]);
+    assert_eq!(
+        Escrow::unpack(&data).err(),
+        Some(ProgramError::InvalidAccountData)
+    );
+
+    let mut escrow = swap();
+    escrow.premium = 25;
+    let mut data = pack(escrow);
+    data.pop();
+    assert_eq!(
+        Escrow::unpack(&data).err(),
+        Some(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn hash_locks_round_trip() {
+    let mut escrow = swap();
+    escrow.mode = EscrowMode::HashLock;
+    escrow.hash_kind = HashKind::Keccak256;
+    escrow.hash = [7; 32];
+    escrow.counterparty = key(8);
+    let data = pack(escrow);
+
+    let escrow = Escrow::unpack(&data).unwrap();
+    assert_eq!(escrow.mode, EscrowMode::HashLock);
+    assert_eq!(escrow.hash_kind, HashKind::Keccak256);
+    assert_eq!(escrow.hash, [7; 32]);
+    assert_eq!(escrow.counterparty, key(8));
+}
+
+#[test]
+fn arbitrated_deals_round_trip() {
+    let mut escrow = swap();
+    escrow.mode = EscrowMode::Arbitrated;
+    escrow.counterparty = key(8);
+    escrow.arbiter = key(9);
+    escrow.deal_state = DealState::Disputed;
+    escrow.default_outcome = DefaultOutcome::PayTaker;
+    let data = pack(escrow);
+
+    let escrow = Escrow::unpack(&data).unwrap();
+    assert_eq!(escrow.mode, EscrowMode::Arbitrated);
+    assert_eq!(escrow.arbiter, key(9));
+    assert_eq!(escrow.deal_state, DealState::Disputed);
+    assert_eq!(escrow.default_outcome, DefaultOutcome::PayTaker);
+}
+
+#[test]
+fn milestones_round_trip() {
+    let mut escrow = swap();
+    escrow.mode = EscrowMode::Milestones;
+    escrow.milestone_count = 2;
+    escrow.remaining = 30;
+    escrow.milestones[0] = Milestone {
+        amount: 10,
+        deadline: Some(500),
+        state: MilestoneState::Approved,
+    };
+    escrow.milestones[1] = Milestone {
+        amount: 30,
+        deadline: None,
+        state: MilestoneState::Pending,
+    };
+    let data = pack(escrow);
+
+    let escrow = Escrow::unpack(&data).unwrap();
+    assert_eq!(escrow.mode, EscrowMode::Milestones);
+    assert_eq!(escrow.remaining, 30);
+    assert_eq!(
+        escrow.milestones(),
+        &[
+            Milestone {
+                amount: 10,
+                deadline: Some(500),
+                state: MilestoneState::Approved,
+            },
+            Milestone {
+                amount: 30,
+                deadline: None,
+                state: MilestoneState::Pending,
+            },
+        ]
+    );
+    assert!(!escrow.milestones()[0].is_expired(501));
+    assert!(!escrow.milestones()[1].is_expired(u64::MAX));
+}
+
+#[test]
+fn bonds_round_trip() {
+    let mut escrow = swap();
+    escrow.bond = 1_000;
+    escrow.bond_vault = key(11);
+    escrow.bond_recipient = key(12);
+    let data = pack(escrow);
+
+    let escrow = Escrow::unpack(&data).unwrap();
+    assert_eq!(escrow.bond, 1_000);
+    assert_eq!(escrow.bond_vault, key(11));
+    assert_eq!(escrow.bond_recipient, key(12));
+}
+
+#[test]
+fn hash_kinds_match_their_digests() {
+    let hex =
+        |digest: [u8; 32]| -> String { digest.iter().map(|b| format!("{:02x}", b)).collect() };
+    assert_eq!(
+        hex(HashKind::Sha256.hash(&[0; 32])),
+        "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925"
+    );
+    assert_eq!(
+        hex(HashKind::Keccak256.hash(&[0; 32])),
+        "290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563"
+    );
+}
+
+#[test]
+fn reservations_last_until_the_time_out() {
+    let mut escrow = swap();
+    escrow.premium = 25;
+    escrow.bond = 3;
+    let data = pack(escrow);
+    let unreserved = Escrow::unpack(&data).unwrap();
+    assert_eq!(unreserved.premium, 25);
+    assert_eq!(unreserved.bond, 3);
+    assert!(!unreserved.is_reserved(0));
+
+    let mut reserved = unreserved;
+    reserved.counterparty = key(8);
+    assert!(reserved.is_reserved(reserved.time_out));
+    assert!(!reserved.is_reserved(reserved.time_out + 1));
+}
+
+#[test]
+fn only_escrows_with_a_delegated_amount_are_delegated() {
+    let mut escrow = swap();
+    assert!(!escrow.is_delegated());
+    escrow.premium = 25;
+    escrow.delegated_amount = 40;
+    let data = pack(escrow);
+    let delegated = Escrow::unpack(&data).unwrap();
+    assert_eq!(delegated.premium, 25);
+    assert_eq!(delegated.delegated_amount, 40);
+    assert!(delegated.is_delegated());
+}
+
+#[test]
+fn exclusivity_ends_after_exclusive_until() {
+    let mut escrow = swap();
+    assert!(!escrow.is_exclusive(escrow.unlock_time));
+    escrow.delegated_amount = 40;
+    escrow.designated_taker = key(8);
+    escrow.exclusive_until = escrow.unlock_time + 10;
+    let data = pack(escrow);
+    let exclusive = Escrow::unpack(&data).unwrap();
+    assert_eq!(exclusive.designated_taker, key(8));
+    assert!(exclusive.is_exclusive(exclusive.unlock_time + 10));
+    assert!(!exclusive.is_exclusive(exclusive.unlock_time + 11));
+}
+
+#[test]
+fn only_swaps_with_a_root_have_an_allow_list() {
+    let mut escrow = swap();
+    assert!(!escrow.has_allow_list());
+    escrow.exclusive_until = 30;
+    escrow.allow_list_root = [9; 32];
+    let data = pack(escrow);
+    let listed = Escrow::unpack(&data).unwrap();
+    assert_eq!(listed.allow_list_root, [9; 32]);
+    assert!(listed.has_allow_list());
+}
+
+#[test]
+fn only_swaps_with_a_condition_read_an_oracle() {
+    let mut escrow = swap();
+    assert!(!escrow.has_price_condition());
+    escrow.allow_list_root = [9; 32];
+    escrow.oracle = key(10);
+    escrow.oracle_kind = OracleKind::Mock;
+    escrow.price_condition = PriceCondition::AtOrAbove;
+    escrow.trigger_price = -11;
+    escrow.trigger_expo = -12;
+    escrow.max_staleness = 13;
+    let data = pack(escrow);
+    let conditional = Escrow::unpack(&data).unwrap();
+    assert_eq!(conditional.oracle, key(10));
+    assert_eq!(conditional.trigger_price, -11);
+    assert_eq!(conditional.trigger_expo, -12);
+    assert!(conditional.has_price_condition());
+}
+
+#[test]
+fn only_swaps_with_a_callback_program_call_back() {
+    let mut escrow = swap();
+    assert!(!escrow.has_callback());
+    escrow.oracle = key(10);
+    escrow.callback_program = key(11);
+    let data = pack(escrow);
+    let calling = Escrow::unpack(&data).unwrap();
+    assert_eq!(calling.callback_program, key(11));
+    assert!(calling.has_callback());
+}
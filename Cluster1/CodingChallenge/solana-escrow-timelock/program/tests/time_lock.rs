@@ -0,0 +1,127 @@
+mod common;
+
+use common::{
+    escrow, escrow_failure, failure, program_test, send, start, token_balance, Swap, OFFERED, SLOT,
+};
+use native_accounts::AccountError;
+use solana_escrow::{error::EscrowError, instruction};
+use solana_program_test::{tokio, ProgramTestContext};
+use solana_sdk::{instruction::Instruction, signature::Signer};
+
+/// A swap open from `unlock_time` through `time_out`
+async fn swap(unlock_time: u64, time_out: u64) -> (ProgramTestContext, Swap) {
+    let swap = Swap::new();
+    let mut test = program_test();
+    swap.add_to(&mut test, swap.escrow_info(unlock_time, time_out));
+    (start(test).await, swap)
+}
+
+fn reset_time_lock(swap: &Swap, unlock_slots: u64, time_out_slots: u64) -> Instruction {
+    instruction::reset_time_lock(
+        &solana_escrow::id(),
+        &swap.maker.pubkey(),
+        &swap.escrow,
+        unlock_slots,
+        time_out_slots,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn swaps_cannot_be_taken_before_they_unlock() {
+    let (mut context, swap) = swap(SLOT + 50, SLOT + 100).await;
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowTimeUnlock))
+    );
+
+    context.warp_to_slot(SLOT + 50).unwrap();
+    send(&mut context, &[swap.exchange()], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+}
+
+#[tokio::test]
+async fn swaps_can_be_taken_through_their_time_out_but_not_after() {
+    let (mut context, swap) = swap(0, SLOT + 10).await;
+    context.warp_to_slot(SLOT + 11).unwrap();
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowTimeOut))
+    );
+
+    // the maker can still get the tokens back
+    send(&mut context, &[swap.cancel()], &[&swap.maker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.maker_refund).await,
+        OFFERED
+    );
+}
+
+#[tokio::test]
+async fn resetting_the_lock_opens_a_new_window_from_the_current_slot() {
+    let (mut context, swap) = swap(0, SLOT + 10).await;
+    context.warp_to_slot(SLOT + 20).unwrap();
+    send(
+        &mut context,
+        &[reset_time_lock(&swap, 30, 100)],
+        &[&swap.maker],
+    )
+    .await
+    .unwrap();
+
+    let escrow_info = escrow(&mut context, &swap.escrow).await;
+    assert_eq!(escrow_info.unlock_time, SLOT + 50);
+    assert_eq!(escrow_info.time_out, SLOT + 150);
+    assert_eq!(
+        send(&mut context, &[swap.exchange()], &[&swap.taker]).await,
+        Err(escrow_failure(EscrowError::EscrowTimeUnlock))
+    );
+
+    context.warp_to_slot(SLOT + 150).unwrap();
+    send(&mut context, &[swap.exchange()], &[&swap.taker])
+        .await
+        .unwrap();
+    assert_eq!(
+        token_balance(&mut context, &swap.taker_receive).await,
+        OFFERED
+    );
+}
+
+#[tokio::test]
+async fn only_the_maker_can_reset_the_lock() {
+    let (mut context, swap) = swap(0, SLOT + 10).await;
+    let reset = instruction::reset_time_lock(
+        &solana_escrow::id(),
+        &swap.taker.pubkey(),
+        &swap.escrow,
+        0,
+        1_000,
+    )
+    .unwrap();
+    assert_eq!(
+        send(&mut context, &[reset], &[&swap.taker]).await,
+        Err(failure(AccountError::ConstraintAddress as u32))
+    );
+    assert_eq!(escrow(&mut context, &swap.escrow).await.time_out, SLOT + 10);
+}
+
+#[tokio::test]
+async fn windows_that_would_overflow_are_rejected() {
+    let (mut context, swap) = swap(0, SLOT + 10).await;
+    assert_eq!(
+        send(
+            &mut context,
+            &[reset_time_lock(&swap, 0, u64::MAX)],
+            &[&swap.maker]
+        )
+        .await,
+        Err(escrow_failure(EscrowError::AmountOverflow))
+    );
+}
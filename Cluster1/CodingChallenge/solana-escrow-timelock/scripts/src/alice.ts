@@ -84,7 +84,12 @@ const alice = async () => {
       { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
     ],
     data: Buffer.from(
-      Uint8Array.of(0, ...new BN(terms.aliceExpectedAmount).toArray("le", 8))
+      Uint8Array.of(
+        0,
+        ...new BN(terms.aliceExpectedAmount).toArray("le", 8),
+        ...new BN(terms.unlockSlots).toArray("le", 8),
+        ...new BN(terms.timeOutSlots).toArray("le", 8)
+      )
     ),
   });
 
@@ -119,7 +124,7 @@ const alice = async () => {
     encodedEscrowState
   ) as EscrowLayout;
 
-  if (!decodedEscrowState.isInitialized) {
+  if (decodedEscrowState.accountType !== 1) {
     logError("Escrow state initialization flag has not been set");
     process.exit(1);
   } else if (
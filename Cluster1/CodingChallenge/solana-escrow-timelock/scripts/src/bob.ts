@@ -40,7 +40,7 @@ const bob = async () => {
   ) as EscrowLayout;
   const escrowState = {
     escrowAccountPubkey: escrowStateAccountPubkey,
-    isInitialized: !!decodedEscrowLayout.isInitialized,
+    isInitialized: decodedEscrowLayout.accountType === 1,
     initializerAccountPubkey: new PublicKey(
       decodedEscrowLayout.initializerPubkey
     ),
@@ -43,6 +43,8 @@ export const getProgramId = () => {
 export const getTerms = (): {
   aliceExpectedAmount: number;
   bobExpectedAmount: number;
+  unlockSlots: number;
+  timeOutSlots: number;
 } => {
   return JSON.parse(fs.readFileSync(`./terms.json`) as unknown as string);
 };
@@ -70,18 +72,35 @@ const uint64 = (property = "uint64") => {
   return BufferLayout.blob(8, property);
 };
 
+/**
+ * Fields every current (version 1) escrow has, and all a plain swap has.
+ * accountType is 1 once initialized. Other escrows go on with tagged
+ * extensions, which the scripts don't read.
+ */
 export const ESCROW_ACCOUNT_DATA_LAYOUT = BufferLayout.struct([
-  BufferLayout.u8("isInitialized"),
+  BufferLayout.u8("accountType"),
+  BufferLayout.u8("version"),
   publicKey("initializerPubkey"),
   publicKey("initializerTempTokenAccountPubkey"),
   publicKey("initializerReceivingTokenAccountPubkey"),
   uint64("expectedAmount"),
+  uint64("unlockTime"),
+  uint64("timeOut"),
+  BufferLayout.u8("pdaBump"),
+  BufferLayout.u8("mode"),
+  publicKey("counterparty"),
 ]);
 
 export interface EscrowLayout {
-  isInitialized: number;
+  accountType: number;
+  version: number;
   initializerPubkey: Uint8Array;
   initializerReceivingTokenAccountPubkey: Uint8Array;
   initializerTempTokenAccountPubkey: Uint8Array;
   expectedAmount: Uint8Array;
+  unlockTime: Uint8Array;
+  timeOut: Uint8Array;
+  pdaBump: number;
+  mode: number;
+  counterparty: Uint8Array;
 }